@@ -1,6 +1,71 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use crate::services::log_redaction::{prompt_for_log, redact};
+use crate::services::{request_log, token_usage};
+use crate::services::token_usage::TokenUsage;
+use super::retry;
+use super::sse::{data_payload, SseLineBuffer};
+
+type CompletionFuture = BoxFuture<'static, Result<ChatCompletionResponse, String>>;
+
+lazy_static::lazy_static! {
+    // 进行中的非流式请求：请求哈希 -> (登记序号, 共享请求 future 的弱引用)。
+    // 完全相同的请求（地址、Key、模型、消息、采样参数都一致）在前一个返回之前再次发起时，
+    // 直接等待同一个结果而不重复调用接口。表中只持有弱引用，登记由请求 future 自己的
+    // InFlightGuard 在完成或被丢弃（所有等待方都已取消）时移除，不缓存已完成的结果
+    static ref IN_FLIGHT: Mutex<HashMap<u64, (u64, WeakShared<CompletionFuture>)>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_IN_FLIGHT_ID: AtomicU64 = AtomicU64::new(0);
+
+// 随请求 future 一起释放，移除自己在 IN_FLIGHT 中的登记（已被新请求替换时不动）
+struct InFlightGuard {
+    key: u64,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        if in_flight.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// 上下文超长错误的标记，调用方据此判断是否裁剪上下文后重试
+pub const CONTEXT_LENGTH_EXCEEDED: &str = "context length exceeded";
+
+/// 按状态码和错误信息识别“提示词超出模型上下文长度”（DeepSeek 与 OpenAI 兼容接口的常见写法）
+pub fn is_context_length_error(status: u16, body: &str) -> bool {
+    if status != 400 && status != 413 {
+        return false;
+    }
+    let body = body.to_ascii_lowercase();
+    [
+        "context_length_exceeded",
+        "maximum context length",
+        "context length",
+        "context window",
+        "prompt is too long",
+        "too many tokens",
+    ]
+    .iter()
+    .any(|pattern| body.contains(pattern))
+}
+
+/// 错误信息是否来自上下文超长（见 CONTEXT_LENGTH_EXCEEDED）
+pub fn is_context_length_exceeded(error: &str) -> bool {
+    error.contains(CONTEXT_LENGTH_EXCEEDED)
+}
 
 #[derive(Debug, Clone)]
 pub struct DeepSeekClient {
@@ -8,9 +73,11 @@ pub struct DeepSeekClient {
     api_key: String,
     base_url: String,
     model: String,
+    /// 合并进行中的相同请求；需要多次独立采样时关闭
+    dedupe_requests: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
@@ -26,34 +93,126 @@ struct ChatCompletionRequest {
     max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ChatCompletionResponse {
     pub id: String,
     pub choices: Vec<Choice>,
     pub usage: Option<Usage>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Choice {
     pub index: u32,
     pub message: ChatMessage,
     pub finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
 }
 
+/// 流式生成中解析出的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// 正文增量
+    Delta(String),
+    /// 模型给出的结束原因（stop / length 等）
+    Finish(String),
+    /// 部分平台在最后一个分片附带的用量
+    Usage(TokenUsage),
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    usage: Option<StreamUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamUsage {
+    #[serde(default)]
+    prompt_tokens: i64,
+    #[serde(default)]
+    completion_tokens: i64,
+    total_tokens: i64,
+}
+
+// 把完整的 SSE 行转换为事件；收到 [DONE] 或结束原因后置 done
+fn parse_stream_lines(lines: Vec<String>, events: &mut VecDeque<StreamEvent>, done: &mut bool) {
+    for line in &lines {
+        let Some(data) = data_payload(line) else {
+            continue;
+        };
+        if data == "[DONE]" {
+            *done = true;
+            continue;
+        }
+        let Ok(chunk) = serde_json::from_str::<StreamChunk>(data) else {
+            continue;
+        };
+        if let Some(usage) = chunk.usage {
+            events.push_back(StreamEvent::Usage(TokenUsage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            }));
+        }
+        if let Some(choice) = chunk.choices.into_iter().next() {
+            if let Some(content) = choice.delta.content.filter(|content| !content.is_empty()) {
+                events.push_back(StreamEvent::Delta(content));
+            }
+            if let Some(reason) = choice.finish_reason {
+                events.push_back(StreamEvent::Finish(reason));
+                *done = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenerationParams {
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub system_prompt: Option<String>,
+    /// 固定采样种子（OpenAI 兼容接口的 `seed`），为空时由服务端随机
+    pub seed: Option<i64>,
 }
 
 impl Default for GenerationParams {
@@ -62,6 +221,7 @@ impl Default for GenerationParams {
             temperature: Some(0.7),
             max_tokens: Some(4000),
             system_prompt: None,
+            seed: None,
         }
     }
 }
@@ -73,6 +233,26 @@ impl DeepSeekClient {
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://api.deepseek.com/v1".to_string()),
             model: model.unwrap_or_else(|| "deepseek-chat".to_string()),
+            dedupe_requests: true,
+        }
+    }
+
+    pub fn without_request_dedup(mut self) -> Self {
+        self.dedupe_requests = false;
+        self
+    }
+
+    /// 复用调用方的 HTTP 客户端（共享连接池）
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn chat_completions_url(&self) -> String {
+        if self.base_url.ends_with("/chat/completions") {
+            self.base_url.clone()
+        } else {
+            format!("{}/chat/completions", self.base_url)
         }
     }
 
@@ -112,25 +292,246 @@ impl DeepSeekClient {
             temperature: params.temperature,
             max_tokens: params.max_tokens,
             stream: Some(false),
+            seed: params.seed,
         };
 
-        let url = format!("{}/chat/completions", self.base_url);
-        
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        let prompt_chars: usize = request.messages.iter().map(|m| m.content.chars().count()).sum();
+        request_log::info(&format!(
+            "Chat request: model={}, messages={}, prompt_chars={}, max_tokens={:?}, temperature={:?}",
+            request.model,
+            request.messages.len(),
+            prompt_chars,
+            request.max_tokens,
+            request.temperature
+        ));
+        if let Some(last) = request.messages.last() {
+            request_log::info(&format!("Chat prompt ({}): {}", last.role, prompt_for_log(&last.content)));
+        }
+
+        if !self.dedupe_requests {
+            let result = self.clone().send(request).await.map_err(|e| anyhow!(e))?;
+            Self::log_response(&result);
+            Self::record_usage(&result);
+            return Ok(result);
+        }
+
+        let key = self.request_key(&request)?;
+        let (completion, reused): (Shared<CompletionFuture>, bool) = {
+            let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+            match in_flight.get(&key).and_then(|(_, weak)| weak.upgrade()) {
+                Some(completion) => (completion, true),
+                None => {
+                    let guard = InFlightGuard {
+                        key,
+                        id: NEXT_IN_FLIGHT_ID.fetch_add(1, Ordering::Relaxed),
+                    };
+                    let id = guard.id;
+                    let send = self.clone().send(request);
+                    let completion = async move {
+                        let _guard = guard;
+                        send.await
+                    }
+                    .boxed()
+                    .shared();
+                    if let Some(weak) = completion.downgrade() {
+                        in_flight.insert(key, (id, weak));
+                    }
+                    (completion, false)
+                }
+            }
+        };
+        if reused {
+            request_log::info("Identical request already in flight, waiting for its result");
+        }
+
+        let result = completion.await;
+        let result = result.map_err(|e| anyhow!(e))?;
+        Self::log_response(&result);
+        // 合并的请求由发起方计数，避免重复统计
+        if !reused {
+            Self::record_usage(&result);
+        }
+        Ok(result)
+    }
+
+    fn record_usage(result: &ChatCompletionResponse) {
+        if let Some(usage) = &result.usage {
+            token_usage::record(TokenUsage {
+                prompt_tokens: usage.prompt_tokens as i64,
+                completion_tokens: usage.completion_tokens as i64,
+                total_tokens: usage.total_tokens as i64,
+            });
+        }
+    }
+
+    fn log_response(result: &ChatCompletionResponse) {
+        let finish_reason = result.choices.first().and_then(|c| c.finish_reason.as_deref());
+        match &result.usage {
+            Some(usage) => request_log::info(&format!(
+                "Chat response: finish_reason={:?}, prompt_tokens={}, completion_tokens={}, total_tokens={}",
+                finish_reason, usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            )),
+            None => request_log::info(&format!("Chat response: finish_reason={:?}, usage unavailable", finish_reason)),
+        }
+    }
+
+    // 去重用的请求哈希，包含地址和 Key，不同账号的相同请求不会合并
+    fn request_key(&self, request: &ChatCompletionRequest) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.base_url.hash(&mut hasher);
+        self.api_key.hash(&mut hasher);
+        serde_json::to_string(request)?.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    async fn send(self, request: ChatCompletionRequest) -> Result<ChatCompletionResponse, String> {
+        let url = self.chat_completions_url();
+        let response = retry::send(|| {
+            self.client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
 
         if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.map_err(|e| e.to_string())?;
+            let error_text = redact(&error_text);
+            request_log::warn(&format!("Chat request failed: status={}", status));
+            if is_context_length_error(status.as_u16(), &error_text) {
+                return Err(format!("DeepSeek API error ({}): {}", CONTEXT_LENGTH_EXCEEDED, error_text));
+            }
+            return Err(format!("DeepSeek API error: {}", error_text));
+        }
+
+        response.json::<ChatCompletionResponse>().await.map_err(|e| e.to_string())
+    }
+
+    /// 流式生成：请求成功后返回按顺序产生的事件流。
+    /// 收到结束标记后的连接断开视为正常结束；此前读取失败时产生一个 Err 后结束，
+    /// 调用方可以保留已收到的内容。请求本身失败（含上下文超长）时直接返回错误
+    pub async fn stream_text(
+        &self,
+        messages: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<BoxStream<'static, Result<StreamEvent>>> {
+        let mut messages = messages;
+        if let Some(system_prompt) = params.system_prompt {
+            messages.insert(0, ChatMessage {
+                role: "system".to_string(),
+                content: system_prompt,
+            });
+        }
+        let request = ChatCompletionRequest {
+            model: self.model.clone(),
+            messages,
+            temperature: params.temperature,
+            max_tokens: params.max_tokens,
+            stream: Some(true),
+            seed: params.seed,
+        };
+
+        let prompt_chars: usize = request.messages.iter().map(|m| m.content.chars().count()).sum();
+        request_log::info(&format!(
+            "Stream request: model={}, prompt_chars={}, max_tokens={:?}, temperature={:?}",
+            request.model, prompt_chars, request.max_tokens, request.temperature
+        ));
+        if let Some(last) = request.messages.last() {
+            request_log::info(&format!("Stream prompt: {}", prompt_for_log(&last.content)));
+        }
+
+        let url = self.chat_completions_url();
+        let response = retry::send(|| {
+            self.client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await
+        .map_err(|e| {
+            request_log::warn(&format!("Stream request failed: {}", e));
+            anyhow!(e)
+        })?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let error_text = redact(&error_text);
+            request_log::warn(&format!("Stream request failed: status={}", status));
+            if is_context_length_error(status.as_u16(), &error_text) {
+                return Err(anyhow!("API error ({}): {}", CONTEXT_LENGTH_EXCEEDED, error_text));
+            }
+            return Err(anyhow!("API error ({}): {}", status, error_text));
+        }
+
+        // 状态：字节流、行缓冲、待产出的事件、是否已收到结束标记、是否已读完
+        let state = (response.bytes_stream(), SseLineBuffer::new(), VecDeque::new(), false, false);
+        let events = stream::unfold(state, |(mut bytes, mut lines, mut events, mut done, mut ended)| async move {
+            loop {
+                if let Some(event) = events.pop_front() {
+                    return Some((Ok(event), (bytes, lines, events, done, ended)));
+                }
+                if ended {
+                    return None;
+                }
+                match bytes.next().await {
+                    // 最后一行可能没有以换行结尾
+                    None => {
+                        ended = true;
+                        parse_stream_lines(lines.finish().into_iter().collect(), &mut events, &mut done);
+                    }
+                    Some(Ok(chunk)) => parse_stream_lines(lines.push(&chunk), &mut events, &mut done),
+                    // 已收到结束标记后的断开不影响结果
+                    Some(Err(_)) if done => ended = true,
+                    Some(Err(e)) => {
+                        ended = true;
+                        return Some((Err(anyhow!(e)), (bytes, lines, events, done, ended)));
+                    }
+                }
+            }
+        });
+        Ok(events.boxed())
+    }
+
+    /// OpenAI 兼容的 /embeddings 接口，模型为客户端的 model；返回顺序与 inputs 一致
+    pub async fn embeddings(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        request_log::info(&format!("Embedding request: model={}, inputs={}", self.model, inputs.len()));
+
+        let url = format!("{}/embeddings", self.base_url);
+        let response = retry::send(|| {
+            self.client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&EmbeddingRequest { model: &self.model, input: inputs })
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
             let error_text = response.text().await?;
-            return Err(anyhow!("DeepSeek API error: {}", error_text));
+            let error_text = redact(&error_text);
+            request_log::warn(&format!("Embedding request failed: status={}", status));
+            return Err(anyhow!("Embedding API error ({}): {}", status, error_text));
         }
 
-        let result = response.json::<ChatCompletionResponse>().await?;
-        Ok(result)
+        let mut data = response.json::<EmbeddingResponse>().await?.data;
+        if data.len() != inputs.len() {
+            return Err(anyhow!(
+                "Embedding API returned {} vectors for {} inputs",
+                data.len(),
+                inputs.len()
+            ));
+        }
+        data.sort_by_key(|item| item.index);
+        Ok(data.into_iter().map(|item| item.embedding).collect())
     }
 
     pub async fn generate_text(
@@ -1,5 +1,7 @@
 pub mod deepseek;
 pub mod pollinations;
+pub mod sse;
+pub mod retry;
 
 pub use deepseek::DeepSeekClient;
 pub use pollinations::PollinationsClient;
@@ -2,6 +2,97 @@ use reqwest::Client;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use base64::{Engine as _, engine::general_purpose};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use crate::services::log_redaction::redact;
+use super::retry;
+
+/// 图片请求进度回调的间隔
+const IMAGE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// 每百万像素低于该字节数的图片视为过滤后的纯色占位图（正常图片通常在 100KB 以上）
+const MIN_BYTES_PER_MEGAPIXEL: u64 = 10_000;
+
+/// 提示词被内容安全过滤的错误标记，调用方据此提示用户改写提示词
+pub const PROMPT_FILTERED: &str = "prompt likely filtered";
+
+/// 错误信息是否来自内容过滤（见 PROMPT_FILTERED）
+pub fn is_prompt_filtered(error: &str) -> bool {
+    error.contains(PROMPT_FILTERED)
+}
+
+/// 请求过于频繁（429）的错误标记，批量生成时据此退避重试
+pub const RATE_LIMITED: &str = "rate limited";
+
+pub fn is_rate_limited(error: &str) -> bool {
+    error.contains(RATE_LIMITED)
+}
+
+fn mentions_filter(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    ["nsfw", "safety", "content policy", "moderation", "filtered", "blocked", "inappropriate"]
+        .iter()
+        .any(|pattern| text.contains(pattern))
+}
+
+// 响应头中的过滤标记，如 x-nsfw: true / x-content-filtered: 1
+fn filter_header(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers.iter().find_map(|(name, value)| {
+        let name = name.as_str();
+        let flagged = ["nsfw", "filtered", "blocked"].iter().any(|key| name.contains(key));
+        let value = value.to_str().ok()?.trim().to_ascii_lowercase();
+        (flagged && matches!(value.as_str(), "true" | "1" | "yes" | "blocked" | "filtered"))
+            .then(|| format!("{}: {}", name, value))
+    })
+}
+
+/// 检查成功返回的图片是否是过滤后的占位内容：带过滤响应头、不是图片，或体积远小于请求尺寸应有的大小
+fn check_image_response(
+    headers: &reqwest::header::HeaderMap,
+    bytes: &[u8],
+    params: &ImageGenerationParams,
+) -> Result<()> {
+    if let Some(header) = filter_header(headers) {
+        return Err(anyhow::anyhow!("Pollinations {} ({})", PROMPT_FILTERED, header));
+    }
+
+    let content_type = headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.is_empty() && !content_type.starts_with("image/") {
+        let text = String::from_utf8_lossy(&bytes[..bytes.len().min(2000)]);
+        if mentions_filter(&text) {
+            return Err(anyhow::anyhow!("Pollinations {}: {}", PROMPT_FILTERED, redact(&text)));
+        }
+        return Err(anyhow::anyhow!("Pollinations returned {} instead of an image", content_type));
+    }
+
+    let pixels = u64::from(params.width.unwrap_or(1024)) * u64::from(params.height.unwrap_or(1024));
+    let min_bytes = pixels * MIN_BYTES_PER_MEGAPIXEL / 1_000_000;
+    if (bytes.len() as u64) < min_bytes {
+        return Err(anyhow::anyhow!(
+            "Pollinations {} (placeholder image of {} bytes for {}x{})",
+            PROMPT_FILTERED,
+            bytes.len(),
+            params.width.unwrap_or(1024),
+            params.height.unwrap_or(1024)
+        ));
+    }
+    Ok(())
+}
+
+// 失败响应：429 标记为 RATE_LIMITED，错误信息提到安全过滤时标记为 PROMPT_FILTERED
+fn api_error(status: reqwest::StatusCode, error_text: &str) -> anyhow::Error {
+    let error_text = redact(error_text);
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        anyhow::anyhow!("Pollinations {} ({}): {}", RATE_LIMITED, status, error_text)
+    } else if mentions_filter(&error_text) {
+        anyhow::anyhow!("Pollinations {} ({}): {}", PROMPT_FILTERED, status, error_text)
+    } else {
+        anyhow::anyhow!("Pollinations API error ({}): {}", status, error_text)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PollinationsClient {
@@ -21,6 +112,26 @@ pub struct ImageGenerationParams {
     pub enhance: Option<bool>,
 }
 
+/// 图片请求进度：waiting 为服务端生成中，downloading 为接收图片数据中
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageDownloadProgress {
+    pub status: &'static str,
+    pub elapsed_ms: u64,
+    pub received_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+impl ImageDownloadProgress {
+    fn new(status: &'static str, started: Instant, received_bytes: u64, total_bytes: Option<u64>) -> Self {
+        Self {
+            status,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            received_bytes,
+            total_bytes,
+        }
+    }
+}
+
 impl Default for ImageGenerationParams {
     fn default() -> Self {
         Self {
@@ -44,6 +155,14 @@ impl PollinationsClient {
         }
     }
 
+    fn image_request(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(url).header("Accept", "*/*");
+        match self.api_key {
+            Some(ref api_key) => request.header("Authorization", format!("Bearer {}", api_key)),
+            None => request,
+        }
+    }
+
     pub async fn test_connection(&self) -> Result<bool> {
         // 简单测试：生成一个小图片URL
         let test_url = format!("{}/image/test?width=64&height=64&model=zimage", self.base_url);
@@ -62,6 +181,29 @@ impl PollinationsClient {
         }
     }
 
+    /// 查询服务端支持的图片模型名称（/image/models，兼容字符串数组和带 name 字段的对象数组）
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let url = format!("{}/image/models", self.base_url);
+        let mut request = self.client.get(&url);
+        if let Some(ref api_key) = self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("Pollinations model list error: {}", response.status()));
+        }
+        let value: serde_json::Value = response.json().await?;
+        let models = value
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Unexpected model list format"))?
+            .iter()
+            .filter_map(|item| item.as_str().or_else(|| item["name"].as_str()))
+            .map(str::to_string)
+            .collect();
+        Ok(models)
+    }
+
     /// 生成图片URL（新版API格式：/image/{prompt}?params）
     pub fn generate_image_url(&self, params: &ImageGenerationParams) -> Result<String> {
         // URL encode the prompt
@@ -104,51 +246,82 @@ impl PollinationsClient {
     /// 生成图片并返回base64编码（用于前端直接显示）
     pub async fn generate_image_base64(&self, params: &ImageGenerationParams) -> Result<String> {
         let url = self.generate_image_url(params)?;
-        
-        let mut request = self.client.get(&url)
-            .header("Accept", "*/*");
-        
-        // 添加API key（如果有）
-        if let Some(ref api_key) = self.api_key {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
-        }
-
-        let response = request.send().await?;
+        let response = retry::send(|| self.image_request(&url)).await?;
         
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow::anyhow!("Pollinations API error ({}): {}", status, error_text));
+            return Err(api_error(status, &error_text));
         }
 
+        let headers = response.headers().clone();
         let bytes = response.bytes().await?;
+        check_image_response(&headers, &bytes, params)?;
         let base64_str = general_purpose::STANDARD.encode(&bytes);
         
         Ok(format!("data:image/png;base64,{}", base64_str))
     }
 
-    /// 下载图片并保存到文件
-    pub async fn generate_and_download(&self, params: &ImageGenerationParams, save_path: &str) -> Result<String> {
+    /// 下载图片并保存到文件，等待期间定期回调进度；`cancel` 置位后尽快中止请求。
+    /// 图片完整接收后才写入文件，中途取消不会留下残缺文件；疑似被内容过滤的占位图返回 PROMPT_FILTERED 错误而不保存。
+    pub async fn generate_and_download<F>(
+        &self,
+        params: &ImageGenerationParams,
+        save_path: &str,
+        cancel: &AtomicBool,
+        mut on_progress: F,
+    ) -> Result<String>
+    where
+        F: FnMut(ImageDownloadProgress),
+    {
         let url = self.generate_image_url(params)?;
-        
-        let mut request = self.client.get(&url)
-            .header("Accept", "*/*");
-        
-        // 添加API key（如果有）
-        if let Some(ref api_key) = self.api_key {
-            request = request.header("Authorization", format!("Bearer {}", api_key));
-        }
+        let started = Instant::now();
+        let mut ticker = tokio::time::interval(IMAGE_PROGRESS_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let cancelled = || anyhow::anyhow!("Image generation cancelled");
 
-        let response = request.send().await?;
+        // 服务端生成期间没有任何响应，靠心跳告知前端仍在等待；重试的退避等待同样可以取消
+        let send = retry::send(|| self.image_request(&url));
+        tokio::pin!(send);
+        let mut response = loop {
+            tokio::select! {
+                result = &mut send => break result?,
+                _ = ticker.tick() => {
+                    if cancel.load(Ordering::SeqCst) {
+                        return Err(cancelled());
+                    }
+                    on_progress(ImageDownloadProgress::new("waiting", started, 0, None));
+                }
+            }
+        };
         
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow::anyhow!("Pollinations API error ({}): {}", status, error_text));
+            return Err(api_error(status, &error_text));
         }
 
-        let bytes = response.bytes().await?;
-        std::fs::write(save_path, bytes)?;
+        let headers = response.headers().clone();
+        let total_bytes = response.content_length();
+        let mut bytes: Vec<u8> = Vec::with_capacity(total_bytes.unwrap_or(0) as usize);
+        loop {
+            tokio::select! {
+                chunk = response.chunk() => match chunk? {
+                    Some(chunk) => bytes.extend_from_slice(&chunk),
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    on_progress(ImageDownloadProgress::new("downloading", started, bytes.len() as u64, total_bytes));
+                }
+            }
+            if cancel.load(Ordering::SeqCst) {
+                return Err(cancelled());
+            }
+        }
+
+        // 过滤后的占位图不落盘
+        check_image_response(&headers, &bytes, params)?;
+        std::fs::write(save_path, &bytes)?;
 
         Ok(save_path.to_string())
     }
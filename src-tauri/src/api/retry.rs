@@ -0,0 +1,103 @@
+//! 临时性请求失败的重试
+//!
+//! 超时、连接失败以及 HTTP 429/500/502/503 按指数退避重试，其他错误和状态码直接交给调用方。
+//! 策略来自设置（RetrySettings）。每次等待在 base_delay × 2^n 的基础上加减 jitter 比例的随机量，不超过 max_delay；
+//! 429 响应带 Retry-After（秒）时按其等待，要求的时间超过 max_delay 则不再重试。
+//! 重试用尽后返回最后一次的响应或错误。
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::sync::RwLock;
+use std::time::Duration;
+use crate::models::RetrySettings;
+use crate::services::request_log;
+
+lazy_static::lazy_static! {
+    static ref POLICY: RwLock<RetrySettings> = RwLock::new(RetrySettings::default());
+}
+
+/// 设置加载或更新后调用
+pub fn set_policy(policy: RetrySettings) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+fn policy() -> RetrySettings {
+    POLICY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// 第 attempt 次失败（从 1 开始）后的等待时间
+fn delay(policy: &RetrySettings, attempt: u32) -> Duration {
+    let base = Duration::from_millis(policy.base_delay_ms);
+    let exponential = base.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+    let jitter = policy.jitter.clamp(0.0, 1.0);
+    // 不引入随机数依赖，用 v4 UUID 的随机位取 [-1, 1) 的浮动
+    let random = (uuid::Uuid::new_v4().as_u128() % 10_000) as f64 / 5_000.0 - 1.0;
+    exponential.mul_f64(1.0 + jitter * random).min(Duration::from_millis(policy.max_delay_ms))
+}
+
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect()
+}
+
+// Retry-After 的秒数形式；HTTP 日期形式按普通退避处理
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// 发送请求，遇到可重试的失败时按当前策略重新构建并发送
+pub async fn send<F>(mut build: F) -> reqwest::Result<Response>
+where
+    F: FnMut() -> RequestBuilder,
+{
+    let policy = policy();
+    let max_delay = Duration::from_millis(policy.max_delay_ms);
+    let mut attempt = 1;
+    loop {
+        let result = build().send().await;
+        if attempt >= policy.max_attempts.max(1) {
+            return result;
+        }
+        let wait = match &result {
+            Ok(response) if is_retryable_status(response.status()) => {
+                let retry_after = (response.status() == StatusCode::TOO_MANY_REQUESTS)
+                    .then(|| retry_after(response.headers()))
+                    .flatten();
+                request_log::warn(&format!(
+                    "Request failed with status {}, retrying ({}/{})",
+                    response.status(),
+                    attempt,
+                    policy.max_attempts - 1
+                ));
+                match retry_after {
+                    // 服务端要求的等待超过上限时不再重试，交给调用方处理
+                    Some(wait) if wait > max_delay => return result,
+                    Some(wait) => wait,
+                    None => delay(&policy, attempt),
+                }
+            }
+            Err(e) if is_retryable_error(e) => {
+                request_log::warn(&format!(
+                    "Request failed ({}), retrying ({}/{})",
+                    e,
+                    attempt,
+                    policy.max_attempts - 1
+                ));
+                delay(&policy, attempt)
+            }
+            _ => return result,
+        };
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
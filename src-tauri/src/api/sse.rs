@@ -0,0 +1,105 @@
+//! Server-Sent Events 行解析
+//!
+//! 网络分块与行边界无关：一行可能跨两个分块，"\r\n" 也可能被拆在两块之间，
+//! 多字节字符同样可能被截断。这里按字节缓存到完整的行再解码，
+//! "\r\n"、"\n"、"\r" 都视为行结束，返回的行不含行尾的 "\r"。
+
+/// 累积字节流并按行切分
+#[derive(Debug, Default)]
+pub struct SseLineBuffer {
+    pending: Vec<u8>,
+}
+
+impl SseLineBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个分块，返回其中已完整的非空行
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for index in 0..self.pending.len() {
+            if matches!(self.pending[index], b'\n' | b'\r') {
+                // "\r\n" 拆成两次结束时第二次得到空行，直接跳过
+                if index > start {
+                    lines.push(String::from_utf8_lossy(&self.pending[start..index]).into_owned());
+                }
+                start = index + 1;
+            }
+        }
+        self.pending.drain(..start);
+        lines
+    }
+
+    /// 流结束时取出最后一行（没有以换行结尾的残余内容）
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        let line = String::from_utf8_lossy(&rest).trim_end_matches('\r').to_string();
+        (!line.trim().is_empty()).then_some(line)
+    }
+}
+
+/// 取出 data 行的内容（"data:" 后的单个空格可省略），其他字段和注释行返回 None
+pub fn data_payload(line: &str) -> Option<&str> {
+    let data = line.strip_prefix("data:")?;
+    Some(data.strip_prefix(' ').unwrap_or(data).trim_end_matches('\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payloads(chunks: &[&[u8]]) -> Vec<String> {
+        let mut buffer = SseLineBuffer::new();
+        let mut lines: Vec<String> = chunks.iter().flat_map(|chunk| buffer.push(chunk)).collect();
+        lines.extend(buffer.finish());
+        lines
+            .iter()
+            .filter_map(|line| data_payload(line).map(str::to_string))
+            .collect()
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        let data = payloads(&[b"data: {\"a\":1}\r", b"\ndata: {\"b\":2}\r\n\r\n"]);
+        assert_eq!(data, vec!["{\"a\":1}", "{\"b\":2}"]);
+    }
+
+    #[test]
+    fn bare_cr_terminates_lines() {
+        let data = payloads(&[b"data: one\rdata: two\r", b"data: three\r"]);
+        assert_eq!(data, vec!["one", "two", "three"]);
+    }
+
+    #[test]
+    fn done_marker_with_crlf() {
+        let data = payloads(&[b"data: {\"x\":1}\r\n\r\ndata: [DONE]\r\n\r\n"]);
+        assert_eq!(data.last().map(String::as_str), Some("[DONE]"));
+    }
+
+    #[test]
+    fn data_without_space_after_colon() {
+        assert_eq!(data_payload("data:[DONE]"), Some("[DONE]"));
+        assert_eq!(data_payload("data:  two spaces"), Some(" two spaces"));
+        assert_eq!(data_payload(": keep-alive"), None);
+        assert_eq!(data_payload("event: message"), None);
+    }
+
+    #[test]
+    fn multibyte_character_split_across_chunks() {
+        let line = "data: 你好\r\n".as_bytes();
+        // “你”占 3 个字节，从中间截断
+        let (head, tail) = line.split_at(7);
+        assert_eq!(payloads(&[head, tail]), vec!["你好"]);
+    }
+
+    #[test]
+    fn unterminated_last_line_is_returned_on_finish() {
+        let mut buffer = SseLineBuffer::new();
+        assert!(buffer.push(b"data: tail").is_empty());
+        assert_eq!(buffer.finish().as_deref(), Some("data: tail"));
+        assert_eq!(buffer.finish(), None);
+    }
+}
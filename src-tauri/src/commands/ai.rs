@@ -1,8 +1,46 @@
-use crate::api::pollinations::ImageGenerationParams;
-use crate::models::TextModelConfigInput;
-use crate::services::GenerationService;
+use crate::api::deepseek::is_context_length_exceeded;
+use crate::api::pollinations::{is_prompt_filtered, is_rate_limited, ImageGenerationParams, PollinationsClient};
+use crate::models::{
+    Chapter, ChapterAmbiance, ChatPersona, ChatTurn, CreateAssetInput, CreateSnapshotInput,
+    EffectiveChapterSettings, TextModelConfigInput, UpdateChapterMetaInput,
+};
+use crate::services::{
+    AssetService, ChapterService, CharacterService, ChatPersonaService, GenerationService, GenerationTaskService,
+    OperationLogService, ProjectService, SettingsService, SnapshotService, TimelineService,
+};
+use crate::services::language_check::{
+    check_language, detect_language as detect_text_language, language_notice, LanguageCheck,
+    LanguageDetection,
+};
+use crate::services::character_service::CharacterField;
+use crate::services::operation_log_service::OperationRecord;
+use crate::services::chapter_context::{chapter_text, format_characters, is_blank, load_chapter_context};
+use crate::services::context_budget::{
+    fit_context, select_head_context, select_tail_context, ContextFitReport, ContextSection, FittedContext,
+};
+use crate::services::generation_mode::{
+    find_generation_mode, generation_modes, GenerationMode, POLISH_REVISION_GOALS,
+};
+use crate::services::generation_task_service::TaskTiming;
+use crate::commands::project::save_outline_version;
+use crate::commands::milestone::emit_milestones;
+use crate::services::{cancellation, request_log, text_service_cache, token_usage};
+use crate::services::cancellation::{CancelToken, TaskKind};
+use crate::services::llm_json::{extract_json, extract_string_field};
+use crate::services::prompt_guard::{data_boundary_notice, wrap_user_field};
+use crate::services::locked_passages::{reassemble, split_locked, LockedRange};
+use crate::services::narrative_check::{
+    apply_rewrites, numbered_batches, paragraph_spans, parse_drifts, NarrativeDrift, POVS, TENSES,
+};
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State, Window};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerateOutlineInput {
@@ -10,7 +48,10 @@ pub struct GenerateOutlineInput {
     pub genre: String,
     pub description: String,
     pub target_chapters: u32,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,7 +62,42 @@ pub struct GenerateChapterInput {
     pub previous_summary: Option<String>,
     pub character_info: Option<String>,
     pub world_info: Option<String>,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// 提供时使用该章节的生成参数覆盖
+    #[serde(default)]
+    pub chapter_id: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
+
+/// 同一章节的一个生成变体，未指定的参数沿用 text_config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantSpec {
+    pub label: Option<String>,
+    pub temperature: Option<f32>,
+    pub seed: Option<i64>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateChapterVariantsInput {
+    pub chapter_id: String,
+    pub variants: Vec<VariantSpec>,
+    pub previous_summary: Option<String>,
+    pub character_info: Option<String>,
+    pub world_info: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChapterVariantResult {
+    pub spec: VariantSpec,
+    pub label: String,
+    pub snapshot_id: Option<String>,
+    pub content: Option<String>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +105,17 @@ pub struct GenerateImageInput {
     pub params: ImageGenerationParams,
     pub save_path: String,
     pub pollinations_key: Option<String>,
+    /// 前端生成的请求 id，用于关联 image-progress 事件和取消；未传时自动生成
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// 提供时使用项目的图片模型和尺寸默认值，并把生成的图片登记为项目素材
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// 素材关联的对象（chapter / character）
+    #[serde(default)]
+    pub linked_to_type: Option<String>,
+    #[serde(default)]
+    pub linked_to_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,14 +123,20 @@ pub struct GeneratePrologueInput {
     pub title: String,
     pub genre: String,
     pub outline: String,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerateRevisionInput {
     pub text: String,
     pub goals: Option<String>,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,7 +147,8 @@ pub struct GenerateCharacterAppearanceInput {
     pub background: Option<String>,
     pub motivation: Option<String>,
     pub style: Option<String>,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -72,7 +166,8 @@ pub struct GenerateCharacterPortraitPromptInput {
     pub background: Option<String>,
     pub motivation: Option<String>,
     pub style: Option<String>,
-    pub text_config: TextModelConfigInput,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -80,295 +175,3189 @@ pub struct CharacterPortraitPromptResult {
     pub image_prompt: String,
 }
 
-fn build_text_service(config: &TextModelConfigInput) -> Result<GenerationService, String> {
-    config.validate()?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateCharacterFieldInput {
+    pub character_id: String,
+    /// personality / background / motivation / description
+    pub field: String,
+    pub instructions: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    Ok(GenerationService::new_with_text_config(
-        Some(config.api_key.clone()),
-        Some(config.normalized_api_base_url()),
-        Some(config.model.clone()),
-        Some(config.normalized_temperature(0.7)),
-        None,
-    ))
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateCharacterFieldResult {
+    pub field: String,
+    pub value: String,
+    /// 重写前的角色快照，可用于撤销
+    pub snapshot_id: Option<String>,
 }
 
-#[tauri::command]
-pub async fn generate_outline(input: GenerateOutlineInput) -> Result<String, String> {
-    let service = build_text_service(&input.text_config)?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateOutlineChapterInput {
+    pub chapter_id: String,
+    pub instructions: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    service
-        .generate_outline(
-            &input.title,
-            &input.genre,
-            &input.description,
-            input.target_chapters,
-        )
-        .await
-        .map_err(|e| e.to_string())
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateOutlineChapterResult {
+    pub outline_goal: String,
+    pub conflict: String,
+    pub cliffhanger: Option<String>,
+    /// 重写前的章节快照，可用于撤销
+    pub snapshot_id: Option<String>,
+    pub chapter: Chapter,
 }
 
-#[tauri::command]
-pub async fn generate_chapter(input: GenerateChapterInput) -> Result<String, String> {
-    let service = build_text_service(&input.text_config)?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateChapterModeInput {
+    pub chapter_id: String,
+    /// fast_draft / polished
+    pub mode: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    service
-        .generate_chapter(
-            &input.chapter_title,
-            &input.outline_goal,
-            &input.conflict,
-            input.previous_summary.as_deref(),
-            input.character_info.as_deref(),
-            input.world_info.as_deref(),
-        )
-        .await
-        .map_err(|e| e.to_string())
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsistencyIssue {
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub excerpt: Option<String>,
 }
 
-#[tauri::command]
-pub async fn generate_image(input: GenerateImageInput) -> Result<String, String> {
-    let service = GenerationService::new(None, input.pollinations_key);
+/// 生成模式流水线的各步结果；润色或检查失败不影响已生成的草稿
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterModeResult {
+    pub mode: String,
+    pub draft: String,
+    pub revised: Option<String>,
+    /// 最终采用的正文（有润色结果时为润色稿）
+    pub final_text: String,
+    pub consistency_issues: Option<Vec<ConsistencyIssue>>,
+    /// 失败但被跳过的后续步骤
+    pub warnings: Vec<String>,
+}
 
-    service
-        .generate_image(input.params, &input.save_path)
-        .await
-        .map_err(|e| e.to_string())
+/// chapter-mode-progress 事件负载：step 为 draft / revise / consistency
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterModeProgressEvent {
+    pub chapter_id: String,
+    pub mode: String,
+    pub step: String,
 }
 
-#[tauri::command]
-pub async fn generate_prologue(input: GeneratePrologueInput) -> Result<String, String> {
-    let service = build_text_service(&input.text_config)?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FindForeshadowingInput {
+    pub project_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    service
-        .generate_prologue(&input.title, &input.genre, &input.outline)
-        .await
-        .map_err(|e| e.to_string())
+#[derive(Debug, Clone, Serialize)]
+pub struct ForeshadowingThread {
+    pub element: String,
+    /// 埋下伏笔的章节；模型给出的序号无法对应章节时为 None
+    pub setup: Option<TimelineIssueChapter>,
+    pub payoffs: Vec<TimelineIssueChapter>,
+    /// 没有回收章节
+    pub unresolved: bool,
+    pub note: Option<String>,
 }
 
-#[tauri::command]
-pub async fn generate_revision(input: GenerateRevisionInput) -> Result<String, String> {
-    let service = build_text_service(&input.text_config)?;
-    let goals = input
-        .goals
-        .unwrap_or_else(|| "润色并保持原意，使表达更自然流畅".to_string());
+#[derive(Debug, Clone, Serialize)]
+pub struct ForeshadowingReport {
+    pub threads: Vec<ForeshadowingThread>,
+    pub unresolved_count: usize,
+    pub chapter_count: usize,
+    /// 既无摘要也无正文、未参与分析的章节
+    pub skipped_chapters: Vec<String>,
+}
 
-    service
-        .generate_revision(&input.text, &goals)
-        .await
-        .map_err(|e| e.to_string())
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateTransitionInput {
+    pub prev_chapter_id: String,
+    pub next_chapter_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
-#[tauri::command]
-pub async fn generate_character_appearance(
-    input: GenerateCharacterAppearanceInput,
-) -> Result<CharacterAppearanceResult, String> {
-    input.text_config.validate()?;
-    let client = Client::new();
-    let api_url = input.text_config.chat_completions_url();
-    let temperature = input.text_config.normalized_temperature(0.7);
-    let style = input.style.unwrap_or_default();
+/// 章节过渡建议，只返回给前端，由用户决定插入或替换
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterTransition {
+    pub prev_chapter_id: String,
+    pub next_chapter_id: String,
+    /// 插在上一章结尾之后的过渡段落
+    pub bridge: String,
+    /// 下一章开头的改写建议
+    pub opening_rewrite: Option<String>,
+    /// 被改写的下一章原始开头，便于前端定位替换
+    pub original_opening: String,
+}
 
-    let prompt = format!(
-        r#"你是一位专业的小说人物设定师和 AI 生图提示词工程师。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateAmbianceInput {
+    pub chapter_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-请基于以下角色信息完成两项任务：
-1) 生成中文“形象”描述（40~120字，具体，可用于小说人设）
-2) 生成英文“一寸证件照风格”图像提示词（image_prompt）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckNarrativeInput {
+    pub chapter_id: String,
+    /// first / third；为空时使用项目设置
+    #[serde(default)]
+    pub expected_pov: Option<String>,
+    /// past / present；为空时使用项目设置
+    #[serde(default)]
+    pub expected_tense: Option<String>,
+    /// 用模型给出的改写替换偏离段落并保存，保存前为原文留快照
+    #[serde(default)]
+    pub auto_fix: bool,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-角色名称：{}
-身份：{}
-性格：{}
-背景：{}
-动机：{}
-用户偏好风格（可能是中文，请先理解再转成英文风格词）：{}
+#[derive(Debug, Serialize)]
+pub struct NarrativeCheckResult {
+    pub chapter_id: String,
+    pub pov: Option<String>,
+    pub tense: Option<String>,
+    pub paragraph_count: usize,
+    pub drifts: Vec<NarrativeDrift>,
+    /// 自动修正替换的段落数
+    pub fixed_paragraphs: usize,
+    /// 自动修正前原文的快照
+    pub snapshot_id: Option<String>,
+}
 
-输出要求：
-- 严格输出 JSON
-- 不要输出任何额外解释
-- JSON 结构如下：
-{{"appearance":"中文形象文本","image_prompt":"English prompt"}}
-"#,
-        input.name.trim(),
-        input.role.unwrap_or_default().trim(),
-        input.personality.unwrap_or_default().trim(),
-        input.background.unwrap_or_default().trim(),
-        input.motivation.unwrap_or_default().trim(),
-        style.trim()
-    );
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegenerateWithLocksInput {
+    pub chapter_id: String,
+    /// 需原样保留的范围（UTF-16 偏移，不能重叠）
+    pub locked_ranges: Vec<LockedRange>,
+    #[serde(default)]
+    pub instructions: Option<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    let request_body = serde_json::json!({
-        "model": input.text_config.model,
-        "messages": [
-            {
-                "role": "system",
-                "content": "You are a character designer and image prompt engineer. Return JSON only."
-            },
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ],
-        "temperature": temperature,
-        "max_tokens": 500
-    });
+#[derive(Debug, Serialize)]
+pub struct LockedRegenerationResult {
+    pub chapter_id: String,
+    pub text: String,
+    /// 锁定段落在新正文中的范围，与传入顺序（按起点排序后）一一对应
+    pub locked_ranges: Vec<LockedRange>,
+    /// 重写前原文的快照
+    pub snapshot_id: String,
+}
 
-    let response = client
-        .post(&api_url)
-        .header("Authorization", format!("Bearer {}", input.text_config.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("请求失败: {}", e))?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CheckTimelineConsistencyInput {
+    pub project_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API 错误: {}", error_text));
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineIssueChapter {
+    pub id: String,
+    pub title: String,
+    pub order_index: i32,
+}
 
-    let response_json: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("解析响应失败: {}", e))?;
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineIssueEvent {
+    pub id: String,
+    pub title: String,
+}
 
-    let content = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or("无法获取 AI 响应内容")?;
+/// kind 为 order / contradiction / character / other
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineIssue {
+    pub kind: String,
+    pub description: String,
+    pub chapters: Vec<TimelineIssueChapter>,
+    pub events: Vec<TimelineIssueEvent>,
+}
 
-    let cleaned_content = content
-        .trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```")
-        .trim();
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineConsistencyReport {
+    pub issues: Vec<TimelineIssue>,
+    pub event_count: usize,
+    pub chapter_count: usize,
+    /// 既无摘要也无正文、未参与检查的章节
+    pub skipped_chapters: Vec<String>,
+}
 
-    let result: serde_json::Value = serde_json::from_str(cleaned_content)
-        .map_err(|e| format!("解析 AI 返回 JSON 失败: {}。原始内容: {}", e, cleaned_content))?;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatInput {
+    /// 提供时把项目简介和角色注入系统提示词
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// 人设 id，未提供时使用通用写作助手
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// 按时间顺序的对话记录，最后一条须为 user
+    pub messages: Vec<ChatTurn>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    let appearance = result["appearance"].as_str().unwrap_or("").trim().to_string();
-    let image_prompt = result["image_prompt"]
-        .as_str()
-        .unwrap_or("studio portrait, one-inch ID photo, clean background, realistic, high detail")
-        .trim()
-        .to_string();
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SummarizeAllChaptersInput {
+    pub project_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    if appearance.is_empty() {
-        return Err("AI 未返回有效的人物形象文本".to_string());
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterSummaryFailure {
+    pub chapter_id: String,
+    pub title: String,
+    pub error: String,
+}
 
-    Ok(CharacterAppearanceResult {
-        appearance,
-        image_prompt,
-    })
+#[derive(Debug, Serialize)]
+pub struct SummarizeAllChaptersResult {
+    pub total: usize,
+    pub summarized: usize,
+    /// 已有摘要或没有正文而跳过的章节数
+    pub skipped: usize,
+    pub failed: Vec<ChapterSummaryFailure>,
 }
 
-#[tauri::command]
-pub async fn generate_character_portrait_prompt(
-    input: GenerateCharacterPortraitPromptInput,
-) -> Result<CharacterPortraitPromptResult, String> {
-    input.text_config.validate()?;
-    let client = Client::new();
-    let api_url = input.text_config.chat_completions_url();
-    let temperature = input.text_config.normalized_temperature(0.6);
-    let style = input.style.unwrap_or_default();
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateAllChaptersInput {
+    pub project_id: String,
+    /// 为 true 时已有正文的章节也重新生成
+    #[serde(default)]
+    pub overwrite: bool,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-    let prompt = format!(
-        r#"你是专业的 AI 人像提示词工程师。请基于以下信息，生成一条用于人物“一寸证件照”风格的英文提示词。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetryFailedChaptersInput {
+    pub batch_task_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+}
 
-角色名称：{}
-形象描述：{}
-身份：{}
-性格：{}
-背景：{}
-动机：{}
-用户偏好风格（可能是中文，请先转换为英文风格词后再融合）：{}
+/// 批量生成中单个章节的结果，status 为 completed / failed / skipped；整体以 JSON 存在批次任务的 output_result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchChapterOutcome {
+    pub chapter_id: String,
+    pub title: String,
+    pub order_index: i32,
+    pub status: String,
+    pub error: Option<String>,
+}
 
-要求：
-- 输出必须是英文提示词（image_prompt）
-- 适合人像特写、一寸照构图、清晰面部细节
-- 不要输出解释
-- 严格输出 JSON：{{"image_prompt":"English prompt"}}
-"#,
-        input.name.trim(),
-        input.appearance.unwrap_or_default().trim(),
-        input.role.unwrap_or_default().trim(),
-        input.personality.unwrap_or_default().trim(),
-        input.background.unwrap_or_default().trim(),
-        input.motivation.unwrap_or_default().trim(),
-        style.trim()
-    );
+#[derive(Debug, Serialize)]
+pub struct ChapterBatchResult {
+    pub batch_task_id: Option<String>,
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub outcomes: Vec<BatchChapterOutcome>,
+}
 
-    let request_body = serde_json::json!({
-        "model": input.text_config.model,
-        "messages": [
-            {
-                "role": "system",
-                "content": "You are a professional portrait prompt engineer. Return JSON only."
-            },
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ],
-        "temperature": temperature,
-        "max_tokens": 300
-    });
+/// chapter-batch-progress 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterBatchProgressEvent {
+    pub batch_task_id: Option<String>,
+    pub chapter_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
 
-    let response = client
-        .post(&api_url)
-        .header("Authorization", format!("Bearer {}", input.text_config.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("请求失败: {}", e))?;
+/// 图片疑似被内容安全过滤时返回给前端的提示
+pub(crate) const PROMPT_FILTERED_MESSAGE: &str =
+    "图片提示词可能触发了内容安全过滤，服务只返回了占位图，已放弃保存。请改写描述（避免暴力、裸露等敏感内容）后重试";
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API 错误: {}", error_text));
-    }
+/// image-progress 事件负载：status 为 waiting / downloading / done / cancelled / filtered / failed
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageProgressEvent {
+    pub request_id: String,
+    pub status: String,
+    pub elapsed_ms: u64,
+    pub received_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub error: Option<String>,
+}
 
-    let response_json: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("解析响应失败: {}", e))?;
+/// summary-progress 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryProgressEvent {
+    pub project_id: String,
+    pub chapter_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
 
-    let content = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .ok_or("无法获取 AI 响应内容")?;
+#[derive(Debug, Serialize)]
+pub struct DetectLanguageResult {
+    pub detection: LanguageDetection,
+    /// 传入期望语言时给出逐段检查结果
+    pub check: Option<LanguageCheck>,
+}
 
-    let cleaned_content = content
-        .trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```")
-        .trim();
+/// language-mismatch 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageMismatchEvent {
+    pub source: String,
+    pub check: LanguageCheck,
+    /// 是否已以更强的语言要求重试过
+    pub retried: bool,
+}
 
-    let result: serde_json::Value = serde_json::from_str(cleaned_content)
-        .map_err(|e| format!("解析 AI 返回 JSON 失败: {}。原始内容: {}", e, cleaned_content))?;
+pub(crate) fn emit_language_mismatch(window: &Window, source: &str, check: LanguageCheck, retried: bool) {
+    log::warn!(
+        "{} output language mismatch: expected {}, {:.0}% of paragraphs differ",
+        source,
+        check.expected,
+        check.mismatch_ratio * 100.0
+    );
+    let _ = window.emit(
+        "language-mismatch",
+        LanguageMismatchEvent {
+            source: source.to_string(),
+            check,
+            retried,
+        },
+    );
+}
 
-    let image_prompt = result["image_prompt"]
-        .as_str()
-        .unwrap_or("studio portrait, one-inch ID photo, clean background, realistic, high detail")
-        .trim()
-        .to_string();
+/// chapter-generation-settings 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterSettingsEvent {
+    pub source: String,
+    pub settings: EffectiveChapterSettings,
+}
 
-    if image_prompt.is_empty() {
-        return Err("AI 未返回有效的人像提示词".to_string());
+/// 读取章节的生成参数覆盖并与传入配置合并；未指定章节时原样返回
+pub(crate) async fn resolve_chapter_overrides(
+    pool: &SqlitePool,
+    chapter_id: Option<&str>,
+    config: &TextModelConfigInput,
+    target_words: Option<u32>,
+) -> Result<(TextModelConfigInput, EffectiveChapterSettings), String> {
+    let chapter = match chapter_id {
+        Some(chapter_id) => Some(
+            ChapterService::get_by_id(pool, chapter_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("章节不存在")?,
+        ),
+        None => None,
+    };
+    Ok(EffectiveChapterSettings::resolve(chapter.as_ref(), config, target_words))
+}
+
+/// 把本次章节生成实际使用的模型、温度和目标字数推送给前端
+pub(crate) fn emit_chapter_settings(window: &Window, source: &str, settings: &EffectiveChapterSettings) {
+    if !settings.overridden.is_empty() {
+        log::info!("{} uses chapter overrides: {}", source, settings.overridden.join(", "));
     }
+    let _ = window.emit(
+        "chapter-generation-settings",
+        ChapterSettingsEvent {
+            source: source.to_string(),
+            settings: settings.clone(),
+        },
+    );
+}
 
-    Ok(CharacterPortraitPromptResult { image_prompt })
+/// context-trimmed 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextTrimmedEvent {
+    pub source: String,
+    pub report: ContextFitReport,
 }
 
-#[tauri::command]
-pub async fn test_deepseek_connection(api_key: String) -> Result<bool, String> {
-    let service = GenerationService::new(Some(api_key), None);
-    service.test_deepseek().await.map_err(|e| e.to_string())
+/// 按注入预算裁剪章节上下文，优先级：前情提要 > 角色 > 世界观 > 时间线
+pub(crate) fn trim_chapter_context(
+    source: &str,
+    budget_tokens: usize,
+    previous_summary: Option<&str>,
+    characters: Option<&str>,
+    world_setting: Option<&str>,
+    timeline: Option<&str>,
+) -> FittedContext {
+    let sections = [
+        ContextSection::new("previous_summary", 0, true, previous_summary),
+        ContextSection::new("characters", 1, false, characters),
+        ContextSection::new("world_setting", 2, false, world_setting),
+        ContextSection::new("timeline", 3, false, timeline),
+    ];
+    let context = fit_context(sections.into_iter().flatten().collect(), budget_tokens);
+
+    if context.report.has_cuts() {
+        log::warn!(
+            "{} context exceeds injection budget of {} tokens, trimmed to fit",
+            source,
+            budget_tokens
+        );
+    }
+    context
 }
 
-#[tauri::command]
-pub async fn test_text_connection(text_config: TextModelConfigInput) -> Result<bool, String> {
-    let service = build_text_service(&text_config)?;
-    service.test_deepseek().await.map_err(|e| e.to_string())
+/// 同 trim_chapter_context，有裁剪时通过 context-trimmed 事件通知前端
+pub(crate) fn fit_chapter_context(
+    window: &Window,
+    source: &str,
+    budget_tokens: usize,
+    previous_summary: Option<&str>,
+    characters: Option<&str>,
+    world_setting: Option<&str>,
+    timeline: Option<&str>,
+) -> FittedContext {
+    let context = trim_chapter_context(source, budget_tokens, previous_summary, characters, world_setting, timeline);
+
+    if context.report.has_cuts() {
+        let _ = window.emit(
+            "context-trimmed",
+            ContextTrimmedEvent {
+                source: source.to_string(),
+                report: context.report.clone(),
+            },
+        );
+    }
+    context
+}
+
+/// 模型报告上下文超长时的重试预算：本次实际注入量的一半；没有注入内容可裁剪时返回 None
+pub(crate) fn context_retry_budget(report: &ContextFitReport) -> Option<usize> {
+    (report.used_tokens > 0).then_some(report.used_tokens / 2)
+}
+
+/// 裁剪上下文后仍超长（或无可裁剪内容）时返回给前端的说明
+pub(crate) fn context_too_long_error(report: &ContextFitReport, retried: bool) -> String {
+    if retried {
+        format!(
+            "提示词过长：已将注入的上下文（前情提要、角色、设定等）裁剪到约 {} tokens 后重试，仍超出模型的上下文长度。请精简大纲或已有正文，或换用上下文更长的模型",
+            report.used_tokens
+        )
+    } else {
+        "提示词过长：超出模型的上下文长度，且没有可裁剪的注入上下文。请精简大纲或已有正文，或换用上下文更长的模型"
+            .to_string()
+    }
+}
+
+pub(crate) fn build_text_service(config: &TextModelConfigInput) -> Result<GenerationService, String> {
+    config.validate()?;
+
+    Ok(text_service_cache::text_service(config))
+}
+
+/// 未传入文本模型配置时回退到已保存的设置
+pub(crate) async fn resolve_text_config(
+    pool: &SqlitePool,
+    config: Option<TextModelConfigInput>,
+) -> Result<TextModelConfigInput, String> {
+    SettingsService::resolve_text_config(pool, config)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 生成任务记录的输入参数（不含 API Key）
+fn task_input_params(config: &TextModelConfigInput, mut params: serde_json::Value) -> serde_json::Value {
+    params["provider"] = serde_json::json!(config.provider);
+    params["model"] = serde_json::json!(config.model);
+    params["temperature"] = serde_json::json!(config.normalized_temperature(0.7));
+    params["seed"] = serde_json::json!(config.effective_seed());
+    params
+}
+
+/// 提供 project_id 时将本次生成写入 generation_tasks；记录失败只写日志，不影响生成结果。
+/// 生成过程中的日志按任务 id 归集，可用 get_logs 取回
+async fn track_generation<F>(
+    pool: &SqlitePool,
+    project_id: Option<&str>,
+    task_type: &str,
+    input_params: serde_json::Value,
+    seed: Option<i64>,
+    generation: F,
+) -> Result<String, String>
+where
+    F: Future<Output = anyhow::Result<String>>,
+{
+    let task = match project_id {
+        Some(project_id) => {
+            match GenerationTaskService::start(pool, project_id, task_type, &input_params, seed).await {
+                Ok(task) => Some(task),
+                Err(e) => {
+                    log::warn!("Failed to record {} task: {}", task_type, e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let correlation_id = request_log::correlation_id(task.as_ref().map(|t| t.id.as_str()));
+    let started = Instant::now();
+    let result = request_log::scope(correlation_id, async {
+        request_log::info(&format!(
+            "{} generation started: provider={}, model={}",
+            task_type,
+            input_params["provider"].as_str().unwrap_or_default(),
+            input_params["model"].as_str().unwrap_or_default()
+        ));
+        let (result, usage) = token_usage::scope(generation).await;
+        match &result {
+            Ok(content) => request_log::info(&format!(
+                "{} generation completed in {} ms ({} chars)",
+                task_type,
+                started.elapsed().as_millis(),
+                content.chars().count()
+            )),
+            Err(e) => request_log::warn(&format!(
+                "{} generation failed after {} ms: {}",
+                task_type,
+                started.elapsed().as_millis(),
+                e
+            )),
+        }
+        (result, usage)
+    })
+    .await;
+    let (result, usage) = result;
+
+    if let Some(task) = task {
+        let timing = TaskTiming {
+            latency_ms: started.elapsed().as_millis() as i64,
+            first_token_ms: None,
+        };
+        let recorded = match &result {
+            Ok(content) => GenerationTaskService::complete(pool, &task.id, Some(content), usage, timing).await,
+            Err(e) => GenerationTaskService::fail(pool, &task.id, &e.to_string(), timing).await,
+        };
+        if let Err(e) = recorded {
+            log::warn!("Failed to update task {}: {}", task.id, e);
+        }
+    }
+
+    result.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn generate_outline(
+    pool: State<'_, SqlitePool>,
+    input: GenerateOutlineInput,
+) -> Result<String, String> {
+    let config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    run_outline(&pool, &input, &config).await
+}
+
+/// 生成大纲并记录生成任务，提供 project_id 时保存为大纲版本；HTTP 服务也走这里
+pub(crate) async fn run_outline(
+    pool: &SqlitePool,
+    input: &GenerateOutlineInput,
+    config: &TextModelConfigInput,
+) -> Result<String, String> {
+    let service = build_text_service(config)?;
+    let params = task_input_params(
+        config,
+        serde_json::json!({ "title": input.title, "target_chapters": input.target_chapters }),
+    );
+
+    let outline = track_generation(
+        pool,
+        input.project_id.as_deref(),
+        "outline",
+        params,
+        config.effective_seed(),
+        service.generate_outline(
+            &input.title,
+            &input.genre,
+            &input.description,
+            input.target_chapters,
+        ),
+    )
+    .await?;
+
+    if let Some(project_id) = input.project_id.as_deref() {
+        if let Err(e) = save_outline_version(pool, project_id, &outline, Some(config), None).await {
+            log::warn!("Failed to save outline version for project {}: {}", project_id, e);
+        }
+    }
+    Ok(outline)
+}
+
+#[tauri::command]
+pub async fn generate_chapter(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: GenerateChapterInput,
+) -> Result<String, String> {
+    let base_config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    run_chapter(&pool, Some(&window), &input, &base_config).await
+}
+
+/// 章节生成：按注入预算裁剪上下文并记录生成任务，超出模型上下文时缩小预算重试一次，再按设置做语言检查。
+/// window 为 None（HTTP 服务）时不推送事件，也不做后台叙事检查
+pub(crate) async fn run_chapter(
+    pool: &SqlitePool,
+    window: Option<&Window>,
+    input: &GenerateChapterInput,
+    base_config: &TextModelConfigInput,
+) -> Result<String, String> {
+    let (config, effective) =
+        resolve_chapter_overrides(pool, input.chapter_id.as_deref(), base_config, None).await?;
+    if let Some(window) = window {
+        emit_chapter_settings(window, "chapter", &effective);
+    }
+    let service = build_text_service(&config)?.with_chapter_target_words(effective.target_words);
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "chapter_title": input.chapter_title, "target_words": effective.target_words }),
+    );
+    let settings = SettingsService::get(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let language_settings = settings.language_check;
+    let fit = |budget: usize| {
+        let previous_summary = input.previous_summary.as_deref();
+        let characters = input.character_info.as_deref();
+        let world_setting = input.world_info.as_deref();
+        match window {
+            Some(window) => {
+                fit_chapter_context(window, "chapter", budget, previous_summary, characters, world_setting, None)
+            }
+            None => trim_chapter_context("chapter", budget, previous_summary, characters, world_setting, None),
+        }
+    };
+    let mut context = fit(settings.context_budget.injection_tokens);
+
+    // 超出模型上下文时按更小的预算重新裁剪，重试一次
+    let mut trimmed_retry = false;
+    let content = loop {
+        let result = track_generation(
+            pool,
+            input.project_id.as_deref(),
+            "chapter",
+            params.clone(),
+            config.effective_seed(),
+            service.generate_chapter(
+                &input.chapter_title,
+                &input.outline_goal,
+                &input.conflict,
+                context.get("previous_summary"),
+                context.get("characters"),
+                context.get("world_setting"),
+            ),
+        )
+        .await;
+        match result {
+            Err(e) if is_context_length_exceeded(&e) => {
+                match context_retry_budget(&context.report).filter(|_| !trimmed_retry) {
+                    Some(budget) => {
+                        log::warn!("Chapter prompt exceeds model context, retrying with {} tokens", budget);
+                        context = fit(budget);
+                        trimmed_retry = true;
+                    }
+                    None => return Err(context_too_long_error(&context.report, trimmed_retry)),
+                }
+            }
+            result => break result?,
+        }
+    };
+
+    if let Some(project_id) = input.project_id.as_deref() {
+        OperationLogService::record(
+            pool,
+            OperationRecord {
+                project_id: Some(project_id),
+                operation: "generate_chapter",
+                target_type: "chapter",
+                target_id: None,
+                summary: format!("生成章节「{}」（{} 字）", input.chapter_title, content.chars().count()),
+                snapshot_id: None,
+            },
+        )
+        .await;
+        if let Some(window) = window {
+            spawn_narrative_check(
+                window.clone(),
+                pool.clone(),
+                config.clone(),
+                "chapter",
+                project_id.to_string(),
+                input.chapter_id.clone(),
+                content.clone(),
+            );
+        }
+    }
+
+    if language_settings.mode == "off" {
+        return Ok(content);
+    }
+
+    let expected = match input.project_id.as_deref() {
+        Some(project_id) => ProjectService::get_by_id(pool, project_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .map(|project| project.language)
+            .unwrap_or_else(|| "zh".to_string()),
+        None => "zh".to_string(),
+    };
+    let check = check_language(&content, &expected, language_settings.mismatch_threshold);
+    if !check.mismatch {
+        return Ok(content);
+    }
+    if language_settings.mode != "retry" {
+        if let Some(window) = window {
+            emit_language_mismatch(window, "chapter", check, false);
+        }
+        return Ok(content);
+    }
+
+    // 以更强的语言要求重试一次，仍不符时返回重试结果并提示
+    let strict = build_text_service(&config)?
+        .with_chapter_target_words(effective.target_words)
+        .with_language_notice(language_notice(&check.expected));
+    let retried = track_generation(
+        pool,
+        input.project_id.as_deref(),
+        "chapter",
+        params,
+        config.effective_seed(),
+        strict.generate_chapter(
+            &input.chapter_title,
+            &input.outline_goal,
+            &input.conflict,
+            context.get("previous_summary"),
+            context.get("characters"),
+            context.get("world_setting"),
+        ),
+    )
+    .await?;
+
+    let recheck = check_language(&retried, &expected, language_settings.mismatch_threshold);
+    if let (true, Some(window)) = (recheck.mismatch, window) {
+        emit_language_mismatch(window, "chapter", recheck, true);
+    }
+    Ok(retried)
+}
+
+/// 列出可选的生成模式
+#[tauri::command]
+pub async fn get_generation_modes(pool: State<'_, SqlitePool>) -> Result<Vec<GenerationMode>, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    Ok(generation_modes(&settings.generation_modes))
+}
+
+/// 按生成模式跑完整流程：生成草稿 → （可选）润色 → （可选）设定一致性检查。
+/// 只返回结果，不写入章节
+#[tauri::command]
+pub async fn generate_chapter_mode(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: GenerateChapterModeInput,
+) -> Result<ChapterModeResult, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    let mode = find_generation_mode(&input.mode, &settings.generation_modes)
+        .ok_or_else(|| format!("未知的生成模式: {}", input.mode))?;
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+
+    let base_config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    let config = mode.apply(&base_config);
+    let service = build_text_service(&config)?.with_chapter_max_tokens(mode.max_tokens);
+    let project_id = Some(chapter.project_id.as_str());
+
+    let loaded = load_chapter_context(&pool, &chapter, Some(&config))
+        .await
+        .map_err(|e| e.to_string())?;
+    let fit = |budget: usize| {
+        fit_chapter_context(
+            &window,
+            "chapter_mode",
+            budget,
+            loaded.previous_tail.as_deref(),
+            loaded.characters_info.as_deref(),
+            loaded.world_setting.as_deref(),
+            loaded.timeline.as_deref(),
+        )
+    };
+    let mut context = fit(settings.context_budget.injection_tokens);
+
+    let emit_step = |step: &str| {
+        let _ = window.emit(
+            "chapter-mode-progress",
+            ChapterModeProgressEvent {
+                chapter_id: chapter.id.clone(),
+                mode: mode.key.to_string(),
+                step: step.to_string(),
+            },
+        );
+    };
+    let params = |step: &str| {
+        task_input_params(
+            &config,
+            serde_json::json!({ "chapter_title": chapter.title, "mode": mode.key, "step": step }),
+        )
+    };
+
+    emit_step("draft");
+    let mut trimmed_retry = false;
+    let draft = loop {
+        let result = track_generation(
+            &pool,
+            project_id,
+            "chapter",
+            params("draft"),
+            config.effective_seed(),
+            service.generate_chapter(
+                &chapter.title,
+                chapter.outline_goal.as_deref().unwrap_or_default(),
+                chapter.conflict.as_deref().unwrap_or_default(),
+                context.get("previous_summary"),
+                context.get("characters"),
+                context.get("world_setting"),
+            ),
+        )
+        .await;
+        match result {
+            Err(e) if is_context_length_exceeded(&e) => {
+                match context_retry_budget(&context.report).filter(|_| !trimmed_retry) {
+                    Some(budget) => {
+                        log::warn!("Chapter prompt exceeds model context, retrying with {} tokens", budget);
+                        context = fit(budget);
+                        trimmed_retry = true;
+                    }
+                    None => return Err(context_too_long_error(&context.report, trimmed_retry)),
+                }
+            }
+            result => break result?,
+        }
+    };
+
+    let mut warnings = Vec::new();
+    let mut revised = None;
+    if mode.revise {
+        emit_step("revise");
+        match track_generation(
+            &pool,
+            project_id,
+            "revision",
+            params("revise"),
+            config.effective_seed(),
+            service.generate_revision(&draft, POLISH_REVISION_GOALS),
+        )
+        .await
+        {
+            Ok(text) if !text.trim().is_empty() => revised = Some(text),
+            Ok(_) => warnings.push("润色结果为空，已保留草稿".to_string()),
+            Err(e) => warnings.push(format!("润色失败，已保留草稿: {}", e)),
+        }
+    }
+    let final_text = revised.clone().unwrap_or_else(|| draft.clone());
+
+    let mut consistency_issues = None;
+    if mode.consistency_check {
+        emit_step("consistency");
+        let checked = service
+            .check_consistency(
+                &chapter.title,
+                &final_text,
+                context.get("characters"),
+                context.get("world_setting"),
+                context.get("timeline"),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|content| extract_json(&content))
+            .and_then(|value| {
+                let issues = if value.is_array() { value } else { value["issues"].clone() };
+                serde_json::from_value::<Vec<ConsistencyIssue>>(issues).map_err(|e| e.to_string())
+            });
+        match checked {
+            Ok(issues) => consistency_issues = Some(issues),
+            Err(e) => warnings.push(format!("一致性检查失败: {}", e)),
+        }
+    }
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id,
+            operation: "generate_chapter",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!(
+                "以{}模式生成章节「{}」（{} 字）",
+                mode.label,
+                chapter.title,
+                final_text.chars().count()
+            ),
+            snapshot_id: None,
+        },
+    )
+    .await;
+
+    Ok(ChapterModeResult {
+        mode: mode.key.to_string(),
+        draft,
+        revised,
+        final_text,
+        consistency_issues,
+        warnings,
+    })
+}
+
+/// 检测文本的主要语言；传入 expected_language 时按设置中的阈值逐段检查
+#[tauri::command]
+pub async fn detect_language(
+    pool: State<'_, SqlitePool>,
+    text: String,
+    expected_language: Option<String>,
+) -> Result<DetectLanguageResult, String> {
+    let check = match expected_language {
+        Some(expected) => {
+            let threshold = SettingsService::get(&pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .language_check
+                .mismatch_threshold;
+            Some(check_language(&text, &expected, threshold))
+        }
+        None => None,
+    };
+
+    Ok(DetectLanguageResult {
+        detection: detect_text_language(&text),
+        check,
+    })
+}
+
+const MAX_CHAPTER_VARIANTS: usize = 6;
+const VARIANT_CONCURRENCY: usize = 3;
+
+impl VariantSpec {
+    fn apply(&self, base: &TextModelConfigInput) -> TextModelConfigInput {
+        let mut config = base.clone();
+        if let Some(temperature) = self.temperature {
+            config.temperature = temperature;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(ref model) = self.model {
+            if !model.trim().is_empty() {
+                config.model = model.trim().to_string();
+            }
+        }
+        config
+    }
+
+    fn display_label(&self, index: usize, config: &TextModelConfigInput) -> String {
+        let name = self
+            .label
+            .clone()
+            .filter(|label| !label.trim().is_empty())
+            .unwrap_or_else(|| format!("变体 {}", index + 1));
+        let seed = config
+            .effective_seed()
+            .map(|seed| seed.to_string())
+            .unwrap_or_else(|| "random".to_string());
+        format!(
+            "{} (model={}, temperature={:.2}, seed={})",
+            name,
+            config.model,
+            config.normalized_temperature(0.7),
+            seed
+        )
+    }
+}
+
+/// 以不同参数并发生成同一章节的多个版本，每个版本保存为快照供对比挑选
+#[tauri::command]
+pub async fn generate_chapter_variants(
+    pool: State<'_, SqlitePool>,
+    input: GenerateChapterVariantsInput,
+) -> Result<Vec<ChapterVariantResult>, String> {
+    if input.variants.is_empty() {
+        return Err("至少需要一个变体配置".to_string());
+    }
+    if input.variants.len() > MAX_CHAPTER_VARIANTS {
+        return Err(format!("一次最多生成 {} 个变体", MAX_CHAPTER_VARIANTS));
+    }
+
+    let base_config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+
+    let pool = pool.inner();
+    let chapter = &chapter;
+    let input = &input;
+
+    let results = stream::iter(input.variants.iter().cloned().enumerate())
+        .map(|(index, spec)| {
+            let config = spec.apply(&base_config);
+            async move {
+                let label = spec.display_label(index, &config);
+                let service = match build_text_service(&config) {
+                    Ok(service) => service.without_request_dedup(),
+                    Err(error) => {
+                        return ChapterVariantResult {
+                            spec,
+                            label,
+                            snapshot_id: None,
+                            content: None,
+                            error: Some(error),
+                        }
+                    }
+                };
+
+                let params = task_input_params(
+                    &config,
+                    serde_json::json!({ "chapter_id": chapter.id, "variant": label }),
+                );
+                let generated = track_generation(
+                    pool,
+                    Some(&chapter.project_id),
+                    "chapter_variant",
+                    params,
+                    config.effective_seed(),
+                    service.generate_chapter(
+                        &chapter.title,
+                        chapter.outline_goal.as_deref().unwrap_or_default(),
+                        chapter.conflict.as_deref().unwrap_or_default(),
+                        input.previous_summary.as_deref(),
+                        input.character_info.as_deref(),
+                        input.world_info.as_deref(),
+                    ),
+                )
+                .await;
+
+                let content = match generated {
+                    Ok(content) => content,
+                    Err(error) => {
+                        return ChapterVariantResult {
+                            spec,
+                            label,
+                            snapshot_id: None,
+                            content: None,
+                            error: Some(error),
+                        }
+                    }
+                };
+
+                let snapshot = SnapshotService::create(
+                    pool,
+                    CreateSnapshotInput {
+                        target_type: "chapter".to_string(),
+                        target_id: chapter.id.clone(),
+                        content: content.clone(),
+                        note: Some(label.clone()),
+                        model: Some(config.model.clone()),
+                        temperature: Some(config.normalized_temperature(0.7) as f64),
+                        prompt_template: Some("chapter".to_string()),
+                    },
+                )
+                .await;
+
+                match snapshot {
+                    Ok(snapshot) => ChapterVariantResult {
+                        spec,
+                        label,
+                        snapshot_id: Some(snapshot.id),
+                        content: Some(content),
+                        error: None,
+                    },
+                    Err(e) => ChapterVariantResult {
+                        spec,
+                        label,
+                        snapshot_id: None,
+                        content: Some(content),
+                        error: Some(format!("保存快照失败: {}", e)),
+                    },
+                }
+            }
+        })
+        .buffered(VARIANT_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(results)
+}
+
+/// 将选中的变体快照写入章节草稿
+#[tauri::command]
+pub async fn promote_chapter_variant(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    snapshot_id: String,
+) -> Result<(), String> {
+    let snapshot = SnapshotService::get_by_id(&pool, &snapshot_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("快照不存在")?;
+    if snapshot.target_type != "chapter" {
+        return Err("该快照不属于章节".to_string());
+    }
+    let chapter = ChapterService::get_by_id(&pool, &snapshot.target_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+
+    let milestones = ChapterService::update_text(
+        &pool,
+        &chapter.id,
+        Some(snapshot.content),
+        chapter.final_text,
+        None,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn generate_image(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: GenerateImageInput,
+) -> Result<String, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    let image = SettingsService::image_settings_for(&pool, input.project_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut params = input.params;
+    image.fill_params(&mut params, (image.width, image.height));
+
+    let pollinations_key = input.pollinations_key.or(settings.pollinations_api_key);
+    let service = GenerationService::new(None, pollinations_key);
+
+    let request_id = input.request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = CancelToken::register(TaskKind::Image, request_id.clone());
+
+    let started = Instant::now();
+    let emit = |status: &str, received_bytes: u64, total_bytes: Option<u64>, error: Option<String>| {
+        let _ = window.emit(
+            "image-progress",
+            ImageProgressEvent {
+                request_id: request_id.clone(),
+                status: status.to_string(),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                received_bytes,
+                total_bytes,
+                error,
+            },
+        );
+    };
+
+    let metadata = serde_json::json!({
+        "prompt": params.prompt,
+        "model": params.model,
+        "width": params.width,
+        "height": params.height,
+        "seed": params.seed,
+    });
+    let result = service
+        .generate_image(params, &input.save_path, cancel.flag(), |progress| {
+            emit(progress.status, progress.received_bytes, progress.total_bytes, None);
+        })
+        .await;
+
+    match result {
+        Ok(path) => {
+            if let Some(project_id) = input.project_id {
+                let registered = AssetService::create(
+                    &pool,
+                    CreateAssetInput {
+                        project_id,
+                        asset_type: "illustration".to_string(),
+                        file_path: path.clone(),
+                        linked_to_type: input.linked_to_type,
+                        linked_to_id: input.linked_to_id,
+                        metadata: Some(metadata.to_string()),
+                    },
+                )
+                .await;
+                // 图片已保存成功，登记失败只记日志
+                if let Err(e) = registered {
+                    log::warn!("Failed to register image asset {}: {}", path, e);
+                }
+            }
+            emit("done", 0, None, None);
+            Ok(path)
+        }
+        Err(_) if cancel.is_cancelled() => {
+            emit("cancelled", 0, None, None);
+            Err("图片生成已取消".to_string())
+        }
+        Err(e) if is_prompt_filtered(&e.to_string()) => {
+            emit("filtered", 0, None, Some(e.to_string()));
+            Err(PROMPT_FILTERED_MESSAGE.to_string())
+        }
+        Err(e) => {
+            emit("failed", 0, None, Some(e.to_string()));
+            Err(e.to_string())
+        }
+    }
+}
+
+/// 取消进行中的图片请求；不传 request_id 时取消全部
+#[tauri::command]
+pub fn cancel_image_generation(request_id: Option<String>) -> Result<(), String> {
+    match request_id {
+        Some(id) if !cancellation::cancel(TaskKind::Image, &id) => Err("图片请求不存在或已结束".to_string()),
+        Some(_) => Ok(()),
+        None => {
+            cancellation::cancel_all(TaskKind::Image);
+            Ok(())
+        }
+    }
+}
+
+/// 批量插图默认同时处理的章节数与上限（Pollinations 对并发请求有限流）
+const DEFAULT_ILLUSTRATION_CONCURRENCY: usize = 2;
+const MAX_ILLUSTRATION_CONCURRENCY: usize = 4;
+/// 图片请求被限流时的重试次数和首次等待时间（之后每次翻倍）
+const IMAGE_RATE_LIMIT_RETRIES: u32 = 3;
+const IMAGE_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(10);
+/// 生成插图提示词时送入模型的正文上限（字符）
+const ILLUSTRATION_INPUT_CHARS: usize = 6000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateIllustrationsBatchInput {
+    pub chapter_ids: Vec<String>,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
+    #[serde(default)]
+    pub pollinations_key: Option<String>,
+    /// 同时处理的章节数，默认 2，最多 4
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// 图片风格（可以是中文）
+    #[serde(default)]
+    pub style: Option<String>,
+    /// 前端生成的批次 id，传给 cancel_image_generation 可取消整批；未传时自动生成
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+/// 单个章节的插图结果：status 为 done / filtered / cancelled / failed
+#[derive(Debug, Clone, Serialize)]
+pub struct IllustrationOutcome {
+    pub chapter_id: String,
+    pub title: Option<String>,
+    pub status: String,
+    pub prompt: Option<String>,
+    pub file_path: Option<String>,
+    pub asset_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// illustration-batch-progress 事件负载：开始处理章节时 status 为 running，结束时同 IllustrationOutcome
+#[derive(Debug, Clone, Serialize)]
+pub struct IllustrationBatchProgressEvent {
+    pub request_id: String,
+    pub chapter_id: String,
+    pub status: String,
+    pub completed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+// 等待一段时间，期间取消则提前返回
+async fn wait_unless_cancelled(duration: Duration, cancel: &AtomicBool) {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline && !cancel.load(Ordering::SeqCst) {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// 为单个章节生成插图提示词和图片，并登记到 assets；prompt 在生成提示词后即写入，失败时也能返回
+#[allow(clippy::too_many_arguments)]
+async fn create_chapter_illustration(
+    pool: &SqlitePool,
+    text_service: &GenerationService,
+    image_service: &GenerationService,
+    config: &TextModelConfigInput,
+    chapter: &Chapter,
+    style: Option<&str>,
+    output_dir: &std::path::Path,
+    cancel: &AtomicBool,
+    prompt: &mut Option<String>,
+) -> Result<(String, String), String> {
+    let text = chapter_text(chapter)
+        .or(chapter.summary.as_deref().filter(|s| !s.trim().is_empty()))
+        .ok_or("章节还没有正文或摘要")?;
+    let params = task_input_params(config, serde_json::json!({ "chapter_id": chapter.id, "style": style }));
+    let content = track_generation(
+        pool,
+        Some(&chapter.project_id),
+        "illustration_prompt",
+        params,
+        config.effective_seed(),
+        text_service.generate_chapter_illustration_prompt(
+            &chapter.title,
+            &excerpt_chars(text, ILLUSTRATION_INPUT_CHARS),
+            style,
+        ),
+    )
+    .await?;
+    let image_prompt = extract_json(&content)?["image_prompt"]
+        .as_str()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .ok_or("AI返回的插图提示词为空")?
+        .to_string();
+    *prompt = Some(image_prompt.clone());
+
+    let image = SettingsService::image_settings_for(pool, Some(&chapter.project_id))
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut params = ImageGenerationParams {
+        prompt: image_prompt,
+        width: None,
+        height: None,
+        seed: None,
+        model: None,
+        nologo: None,
+        enhance: None,
+    };
+    image.fill_params(&mut params, (image.width, image.height));
+
+    std::fs::create_dir_all(output_dir).map_err(|e| format!("创建插图目录失败: {}", e))?;
+    let save_path = output_dir
+        .join(format!("{}-{}.png", chapter.id, chrono::Utc::now().timestamp_millis()))
+        .to_string_lossy()
+        .to_string();
+
+    let mut attempt = 0;
+    let file_path = loop {
+        if cancel.load(Ordering::SeqCst) {
+            return Err("图片生成已取消".to_string());
+        }
+        match image_service.generate_image(params.clone(), &save_path, cancel, |_| {}).await {
+            Ok(path) => break path,
+            Err(e) if is_rate_limited(&e.to_string()) && attempt < IMAGE_RATE_LIMIT_RETRIES => {
+                let wait = IMAGE_RATE_LIMIT_BACKOFF * 2u32.pow(attempt);
+                attempt += 1;
+                log::warn!("Image request rate limited, retrying chapter {} in {:?}", chapter.id, wait);
+                wait_unless_cancelled(wait, cancel).await;
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+
+    let metadata = serde_json::json!({
+        "prompt": params.prompt,
+        "model": params.model,
+        "width": params.width,
+        "height": params.height,
+        "seed": params.seed,
+    });
+    let asset = AssetService::create(
+        pool,
+        CreateAssetInput {
+            project_id: chapter.project_id.clone(),
+            asset_type: "illustration".to_string(),
+            file_path,
+            linked_to_type: Some("chapter".to_string()),
+            linked_to_id: Some(chapter.id.clone()),
+            metadata: Some(metadata.to_string()),
+        },
+    )
+    .await
+    .map_err(|e| format!("保存素材记录失败: {}", e))?;
+    Ok((asset.id, asset.file_path))
+}
+
+/// 批量为章节生成插图：每章先生成插图提示词再生成图片，保存到应用数据目录并登记为素材。
+/// 并发受限，被限流时退避重试；可用 request_id 取消。返回每章结果（含失败原因）
+#[tauri::command]
+pub async fn generate_illustrations_batch(
+    window: Window,
+    app_handle: AppHandle,
+    pool: State<'_, SqlitePool>,
+    input: GenerateIllustrationsBatchInput,
+) -> Result<Vec<IllustrationOutcome>, String> {
+    let mut chapter_ids: Vec<String> = Vec::new();
+    for id in input.chapter_ids {
+        if !chapter_ids.contains(&id) {
+            chapter_ids.push(id);
+        }
+    }
+    if chapter_ids.is_empty() {
+        return Err("未选择章节".to_string());
+    }
+    let illustrations_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("无法获取应用数据目录")?
+        .join("illustrations");
+
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let text_service = build_text_service(&config)?;
+    let image_service = GenerationService::new(None, input.pollinations_key.or(settings.pollinations_api_key));
+    let concurrency = input
+        .concurrency
+        .unwrap_or(DEFAULT_ILLUSTRATION_CONCURRENCY)
+        .clamp(1, MAX_ILLUSTRATION_CONCURRENCY);
+
+    let request_id = input.request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel_token = CancelToken::register(TaskKind::Image, request_id.clone());
+
+    let total = chapter_ids.len();
+    let completed = AtomicUsize::new(0);
+    let pool = pool.inner();
+    let style = input.style.as_deref();
+    let (text_service, image_service, config, window, completed, cancel, request_id, illustrations_dir) = (
+        &text_service,
+        &image_service,
+        &config,
+        &window,
+        &completed,
+        cancel_token.flag(),
+        &request_id,
+        &illustrations_dir,
+    );
+    let emit = move |chapter_id: &str, status: &str, completed: usize, error: Option<String>| {
+        let _ = window.emit(
+            "illustration-batch-progress",
+            IllustrationBatchProgressEvent {
+                request_id: request_id.clone(),
+                chapter_id: chapter_id.to_string(),
+                status: status.to_string(),
+                completed,
+                total,
+                error,
+            },
+        );
+    };
+
+    let outcomes = stream::iter(chapter_ids)
+        .map(|chapter_id| async move {
+            let mut outcome = IllustrationOutcome {
+                chapter_id: chapter_id.clone(),
+                title: None,
+                status: "failed".to_string(),
+                prompt: None,
+                file_path: None,
+                asset_id: None,
+                error: None,
+            };
+            let result = if cancel.load(Ordering::SeqCst) {
+                Err("图片生成已取消".to_string())
+            } else {
+                match ChapterService::get_by_id(pool, &chapter_id).await {
+                    Ok(Some(chapter)) => {
+                        outcome.title = Some(chapter.title.clone());
+                        emit(&chapter_id, "running", completed.load(Ordering::SeqCst), None);
+                        let output_dir = illustrations_dir.join(&chapter.project_id);
+                        create_chapter_illustration(
+                            pool,
+                            text_service,
+                            image_service,
+                            config,
+                            &chapter,
+                            style,
+                            &output_dir,
+                            cancel,
+                            &mut outcome.prompt,
+                        )
+                        .await
+                    }
+                    Ok(None) => Err("章节不存在".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            };
+
+            match result {
+                Ok((asset_id, file_path)) => {
+                    outcome.status = "done".to_string();
+                    outcome.asset_id = Some(asset_id);
+                    outcome.file_path = Some(file_path);
+                }
+                Err(_) if cancel.load(Ordering::SeqCst) => {
+                    outcome.status = "cancelled".to_string();
+                    outcome.error = Some("图片生成已取消".to_string());
+                }
+                Err(e) if is_prompt_filtered(&e) => {
+                    outcome.status = "filtered".to_string();
+                    outcome.error = Some(PROMPT_FILTERED_MESSAGE.to_string());
+                }
+                Err(e) => outcome.error = Some(e),
+            }
+            let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+            emit(&chapter_id, &outcome.status, done, outcome.error.clone());
+            outcome
+        })
+        .buffered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    Ok(outcomes)
+}
+
+#[tauri::command]
+pub async fn generate_prologue(
+    pool: State<'_, SqlitePool>,
+    input: GeneratePrologueInput,
+) -> Result<String, String> {
+    let config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    run_prologue(&pool, &input, &config).await
+}
+
+/// 生成序章并记录生成任务；HTTP 服务也走这里
+pub(crate) async fn run_prologue(
+    pool: &SqlitePool,
+    input: &GeneratePrologueInput,
+    config: &TextModelConfigInput,
+) -> Result<String, String> {
+    let service = build_text_service(config)?;
+    let params = task_input_params(config, serde_json::json!({ "title": input.title }));
+
+    track_generation(
+        pool,
+        input.project_id.as_deref(),
+        "prologue",
+        params,
+        config.effective_seed(),
+        service.generate_prologue(&input.title, &input.genre, &input.outline),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn generate_revision(
+    pool: State<'_, SqlitePool>,
+    input: GenerateRevisionInput,
+) -> Result<String, String> {
+    let config = resolve_text_config(&pool, input.text_config.clone()).await?;
+    run_revision(&pool, &input, &config).await
+}
+
+/// 润色文本并记录生成任务；HTTP 服务也走这里
+pub(crate) async fn run_revision(
+    pool: &SqlitePool,
+    input: &GenerateRevisionInput,
+    config: &TextModelConfigInput,
+) -> Result<String, String> {
+    let service = build_text_service(config)?;
+    let goals = input
+        .goals
+        .clone()
+        .unwrap_or_else(|| "润色并保持原意，使表达更自然流畅".to_string());
+    let params = task_input_params(config, serde_json::json!({ "goals": goals }));
+
+    track_generation(
+        pool,
+        input.project_id.as_deref(),
+        "revision",
+        params,
+        config.effective_seed(),
+        service.generate_revision(&input.text, &goals),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn generate_character_appearance(
+    pool: State<'_, SqlitePool>,
+    input: GenerateCharacterAppearanceInput,
+) -> Result<CharacterAppearanceResult, String> {
+    let text_config = SettingsService::resolve_text_config(&pool, input.text_config.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+    text_config.validate()?;
+    let client = Client::new();
+    let api_url = text_config.chat_completions_url();
+    let temperature = text_config.normalized_temperature(0.7);
+    let style = input.style.unwrap_or_default();
+
+    let prompt = format!(
+        r#"你是一位专业的小说人物设定师和 AI 生图提示词工程师。
+
+请基于以下角色信息完成两项任务：
+1) 生成中文“形象”描述（40~120字，具体，可用于小说人设）
+2) 生成英文“一寸证件照风格”图像提示词（image_prompt）
+
+角色名称：{}
+身份：{}
+性格：{}
+背景：{}
+动机：{}
+用户偏好风格（可能是中文，请先理解再转成英文风格词）：{}
+
+输出要求：
+- 严格输出 JSON
+- 不要输出任何额外解释
+- JSON 结构如下：
+{{"appearance":"中文形象文本","image_prompt":"English prompt"}}
+"#,
+        input.name.trim(),
+        input.role.unwrap_or_default().trim(),
+        input.personality.unwrap_or_default().trim(),
+        input.background.unwrap_or_default().trim(),
+        input.motivation.unwrap_or_default().trim(),
+        style.trim()
+    );
+
+    let mut request_body = serde_json::json!({
+        "model": text_config.model,
+        "messages": [
+            {
+                "role": "system",
+                "content": "You are a character designer and image prompt engineer. Return JSON only."
+            },
+            {
+                "role": "user",
+                "content": prompt
+            }
+        ],
+        "temperature": temperature,
+        "max_tokens": 500
+    });
+    if let Some(seed) = text_config.effective_seed() {
+        request_body["seed"] = serde_json::json!(seed);
+    }
+
+    let response = client
+        .post(&api_url)
+        .header("Authorization", format!("Bearer {}", text_config.api_key))
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API 错误: {}", error_text));
+    }
+
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析响应失败: {}", e))?;
+
+    let content = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or("无法获取 AI 响应内容")?;
+
+    let default_prompt = "studio portrait, one-inch ID photo, clean background, realistic, high detail";
+    let (appearance, image_prompt) = match extract_json(content) {
+        Ok(result) => {
+            let appearance = result["appearance"].as_str().unwrap_or("").trim().to_string();
+            if appearance.is_empty() {
+                return Err("AI 未返回有效的人物形象文本".to_string());
+            }
+            let image_prompt = result["image_prompt"]
+                .as_str()
+                .unwrap_or(default_prompt)
+                .trim()
+                .to_string();
+            (appearance, image_prompt)
+        }
+        // JSON 略有损坏（如中文描述里有未转义的引号）时逐字段提取，两个字段都取不到才报错
+        Err(e) => {
+            let appearance = extract_string_field(content, "appearance");
+            let image_prompt = extract_string_field(content, "image_prompt");
+            if appearance.is_none() && image_prompt.is_none() {
+                return Err(e);
+            }
+            log::warn!("Recovered character appearance fields from malformed JSON");
+            (
+                appearance.unwrap_or_default(),
+                image_prompt.unwrap_or_else(|| default_prompt.to_string()),
+            )
+        }
+    };
+
+    Ok(CharacterAppearanceResult {
+        appearance,
+        image_prompt,
+    })
+}
+
+#[tauri::command]
+pub async fn generate_character_portrait_prompt(
+    pool: State<'_, SqlitePool>,
+    input: GenerateCharacterPortraitPromptInput,
+) -> Result<CharacterPortraitPromptResult, String> {
+    let text_config = SettingsService::resolve_text_config(&pool, input.text_config.clone())
+        .await
+        .map_err(|e| e.to_string())?;
+    text_config.validate()?;
+    let client = Client::new();
+    let api_url = text_config.chat_completions_url();
+    let temperature = text_config.normalized_temperature(0.6);
+    let style = input.style.unwrap_or_default();
+
+    let prompt = format!(
+        r#"你是专业的 AI 人像提示词工程师。请基于以下信息，生成一条用于人物“一寸证件照”风格的英文提示词。
+
+角色名称：{}
+形象描述：{}
+身份：{}
+性格：{}
+背景：{}
+动机：{}
+用户偏好风格（可能是中文，请先转换为英文风格词后再融合）：{}
+
+要求：
+- 输出必须是英文提示词（image_prompt）
+- 适合人像特写、一寸照构图、清晰面部细节
+- 不要输出解释
+- 严格输出 JSON：{{"image_prompt":"English prompt"}}
+"#,
+        input.name.trim(),
+        input.appearance.unwrap_or_default().trim(),
+        input.role.unwrap_or_default().trim(),
+        input.personality.unwrap_or_default().trim(),
+        input.background.unwrap_or_default().trim(),
+        input.motivation.unwrap_or_default().trim(),
+        style.trim()
+    );
+
+    let mut request_body = serde_json::json!({
+        "model": text_config.model,
+        "messages": [
+            {
+                "role": "system",
+                "content": "You are a professional portrait prompt engineer. Return JSON only."
+            },
+            {
+                "role": "user",
+                "content": prompt
+            }
+        ],
+        "temperature": temperature,
+        "max_tokens": 300
+    });
+    if let Some(seed) = text_config.effective_seed() {
+        request_body["seed"] = serde_json::json!(seed);
+    }
+
+    let response = client
+        .post(&api_url)
+        .header("Authorization", format!("Bearer {}", text_config.api_key))
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API 错误: {}", error_text));
+    }
+
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析响应失败: {}", e))?;
+
+    let content = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or("无法获取 AI 响应内容")?;
+
+    let result = extract_json(content)?;
+
+    let image_prompt = result["image_prompt"]
+        .as_str()
+        .unwrap_or("studio portrait, one-inch ID photo, clean background, realistic, high detail")
+        .trim()
+        .to_string();
+
+    if image_prompt.is_empty() {
+        return Err("AI 未返回有效的人像提示词".to_string());
+    }
+
+    Ok(CharacterPortraitPromptResult { image_prompt })
+}
+
+#[tauri::command]
+pub async fn regenerate_character_field(
+    pool: State<'_, SqlitePool>,
+    input: RegenerateCharacterFieldInput,
+) -> Result<RegenerateCharacterFieldResult, String> {
+    let field = CharacterField::parse(&input.field)
+        .ok_or_else(|| format!("不支持重写的字段: {}", input.field))?;
+    if input.instructions.trim().is_empty() {
+        return Err("修改要求不能为空".to_string());
+    }
+
+    let character = CharacterService::get_by_id(&pool, &input.character_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("角色不存在")?;
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(
+        &config,
+        serde_json::json!({
+            "character_id": character.id,
+            "field": field.column(),
+            "instructions": input.instructions,
+        }),
+    );
+
+    let value = track_generation(
+        &pool,
+        Some(&character.project_id),
+        "character_field",
+        params,
+        config.effective_seed(),
+        service.regenerate_character_field(&character, field, &input.instructions),
+    )
+    .await?;
+
+    // 先保存旧版本再覆盖，快照失败不阻断更新
+    let snapshot_id =
+        OperationLogService::snapshot_character(&pool, &character, &format!("重写{}前", field.label())).await;
+
+    CharacterService::update_field(&pool, &character.id, field, &value)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&character.project_id),
+            operation: "regenerate_character_field",
+            target_type: "character",
+            target_id: Some(&character.id),
+            summary: format!("重写角色「{}」的{}", character.name, field.label()),
+            snapshot_id: snapshot_id.clone(),
+        },
+    )
+    .await;
+
+    Ok(RegenerateCharacterFieldResult {
+        field: field.column().to_string(),
+        value,
+        snapshot_id,
+    })
+}
+
+/// 大纲上下文中取前后各几章
+const OUTLINE_NEIGHBOR_CHAPTERS: usize = 3;
+
+/// 只重写一章的大纲（目标/冲突/结尾钩子），前后章节大纲作为上下文；旧元数据存快照以便撤销
+#[tauri::command]
+pub async fn regenerate_outline_chapter(
+    pool: State<'_, SqlitePool>,
+    input: RegenerateOutlineChapterInput,
+) -> Result<RegenerateOutlineChapterResult, String> {
+    if input.instructions.trim().is_empty() {
+        return Err("修改要求不能为空".to_string());
+    }
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let chapters = ChapterService::get_by_project(&pool, &chapter.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let position = chapters.iter().position(|c| c.id == chapter.id).unwrap_or(0);
+    let surrounding: Vec<&Chapter> = chapters
+        .iter()
+        .enumerate()
+        .filter(|(index, c)| c.id != chapter.id && index.abs_diff(position) <= OUTLINE_NEIGHBOR_CHAPTERS)
+        .map(|(_, c)| c)
+        .collect();
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "chapter_id": chapter.id, "instructions": input.instructions }),
+    );
+
+    let content = track_generation(
+        &pool,
+        Some(&chapter.project_id),
+        "outline_chapter",
+        params,
+        config.effective_seed(),
+        service.regenerate_outline_entry(&chapter, &surrounding, &input.instructions),
+    )
+    .await?;
+
+    let value = extract_json(&content)?;
+    let text_field = |key: &str| {
+        value[key]
+            .as_str()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let outline_goal = text_field("outline_goal").ok_or("AI未返回本章目标")?;
+    let conflict = text_field("conflict").ok_or("AI未返回核心冲突")?;
+    let cliffhanger = text_field("cliffhanger");
+
+    // 先保存旧元数据再覆盖，快照失败不阻断更新
+    let snapshot_id = OperationLogService::snapshot_chapter(&pool, &chapter).await;
+    let updated = ChapterService::update_meta(
+        &pool,
+        &chapter.id,
+        UpdateChapterMetaInput {
+            title: None,
+            order_index: None,
+            outline_goal: Some(outline_goal.clone()),
+            conflict: Some(conflict.clone()),
+            cliffhanger: cliffhanger.clone(),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "regenerate_outline_chapter",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("重写章节「{}」的大纲", chapter.title),
+            snapshot_id: snapshot_id.clone(),
+        },
+    )
+    .await;
+
+    Ok(RegenerateOutlineChapterResult {
+        outline_goal,
+        conflict,
+        cliffhanger,
+        snapshot_id,
+        chapter: updated,
+    })
+}
+
+/// 时间线检查中每章摘要的最大字数（无摘要时取正文开头）
+const TIMELINE_CHECK_CHAPTER_CHARS: usize = 300;
+
+fn excerpt_chars(text: &str, max: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() > max {
+        text.chars().take(max).collect::<String>() + "..."
+    } else {
+        text.to_string()
+    }
+}
+
+/// 按 "[C序号] 标题：摘要" 逐章列出摘要（无摘要时取正文开头），返回 (参与的章节, 各行, 跳过的章节 id)
+fn chapter_summary_lines(chapters: &[Chapter], max_chars: usize) -> (Vec<&Chapter>, Vec<String>, Vec<String>) {
+    let mut skipped = Vec::new();
+    let mut included: Vec<&Chapter> = Vec::new();
+    let mut lines = Vec::new();
+    for chapter in chapters {
+        let summary = chapter
+            .summary
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .or_else(|| chapter_text(chapter));
+        let Some(summary) = summary else {
+            skipped.push(chapter.id.clone());
+            continue;
+        };
+        lines.push(format!(
+            "[C{}] {}：{}",
+            chapter.order_index,
+            chapter.title,
+            excerpt_chars(summary, max_chars)
+        ));
+        included.push(chapter);
+    }
+    (included, lines, skipped)
+}
+
+/// 解析模型返回的序号列表，忽略无法识别的项
+fn referenced_numbers(value: &serde_json::Value) -> Vec<i64> {
+    value
+        .as_array()
+        .map(|items| items.iter().filter_map(referenced_number).collect())
+        .unwrap_or_default()
+}
+
+/// 兼容 "C3" / "E2" 这类带前缀的写法
+fn referenced_number(item: &serde_json::Value) -> Option<i64> {
+    item.as_i64().or_else(|| {
+        item.as_str()
+            .and_then(|s| s.trim_matches(|c: char| !c.is_ascii_digit()).parse().ok())
+    })
+}
+
+/// 把时间线和各章摘要交给模型，检查事件顺序错乱、前后矛盾和角色提前出场
+#[tauri::command]
+pub async fn check_timeline_consistency(
+    pool: State<'_, SqlitePool>,
+    input: CheckTimelineConsistencyInput,
+) -> Result<TimelineConsistencyReport, String> {
+    let events = TimelineService::get_by_project(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if events.is_empty() {
+        return Err("项目还没有时间线事件".to_string());
+    }
+    let chapters = ChapterService::get_by_project(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let characters = CharacterService::get_by_project(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let (checked, chapter_lines, skipped_chapters) = chapter_summary_lines(&chapters, TIMELINE_CHECK_CHAPTER_CHARS);
+    if checked.is_empty() {
+        return Err("没有可供检查的章节摘要或正文".to_string());
+    }
+
+    let event_lines: Vec<String> = events
+        .iter()
+        .enumerate()
+        .map(|(index, event)| {
+            let mut line = format!("[E{}] {}", index + 1, event.title);
+            if let Some(time) = event.event_time.as_deref().filter(|t| !t.trim().is_empty()) {
+                line.push_str(&format!("（{}）", time));
+            }
+            if let Some(order) = event
+                .chapter_id
+                .as_deref()
+                .and_then(|id| chapters.iter().find(|c| c.id == id))
+                .map(|c| c.order_index)
+            {
+                line.push_str(&format!(" 关联章节 [C{}]", order));
+            }
+            if let Some(description) = event.description.as_deref().filter(|d| !d.trim().is_empty()) {
+                line.push_str(&format!("：{}", description.trim()));
+            }
+            line
+        })
+        .collect();
+    let character_names = characters
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>()
+        .join("、");
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "events": events.len(), "chapters": checked.len() }),
+    );
+
+    let content = track_generation(
+        &pool,
+        Some(&input.project_id),
+        "timeline_check",
+        params,
+        config.effective_seed(),
+        service.check_timeline(&event_lines.join("\n"), &chapter_lines.join("\n"), &character_names),
+    )
+    .await?;
+
+    let value = extract_json(&content)?;
+    let raw_issues = if value.is_array() { value } else { value["issues"].clone() };
+    let issues = raw_issues
+        .as_array()
+        .ok_or("AI返回的检查结果格式错误")?
+        .iter()
+        .filter_map(|item| {
+            let description = item["description"].as_str().map(str::trim).filter(|d| !d.is_empty())?;
+            let chapters = referenced_numbers(&item["chapters"])
+                .into_iter()
+                .filter_map(|order| checked.iter().find(|c| c.order_index as i64 == order))
+                .map(|c| TimelineIssueChapter {
+                    id: c.id.clone(),
+                    title: c.title.clone(),
+                    order_index: c.order_index,
+                })
+                .collect();
+            let events = referenced_numbers(&item["events"])
+                .into_iter()
+                .filter_map(|number| usize::try_from(number - 1).ok().and_then(|i| events.get(i)))
+                .map(|e| TimelineIssueEvent { id: e.id.clone(), title: e.title.clone() })
+                .collect();
+            Some(TimelineIssue {
+                kind: item["type"].as_str().unwrap_or("other").trim().to_string(),
+                description: description.to_string(),
+                chapters,
+                events,
+            })
+        })
+        .collect();
+
+    Ok(TimelineConsistencyReport {
+        issues,
+        event_count: events.len(),
+        chapter_count: checked.len(),
+        skipped_chapters,
+    })
+}
+
+/// 伏笔分析中每章摘要的最大字数（无摘要时取正文开头）
+const FORESHADOWING_CHAPTER_CHARS: usize = 400;
+
+/// 把各章摘要交给模型，找出伏笔与回收章节，并标记尚未回收的伏笔
+#[tauri::command]
+pub async fn find_foreshadowing(
+    pool: State<'_, SqlitePool>,
+    input: FindForeshadowingInput,
+) -> Result<ForeshadowingReport, String> {
+    let chapters = ChapterService::get_by_project(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let (analyzed, chapter_lines, skipped_chapters) =
+        chapter_summary_lines(&chapters, FORESHADOWING_CHAPTER_CHARS);
+    if analyzed.len() < 2 {
+        return Err("至少需要两个有摘要或正文的章节".to_string());
+    }
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(&config, serde_json::json!({ "chapters": analyzed.len() }));
+
+    let content = track_generation(
+        &pool,
+        Some(&input.project_id),
+        "foreshadowing",
+        params,
+        config.effective_seed(),
+        service.find_foreshadowing(&chapter_lines.join("\n")),
+    )
+    .await?;
+
+    let chapter_ref = |order: i64| {
+        analyzed
+            .iter()
+            .find(|c| c.order_index as i64 == order)
+            .map(|c| TimelineIssueChapter {
+                id: c.id.clone(),
+                title: c.title.clone(),
+                order_index: c.order_index,
+            })
+    };
+    let value = extract_json(&content)?;
+    let raw = if value.is_array() { value } else { value["foreshadowing"].clone() };
+    let threads: Vec<ForeshadowingThread> = raw
+        .as_array()
+        .ok_or("AI返回的伏笔分析格式错误")?
+        .iter()
+        .filter_map(|item| {
+            let element = item["element"].as_str().map(str::trim).filter(|e| !e.is_empty())?;
+            let setup = referenced_number(&item["setup"]).and_then(chapter_ref);
+            let mut payoffs: Vec<TimelineIssueChapter> = referenced_numbers(&item["payoffs"])
+                .into_iter()
+                .filter_map(chapter_ref)
+                .collect();
+            payoffs.sort_by_key(|c| c.order_index);
+            payoffs.dedup_by(|a, b| a.id == b.id);
+            Some(ForeshadowingThread {
+                element: element.to_string(),
+                unresolved: payoffs.is_empty(),
+                setup,
+                payoffs,
+                note: item["note"].as_str().map(str::trim).filter(|n| !n.is_empty()).map(str::to_string),
+            })
+        })
+        .collect();
+
+    Ok(ForeshadowingReport {
+        unresolved_count: threads.iter().filter(|t| t.unresolved).count(),
+        threads,
+        chapter_count: analyzed.len(),
+        skipped_chapters,
+    })
+}
+
+/// 过渡生成时上一章结尾 / 下一章开头各取的 token 数
+const TRANSITION_CONTEXT_TOKENS: usize = 1200;
+
+/// 读取上一章结尾和下一章开头，生成过渡段落和开头改写建议；不修改章节内容
+#[tauri::command]
+pub async fn generate_transition(
+    pool: State<'_, SqlitePool>,
+    input: GenerateTransitionInput,
+) -> Result<ChapterTransition, String> {
+    if input.prev_chapter_id == input.next_chapter_id {
+        return Err("请选择两个不同的章节".to_string());
+    }
+    let prev = ChapterService::get_by_id(&pool, &input.prev_chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("上一章不存在")?;
+    let next = ChapterService::get_by_id(&pool, &input.next_chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("下一章不存在")?;
+    if prev.project_id != next.project_id {
+        return Err("两个章节不属于同一项目".to_string());
+    }
+    let prev_text = chapter_text(&prev).ok_or("上一章还没有正文")?;
+    let next_text = chapter_text(&next).ok_or("下一章还没有正文")?;
+    let prev_tail = select_tail_context(prev_text, TRANSITION_CONTEXT_TOKENS);
+    let next_head = select_head_context(next_text, TRANSITION_CONTEXT_TOKENS);
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "prev_chapter_id": prev.id, "next_chapter_id": next.id }),
+    );
+
+    let content = track_generation(
+        &pool,
+        Some(&prev.project_id),
+        "transition",
+        params,
+        config.effective_seed(),
+        service.generate_transition(&prev.title, prev_tail, &next.title, next_head),
+    )
+    .await?;
+
+    let value = extract_json(&content)?;
+    let bridge = value["bridge"]
+        .as_str()
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .ok_or("AI返回的过渡段落为空")?
+        .to_string();
+    let opening_rewrite = value["opening_rewrite"]
+        .as_str()
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(str::to_string);
+    let original_opening = next_head.to_string();
+
+    Ok(ChapterTransition {
+        prev_chapter_id: prev.id,
+        next_chapter_id: next.id,
+        bridge,
+        opening_rewrite,
+        original_opening,
+    })
+}
+
+/// 配乐提示词分析时送入模型的正文上限（字符）
+const AMBIANCE_INPUT_CHARS: usize = 6000;
+
+fn string_list(value: &serde_json::Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::trim).filter(|s| !s.is_empty()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 分析章节情绪，生成 AI 音乐生成工具可用的配乐提示词并保存到章节
+#[tauri::command]
+pub async fn generate_ambiance_prompt(
+    pool: State<'_, SqlitePool>,
+    input: GenerateAmbianceInput,
+) -> Result<ChapterAmbiance, String> {
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let text = chapter_text(&chapter)
+        .or(chapter.summary.as_deref().filter(|s| !s.trim().is_empty()))
+        .ok_or("章节还没有正文或摘要")?;
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(&config, serde_json::json!({ "chapter_id": chapter.id }));
+
+    let content = track_generation(
+        &pool,
+        Some(&chapter.project_id),
+        "ambiance",
+        params,
+        config.effective_seed(),
+        service.generate_ambiance(&chapter.title, &excerpt_chars(text, AMBIANCE_INPUT_CHARS)),
+    )
+    .await?;
+
+    let value = extract_json(&content)?;
+    let prompt = value["prompt"]
+        .as_str()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .ok_or("AI返回的配乐提示词为空")?
+        .to_string();
+    let ambiance = ChapterAmbiance {
+        prompt,
+        tempo: value["tempo"].as_str().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string),
+        instrumentation: string_list(&value["instrumentation"]),
+        mood: string_list(&value["mood"]),
+        tags: string_list(&value["tags"]).into_iter().map(|tag| tag.to_lowercase()).collect(),
+        generated_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    let json = serde_json::to_string(&ambiance).map_err(|e| e.to_string())?;
+    ChapterService::update_ambiance(&pool, &chapter.id, &json)
+        .await
+        .map_err(|e| e.to_string())?;
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "generate_ambiance_prompt",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("生成章节「{}」的配乐提示词", chapter.title),
+            snapshot_id: None,
+        },
+    )
+    .await;
+    Ok(ambiance)
+}
+
+/// 人称/时态检查时单批送入模型的正文上限（字符）
+const NARRATIVE_BATCH_CHARS: usize = 6000;
+
+/// narrative-drift 事件负载：生成后自动检查发现偏离时发出
+#[derive(Debug, Clone, Serialize)]
+pub struct NarrativeDriftEvent {
+    pub source: String,
+    pub chapter_id: Option<String>,
+    pub pov: Option<String>,
+    pub tense: Option<String>,
+    pub drifts: Vec<NarrativeDrift>,
+}
+
+fn normalize_narrative_value(value: Option<&str>, allowed: &[&str], label: &str) -> Result<Option<String>, String> {
+    match value.map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty()) {
+        Some(value) if !allowed.contains(&value.as_str()) => {
+            Err(format!("不支持的叙述{}: {}（可选值: {}）", label, value, allowed.join(", ")))
+        }
+        value => Ok(value),
+    }
+}
+
+// 改写结果写回正文所在的字段：有定稿时改定稿，否则改草稿（与 chapter_text 的取值一致）；
+// 保存后推送新达成的字数里程碑
+async fn save_chapter_body(window: &Window, pool: &SqlitePool, chapter: &Chapter, text: String) -> Result<(), String> {
+    let has_final = chapter.final_text.as_deref().is_some_and(|t| !t.trim().is_empty());
+    let (draft_text, final_text) = if has_final {
+        (chapter.draft_text.clone(), Some(text))
+    } else {
+        (Some(text), chapter.final_text.clone())
+    };
+    let milestones = ChapterService::update_text(pool, &chapter.id, draft_text, final_text, None)
+        .await
+        .map_err(|e| format!("保存章节失败: {}", e))?;
+    emit_milestones(window, milestones);
+    Ok(())
+}
+
+// 分批检查正文的人称与时态，返回按段落排序的偏离列表
+async fn detect_narrative_drift(
+    pool: &SqlitePool,
+    project_id: &str,
+    config: &TextModelConfigInput,
+    text: &str,
+    pov: Option<&str>,
+    tense: Option<&str>,
+    rewrite: bool,
+) -> Result<Vec<NarrativeDrift>, String> {
+    let service = build_text_service(config)?;
+    let spans = paragraph_spans(text);
+    let mut drifts = Vec::new();
+    for (index, batch) in numbered_batches(&spans, NARRATIVE_BATCH_CHARS).iter().enumerate() {
+        let params = task_input_params(
+            config,
+            serde_json::json!({ "pov": pov, "tense": tense, "batch": index, "rewrite": rewrite }),
+        );
+        let content = track_generation(
+            pool,
+            Some(project_id),
+            "narrative_check",
+            params,
+            config.effective_seed(),
+            service.check_narrative(batch, pov, tense, rewrite),
+        )
+        .await?;
+        drifts.extend(parse_drifts(&extract_json(&content)?, &spans, pov.is_some(), tense.is_some()));
+    }
+    drifts.sort_by_key(|drift| (drift.paragraph_index, drift.kind.clone()));
+    Ok(drifts)
+}
+
+/// 检查章节叙述是否偏离期望的人称与时态，返回偏离段落的 UTF-16 偏移；
+/// auto_fix 时用改写替换偏离段落并保存（原文留快照）
+#[tauri::command]
+pub async fn check_narrative_consistency(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: CheckNarrativeInput,
+) -> Result<NarrativeCheckResult, String> {
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let text = chapter_text(&chapter).ok_or("章节还没有正文")?.to_string();
+
+    let project_settings = SettingsService::get_project_narrative(&pool, &chapter.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let pov = match normalize_narrative_value(input.expected_pov.as_deref(), &POVS, "人称")? {
+        Some(pov) => Some(pov),
+        None => project_settings.pov,
+    };
+    let tense = match normalize_narrative_value(input.expected_tense.as_deref(), &TENSES, "时态")? {
+        Some(tense) => Some(tense),
+        None => project_settings.tense,
+    };
+    if pov.is_none() && tense.is_none() {
+        return Err("未设置期望的叙述人称或时态".to_string());
+    }
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let drifts = detect_narrative_drift(
+        &pool,
+        &chapter.project_id,
+        &config,
+        &text,
+        pov.as_deref(),
+        tense.as_deref(),
+        input.auto_fix,
+    )
+    .await?;
+    let spans = paragraph_spans(&text);
+    let mut result = NarrativeCheckResult {
+        chapter_id: chapter.id.clone(),
+        pov,
+        tense,
+        paragraph_count: spans.len(),
+        drifts,
+        fixed_paragraphs: 0,
+        snapshot_id: None,
+    };
+    if !input.auto_fix {
+        return Ok(result);
+    }
+
+    let (fixed, count) = apply_rewrites(&text, &spans, &result.drifts);
+    if count == 0 {
+        return Ok(result);
+    }
+    let snapshot = SnapshotService::create(
+        &pool,
+        CreateSnapshotInput {
+            target_type: "chapter".to_string(),
+            target_id: chapter.id.clone(),
+            content: text,
+            note: Some("人称/时态修正前".to_string()),
+            model: None,
+            temperature: None,
+            prompt_template: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    save_chapter_body(&window, &pool, &chapter, fixed).await?;
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "fix_narrative_drift",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("修正章节「{}」中 {} 段的人称/时态", chapter.title, count),
+            snapshot_id: Some(snapshot.id.clone()),
+        },
+    )
+    .await;
+
+    result.fixed_paragraphs = count;
+    result.snapshot_id = Some(snapshot.id);
+    Ok(result)
+}
+
+/// 项目开启了自动检查时，在后台检查刚生成的内容，发现偏离时发出 narrative-drift 事件
+pub(crate) fn spawn_narrative_check(
+    window: Window,
+    pool: SqlitePool,
+    config: TextModelConfigInput,
+    source: &str,
+    project_id: String,
+    chapter_id: Option<String>,
+    content: String,
+) {
+    let source = source.to_string();
+    tauri::async_runtime::spawn(async move {
+        let settings = match SettingsService::get_project_narrative(&pool, &project_id).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Failed to load narrative settings for {}: {}", project_id, e);
+                return;
+            }
+        };
+        if !settings.auto_check || (settings.pov.is_none() && settings.tense.is_none()) {
+            return;
+        }
+        let drifts = detect_narrative_drift(
+            &pool,
+            &project_id,
+            &config,
+            &content,
+            settings.pov.as_deref(),
+            settings.tense.as_deref(),
+            false,
+        )
+        .await;
+        match drifts {
+            Ok(drifts) if drifts.is_empty() => {}
+            Ok(drifts) => {
+                log::warn!("{} output drifts from the project POV/tense in {} places", source, drifts.len());
+                let _ = window.emit(
+                    "narrative-drift",
+                    NarrativeDriftEvent {
+                        source,
+                        chapter_id,
+                        pov: settings.pov,
+                        tense: settings.tense,
+                        drifts,
+                    },
+                );
+            }
+            Err(e) => log::warn!("Automatic narrative check failed: {}", e),
+        }
+    });
+}
+
+/// 保留锁定段落重写章节：先为原文留快照，模型只重写锁定段落之间的内容，
+/// 锁定段落原样拼回；返回新正文及锁定段落在新正文中的 UTF-16 范围
+#[tauri::command]
+pub async fn regenerate_chapter_with_locks(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: RegenerateWithLocksInput,
+) -> Result<LockedRegenerationResult, String> {
+    let chapter = ChapterService::get_by_id(&pool, &input.chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let text = chapter_text(&chapter).ok_or("章节还没有正文")?.to_string();
+    let segments = split_locked(&text, &input.locked_ranges).map_err(|e| format!("锁定范围无效: {}", e))?;
+
+    let mut annotated = String::new();
+    for (index, gap) in segments.gaps.iter().enumerate() {
+        annotated.push_str(&format!("[GAP{}]\n{}\n", index + 1, gap.trim()));
+        if let Some(lock) = segments.locks.get(index) {
+            annotated.push_str(&format!("[LOCK{}]\n{}\n", index + 1, lock.trim()));
+        }
+    }
+
+    let snapshot = SnapshotService::create(
+        &pool,
+        CreateSnapshotInput {
+            target_type: "chapter".to_string(),
+            target_id: chapter.id.clone(),
+            content: text.clone(),
+            note: Some("保留锁定段落重写前".to_string()),
+            model: None,
+            temperature: None,
+            prompt_template: None,
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let instructions = input.instructions.unwrap_or_default();
+    let params = task_input_params(
+        &config,
+        serde_json::json!({
+            "chapter_id": chapter.id,
+            "locked_ranges": segments.locks.len(),
+            "instructions": instructions,
+        }),
+    );
+    let content = track_generation(
+        &pool,
+        Some(&chapter.project_id),
+        "regenerate_locked",
+        params,
+        config.effective_seed(),
+        service.regenerate_with_locks(
+            &chapter.title,
+            chapter.outline_goal.as_deref().unwrap_or_default(),
+            chapter.conflict.as_deref().unwrap_or_default(),
+            &annotated,
+            segments.gaps.len(),
+            &instructions,
+        ),
+    )
+    .await?;
+
+    let value = extract_json(&content)?;
+    let gaps: Vec<String> = value["gaps"]
+        .as_array()
+        .ok_or("AI未返回重写内容")?
+        .iter()
+        .map(|gap| gap.as_str().unwrap_or_default().to_string())
+        .collect();
+    if gaps.len() != segments.gaps.len() {
+        return Err(format!(
+            "AI返回的段落数量不符（期望 {}，实际 {}），章节未修改",
+            segments.gaps.len(),
+            gaps.len()
+        ));
+    }
+    let (new_text, locked_ranges) = reassemble(&segments, &gaps);
+    if new_text.trim().is_empty() {
+        return Err("AI返回了空内容".to_string());
+    }
+
+    save_chapter_body(&window, &pool, &chapter, new_text.clone()).await?;
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "regenerate_chapter_with_locks",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("保留 {} 段锁定内容重写章节「{}」", locked_ranges.len(), chapter.title),
+            snapshot_id: Some(snapshot.id.clone()),
+        },
+    )
+    .await;
+
+    Ok(LockedRegenerationResult {
+        chapter_id: chapter.id,
+        text: new_text,
+        locked_ranges,
+        snapshot_id: snapshot.id,
+    })
+}
+
+/// 候选标题默认个数与上限
+const DEFAULT_TITLE_SUGGESTIONS: usize = 5;
+const MAX_TITLE_SUGGESTIONS: usize = 10;
+
+/// 去掉模型常加的书名号和引号
+fn clean_title(title: &str) -> String {
+    title
+        .trim()
+        .trim_matches(|c| matches!(c, '《' | '》' | '「' | '」' | '“' | '”' | '"' | '\''))
+        .trim()
+        .to_string()
+}
+
+/// 根据章节内容拟定候选标题（使用项目语言），没有正文时依据摘要和本章目标
+#[tauri::command]
+pub async fn suggest_chapter_title(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    count: Option<usize>,
+    text_config: Option<TextModelConfigInput>,
+) -> Result<Vec<String>, String> {
+    let count = count.unwrap_or(DEFAULT_TITLE_SUGGESTIONS).clamp(1, MAX_TITLE_SUGGESTIONS);
+    let chapter = ChapterService::get_by_id(&pool, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let project = ProjectService::get_by_id(&pool, &chapter.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("项目不存在")?;
+
+    let text = chapter_text(&chapter)
+        .or(chapter.summary.as_deref())
+        .or(chapter.outline_goal.as_deref())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or("章节还没有内容，无法拟定标题")?;
+    let language = if project.language == "en" { "en" } else { "zh" };
+
+    let config = resolve_text_config(&pool, text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(&config, serde_json::json!({ "chapter_id": chapter.id, "count": count }));
+    let content = track_generation(
+        &pool,
+        Some(&chapter.project_id),
+        "chapter_title",
+        params,
+        config.effective_seed(),
+        service.suggest_chapter_titles(&chapter.title, text, count, language),
+    )
+    .await?;
+
+    let mut titles: Vec<String> = Vec::new();
+    for title in string_list(&extract_json(&content)?["titles"]) {
+        let title = clean_title(&title);
+        if !title.is_empty() && title != chapter.title.trim() && !titles.contains(&title) {
+            titles.push(title);
+        }
+    }
+    if titles.is_empty() {
+        return Err("模型未返回可用的标题".to_string());
+    }
+    titles.truncate(count);
+    Ok(titles)
+}
+
+/// 对话默认使用的人设
+const DEFAULT_CHAT_PERSONA: &str = "assistant";
+/// 送入模型的最近对话条数
+const CHAT_HISTORY_MESSAGES: usize = 20;
+/// 对话中注入角色资料的 token 上限
+const CHAT_CHARACTER_TOKENS: usize = 1500;
+
+#[tauri::command]
+pub async fn get_chat_personas(pool: State<'_, SqlitePool>) -> Result<Vec<ChatPersona>, String> {
+    ChatPersonaService::get_all(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 人设提示词 + 项目资料（书名、题材、简介、角色）
+async fn chat_system_prompt(
+    pool: &SqlitePool,
+    persona: &ChatPersona,
+    project_id: Option<&str>,
+) -> Result<String, String> {
+    let mut prompt = persona.system_prompt.clone();
+    if let Some(project_id) = project_id {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("项目不存在")?;
+        let characters = CharacterService::get_by_project(pool, project_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut material = format!("书名：{}", project.title);
+        if let Some(genre) = project.genre.as_deref().filter(|g| !g.trim().is_empty()) {
+            material.push_str(&format!("\n题材：{}", genre.trim()));
+        }
+        if let Some(description) = project.description.as_deref().filter(|d| !d.trim().is_empty()) {
+            material.push_str(&format!("\n简介：{}", description.trim()));
+        }
+        let characters = format_characters(&characters);
+        if !characters.is_empty() {
+            material.push_str(&format!(
+                "\n主要角色：\n{}",
+                select_head_context(&characters, CHAT_CHARACTER_TOKENS)
+            ));
+        }
+        prompt.push_str(&format!(
+            "\n\n以下是我们正在讨论的小说的资料，回答时结合这些设定：\n{}",
+            wrap_user_field("项目资料", &material)
+        ));
+    }
+    prompt.push_str("\n\n");
+    prompt.push_str(data_boundary_notice("zh"));
+    Ok(prompt)
+}
+
+/// 自由对话：可选人设，提供 project_id 时助手预先了解书名、题材和角色
+#[tauri::command]
+pub async fn chat(pool: State<'_, SqlitePool>, input: ChatInput) -> Result<String, String> {
+    if input.messages.iter().any(|m| m.role != "user" && m.role != "assistant") {
+        return Err("对话角色只能是 user 或 assistant".to_string());
+    }
+    match input.messages.last() {
+        Some(last) if last.role == "user" && !last.content.trim().is_empty() => {}
+        _ => return Err("最后一条消息须为非空的用户消息".to_string()),
+    }
+
+    let persona_id = input.persona.as_deref().map(str::trim).filter(|p| !p.is_empty());
+    let persona = ChatPersonaService::get_by_id(&pool, persona_id.unwrap_or(DEFAULT_CHAT_PERSONA))
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("人设不存在")?;
+    let system_prompt = chat_system_prompt(&pool, &persona, input.project_id.as_deref()).await?;
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "persona": persona.id, "messages": input.messages.len() }),
+    );
+    let history = &input.messages[input.messages.len().saturating_sub(CHAT_HISTORY_MESSAGES)..];
+
+    track_generation(
+        &pool,
+        input.project_id.as_deref(),
+        "chat",
+        params,
+        config.effective_seed(),
+        service.chat(&system_prompt, history),
+    )
+    .await
+}
+
+const SUMMARY_CONCURRENCY: usize = 3;
+
+/// 为项目中所有尚无摘要的章节生成摘要；已有摘要或没有正文的章节跳过
+#[tauri::command]
+pub async fn summarize_all_chapters(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: SummarizeAllChaptersInput,
+) -> Result<SummarizeAllChaptersResult, String> {
+    let project = ProjectService::get_by_id(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("项目不存在")?;
+    let chapters = ChapterService::get_by_project(&pool, &project.id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let pending: Vec<_> = chapters
+        .iter()
+        .filter(|c| is_blank(&c.summary))
+        .filter_map(|c| chapter_text(c).map(|text| (c.clone(), text.to_string())))
+        .collect();
+    let total = pending.len();
+    let skipped = chapters.len() - total;
+    if total == 0 {
+        return Ok(SummarizeAllChaptersResult {
+            total: 0,
+            summarized: 0,
+            skipped,
+            failed: Vec::new(),
+        });
+    }
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let service = build_text_service(&config)?;
+    let language = if project.language == "en" { "en" } else { "zh" };
+    let completed = AtomicUsize::new(0);
+
+    let pool = pool.inner();
+    let (service, config, window, completed) = (&service, &config, &window, &completed);
+    let results = stream::iter(pending)
+        .map(|(chapter, text)| async move {
+            let params = task_input_params(config, serde_json::json!({ "chapter_id": chapter.id }));
+            let result = match track_generation(
+                pool,
+                Some(&chapter.project_id),
+                "chapter_summary",
+                params,
+                config.effective_seed(),
+                service.summarize_chapter(&chapter.title, &text, language),
+            )
+            .await
+            {
+                Ok(summary) => ChapterService::update_summary(pool, &chapter.id, &summary)
+                    .await
+                    .map_err(|e| format!("保存摘要失败: {}", e)),
+                Err(e) => Err(e),
+            };
+
+            let _ = window.emit(
+                "summary-progress",
+                SummaryProgressEvent {
+                    project_id: chapter.project_id.clone(),
+                    chapter_id: chapter.id.clone(),
+                    completed: completed.fetch_add(1, Ordering::SeqCst) + 1,
+                    total,
+                    error: result.as_ref().err().cloned(),
+                },
+            );
+
+            result.map_err(|error| ChapterSummaryFailure {
+                chapter_id: chapter.id.clone(),
+                title: chapter.title.clone(),
+                error,
+            })
+        })
+        .buffer_unordered(SUMMARY_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let failed: Vec<ChapterSummaryFailure> = results.into_iter().filter_map(Result::err).collect();
+    Ok(SummarizeAllChaptersResult {
+        total,
+        summarized: total - failed.len(),
+        skipped,
+        failed,
+    })
+}
+
+/// 批次任务记录的任务类型
+const CHAPTER_BATCH_TASK: &str = "chapter_batch";
+
+impl ChapterBatchResult {
+    fn from_outcomes(batch_task_id: Option<String>, outcomes: Vec<BatchChapterOutcome>) -> Self {
+        let count = |status: &str| outcomes.iter().filter(|o| o.status == status).count();
+        Self {
+            batch_task_id,
+            total: outcomes.len(),
+            completed: count("completed"),
+            failed: count("failed"),
+            outcomes,
+        }
+    }
+}
+
+// 依次生成章节（后一章的上下文依赖前一章正文，不能并发），每章结束后把结果写回批次任务
+async fn run_chapter_batch(
+    window: &Window,
+    pool: &SqlitePool,
+    batch_task_id: Option<&str>,
+    config: &TextModelConfigInput,
+    chapter_ids: &[String],
+    outcomes: &mut Vec<BatchChapterOutcome>,
+) -> Result<(), String> {
+    let settings = SettingsService::get(pool).await.map_err(|e| e.to_string())?;
+    let service = build_text_service(config)?;
+    let total = chapter_ids.len();
+
+    for (index, chapter_id) in chapter_ids.iter().enumerate() {
+        let result = async {
+            let chapter = ChapterService::get_by_id(pool, chapter_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("章节不存在")?;
+            let loaded = load_chapter_context(pool, &chapter, Some(config))
+                .await
+                .map_err(|e| e.to_string())?;
+            let context = fit_chapter_context(
+                window,
+                "chapter_batch",
+                settings.context_budget.injection_tokens,
+                loaded.previous_tail.as_deref(),
+                loaded.characters_info.as_deref(),
+                loaded.world_setting.as_deref(),
+                loaded.timeline.as_deref(),
+            );
+            let params = task_input_params(
+                config,
+                serde_json::json!({ "chapter_id": chapter.id, "batch_task_id": batch_task_id }),
+            );
+            let content = track_generation(
+                pool,
+                Some(&chapter.project_id),
+                "chapter",
+                params,
+                config.effective_seed(),
+                service.generate_chapter(
+                    &chapter.title,
+                    chapter.outline_goal.as_deref().unwrap_or_default(),
+                    chapter.conflict.as_deref().unwrap_or_default(),
+                    context.get("previous_summary"),
+                    context.get("characters"),
+                    context.get("world_setting"),
+                ),
+            )
+            .await?;
+            if content.trim().is_empty() {
+                return Err("AI返回了空内容".to_string());
+            }
+            let milestones = ChapterService::update_text(pool, &chapter.id, Some(content), chapter.final_text.clone(), None)
+                .await
+                .map_err(|e| format!("保存章节失败: {}", e))?;
+            emit_milestones(window, milestones);
+            Ok(())
+        }
+        .await;
+
+        if let Some(outcome) = outcomes.iter_mut().find(|o| &o.chapter_id == chapter_id) {
+            outcome.status = if result.is_ok() { "completed" } else { "failed" }.to_string();
+            outcome.error = result.as_ref().err().cloned();
+        }
+        if let Some(task_id) = batch_task_id {
+            let progress = serde_json::to_string(outcomes).map_err(|e| e.to_string())?;
+            if let Err(e) = GenerationTaskService::update_progress(pool, task_id, &progress).await {
+                log::warn!("Failed to save progress of batch {}: {}", task_id, e);
+            }
+        }
+        let _ = window.emit(
+            "chapter-batch-progress",
+            ChapterBatchProgressEvent {
+                batch_task_id: batch_task_id.map(str::to_string),
+                chapter_id: chapter_id.clone(),
+                completed: index + 1,
+                total,
+                error: result.err(),
+            },
+        );
+    }
+    Ok(())
+}
+
+// 批次结束时按是否仍有失败章节更新任务状态
+async fn finish_chapter_batch(
+    pool: &SqlitePool,
+    batch_task_id: &str,
+    outcomes: &[BatchChapterOutcome],
+    started: Instant,
+) {
+    let timing = TaskTiming {
+        latency_ms: started.elapsed().as_millis() as i64,
+        first_token_ms: None,
+    };
+    let failed = outcomes.iter().filter(|o| o.status == "failed").count();
+    let recorded = match serde_json::to_string(outcomes) {
+        Ok(output) if failed == 0 => {
+            GenerationTaskService::complete(pool, batch_task_id, Some(&output), None, timing).await
+        }
+        Ok(_) => {
+            GenerationTaskService::fail(pool, batch_task_id, &format!("{} 个章节生成失败", failed), timing).await
+        }
+        Err(e) => GenerationTaskService::fail(pool, batch_task_id, &e.to_string(), timing).await,
+    };
+    if let Err(e) = recorded {
+        log::warn!("Failed to finish batch {}: {}", batch_task_id, e);
+    }
+}
+
+/// 按章节顺序批量生成正文（默认跳过已有正文的章节）；每章结果记录在批次任务中，可用 retry_failed_chapters 只重试失败的章节
+#[tauri::command]
+pub async fn generate_all_chapters(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: GenerateAllChaptersInput,
+) -> Result<ChapterBatchResult, String> {
+    let chapters = ChapterService::get_by_project(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if chapters.is_empty() {
+        return Err("项目还没有章节".to_string());
+    }
+    let config = resolve_text_config(&pool, input.text_config).await?;
+
+    let mut outcomes: Vec<BatchChapterOutcome> = chapters
+        .iter()
+        .map(|c| BatchChapterOutcome {
+            chapter_id: c.id.clone(),
+            title: c.title.clone(),
+            order_index: c.order_index,
+            status: if input.overwrite || chapter_text(c).is_none() { "pending" } else { "skipped" }.to_string(),
+            error: None,
+        })
+        .collect();
+    let pending: Vec<String> = outcomes
+        .iter()
+        .filter(|o| o.status == "pending")
+        .map(|o| o.chapter_id.clone())
+        .collect();
+    if pending.is_empty() {
+        return Ok(ChapterBatchResult::from_outcomes(None, outcomes));
+    }
+
+    let params = task_input_params(
+        &config,
+        serde_json::json!({ "chapters": pending.len(), "overwrite": input.overwrite }),
+    );
+    let batch_task_id =
+        match GenerationTaskService::start(&pool, &input.project_id, CHAPTER_BATCH_TASK, &params, config.effective_seed())
+            .await
+        {
+            Ok(task) => Some(task.id),
+            Err(e) => {
+                log::warn!("Failed to record chapter batch task: {}", e);
+                None
+            }
+        };
+
+    let started = Instant::now();
+    let run = run_chapter_batch(&window, &pool, batch_task_id.as_deref(), &config, &pending, &mut outcomes).await;
+    if let Some(task_id) = batch_task_id.as_deref() {
+        finish_chapter_batch(&pool, task_id, &outcomes, started).await;
+    }
+    run?;
+
+    Ok(ChapterBatchResult::from_outcomes(batch_task_id, outcomes))
+}
+
+/// 重试批量生成中失败或仍没有正文的章节，已成功的章节不会重新生成
+#[tauri::command]
+pub async fn retry_failed_chapters(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    input: RetryFailedChaptersInput,
+) -> Result<ChapterBatchResult, String> {
+    let task = GenerationTaskService::get_by_id(&pool, &input.batch_task_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|task| task.task_type == CHAPTER_BATCH_TASK)
+        .ok_or("批量生成任务不存在")?;
+    let mut outcomes: Vec<BatchChapterOutcome> = task
+        .output_result
+        .as_deref()
+        .map(serde_json::from_str)
+        .transpose()
+        .map_err(|e| format!("批次记录无法解析: {}", e))?
+        .unwrap_or_default();
+
+    let chapters = ChapterService::get_by_project(&pool, &task.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    // 已删除的章节不再重试
+    outcomes.retain(|o| chapters.iter().any(|c| c.id == o.chapter_id));
+    let retry: Vec<String> = outcomes
+        .iter()
+        .filter(|o| o.status != "skipped")
+        .filter(|o| {
+            o.status != "completed"
+                || chapters
+                    .iter()
+                    .find(|c| c.id == o.chapter_id)
+                    .is_some_and(|c| chapter_text(c).is_none())
+        })
+        .map(|o| o.chapter_id.clone())
+        .collect();
+    if retry.is_empty() {
+        return Ok(ChapterBatchResult::from_outcomes(Some(task.id), outcomes));
+    }
+
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    let started = Instant::now();
+    let run = run_chapter_batch(&window, &pool, Some(&task.id), &config, &retry, &mut outcomes).await;
+    finish_chapter_batch(&pool, &task.id, &outcomes, started).await;
+    run?;
+
+    Ok(ChapterBatchResult::from_outcomes(Some(task.id), outcomes))
+}
+
+#[tauri::command]
+pub async fn test_deepseek_connection(api_key: String) -> Result<bool, String> {
+    let service = GenerationService::new(Some(api_key), None);
+    service.test_deepseek().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn test_text_connection(text_config: TextModelConfigInput) -> Result<bool, String> {
+    let service = build_text_service(&text_config)?;
+    service.test_deepseek().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_pollinations_models(
+    pool: State<'_, SqlitePool>,
+    api_key: Option<String>,
+) -> Result<Vec<String>, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    PollinationsClient::new(api_key.or(settings.pollinations_api_key), None)
+        .list_models()
+        .await
+        .map_err(|e| format!("获取图片模型列表失败: {}", e))
 }
 
 #[tauri::command]
@@ -0,0 +1,66 @@
+use tauri::State;
+use sqlx::SqlitePool;
+use crate::models::{Asset, CreateAssetInput};
+use crate::services::AssetService;
+
+const LINK_TYPES: [&str; 2] = ["chapter", "character"];
+
+fn check_link_type(linked_to_type: &str) -> Result<(), String> {
+    if !LINK_TYPES.contains(&linked_to_type) {
+        return Err(format!("不支持的关联类型: {}（仅支持 chapter、character）", linked_to_type));
+    }
+    Ok(())
+}
+
+/// 登记已保存到本地的图片等文件（如 generate_and_download 的结果）
+#[tauri::command]
+pub async fn register_asset(
+    pool: State<'_, SqlitePool>,
+    input: CreateAssetInput,
+) -> Result<Asset, String> {
+    if input.file_path.trim().is_empty() {
+        return Err("文件路径不能为空".to_string());
+    }
+    if input.linked_to_type.is_some() != input.linked_to_id.is_some() {
+        return Err("关联类型和关联对象需要同时提供".to_string());
+    }
+    if let Some(linked_to_type) = input.linked_to_type.as_deref() {
+        check_link_type(linked_to_type)?;
+    }
+    AssetService::create(&pool, input)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 项目素材库，可按类型（illustration / cover / portrait）过滤
+#[tauri::command]
+pub async fn get_project_assets(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    asset_type: Option<String>,
+) -> Result<Vec<Asset>, String> {
+    AssetService::get_by_project(&pool, &project_id, asset_type.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 关联到某个章节或角色的素材
+#[tauri::command]
+pub async fn get_linked_assets(
+    pool: State<'_, SqlitePool>,
+    linked_to_type: String,
+    linked_to_id: String,
+) -> Result<Vec<Asset>, String> {
+    check_link_type(&linked_to_type)?;
+    AssetService::get_by_link(&pool, &linked_to_type, &linked_to_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 删除素材记录并删除对应文件
+#[tauri::command]
+pub async fn delete_asset(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    AssetService::delete(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
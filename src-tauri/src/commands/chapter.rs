@@ -1,16 +1,48 @@
-use tauri::State;
+use tauri::{State, Window};
 use sqlx::SqlitePool;
-use crate::models::{Chapter, CreateChapterInput, UpdateChapterMetaInput};
-use crate::services::ChapterService;
+use crate::models::{
+    Chapter, ChapterNumberingScheme, ChapterOutlineItem, ChapterRenumberItem, ChapterRenumberResult,
+    CreateChapterInput, DuplicateChapterPair, ReflowResult, ReflowRules, UpdateChapterMetaInput,
+};
+use crate::services::{ChapterService, OperationLogService};
+use crate::services::chapter_context::chapter_text;
+use crate::services::chapter_service::{ACT_COUNT, CHAPTER_STATUSES};
+use crate::services::chapter_number::{ChapterNumbering, NumeralStyle};
+use crate::services::reflow::{reflow_text, IndentStyle};
+use crate::services::operation_log_service::OperationRecord;
+use super::milestone::emit_milestones;
 
 #[tauri::command]
 pub async fn create_chapter(
     pool: State<'_, SqlitePool>,
     input: CreateChapterInput,
 ) -> Result<Chapter, String> {
-    ChapterService::create(&pool, input)
+    let chapter = ChapterService::create(&pool, input)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "create_chapter",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("新建章节「{}」", chapter.title),
+            snapshot_id: None,
+        },
+    )
+    .await;
+    Ok(chapter)
+}
+
+// 修改前保存章节状态用于撤销；与上一条日志合并时不再重复保存
+async fn snapshot_before(pool: &SqlitePool, operation: &str, chapter: &Chapter) -> Option<String> {
+    if OperationLogService::needs_snapshot(pool, operation, &chapter.id).await {
+        OperationLogService::snapshot_chapter(pool, chapter).await
+    } else {
+        None
+    }
 }
 
 #[tauri::command]
@@ -23,17 +55,246 @@ pub async fn get_chapters(
         .map_err(|e| e.to_string())
 }
 
+/// 不含正文的章节列表（含标签），供侧边栏使用
+#[tauri::command]
+pub async fn get_chapter_outlines(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<ChapterOutlineItem>, String> {
+    ChapterService::get_outlines(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_chapter_tags(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+) -> Result<Vec<String>, String> {
+    ChapterService::get_tags(&pool, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_chapter_tag(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    tag: String,
+) -> Result<Vec<String>, String> {
+    ChapterService::add_tag(&pool, &chapter_id, &tag)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_chapter_tag(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    tag: String,
+) -> Result<Vec<String>, String> {
+    ChapterService::remove_tag(&pool, &chapter_id, &tag)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_chapters_by_tag(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    tag: String,
+) -> Result<Vec<Chapter>, String> {
+    ChapterService::get_by_tag(&pool, &project_id, &tag)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn update_chapter(
+    window: Window,
     pool: State<'_, SqlitePool>,
     id: String,
     draft_text: Option<String>,
     final_text: Option<String>,
     illustrations: Option<String>,
 ) -> Result<(), String> {
-    ChapterService::update_text(&pool, &id, draft_text, final_text, illustrations)
+    let before = ChapterService::get_by_id(&pool, &id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    let snapshot_id = match before {
+        Some(ref chapter) => snapshot_before(&pool, "update_chapter", chapter).await,
+        None => None,
+    };
+
+    let milestones = ChapterService::update_text(&pool, &id, draft_text, final_text, illustrations)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
+
+    if let Some(chapter) = ChapterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?
+    {
+        OperationLogService::record(
+            &pool,
+            OperationRecord {
+                project_id: Some(&chapter.project_id),
+                operation: "update_chapter",
+                target_type: "chapter",
+                target_id: Some(&chapter.id),
+                summary: format!("保存章节「{}」正文（{} 字）", chapter.title, chapter.word_count),
+                snapshot_id,
+            },
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// 整理章节段落：合并段中硬换行、统一首行缩进和段间空行；草稿和定稿都会处理，整理前保存快照以便撤销
+#[tauri::command]
+pub async fn reflow_chapter(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    rules: Option<ReflowRules>,
+) -> Result<ReflowResult, String> {
+    let rules = rules.unwrap_or_default();
+    if IndentStyle::parse(&rules.indent).is_none() {
+        return Err(format!("不支持的缩进方式: {}", rules.indent));
+    }
+    let chapter = ChapterService::get_by_id(&pool, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let main_text = chapter_text(&chapter).ok_or("章节没有正文")?;
+
+    let reflow = |text: Option<&str>| -> Result<Option<String>, String> {
+        match text.filter(|t| !t.trim().is_empty()) {
+            Some(text) => reflow_text(text, &rules).map(|o| Some(o.text)).map_err(|e| e.to_string()),
+            None => Ok(text.map(str::to_string)),
+        }
+    };
+    let stats = reflow_text(main_text, &rules).map_err(|e| e.to_string())?;
+    let draft_text = reflow(chapter.draft_text.as_deref())?;
+    let final_text = reflow(chapter.final_text.as_deref())?;
+
+    let snapshot_id = OperationLogService::snapshot_chapter(&pool, &chapter).await;
+    let milestones = ChapterService::update_text(&pool, &chapter.id, draft_text, final_text, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
+    let updated = ChapterService::get_by_id(&pool, &chapter.id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "reflow_chapter",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!(
+                "整理章节「{}」段落（{} 段，合并 {} 处换行）",
+                chapter.title, stats.paragraphs, stats.joined_lines
+            ),
+            snapshot_id: snapshot_id.clone(),
+        },
+    )
+    .await;
+
+    Ok(ReflowResult {
+        chapter: updated,
+        paragraphs: stats.paragraphs,
+        joined_lines: stats.joined_lines,
+        snapshot_id,
+    })
+}
+
+/// 按编号方案重新生成章节显示标题：编号按 order_index 排序后的位置计算，保留标题中的自定义部分。
+/// dry_run 时只返回预览；应用前为每个改名的章节保存快照
+#[tauri::command]
+pub async fn renumber_chapters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    scheme: Option<ChapterNumberingScheme>,
+    dry_run: Option<bool>,
+) -> Result<ChapterRenumberResult, String> {
+    let scheme = scheme.unwrap_or_default();
+    let numeral = NumeralStyle::parse(&scheme.numeral)
+        .ok_or_else(|| format!("不支持的数字写法: {}", scheme.numeral))?;
+    let numbering = ChapterNumbering::new(&scheme.format, numeral, &scheme.separator)
+        .map_err(|_| "编号格式必须包含 {n}".to_string())?;
+    let chapters = ChapterService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if chapters.is_empty() {
+        return Err("项目还没有章节".to_string());
+    }
+
+    let items: Vec<ChapterRenumberItem> = chapters
+        .iter()
+        .zip(scheme.start.max(1)..)
+        .map(|(chapter, number)| {
+            let new_title = numbering.title(number, numbering.custom_title(&chapter.title));
+            ChapterRenumberItem {
+                chapter_id: chapter.id.clone(),
+                order_index: chapter.order_index,
+                number,
+                changed: new_title != chapter.title,
+                old_title: chapter.title.clone(),
+                new_title,
+            }
+        })
+        .collect();
+    let changed_count = items.iter().filter(|item| item.changed).count();
+
+    if dry_run.unwrap_or(false) || changed_count == 0 {
+        return Ok(ChapterRenumberResult {
+            applied: false,
+            items,
+            changed_count,
+            snapshot_ids: Vec::new(),
+        });
+    }
+
+    let mut snapshot_ids = Vec::new();
+    for (chapter, item) in chapters.iter().zip(&items) {
+        if item.changed {
+            snapshot_ids.extend(OperationLogService::snapshot_chapter(&pool, chapter).await);
+        }
+    }
+    let titles: Vec<(String, String)> = items
+        .iter()
+        .filter(|item| item.changed)
+        .map(|item| (item.chapter_id.clone(), item.new_title.clone()))
+        .collect();
+    ChapterService::update_titles(&pool, &titles)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&project_id),
+            operation: "renumber_chapters",
+            target_type: "project",
+            target_id: Some(&project_id),
+            summary: format!("按“{}”重新编号 {} 个章节标题", scheme.format, changed_count),
+            snapshot_id: None,
+        },
+    )
+    .await;
+
+    Ok(ChapterRenumberResult {
+        applied: true,
+        items,
+        changed_count,
+        snapshot_ids,
+    })
 }
 
 #[tauri::command]
@@ -42,26 +303,156 @@ pub async fn update_chapter_meta(
     id: String,
     input: UpdateChapterMetaInput,
 ) -> Result<Chapter, String> {
-    ChapterService::update_meta(&pool, &id, input)
+    let before = ChapterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let snapshot_id = snapshot_before(&pool, "update_chapter_meta", &before).await;
+
+    let chapter = ChapterService::update_meta(&pool, &id, input)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "update_chapter_meta",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("修改章节「{}」信息", chapter.title),
+            snapshot_id,
+        },
+    )
+    .await;
+    Ok(chapter)
+}
+
+/// 采用选定的标题（如 suggest_chapter_title 的候选）
+#[tauri::command]
+pub async fn apply_chapter_title(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    title: String,
+) -> Result<Chapter, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("标题不能为空".to_string());
+    }
+    let before = ChapterService::get_by_id(&pool, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    if before.title == title {
+        return Ok(before);
+    }
+    let snapshot_id = snapshot_before(&pool, "apply_chapter_title", &before).await;
+
+    let chapter = ChapterService::update_meta(
+        &pool,
+        &chapter_id,
+        UpdateChapterMetaInput {
+            title: Some(title.to_string()),
+            ..Default::default()
+        },
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "apply_chapter_title",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("章节标题「{}」改为「{}」", before.title, chapter.title),
+            snapshot_id,
+        },
+    )
+    .await;
+    Ok(chapter)
+}
+
+/// 批量设置章节状态（draft / review / final），返回更新后的章节
+#[tauri::command]
+pub async fn set_chapters_status(
+    pool: State<'_, SqlitePool>,
+    ids: Vec<String>,
+    status: String,
+) -> Result<Vec<Chapter>, String> {
+    if !CHAPTER_STATUSES.contains(&status.as_str()) {
+        return Err(format!("不支持的章节状态: {}", status));
+    }
+    if ids.is_empty() {
+        return Err("未选择章节".to_string());
+    }
+    ChapterService::set_status_bulk(&pool, &ids, &status)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 批量设置章节所属的幕（1-3），act 为空时取消分配；用于 get_act_distribution
+#[tauri::command]
+pub async fn set_chapters_act(
+    pool: State<'_, SqlitePool>,
+    ids: Vec<String>,
+    act: Option<i64>,
+) -> Result<Vec<Chapter>, String> {
+    if let Some(act) = act {
+        if !(1..=ACT_COUNT).contains(&act) {
+            return Err(format!("幕只能是 1 到 {}", ACT_COUNT));
+        }
+    }
+    if ids.is_empty() {
+        return Err("未选择章节".to_string());
+    }
+    ChapterService::set_act_bulk(&pool, &ids, act)
         .await
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn delete_chapter(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    let before = ChapterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let snapshot_id = match before {
+        Some(ref chapter) => OperationLogService::snapshot_chapter(&pool, chapter).await,
+        None => None,
+    };
+
     ChapterService::delete(&pool, &id)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    if let Some(chapter) = before {
+        OperationLogService::record(
+            &pool,
+            OperationRecord {
+                project_id: Some(&chapter.project_id),
+                operation: "delete_chapter",
+                target_type: "chapter",
+                target_id: Some(&chapter.id),
+                summary: format!("删除章节「{}」", chapter.title),
+                snapshot_id,
+            },
+        )
+        .await;
+    }
+    Ok(())
 }
 
 #[tauri::command]
 pub async fn recalculate_project_word_count(
+    window: Window,
     pool: State<'_, SqlitePool>,
     project_id: String,
 ) -> Result<i64, String> {
-    ChapterService::update_project_word_count_only(&pool, &project_id)
+    let milestones = ChapterService::update_project_word_count_only(&pool, &project_id)
         .await
         .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
     
     // 返回新的总字数
     let total: i64 = sqlx::query_scalar(
@@ -74,3 +465,20 @@ pub async fn recalculate_project_word_count(
     
     Ok(total)
 }
+
+/// 查找内容高度相似的章节（默认阈值 0.8）
+#[tauri::command]
+pub async fn find_duplicate_chapters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    similarity_threshold: Option<f64>,
+) -> Result<Vec<DuplicateChapterPair>, String> {
+    let threshold = similarity_threshold.unwrap_or(0.8);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err("相似度阈值需在 0 到 1 之间".to_string());
+    }
+
+    ChapterService::find_duplicates(&pool, &project_id, threshold)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,282 @@
+use tauri::State;
+use sqlx::SqlitePool;
+use crate::models::{
+    Character, CharacterAppearance, CharacterArc, CharacterImportReport, CreateCharacterInput, DuplicateCharacterPair,
+    UpdateCharacterInput,
+};
+use crate::services::{CharacterArcService, CharacterService, OperationLogService};
+use crate::services::operation_log_service::OperationRecord;
+use crate::services::character_io::{self, SheetFormat};
+
+// 名字去掉首尾空白，不能为空
+fn character_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("角色名不能为空".to_string());
+    }
+    Ok(name.to_string())
+}
+
+#[tauri::command]
+pub async fn create_character(
+    pool: State<'_, SqlitePool>,
+    input: CreateCharacterInput,
+) -> Result<Character, String> {
+    let input = CreateCharacterInput {
+        name: character_name(&input.name)?,
+        ..input
+    };
+    let character = CharacterService::create(&pool, input)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&character.project_id),
+            operation: "create_character",
+            target_type: "character",
+            target_id: Some(&character.id),
+            summary: format!("新建角色「{}」", character.name),
+            snapshot_id: None,
+        },
+    )
+    .await;
+    Ok(character)
+}
+
+/// 项目的全部角色（按创建顺序），可拼成 character_info 传给章节生成
+#[tauri::command]
+pub async fn get_characters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<Character>, String> {
+    CharacterService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_character(
+    pool: State<'_, SqlitePool>,
+    id: String,
+) -> Result<Option<Character>, String> {
+    CharacterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 更新角色设定（未传的字段保持不变）；修改前保存快照，可撤销
+#[tauri::command]
+pub async fn update_character(
+    pool: State<'_, SqlitePool>,
+    id: String,
+    input: UpdateCharacterInput,
+) -> Result<Character, String> {
+    let before = CharacterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("角色不存在")?;
+    let input = UpdateCharacterInput {
+        name: input.name.as_deref().map(character_name).transpose()?,
+        ..input
+    };
+
+    let snapshot_id = OperationLogService::snapshot_character(&pool, &before, "修改前").await;
+    let character = CharacterService::update(&pool, &id, input)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&character.project_id),
+            operation: "update_character",
+            target_type: "character",
+            target_id: Some(&character.id),
+            summary: format!("修改角色「{}」", character.name),
+            snapshot_id,
+        },
+    )
+    .await;
+    Ok(character)
+}
+
+/// 删除角色及其出场记录；删除前保存快照，撤销时恢复角色设定
+#[tauri::command]
+pub async fn delete_character(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    let before = CharacterService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let snapshot_id = match before {
+        Some(ref character) => OperationLogService::snapshot_character(&pool, character, "删除前").await,
+        None => None,
+    };
+
+    CharacterService::delete(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Some(character) = before {
+        OperationLogService::record(
+            &pool,
+            OperationRecord {
+                project_id: Some(&character.project_id),
+                operation: "delete_character",
+                target_type: "character",
+                target_id: Some(&character.id),
+                summary: format!("删除角色「{}」", character.name),
+                snapshot_id,
+            },
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// 手动标记角色在某章出场，并记录其状态/成长备注
+#[tauri::command]
+pub async fn set_character_appearance(
+    pool: State<'_, SqlitePool>,
+    character_id: String,
+    chapter_id: String,
+    note: Option<String>,
+) -> Result<CharacterAppearance, String> {
+    CharacterArcService::set_appearance(&pool, &character_id, &chapter_id, note)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_character_appearance(
+    pool: State<'_, SqlitePool>,
+    character_id: String,
+    chapter_id: String,
+) -> Result<(), String> {
+    CharacterArcService::remove_appearance(&pool, &character_id, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 扫描章节正文中的角色名，更新该章的出场记录
+#[tauri::command]
+pub async fn detect_character_appearances(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+) -> Result<Vec<CharacterAppearance>, String> {
+    CharacterArcService::detect_appearances(&pool, &chapter_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 角色按章节顺序的出场记录，可用于发现长时间消失的角色
+#[tauri::command]
+pub async fn get_character_arc(
+    pool: State<'_, SqlitePool>,
+    character_id: String,
+) -> Result<CharacterArc, String> {
+    CharacterArcService::get_arc(&pool, &character_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 固定角色插图的种子和英文外貌描述，插图生成时复用以保持形象一致
+#[tauri::command]
+pub async fn set_character_portrait_seed(
+    pool: State<'_, SqlitePool>,
+    character_id: String,
+    seed: Option<i64>,
+    descriptor: Option<String>,
+) -> Result<Character, String> {
+    if seed.is_some_and(|value| value < 0) {
+        return Err("种子必须为非负整数".to_string());
+    }
+    CharacterService::set_portrait(&pool, &character_id, seed, descriptor)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 查找名字相近的疑似重复角色（默认阈值 0.8）
+#[tauri::command]
+pub async fn find_duplicate_characters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    similarity_threshold: Option<f64>,
+) -> Result<Vec<DuplicateCharacterPair>, String> {
+    let threshold = similarity_threshold.unwrap_or(0.8);
+    if !(threshold > 0.0 && threshold <= 1.0) {
+        return Err("相似度阈值需在 0 到 1 之间".to_string());
+    }
+
+    CharacterService::find_duplicates(&pool, &project_id, threshold)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 把 merge_id 合并进 keep_id 并删除前者，返回合并后的角色；被合并角色删除前保存快照
+#[tauri::command]
+pub async fn merge_characters(
+    pool: State<'_, SqlitePool>,
+    keep_id: String,
+    merge_id: String,
+) -> Result<Character, String> {
+    if keep_id == merge_id {
+        return Err("不能把角色合并到自身".to_string());
+    }
+    let merged = CharacterService::get_by_id(&pool, &merge_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("要合并的角色不存在")?;
+
+    let snapshot_id = OperationLogService::snapshot_character(&pool, &merged, "合并前").await;
+
+    let character = CharacterService::merge(&pool, &keep_id, &merge_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&character.project_id),
+            operation: "merge_characters",
+            target_type: "character",
+            target_id: Some(&character.id),
+            summary: format!("将角色「{}」合并到「{}」", merged.name, character.name),
+            snapshot_id,
+        },
+    )
+    .await;
+    Ok(character)
+}
+
+fn sheet_format(format: &str) -> Result<SheetFormat, String> {
+    SheetFormat::parse(format).ok_or_else(|| format!("不支持的格式: {}（仅支持 csv、json）", format))
+}
+
+/// 从 CSV/JSON 表格导入角色；同名角色按 merge_duplicates 合并或跳过，逐行报告错误
+#[tauri::command]
+pub async fn import_characters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    data: String,
+    format: String,
+    merge_duplicates: Option<bool>,
+) -> Result<CharacterImportReport, String> {
+    let format = sheet_format(&format)?;
+    character_io::import_characters(&pool, &project_id, &data, format, merge_duplicates.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 导出项目角色为 CSV/JSON 文本
+#[tauri::command]
+pub async fn export_characters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    format: String,
+) -> Result<String, String> {
+    let format = sheet_format(&format)?;
+    character_io::export_characters(&pool, &project_id, format)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,272 @@
+use tauri::{State, Window};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use uuid::Uuid;
+use crate::models::{Chapter, ExportChapterSelection, ProjectExportSettings, TextExportResult};
+use crate::services::export_service::{PDF_FONT_SIZE_RANGE, PDF_LINE_SPACING_RANGE};
+use crate::services::{cancellation, story_bible, ExportService, SettingsService};
+use crate::services::cancellation::{CancelToken, TaskKind};
+use super::system::read_system_font;
+
+/// export-progress 事件负载：phase 为 layout / rendering / writing / done / cancelled / failed，
+/// current / total 为当前阶段的进度（layout 阶段按章节计）。
+/// 前端渲染的 PDF/EPUB 导出也使用同样的负载，便于共用进度条
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgressEvent {
+    pub request_id: String,
+    pub format: String,
+    pub phase: String,
+    pub current: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+#[tauri::command]
+pub async fn export_project_docx(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+    selection: Option<ExportChapterSelection>,
+    request_id: Option<String>,
+) -> Result<String, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let cancel = CancelToken::register(TaskKind::Export, request_id.clone());
+
+    let emit = |phase: &str, current: usize, total: usize, error: Option<String>| {
+        let _ = window.emit(
+            "export-progress",
+            ExportProgressEvent {
+                request_id: request_id.clone(),
+                format: "docx".to_string(),
+                phase: phase.to_string(),
+                current,
+                total,
+                error,
+            },
+        );
+    };
+
+    let selection = selection.unwrap_or_default();
+    let result = ExportService::export_project_docx(
+        &pool,
+        &project_id,
+        &output_path,
+        &selection,
+        cancel.flag(),
+        |progress| emit(progress.phase, progress.current, progress.total, None),
+    )
+    .await;
+
+    let path = match result {
+        Ok(path) => path,
+        Err(_) if cancel.is_cancelled() => {
+            emit("cancelled", 0, 0, None);
+            return Err("导出已取消".to_string());
+        }
+        Err(e) => {
+            emit("failed", 0, 0, Some(e.to_string()));
+            return Err(e.to_string());
+        }
+    };
+    emit("done", 1, 1, None);
+
+    let patch = serde_json::json!({ "format": "docx" });
+    if let Err(e) = SettingsService::save_project_export(&pool, &project_id, patch).await {
+        log::warn!("Failed to remember export settings for {}: {}", project_id, e);
+    }
+    Ok(path)
+}
+
+/// 导出整本书 DOCX（全部章节，按 order_index 排序）；与 export_project_docx 不同，
+/// 输出目录必须已存在，不会自动创建
+#[tauri::command]
+pub async fn export_docx(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+) -> Result<String, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+    if let Some(parent) = Path::new(&output_path).parent() {
+        if !parent.as_os_str().is_empty() && !parent.is_dir() {
+            return Err(format!("导出目录不存在: {}", parent.display()));
+        }
+    }
+
+    let cancel = AtomicBool::new(false);
+    let path = ExportService::export_project_docx(
+        &pool,
+        &project_id,
+        &output_path,
+        &ExportChapterSelection::default(),
+        &cancel,
+        |_| {},
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let patch = serde_json::json!({ "format": "docx" });
+    if let Err(e) = SettingsService::save_project_export(&pool, &project_id, patch).await {
+        log::warn!("Failed to remember export settings for {}: {}", project_id, e);
+    }
+    Ok(path)
+}
+
+/// 导出整本书 PDF：扉页 + 每章一页起排，页脚页码；字体取系统字体目录下的 TTF/OTF。
+/// 未指定字体时使用项目上次导出的 PDF 字体，字号默认 12 磅、行距默认 1.5 倍
+#[tauri::command]
+pub async fn export_pdf(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+    font_file_name: Option<String>,
+    font_size: Option<f32>,
+    line_spacing: Option<f32>,
+) -> Result<String, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+    let font_size = font_size.unwrap_or(12.0);
+    let (min_size, max_size) = PDF_FONT_SIZE_RANGE;
+    if !(min_size..=max_size).contains(&font_size) {
+        return Err(format!("字号必须在 {} 到 {} 之间", min_size, max_size));
+    }
+    let line_spacing = line_spacing.unwrap_or(1.5);
+    let (min_spacing, max_spacing) = PDF_LINE_SPACING_RANGE;
+    if !(min_spacing..=max_spacing).contains(&line_spacing) {
+        return Err(format!("行距必须在 {} 到 {} 倍之间", min_spacing, max_spacing));
+    }
+
+    let font_file_name = match font_file_name.filter(|name| !name.trim().is_empty()) {
+        Some(name) => name,
+        None => SettingsService::get_project_export(&pool, &project_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .font_file_name
+            .ok_or("请先选择 PDF 字体")?,
+    };
+    let font_bytes = read_system_font(&font_file_name)?;
+
+    let path = ExportService::export_project_pdf(&pool, &project_id, &output_path, font_bytes, font_size, line_spacing)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let patch = serde_json::json!({ "format": "pdf", "fontFileName": font_file_name });
+    if let Err(e) = SettingsService::save_project_export(&pool, &project_id, patch).await {
+        log::warn!("Failed to remember export settings for {}: {}", project_id, e);
+    }
+    Ok(path)
+}
+
+/// 导出为 Markdown：output_dir 下每章一个 .md 文件和一个 index.md 目录
+#[tauri::command]
+pub async fn export_markdown(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_dir: String,
+) -> Result<TextExportResult, String> {
+    if output_dir.trim().is_empty() {
+        return Err("导出目录不能为空".to_string());
+    }
+    ExportService::export_markdown(&pool, &project_id, &output_dir)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 导出为单个 TXT 文件，各章以标题开头
+#[tauri::command]
+pub async fn export_txt(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+) -> Result<TextExportResult, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+    ExportService::export_txt(&pool, &project_id, &output_path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 导出设定集 PDF（角色、世界观、时间线、人物关系）；未指定字体时使用项目上次导出的 PDF 字体
+#[tauri::command]
+pub async fn export_story_bible_pdf(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+    font_file_name: Option<String>,
+) -> Result<String, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+
+    let font_file_name = match font_file_name.filter(|name| !name.trim().is_empty()) {
+        Some(name) => name,
+        None => SettingsService::get_project_export(&pool, &project_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .font_file_name
+            .ok_or("请先选择 PDF 字体")?,
+    };
+    let font_bytes = read_system_font(&font_file_name)?;
+
+    story_bible::export_story_bible_pdf(&pool, &project_id, &output_path, font_bytes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 取消进行中的导出；不传 request_id 时取消全部
+#[tauri::command]
+pub fn cancel_export(request_id: Option<String>) -> Result<(), String> {
+    match request_id {
+        Some(id) if !cancellation::cancel(TaskKind::Export, &id) => Err("导出任务不存在或已结束".to_string()),
+        Some(_) => Ok(()),
+        None => {
+            cancellation::cancel_all(TaskKind::Export);
+            Ok(())
+        }
+    }
+}
+
+/// 前端导出（PDF/EPUB/TXT/Markdown）用：按 id 或序号范围取出要导出的章节，并校验范围有效
+#[tauri::command]
+pub async fn get_export_chapters(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    selection: Option<ExportChapterSelection>,
+) -> Result<Vec<Chapter>, String> {
+    ExportService::select_chapters(&pool, &project_id, &selection.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 项目上次导出使用的参数（格式、字体、封面、页面尺寸、页边距），用于预填导出对话框
+#[tauri::command]
+pub async fn get_last_export_settings(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectExportSettings, String> {
+    SettingsService::get_project_export(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 前端完成导出后记录实际使用的参数（部分字段即可）
+#[tauri::command]
+pub async fn save_last_export_settings(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    patch: serde_json::Value,
+) -> Result<ProjectExportSettings, String> {
+    SettingsService::save_project_export(&pool, &project_id, patch)
+        .await
+        .map_err(|e| e.to_string())
+}
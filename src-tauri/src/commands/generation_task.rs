@@ -0,0 +1,94 @@
+use tauri::State;
+use sqlx::SqlitePool;
+use crate::models::{GenerationMetrics, GenerationTask, ProjectCostSummary, TaskCsvExport, TaskDateRange};
+use crate::services::GenerationTaskService;
+use crate::services::request_log::{self, RequestLogLine};
+
+const DEFAULT_METRICS_WINDOW_HOURS: i64 = 24 * 7;
+const DEFAULT_TASK_HISTORY_LIMIT: i64 = 100;
+const MAX_TASK_HISTORY_LIMIT: i64 = 1000;
+
+/// 项目的生成历史（含状态、token 用量、费用、耗时），用于历史面板
+#[tauri::command]
+pub async fn get_generation_tasks(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    task_type: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<GenerationTask>, String> {
+    let limit = limit
+        .unwrap_or(DEFAULT_TASK_HISTORY_LIMIT)
+        .clamp(1, MAX_TASK_HISTORY_LIMIT);
+
+    GenerationTaskService::get_by_project(&pool, &project_id, task_type.as_deref(), limit)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 按任务类型汇总项目的 token 用量和估算费用，单价在设置的 pricing 中配置
+#[tauri::command]
+pub async fn get_project_cost_summary(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectCostSummary, String> {
+    GenerationTaskService::cost_summary(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_generation_metrics(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    window_hours: Option<i64>,
+) -> Result<GenerationMetrics, String> {
+    let window_hours = window_hours
+        .filter(|hours| *hours > 0)
+        .unwrap_or(DEFAULT_METRICS_WINDOW_HOURS);
+
+    GenerationTaskService::metrics(&pool, &project_id, window_hours)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 导出项目的生成任务记录（类型、状态、用量、费用、耗时、模型）为 CSV，便于对账
+#[tauri::command]
+pub async fn export_tasks_csv(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    output_path: String,
+    date_range: Option<TaskDateRange>,
+) -> Result<TaskCsvExport, String> {
+    write_tasks_csv(&pool, Some(&project_id), output_path, date_range).await
+}
+
+/// 导出所有项目的生成任务记录
+#[tauri::command]
+pub async fn export_all_tasks_csv(
+    pool: State<'_, SqlitePool>,
+    output_path: String,
+    date_range: Option<TaskDateRange>,
+) -> Result<TaskCsvExport, String> {
+    write_tasks_csv(&pool, None, output_path, date_range).await
+}
+
+async fn write_tasks_csv(
+    pool: &SqlitePool,
+    project_id: Option<&str>,
+    output_path: String,
+    date_range: Option<TaskDateRange>,
+) -> Result<TaskCsvExport, String> {
+    if output_path.trim().is_empty() {
+        return Err("导出路径不能为空".to_string());
+    }
+    let rows = GenerationTaskService::export_csv(pool, project_id, &date_range.unwrap_or_default(), &output_path)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(TaskCsvExport { path: output_path, rows })
+}
+
+/// 取回某次生成（任务 id）在本次运行中记录的日志，重启后清空
+#[tauri::command]
+pub fn get_logs(task_id: String) -> Result<Vec<RequestLogLine>, String> {
+    Ok(request_log::get_lines(task_id.trim()))
+}
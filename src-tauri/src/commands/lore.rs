@@ -0,0 +1,143 @@
+use tauri::State;
+use sqlx::SqlitePool;
+use crate::commands::ai::resolve_text_config;
+use crate::models::{CreateLoreInput, Lore, LoreCategory, TextModelConfigInput, UpdateLoreInput};
+use crate::services::{LoreService, SettingsService};
+use crate::services::chapter_context::format_lore;
+use crate::services::retrieval::{refresh_project_embeddings, EmbeddingRefreshReport};
+
+// 分类和标题去掉首尾空白，不能为空
+fn required_field(value: &str, message: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(message.to_string());
+    }
+    Ok(value.to_string())
+}
+
+#[tauri::command]
+pub async fn create_lore(
+    pool: State<'_, SqlitePool>,
+    input: CreateLoreInput,
+) -> Result<Lore, String> {
+    let input = CreateLoreInput {
+        category: required_field(&input.category, "设定分类不能为空")?,
+        title: required_field(&input.title, "设定标题不能为空")?,
+        ..input
+    };
+    LoreService::create(&pool, input)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 项目的全部设定，按分类、分类内顺序、标题排列
+#[tauri::command]
+pub async fn get_lore(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<Lore>, String> {
+    LoreService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_lore(
+    pool: State<'_, SqlitePool>,
+    id: String,
+    input: UpdateLoreInput,
+) -> Result<Lore, String> {
+    let input = UpdateLoreInput {
+        category: input.category.as_deref().map(|v| required_field(v, "设定分类不能为空")).transpose()?,
+        title: input.title.as_deref().map(|v| required_field(v, "设定标题不能为空")).transpose()?,
+        ..input
+    };
+    LoreService::update(&pool, &id, input)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_lore(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    LoreService::delete(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 把选中的设定拼成世界观文本，供 generate_chapter_stream 的 worldSetting 参数使用；不传 ids 时取全部
+#[tauri::command]
+pub async fn compose_world_setting(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    ids: Option<Vec<String>>,
+) -> Result<String, String> {
+    let mut entries = LoreService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(ids) = ids {
+        entries.retain(|entry| ids.contains(&entry.id));
+    }
+    Ok(format_lore(&entries))
+}
+
+#[tauri::command]
+pub async fn get_lore_by_category(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    category: String,
+) -> Result<Vec<Lore>, String> {
+    LoreService::get_by_category(&pool, &project_id, &category)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn reorder_lore(
+    pool: State<'_, SqlitePool>,
+    category: String,
+    ordered_ids: Vec<String>,
+) -> Result<(), String> {
+    LoreService::reorder(&pool, &category, &ordered_ids)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_lore_categories(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<LoreCategory>, String> {
+    LoreService::get_categories(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 将大纲中的世界观设定拆分为 era/geography/society/rules/faction 分类条目
+#[tauri::command]
+pub async fn sync_outline_lore(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    outline: String,
+) -> Result<Vec<Lore>, String> {
+    LoreService::sync_from_outline(&pool, &project_id, &outline)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 预先计算项目设定和角色的向量（设置中配置了向量模型时）；生成章节时也会按需补算
+#[tauri::command]
+pub async fn refresh_embeddings(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    text_config: Option<TextModelConfigInput>,
+) -> Result<EmbeddingRefreshReport, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    if settings.retrieval.embedding_model.trim().is_empty() {
+        return Err("未配置向量模型".to_string());
+    }
+    let config = resolve_text_config(&pool, text_config).await?;
+    config.validate()?;
+    refresh_project_embeddings(&pool, &config, &settings.retrieval, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
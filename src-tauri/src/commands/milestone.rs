@@ -0,0 +1,43 @@
+use tauri::{State, Window};
+use sqlx::SqlitePool;
+use crate::models::{CompletionEstimate, DailyWordCount, Milestone};
+use crate::services::{MilestoneService, WordCountHistoryService};
+
+/// 推送新达成的字数里程碑（由重算项目字数的服务返回）
+pub(crate) fn emit_milestones(window: &Window, milestones: Vec<Milestone>) {
+    for milestone in milestones {
+        let _ = window.emit("milestone-reached", milestone);
+    }
+}
+
+#[tauri::command]
+pub async fn get_milestones(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<Milestone>, String> {
+    MilestoneService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 每天结束时的项目总字数
+#[tauri::command]
+pub async fn get_word_count_history(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<DailyWordCount>, String> {
+    WordCountHistoryService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 按近期日均字数推算达到目标字数的日期；记录不足时 estimated_date 为空并说明原因
+#[tauri::command]
+pub async fn estimate_completion_date(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<CompletionEstimate, String> {
+    WordCountHistoryService::estimate_completion(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
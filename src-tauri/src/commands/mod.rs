@@ -3,3 +3,13 @@ pub mod chapter;
 pub mod ai;
 pub mod stream;
 pub mod system;
+pub mod snapshot;
+pub mod settings;
+pub mod milestone;
+pub mod timeline;
+pub mod export;
+pub mod generation_task;
+pub mod lore;
+pub mod character;
+pub mod operation;
+pub mod asset;
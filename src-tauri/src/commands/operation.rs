@@ -0,0 +1,30 @@
+use tauri::{State, Window};
+use sqlx::SqlitePool;
+use crate::models::OperationLogEntry;
+use crate::services::OperationLogService;
+use super::milestone::emit_milestones;
+
+const DEFAULT_RECENT_OPERATIONS: i64 = 50;
+
+#[tauri::command]
+pub async fn get_recent_operations(
+    pool: State<'_, SqlitePool>,
+    limit: Option<i64>,
+) -> Result<Vec<OperationLogEntry>, String> {
+    OperationLogService::get_recent(&pool, limit.unwrap_or(DEFAULT_RECENT_OPERATIONS))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 撤销最近一次操作，返回被撤销的日志条目
+#[tauri::command]
+pub async fn undo_last_operation(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+) -> Result<OperationLogEntry, String> {
+    let (entry, milestones) = OperationLogService::undo_last(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
+    Ok(entry)
+}
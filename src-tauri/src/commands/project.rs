@@ -1,16 +1,47 @@
-use tauri::State;
+use tauri::{AppHandle, State};
 use sqlx::SqlitePool;
-use crate::models::{Project, CreateProjectInput};
-use crate::services::ProjectService;
+use crate::models::{
+    CreateProjectInput, CreateSnapshotInput, OutlineEntitiesReport, Project, ProjectBible, ProjectDashboard,
+    ProjectNotes, ProjectOutline, Snapshot, TextModelConfigInput, UpdateProjectInput,
+};
+use crate::services::{
+    ArchiveService, CharacterService, LoreService, OperationLogService, ProjectService, SettingsService,
+    SnapshotService, TimelineService,
+};
+use crate::services::snapshot_service::content_hash;
+use crate::services::operation_log_service::OperationRecord;
+use crate::services::genre::{self, GenreInfo};
+use crate::services::search::{self, GlobalSearchOptions, ProjectSearchGroup};
+use crate::services::structure_check::{self, ProjectStructureReport};
+use crate::services::act_distribution::{self, ActDistributionReport};
+use crate::services::vocabulary::{self, VocabularyReport};
 
 #[tauri::command]
 pub async fn create_project(
     pool: State<'_, SqlitePool>,
     input: CreateProjectInput,
 ) -> Result<Project, String> {
-    ProjectService::create(&pool, input)
+    let project = ProjectService::create(&pool, input)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    record_project_operation(&pool, "create_project", &project.id, format!("新建项目「{}」", project.title)).await;
+    Ok(project)
+}
+
+async fn record_project_operation(pool: &SqlitePool, operation: &str, project_id: &str, summary: String) {
+    OperationLogService::record(
+        pool,
+        OperationRecord {
+            project_id: Some(project_id),
+            operation,
+            target_type: "project",
+            target_id: Some(project_id),
+            summary,
+            snapshot_id: None,
+        },
+    )
+    .await;
 }
 
 #[tauri::command]
@@ -33,14 +64,328 @@ pub async fn update_project(
     id: String,
     input: CreateProjectInput,
 ) -> Result<Project, String> {
-    ProjectService::update(&pool, &id, input)
+    let project = ProjectService::update(&pool, &id, input)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    record_project_operation(&pool, "update_project", &project.id, format!("修改项目「{}」", project.title)).await;
+    Ok(project)
+}
+
+/// 只更新传入的字段，表单未回传的字段（如封面）保持不变
+#[tauri::command]
+pub async fn update_project_partial(
+    pool: State<'_, SqlitePool>,
+    id: String,
+    input: UpdateProjectInput,
+) -> Result<Project, String> {
+    let project = ProjectService::update_partial(&pool, &id, input)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    record_project_operation(&pool, "update_project", &project.id, format!("修改项目「{}」", project.title)).await;
+    Ok(project)
 }
 
 #[tauri::command]
 pub async fn delete_project(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    let title = ProjectService::get_by_id(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|project| project.title)
+        .unwrap_or_default();
+
     ProjectService::delete(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    record_project_operation(&pool, "delete_project", &id, format!("删除项目「{}」", title)).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn archive_project(
+    app_handle: AppHandle,
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    purge_text: Option<bool>,
+) -> Result<Project, String> {
+    let archive_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or("无法获取应用数据目录")?
+        .join("archives");
+
+    ArchiveService::archive(&pool, &archive_dir, &project_id, purge_text.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn unarchive_project(pool: State<'_, SqlitePool>, project_id: String) -> Result<Project, String> {
+    ArchiveService::unarchive(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_archived_projects(pool: State<'_, SqlitePool>) -> Result<Vec<Project>, String> {
+    ProjectService::get_archived(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 项目首页所需的全部统计（章节状态、字数进度、最近任务、费用、角色/设定数、最近快照）
+#[tauri::command]
+pub async fn get_project_dashboard(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectDashboard, String> {
+    ProjectService::dashboard(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "项目不存在".to_string())
+}
+
+#[tauri::command]
+pub async fn get_project_notes(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectNotes, String> {
+    ProjectService::get_notes(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "项目不存在".to_string())
+}
+
+#[tauri::command]
+pub async fn save_project_notes(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    notes: String,
+) -> Result<ProjectNotes, String> {
+    ProjectService::save_notes(&pool, &project_id, &notes)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 上次生成的系列设定集；重新生成见 generate_series_bible
+#[tauri::command]
+pub async fn get_series_bible(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectBible, String> {
+    ProjectService::get_bible(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "项目不存在".to_string())
+}
+
+/// 大纲快照的 target_type，target_id 为项目 id
+const OUTLINE_SNAPSHOT_TYPE: &str = "outline";
+
+/// 保存项目大纲；内容有变化且开启了 outline_snapshots 时同时保存一个版本快照。
+/// generation 为生成该大纲的模型配置，手动编辑时为 None
+pub(crate) async fn save_outline_version(
+    pool: &SqlitePool,
+    project_id: &str,
+    outline: &str,
+    generation: Option<&TextModelConfigInput>,
+    note: Option<String>,
+) -> anyhow::Result<ProjectOutline> {
+    let current = ProjectService::get_outline(pool, project_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+    if current.outline.as_deref() == Some(outline) {
+        return Ok(current);
+    }
+    let saved = ProjectService::save_outline(pool, project_id, outline).await?;
+
+    if SettingsService::get(pool).await?.outline_snapshots {
+        // 与最近一个版本相同（如恢复后再次保存）时不重复记录
+        let latest = SnapshotService::get_by_target(pool, OUTLINE_SNAPSHOT_TYPE, project_id).await?;
+        if latest.first().map(|s| s.content_hash.as_str()) != Some(content_hash(outline).as_str()) {
+            SnapshotService::create(
+                pool,
+                CreateSnapshotInput {
+                    target_type: OUTLINE_SNAPSHOT_TYPE.to_string(),
+                    target_id: project_id.to_string(),
+                    content: outline.to_string(),
+                    note,
+                    model: generation.map(|config| config.model.clone()),
+                    temperature: generation.map(|config| config.normalized_temperature(0.8) as f64),
+                    prompt_template: generation.map(|_| "outline".to_string()),
+                },
+            )
+            .await?;
+        }
+    }
+    Ok(saved)
+}
+
+#[tauri::command]
+pub async fn get_project_outline(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectOutline, String> {
+    ProjectService::get_outline(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "项目不存在".to_string())
+}
+
+/// 手动编辑后保存大纲
+#[tauri::command]
+pub async fn save_project_outline(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    outline: String,
+) -> Result<ProjectOutline, String> {
+    save_outline_version(&pool, &project_id, &outline, None, None)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从大纲的“世界观设定”“时间线事件”“主要角色”生成设定条目、时间线和角色，
+/// 供章节生成注入上下文。设定和时间线按大纲同步（重复提取只更新），同名角色只补全空字段
+#[tauri::command]
+pub async fn extract_outline_entities(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    outline_markdown: String,
+) -> Result<OutlineEntitiesReport, String> {
+    ProjectService::get_by_id(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("项目不存在")?;
+
+    let lore = if LoreService::outline_has_entries(&outline_markdown) {
+        LoreService::sync_from_outline(&pool, &project_id, &outline_markdown)
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    let timeline_events = if TimelineService::outline_has_entries(&outline_markdown) {
+        TimelineService::sync_from_outline(&pool, &project_id, &outline_markdown)
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        Vec::new()
+    };
+    let (created_characters, updated_characters) =
+        CharacterService::sync_from_outline(&pool, &project_id, &outline_markdown)
+            .await
+            .map_err(|e| e.to_string())?;
+
+    if lore.is_empty() && timeline_events.is_empty() && created_characters.is_empty() && updated_characters.is_empty() {
+        return Err("大纲中没有可提取的世界观设定、时间线事件或角色".to_string());
+    }
+    Ok(OutlineEntitiesReport {
+        lore,
+        timeline_events,
+        created_characters,
+        updated_characters,
+    })
+}
+
+/// 大纲的历史版本，最新的在前
+#[tauri::command]
+pub async fn list_outline_versions(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<Snapshot>, String> {
+    SnapshotService::get_by_target(&pool, OUTLINE_SNAPSHOT_TYPE, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 把大纲恢复为某个历史版本（恢复本身也会成为最新版本）
+#[tauri::command]
+pub async fn restore_outline_version(
+    pool: State<'_, SqlitePool>,
+    snapshot_id: String,
+) -> Result<ProjectOutline, String> {
+    let snapshot = SnapshotService::get_by_id(&pool, &snapshot_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|s| s.target_type == OUTLINE_SNAPSHOT_TYPE)
+        .ok_or("大纲版本不存在")?;
+
+    let note = format!("恢复自 {} 的版本", snapshot.created_at);
+    let outline = save_outline_version(&pool, &snapshot.target_id, &snapshot.content, None, Some(note))
+        .await
+        .map_err(|e| e.to_string())?;
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&snapshot.target_id),
+            operation: "restore_outline_version",
+            target_type: "project",
+            target_id: Some(&snapshot.target_id),
+            summary: "恢复大纲历史版本".to_string(),
+            snapshot_id: Some(snapshot.id.clone()),
+        },
+    )
+    .await;
+    Ok(outline)
+}
+
+/// 规范题材列表（用于下拉框）
+#[tauri::command]
+pub async fn list_genres() -> Result<Vec<GenreInfo>, String> {
+    Ok(genre::list_genres())
+}
+
+/// 发布前的结构检查：空章节、序号断档/重复、缺少大纲要素、未出场角色和未用到的设定
+#[tauri::command]
+pub async fn validate_project_structure(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectStructureReport, String> {
+    structure_check::validate_project_structure(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 各幕实际字数占比与 20/60/20 目标的对照，标出过长或过短的幕（需先为章节分配幕）
+#[tauri::command]
+pub async fn get_act_distribution(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ActDistributionReport, String> {
+    act_distribution::act_distribution(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 统计整个项目或单个章节的词频、类符/形符比和用得过多的词；传入 chapter_id 时只统计该章
+#[tauri::command]
+pub async fn analyze_vocabulary(
+    pool: State<'_, SqlitePool>,
+    project_id: Option<String>,
+    chapter_id: Option<String>,
+) -> Result<VocabularyReport, String> {
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?.vocabulary;
+    let report = match (chapter_id.as_deref(), project_id.as_deref()) {
+        (Some(chapter_id), _) => vocabulary::analyze_chapter(&pool, chapter_id, &settings).await,
+        (None, Some(project_id)) => vocabulary::analyze_project(&pool, project_id, &settings).await,
+        (None, None) => return Err("请指定项目或章节".to_string()),
+    };
+    report.map_err(|e| e.to_string())
+}
+
+/// 在所有未归档项目中搜索章节标题、大纲和正文，按命中次数分组排序
+#[tauri::command]
+pub async fn search_all_projects(
+    pool: State<'_, SqlitePool>,
+    query: String,
+    options: Option<GlobalSearchOptions>,
+) -> Result<Vec<ProjectSearchGroup>, String> {
+    if query.trim().is_empty() {
+        return Err("搜索内容不能为空".to_string());
+    }
+    search::search_all_projects(&pool, &query, &options.unwrap_or_default())
         .await
         .map_err(|e| e.to_string())
 }
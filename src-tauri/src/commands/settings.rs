@@ -0,0 +1,92 @@
+use tauri::{State, Window};
+use sqlx::SqlitePool;
+use crate::api::{retry, PollinationsClient};
+use crate::models::{
+    AppSettings, ProjectImageConfig, ProjectNarrativeSettings, TextConfigValidation, TextModelConfigInput,
+};
+use crate::services::{log_redaction, text_service_cache, SettingsService};
+
+#[tauri::command]
+pub async fn get_settings(pool: State<'_, SqlitePool>) -> Result<AppSettings, String> {
+    SettingsService::get(&pool)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    patch: serde_json::Value,
+) -> Result<AppSettings, String> {
+    let settings = SettingsService::update(&pool, patch)
+        .await
+        .map_err(|e| e.to_string())?;
+    text_service_cache::clear();
+    log_redaction::set_verbose(settings.verbose_request_logging);
+    retry::set_policy(settings.retry.clone());
+
+    let _ = window.emit("settings-changed", settings.clone());
+    Ok(settings)
+}
+
+/// 不发请求，只检查 URL、模型名称、Temperature 等字段的格式
+#[tauri::command]
+pub fn validate_text_config(config: TextModelConfigInput) -> Result<TextConfigValidation, String> {
+    Ok(SettingsService::check_text_config(&config))
+}
+
+#[tauri::command]
+pub async fn get_project_image_config(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectImageConfig, String> {
+    SettingsService::get_project_image(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 保存项目的图片模型和默认尺寸；指定模型时先向 Pollinations 确认该模型可用
+#[tauri::command]
+pub async fn set_project_image_config(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    config: ProjectImageConfig,
+) -> Result<ProjectImageConfig, String> {
+    if let Some(model) = config.model.as_deref().map(str::trim).filter(|m| !m.is_empty()) {
+        let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+        let models = PollinationsClient::new(settings.pollinations_api_key, None)
+            .list_models()
+            .await
+            .map_err(|e| format!("无法获取图片模型列表，暂不能校验模型: {}", e))?;
+        if !models.iter().any(|m| m == model) {
+            return Err(format!("不支持的图片模型: {}（可用模型: {}）", model, models.join(", ")));
+        }
+    }
+
+    SettingsService::save_project_image(&pool, &project_id, config)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_project_narrative_settings(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<ProjectNarrativeSettings, String> {
+    SettingsService::get_project_narrative(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 保存项目的叙述人称（first/third）、时态（past/present）及是否在生成后自动检查
+#[tauri::command]
+pub async fn set_project_narrative_settings(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    settings: ProjectNarrativeSettings,
+) -> Result<ProjectNarrativeSettings, String> {
+    SettingsService::save_project_narrative(&pool, &project_id, settings)
+        .await
+        .map_err(|e| e.to_string())
+}
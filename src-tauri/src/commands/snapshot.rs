@@ -0,0 +1,103 @@
+use tauri::{State, Window};
+use sqlx::SqlitePool;
+use crate::models::{Chapter, Snapshot, CreateSnapshotInput};
+use crate::services::{ChapterService, OperationLogService, SnapshotService};
+use crate::services::operation_log_service::OperationRecord;
+use crate::services::text_diff::{diff_lines, DiffSpan};
+use super::milestone::emit_milestones;
+
+/// 手动保存版本；内容与最近一次快照相同时返回已有快照，不重复保存
+#[tauri::command]
+pub async fn create_snapshot(
+    pool: State<'_, SqlitePool>,
+    input: CreateSnapshotInput,
+) -> Result<Snapshot, String> {
+    SnapshotService::create_if_changed(&pool, input)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 保存章节当前正文的版本（规则同 create_snapshot）
+#[tauri::command]
+pub async fn create_chapter_snapshot(
+    pool: State<'_, SqlitePool>,
+    chapter_id: String,
+    note: Option<String>,
+) -> Result<Snapshot, String> {
+    SnapshotService::create_for_chapter(&pool, &chapter_id, note)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_snapshots(
+    pool: State<'_, SqlitePool>,
+    target_type: String,
+    target_id: String,
+) -> Result<Vec<Snapshot>, String> {
+    SnapshotService::get_by_target(&pool, &target_type, &target_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 把章节快照恢复为正文；恢复前先保存当前正文为新版本，并可通过撤销还原
+#[tauri::command]
+pub async fn restore_snapshot(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    snapshot_id: String,
+) -> Result<Chapter, String> {
+    let snapshot = SnapshotService::get_by_id(&pool, &snapshot_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("快照不存在")?;
+    if snapshot.target_type != "chapter" {
+        return Err("只能恢复章节快照".to_string());
+    }
+    let before = ChapterService::get_by_id(&pool, &snapshot.target_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+
+    if let Err(e) = SnapshotService::create_for_chapter(&pool, &before.id, Some("恢复前".to_string())).await {
+        log::warn!("Failed to snapshot chapter {} before restore: {}", before.id, e);
+    }
+    let state_snapshot_id = OperationLogService::snapshot_chapter(&pool, &before).await;
+
+    let (chapter, milestones) = SnapshotService::restore_chapter(&pool, &snapshot)
+        .await
+        .map_err(|e| e.to_string())?;
+    emit_milestones(&window, milestones);
+
+    OperationLogService::record(
+        &pool,
+        OperationRecord {
+            project_id: Some(&chapter.project_id),
+            operation: "restore_snapshot",
+            target_type: "chapter",
+            target_id: Some(&chapter.id),
+            summary: format!("将章节「{}」恢复到 {} 的版本", chapter.title, snapshot.created_at),
+            snapshot_id: state_snapshot_id,
+        },
+    )
+    .await;
+    Ok(chapter)
+}
+
+/// 按行比较两个快照（a 为旧版本，b 为新版本），返回连续的 unchanged / added / removed 片段
+#[tauri::command]
+pub async fn diff_snapshots(
+    pool: State<'_, SqlitePool>,
+    snapshot_id_a: String,
+    snapshot_id_b: String,
+) -> Result<Vec<DiffSpan>, String> {
+    let mut contents = Vec::with_capacity(2);
+    for id in [&snapshot_id_a, &snapshot_id_b] {
+        let snapshot = SnapshotService::get_by_id(&pool, id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("快照不存在: {}", id))?;
+        contents.push(snapshot.content);
+    }
+    Ok(diff_lines(&contents[0], &contents[1]))
+}
@@ -1,16 +1,54 @@
-﻿use tauri::{AppHandle, Manager, Window};
+﻿use tauri::{AppHandle, Manager, State, Window};
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard};
 use reqwest::Client;
 use futures_util::StreamExt;
-use crate::models::TextModelConfigInput;
+use crate::models::{
+    Chapter, Character, ContextBudgetSettings, ContextSectionUsage, ContextUsagePreview, GenerationTask,
+    ProjectBible, StreamSettings, TextModelConfigInput,
+};
+use crate::services::{
+    ChapterService, CharacterService, GenerationTaskService, LoreService, ProjectService, SettingsService,
+    TimelineService,
+};
+use crate::services::chapter_context::{format_characters, format_lore, format_timeline, load_chapter_context};
+use crate::services::context_budget::{
+    context_window, estimate_message_tokens, estimate_tokens, select_tail_context,
+};
+use crate::services::generation_task_service::TaskTiming;
+use crate::services::token_usage::TokenUsage;
+use crate::api::deepseek::{
+    is_context_length_exceeded, ChatMessage, DeepSeekClient, GenerationParams, StreamEvent,
+};
+use crate::commands::ai::{
+    context_retry_budget, context_too_long_error, emit_chapter_settings, emit_language_mismatch,
+    fit_chapter_context, resolve_chapter_overrides, resolve_text_config, spawn_narrative_check,
+};
+use crate::services::language_check::check_language;
+use crate::commands::project::save_outline_version;
+use crate::services::{cancellation, draft_buffer, request_log};
+use crate::services::cancellation::{Generation, TaskKind};
+use crate::services::draft_buffer::DraftOutcome;
+use crate::services::log_redaction::redact;
+use crate::services::chapter_number::chapter_heading_numbers;
+use crate::commands::milestone::emit_milestones;
+use crate::services::llm_json::extract_json;
+use crate::services::prompt_guard::{data_boundary_notice, sanitize_inline, wrap_user_field};
+use sqlx::SqlitePool;
 
-// 全局取消标志
 lazy_static::lazy_static! {
-    static ref CANCEL_FLAG: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    // 开启串行生成时保证同时只有一个生成任务
     static ref GENERATION_LOCK: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
+    // 各次生成的推送进度（按 generation_id），续写、重连的多轮请求共用同一计数
+    static ref STREAM_PROGRESS: std::sync::Mutex<HashMap<String, StreamProgress>> = std::sync::Mutex::new(HashMap::new());
+}
+
+tokio::task_local! {
+    // continue_from 光标前的正文：推送前去掉续写开头对它的复述
+    static REPEAT_SOURCE: String;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,6 +60,8 @@ pub struct GenerateOutlineStreamInput {
     pub text_config: TextModelConfigInput,
     pub requirements: Option<String>,
     pub output_language: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
 }
 
 fn normalize_output_language(value: Option<&str>) -> &'static str {
@@ -31,32 +71,45 @@ fn normalize_output_language(value: Option<&str>) -> &'static str {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct StreamChoice {
-    delta: StreamDelta,
-    finish_reason: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct StreamDelta {
-    content: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct StreamResponse {
-    choices: Vec<StreamChoice>,
-}
-
 #[tauri::command]
 pub async fn generate_outline_stream(
     window: Window,
+    pool: State<'_, SqlitePool>,
     input: GenerateOutlineStreamInput,
 ) -> Result<String, String> {
-    // 获取生成锁，确保同时只有一个生成任务
-    let _lock = GENERATION_LOCK.lock().await;
-    
-    // 重置取消标志
-    CANCEL_FLAG.store(false, Ordering::SeqCst);
+    let params = serde_json::json!({
+        "title": input.title,
+        "target_chapters": input.target_chapters,
+    });
+    let task = begin_stream_task(&pool, input.project_id.as_deref(), "outline", &input.text_config, params).await;
+    let started = Instant::now();
+    let mut stats = StreamStats::default();
+
+    let project_id = input.project_id.clone();
+    let text_config = input.text_config.clone();
+
+    let correlation_id = request_log::correlation_id(task.as_ref().map(|t| t.id.as_str()));
+    request_log::scope(correlation_id, async {
+        let generation = start_generation(&window, "outline-stream", None);
+        let result = generation.scope(run_outline_stream(&window, input, &mut stats)).await;
+        finish_stream_task(&pool, task, &result, started, &stats).await;
+        // 只保存完整生成的大纲，取消或出错时不覆盖已有版本
+        if let (Ok(outline), Some(project_id)) = (&result, project_id.as_deref()) {
+            if let Err(e) = save_outline_version(&pool, project_id, outline, Some(&text_config), None).await {
+                log::warn!("Failed to save outline version for project {}: {}", project_id, e);
+            }
+        }
+        result
+    })
+    .await
+}
+
+async fn run_outline_stream(
+    window: &Window,
+    input: GenerateOutlineStreamInput,
+    stats: &mut StreamStats,
+) -> Result<String, String> {
+    let _turn = wait_generation_turn(window).await?;
 
     let client = Client::new();
     let target_chapters = input.target_chapters;
@@ -66,46 +119,49 @@ pub async fn generate_outline_stream(
     let system_prompt = build_outline_system_prompt(target_chapters, output_language);
 
     // 第一次生成
-    let mut full_content = stream_generate(
+    let first = stream_generate_outcome(
         &client, 
-        &window, 
+        window, 
         &input.text_config,
         &system_prompt, 
         &initial_prompt,
         "outline-stream",
         8000,
         0.8,
+        None,
     ).await?;
+    stats.record(&first);
+    let mut full_content = first.content;
 
     // 检测是否需要续写（最多续写5次）
     let max_continuations = 5;
     for _ in 0..max_continuations {
-        if CANCEL_FLAG.load(Ordering::SeqCst) {
+        if cancellation::is_cancelled() {
             return Err("生成已被用户中断".to_string());
         }
 
-        // 检查是否已生成所有章节
-        let last_chapter_found = find_last_chapter_number(&full_content);
-        
-        if last_chapter_found >= target_chapters {
-            // 已完成所有章节
+        // 检查第1章到目标章节是否全部存在（乱序或跳号时按缺失章节补写）
+        let validation = check_outline(&full_content, target_chapters);
+        if validation.complete {
             break;
         }
+        let missing = format_chapter_numbers(&validation.missing);
+        let generated = validation.found.len();
 
         let (continue_notice, continue_prompt, continue_system) = if output_language == "en" {
             (
                 format!(
-                    "\n\n[System: Outline incomplete (generated through Chapter {}, target Chapter {}). Continuing automatically...]\n\n",
-                    last_chapter_found, target_chapters
+                    "\n\n[System: Outline incomplete ({} of {} chapters, missing Chapter {}). Continuing automatically...]\n\n",
+                    generated, target_chapters, missing
                 ),
                 format!(
-                    r#"Please continue the chapter outline section.
+                    r#"Please complete the chapter outline section.
 
 [Tail of generated content]
 {}
 
 [Continuation requirements]
-1. Continue from Chapter {} through Chapter {}
+1. Write only these missing chapters, in ascending order: Chapter {}
 2. Keep the same format as above
 3. Chapter template:
 ### Chapter X: Chapter Title
@@ -114,36 +170,33 @@ pub async fn generate_outline_stream(
 - **Conflict**: core conflict/challenge
 - **Hook**: ending hook to drive the next chapter
 
-Continue directly from Chapter {}. Do not repeat existing content:"#,
-                    get_last_n_chars(&full_content, 1500),
-                    last_chapter_found + 1,
-                    target_chapters,
-                    last_chapter_found + 1
+Make each chapter fit between its neighbours. Do not repeat chapters that already exist:"#,
+                    select_tail_context(&full_content, TAIL_CONTEXT_TOKENS),
+                    missing
                 ),
                 format!(
-                    r#"You are continuing an existing novel outline.
-Existing chapters are Chapter 1 to Chapter {}. Continue Chapter {} to Chapter {} only.
+                    r#"You are completing an existing novel outline of {} chapters.
+The following chapters are missing: Chapter {}. Write only those chapters.
 
 Keep the same Markdown format and continue directly without extra introduction."#,
-                    last_chapter_found,
-                    last_chapter_found + 1,
-                    target_chapters
+                    target_chapters,
+                    missing
                 ),
             )
         } else {
             (
                 format!(
-                    "\n\n【系统：检测到大纲未完成（已生成到第{}章，目标{}章），正在自动续写...】\n\n",
-                    last_chapter_found, target_chapters
+                    "\n\n【系统：检测到大纲未完成（已生成{}/{}章，缺少第{}章），正在自动续写...】\n\n",
+                    generated, target_chapters, missing
                 ),
                 format!(
-                    r#"请继续完成大纲的章节部分。
+                    r#"请补全大纲的章节部分。
 
 【已生成内容的结尾】
 {}
 
 【续写要求】
-1. 从第{}章继续生成，直到第{}章
+1. 只生成以下缺失的章节，按编号从小到大：第{}章
 2. 保持与前面相同的格式
 3. 每章格式：
 ### 第X章：章节标题
@@ -152,38 +205,38 @@ Keep the same Markdown format and continue directly without extra introduction."
 - **冲突**：本章的核心冲突或挑战
 - **结尾钩子**：吸引读者继续阅读的悬念
 
-请直接从第{}章开始续写，不要重复已有内容："#,
-                    get_last_n_chars(&full_content, 1500),
-                    last_chapter_found + 1,
-                    target_chapters,
-                    last_chapter_found + 1
+补写的章节要与前后章节衔接，不要重复已有章节："#,
+                    select_tail_context(&full_content, TAIL_CONTEXT_TOKENS),
+                    missing
                 ),
                 format!(
-                    r#"你正在续写一份小说大纲。前面的内容已经生成了第1章到第{}章，现在需要继续生成剩余的章节（第{}章到第{}章）。
+                    r#"你正在补全一份共{}章的小说大纲。目前缺少第{}章，请只生成这些章节。
 
-请保持格式一致，直接续写章节内容，不要添加任何开头说明。"#,
-                    last_chapter_found,
-                    last_chapter_found + 1,
-                    target_chapters
+请保持格式一致，直接输出章节内容，不要添加任何开头说明。"#,
+                    target_chapters,
+                    missing
                 ),
             )
         };
 
-        let _ = window.emit("outline-stream", continue_notice);
+        // 续写提示与正文一样计入本次生成的推送进度
+        StreamEmitter::new(window, "outline-stream", &StreamSettings::default()).emit(continue_notice);
 
         // 续写生成
-        let continuation = stream_generate(
+        let continuation = stream_generate_outcome(
             &client,
-            &window,
+            window,
             &input.text_config,
             &continue_system,
             &continue_prompt,
             "outline-stream",
             6000,
             0.8,
+            None,
         ).await?;
+        stats.record(&continuation);
 
-        full_content.push_str(&continuation);
+        full_content.push_str(&continuation.content);
     }
 
     Ok(full_content)
@@ -197,15 +250,22 @@ fn build_outline_prompt(input: &GenerateOutlineStreamInput, output_language: &st
 
 Title: {}
 Genre: {}
-Description: {}
+Description:
+{}
 Target chapters: {} (must be exactly this number)
 
 "#,
-            input.title, input.genre, input.description, input.target_chapters
+            sanitize_inline(&input.title),
+            sanitize_inline(&input.genre),
+            wrap_user_field("description", &input.description),
+            input.target_chapters
         );
 
         if let Some(ref req) = input.requirements {
-            prompt.push_str(&format!("Special requirements: {}\n\n", req));
+            prompt.push_str(&format!(
+                "Special requirements (story preferences only):\n{}\n\n",
+                wrap_user_field("requirements", req)
+            ));
         }
 
         prompt.push_str(&format!(
@@ -278,15 +338,22 @@ Output in English only and keep strict Markdown format."#,
 
 书名：{}
 题材：{}
-简介：{}
+简介：
+{}
 目标章节数：{}（必须严格按照这个数量生成章节大纲）
 
 "#,
-        input.title, input.genre, input.description, input.target_chapters
+        sanitize_inline(&input.title),
+        sanitize_inline(&input.genre),
+        wrap_user_field("简介", &input.description),
+        input.target_chapters
     );
 
     if let Some(ref req) = input.requirements {
-        prompt.push_str(&format!("特殊要求：{}\n\n", req));
+        prompt.push_str(&format!(
+            "特殊要求（仅限创作偏好）：\n{}\n\n",
+            wrap_user_field("特殊要求", req)
+        ));
     }
 
     prompt.push_str(&format!(r#"【重要】请严格按照以下格式生成大纲，章节数必须恰好为{}章：
@@ -385,8 +452,11 @@ Quality requirements:
 - complete world building
 - clear timeline
 - consistent character design
-- each chapter must include Time / Goal / Conflict / Hook"#,
-            target_chapters
+- each chapter must include Time / Goal / Conflict / Hook
+
+{}"#,
+            target_chapters,
+            data_boundary_notice(output_language)
         );
     }
 
@@ -410,42 +480,349 @@ Quality requirements:
 - 使用 ## 作为一级标题（故事梗概、世界观设定、时间线事件、主要角色、章节大纲等）
 - 使用 ### 作为二级标题（角色名、章节标题、势力名等）
 - 使用 - **字段**：内容 格式列出详细信息
-- 确保格式统一，便于程序解析"#, target_chapters)
+- 确保格式统一，便于程序解析
+
+{}"#, target_chapters, data_boundary_notice(output_language))
 }
 
-// 查找已生成的最后一章编号
-fn find_last_chapter_number(content: &str) -> u32 {
-    use regex::Regex;
-    
-    let zh_re = Regex::new(r"第(\d+)章").unwrap();
-    let en_re = Regex::new(r"(?i)chapter\s+(\d+)").unwrap();
-    let mut max_chapter = 0u32;
-    
-    for cap in zh_re.captures_iter(content).chain(en_re.captures_iter(content)) {
-        if let Some(num_str) = cap.get(1) {
-            if let Ok(num) = num_str.as_str().parse::<u32>() {
-                if num > max_chapter {
-                    max_chapter = num;
-                }
-            }
+/// 大纲完整性检查结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutlineValidation {
+    pub target_chapters: u32,
+    pub found: Vec<u32>,
+    pub missing: Vec<u32>,
+    pub complete: bool,
+}
+
+// 只统计以章节号开头的标题行（### 第X章 / **Chapter X**），避免时间线等正文中提到的章节号被误计
+fn check_outline(content: &str, target_chapters: u32) -> OutlineValidation {
+    let found = chapter_heading_numbers(content);
+    let missing: Vec<u32> = (1..=target_chapters)
+        .filter(|num| !found.contains(num))
+        .collect();
+
+    OutlineValidation {
+        target_chapters,
+        complete: missing.is_empty(),
+        found: found.into_iter().collect(),
+        missing,
+    }
+}
+
+// 将章节号压缩成区间描述，如 3, 5, 7-10
+fn format_chapter_numbers(numbers: &[u32]) -> String {
+    let mut parts = Vec::new();
+    let mut iter = numbers.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&(end + 1)) {
+            end = iter.next().unwrap_or(end);
+        }
+        if start == end {
+            parts.push(start.to_string());
+        } else {
+            parts.push(format!("{}-{}", start, end));
         }
     }
-    
-    max_chapter
+    parts.join(", ")
+}
+
+/// 检查大纲是否包含第1章到目标章节的全部章节
+#[tauri::command]
+pub fn validate_outline(
+    outline: String,
+    #[allow(non_snake_case)] targetChapters: u32,
+) -> Result<OutlineValidation, String> {
+    Ok(check_outline(&outline, targetChapters))
 }
 
-// 获取字符串最后N个字符
-fn get_last_n_chars(s: &str, n: usize) -> &str {
-    let len = s.len();
-    if len <= n {
-        s
+/// 自动续写时作为上下文的已生成内容结尾（按整段/整句选取）
+const TAIL_CONTEXT_TOKENS: usize = 1500;
+
+// 统计字数：中文按非空白字符计，英文按单词计
+fn count_words(text: &str, output_language: &str) -> usize {
+    if output_language == "en" {
+        text.split_whitespace().count()
     } else {
-        // 找到合适的 UTF-8 边界
-        let mut start = len - n;
-        while start > 0 && !s.is_char_boundary(start) {
-            start -= 1;
+        text.chars().filter(|c| !c.is_whitespace()).count()
+    }
+}
+
+// 单次流式请求的结果
+struct StreamOutcome {
+    content: String,
+    finish_reason: Option<String>,
+    first_token_ms: Option<i64>,
+    usage: Option<TokenUsage>,
+    /// 在收到结束标记前读取失败时的错误；content 保留断开前已收到的内容
+    interrupted: Option<String>,
+}
+
+impl StreamOutcome {
+    // 不支持断线重连的调用方：中途断开视为失败
+    fn into_complete(self) -> Result<Self, String> {
+        match self.interrupted {
+            Some(error) => Err(format!("读取流失败: {}", error)),
+            None => Ok(self),
+        }
+    }
+}
+
+// 一次流式任务（可能包含多轮续写）的累计统计
+#[derive(Default)]
+struct StreamStats {
+    first_token_ms: Option<i64>,
+    usage: Option<TokenUsage>,
+}
+
+impl StreamStats {
+    fn record(&mut self, outcome: &StreamOutcome) {
+        if self.first_token_ms.is_none() {
+            self.first_token_ms = outcome.first_token_ms;
+        }
+        if let Some(usage) = outcome.usage {
+            self.usage = Some(self.usage.unwrap_or_default().add(usage));
+        }
+    }
+}
+
+// 提供 project_id 时记录流式生成任务；记录失败只写日志
+async fn begin_stream_task(
+    pool: &SqlitePool,
+    project_id: Option<&str>,
+    task_type: &str,
+    text_config: &TextModelConfigInput,
+    mut params: serde_json::Value,
+) -> Option<GenerationTask> {
+    let project_id = project_id?;
+    params["provider"] = serde_json::json!(text_config.provider);
+    params["model"] = serde_json::json!(text_config.model);
+    params["seed"] = serde_json::json!(text_config.effective_seed());
+    params["stream"] = serde_json::json!(true);
+
+    match GenerationTaskService::start(pool, project_id, task_type, &params, text_config.effective_seed()).await {
+        Ok(task) => Some(task),
+        Err(e) => {
+            log::warn!("Failed to record {} stream task: {}", task_type, e);
+            None
+        }
+    }
+}
+
+async fn finish_stream_task(
+    pool: &SqlitePool,
+    task: Option<GenerationTask>,
+    result: &Result<String, String>,
+    started: Instant,
+    stats: &StreamStats,
+) {
+    match result {
+        Ok(content) => request_log::info(&format!(
+            "Stream generation completed in {} ms ({} chars)",
+            started.elapsed().as_millis(),
+            content.chars().count()
+        )),
+        Err(e) => request_log::warn(&format!(
+            "Stream generation failed after {} ms: {}",
+            started.elapsed().as_millis(),
+            e
+        )),
+    }
+    let Some(task) = task else {
+        return;
+    };
+    let timing = TaskTiming {
+        latency_ms: started.elapsed().as_millis() as i64,
+        first_token_ms: stats.first_token_ms,
+    };
+    let recorded = match result {
+        Ok(content) => {
+            GenerationTaskService::complete(pool, &task.id, Some(content), stats.usage, timing).await
+        }
+        Err(e) => GenerationTaskService::fail(pool, &task.id, e, timing).await,
+    };
+    if let Err(e) = recorded {
+        log::warn!("Failed to update task {}: {}", task.id, e);
+    }
+}
+
+/// 流式正文事件负载：generation_id 供前端调用 ack_stream_events 回报进度
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamChunkEvent {
+    pub generation_id: Option<String>,
+    pub content: String,
+}
+
+// 一次生成已推送的事件数与前端确认数；从未确认过时为 None，不做背压
+#[derive(Debug, Default)]
+struct StreamProgress {
+    emitted: u64,
+    acked: Option<u64>,
+}
+
+// 续写开头可能复述光标前的文本：开头仍是前文的一部分时先不推送，
+// 一旦不再匹配就按 strip_repeated_prefix 去掉重复部分，之后的增量原样推送
+struct RepeatGuard {
+    before: String,
+    held: String,
+}
+
+impl RepeatGuard {
+    // 返回可以推送的内容；仍无法判断时返回 None
+    fn push(&mut self, delta: &str) -> Option<String> {
+        self.held.push_str(delta);
+        let candidate = self.held.trim_start();
+        if candidate.is_empty() || self.before.contains(candidate) {
+            return None;
+        }
+        Some(strip_repeated_prefix(&self.before, &self.held))
+    }
+
+    fn finish(self) -> String {
+        strip_repeated_prefix(&self.before, &self.held)
+    }
+}
+
+// 合并流式增量，按字符数或时间间隔批量推送，减少窗口事件数量；析构时推送剩余内容。
+// 前端确认过事件后启用背压：未确认事件过多时批次留在 queue 中，超出上限时合并最早的批次。
+// 计数记在所属生成上，同一生成的多轮请求各自创建的 emitter 共享进度
+struct StreamEmitter<'a> {
+    window: &'a Window,
+    event_name: &'a str,
+    generation_id: Option<String>,
+    pending: String,
+    pending_chars: usize,
+    flush_chars: usize,
+    interval: Duration,
+    last_flush: Instant,
+    queue: VecDeque<String>,
+    max_buffered: usize,
+    max_unacked: u64,
+    repeat_guard: Option<RepeatGuard>,
+}
+
+impl<'a> StreamEmitter<'a> {
+    fn new(window: &'a Window, event_name: &'a str, settings: &StreamSettings) -> Self {
+        Self {
+            window,
+            event_name,
+            generation_id: cancellation::current_id(),
+            pending: String::new(),
+            pending_chars: 0,
+            flush_chars: settings.flush_chars.max(1),
+            interval: Duration::from_millis(settings.flush_interval_ms.max(1)),
+            last_flush: Instant::now(),
+            queue: VecDeque::new(),
+            max_buffered: settings.max_buffered_events.max(1),
+            max_unacked: settings.max_unacked_events,
+            repeat_guard: REPEAT_SOURCE
+                .try_with(|before| RepeatGuard { before: before.clone(), held: String::new() })
+                .ok(),
+        }
+    }
+
+    fn push(&mut self, delta: &str) {
+        if let Some(guard) = &mut self.repeat_guard {
+            let Some(text) = guard.push(delta) else {
+                return;
+            };
+            self.repeat_guard = None;
+            self.push(&text);
+            return;
+        }
+        self.pending.push_str(delta);
+        self.pending_chars += delta.chars().count();
+        if self.pending_chars >= self.flush_chars || self.last_flush.elapsed() >= self.interval {
+            self.flush();
+        }
+    }
+
+    fn flush_if_due(&mut self) {
+        if !self.pending.is_empty() && self.last_flush.elapsed() >= self.interval {
+            self.flush();
+        } else if !self.queue.is_empty() {
+            self.drain();
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            self.queue.push_back(std::mem::take(&mut self.pending));
+        }
+        self.pending_chars = 0;
+        self.last_flush = Instant::now();
+        self.drain();
+    }
+
+    // 不在生成中或前端从未确认过时保持原来的即发即弃
+    fn can_emit(&self) -> bool {
+        if self.max_unacked == 0 {
+            return true;
+        }
+        let Some(generation_id) = &self.generation_id else {
+            return true;
+        };
+        match stream_progress().get(generation_id) {
+            Some(StreamProgress { emitted, acked: Some(acked) }) => {
+                emitted.saturating_sub(*acked) < self.max_unacked
+            }
+            _ => true,
+        }
+    }
+
+    fn emit(&self, content: String) {
+        if let Some(generation_id) = &self.generation_id {
+            stream_progress().entry(generation_id.clone()).or_default().emitted += 1;
+        }
+        let _ = self.window.emit(
+            self.event_name,
+            StreamChunkEvent {
+                generation_id: self.generation_id.clone(),
+                content,
+            },
+        );
+    }
+
+    fn drain(&mut self) {
+        while !self.queue.is_empty() && self.can_emit() {
+            if let Some(batch) = self.queue.pop_front() {
+                self.emit(batch);
+            }
+        }
+        while self.queue.len() > self.max_buffered {
+            if let (Some(first), Some(second)) = (self.queue.pop_front(), self.queue.pop_front()) {
+                self.queue.push_front(first + &second);
+            }
+        }
+    }
+}
+
+impl Drop for StreamEmitter<'_> {
+    // 结束时不再等待确认，剩余内容合并为一个事件推送，保证前端拿到完整正文
+    fn drop(&mut self) {
+        let mut rest: String = self.queue.drain(..).collect();
+        rest.push_str(&self.pending);
+        if let Some(guard) = self.repeat_guard.take() {
+            rest.push_str(&guard.finish());
+        }
+        if !rest.is_empty() {
+            self.emit(rest);
         }
-        &s[start..]
+    }
+}
+
+fn stream_progress() -> std::sync::MutexGuard<'static, HashMap<String, StreamProgress>> {
+    STREAM_PROGRESS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// 读取流式推送设置；数据库未就绪时使用默认值
+async fn load_stream_settings(window: &Window) -> StreamSettings {
+    match window.try_state::<SqlitePool>() {
+        Some(pool) => SettingsService::get(pool.inner())
+            .await
+            .map(|settings| settings.stream)
+            .unwrap_or_default(),
+        None => StreamSettings::default(),
     }
 }
 
@@ -460,71 +837,269 @@ async fn stream_generate(
     max_tokens: u32,
     default_temperature: f32,
 ) -> Result<String, String> {
+    let outcome = stream_generate_outcome(
+        client,
+        window,
+        text_config,
+        system_prompt,
+        user_prompt,
+        event_name,
+        max_tokens,
+        default_temperature,
+        None,
+    )
+    .await?;
+    Ok(outcome.content)
+}
+
+// 流式生成，同时返回结束原因（用于判断是否因长度截断）
+async fn stream_generate_outcome(
+    client: &Client,
+    window: &Window,
+    text_config: &TextModelConfigInput,
+    system_prompt: &str,
+    user_prompt: &str,
+    event_name: &str,
+    max_tokens: u32,
+    default_temperature: f32,
+    draft_chapter_id: Option<&str>,
+) -> Result<StreamOutcome, String> {
+    stream_generate_attempt(
+        client,
+        window,
+        text_config,
+        system_prompt,
+        user_prompt,
+        event_name,
+        max_tokens,
+        default_temperature,
+        draft_chapter_id,
+    )
+    .await?
+    .into_complete()
+}
+
+// 单次流式请求；读取中途断开时返回已收到的内容并在 interrupted 中记录错误
+async fn stream_generate_attempt(
+    client: &Client,
+    window: &Window,
+    text_config: &TextModelConfigInput,
+    system_prompt: &str,
+    user_prompt: &str,
+    event_name: &str,
+    max_tokens: u32,
+    default_temperature: f32,
+    draft_chapter_id: Option<&str>,
+) -> Result<StreamOutcome, String> {
     text_config.validate()?;
-    let api_url = text_config.chat_completions_url();
     let temperature = text_config.normalized_temperature(default_temperature);
+    let deepseek = DeepSeekClient::new(
+        text_config.api_key.clone(),
+        Some(text_config.normalized_api_base_url()),
+        Some(text_config.model.clone()),
+    )
+    .with_http_client(client.clone());
+    let messages = vec![ChatMessage {
+        role: "user".to_string(),
+        content: user_prompt.to_string(),
+    }];
+    let params = GenerationParams {
+        temperature: Some(temperature),
+        max_tokens: Some(max_tokens),
+        system_prompt: Some(system_prompt.to_string()),
+        seed: text_config.effective_seed(),
+    };
 
-    let request_body = serde_json::json!({
-        "model": text_config.model,
-        "messages": [
-            {"role": "system", "content": system_prompt},
-            {"role": "user", "content": user_prompt}
-        ],
-        "temperature": temperature,
-        "max_tokens": max_tokens,
-        "stream": true
-    });
-
-    let response = client
-        .post(&api_url)
-        .header("Authorization", format!("Bearer {}", text_config.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
+    let started = Instant::now();
+    // 错误信息保留上下文超长标记，供调用方缩减上下文后重试
+    let mut stream = deepseek
+        .stream_text(messages, params)
         .await
         .map_err(|e| format!("请求失败: {}", e))?;
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API错误: {}", error_text));
-    }
-
     let mut full_content = String::new();
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk_result) = stream.next().await {
-        if CANCEL_FLAG.load(Ordering::SeqCst) {
+    let mut finish_reason = None;
+    let mut first_token_ms = None;
+    let mut usage = None;
+    let mut interrupted = None;
+    let mut emitter = StreamEmitter::new(window, event_name, &load_stream_settings(window).await);
+    let mut ticker = tokio::time::interval(emitter.interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        // 等待下一个事件的同时，按时间间隔推送已缓冲的内容
+        let next = tokio::select! {
+            next = stream.next() => next,
+            _ = ticker.tick() => {
+                // 连接空闲时也要响应取消，不必等到读取超时
+                if cancellation::is_cancelled() {
+                    return Err("生成已被用户中断".to_string());
+                }
+                emitter.flush_if_due();
+                continue;
+            }
+        };
+        let Some(event) = next else {
+            break;
+        };
+        if cancellation::is_cancelled() {
             return Err("生成已被用户中断".to_string());
         }
 
-        let chunk = chunk_result.map_err(|e| format!("读取流失败: {}", e))?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
-
-        for line in chunk_str.lines() {
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-                if data == "[DONE]" {
-                    continue;
+        match event {
+            Ok(StreamEvent::Delta(content)) => {
+                if first_token_ms.is_none() {
+                    first_token_ms = Some(started.elapsed().as_millis() as i64);
                 }
-
-                if let Ok(stream_response) = serde_json::from_str::<StreamResponse>(data) {
-                    if let Some(choice) = stream_response.choices.first() {
-                        if let Some(content) = &choice.delta.content {
-                            full_content.push_str(content);
-                            let _ = window.emit(event_name, content.clone());
-                        }
-                    }
+                full_content.push_str(&content);
+                emitter.push(&content);
+                if let Some(chapter_id) = draft_chapter_id {
+                    draft_buffer::append(chapter_id, &content);
                 }
             }
+            Ok(StreamEvent::Finish(reason)) => finish_reason = Some(reason),
+            Ok(StreamEvent::Usage(chunk_usage)) => usage = Some(chunk_usage),
+            Err(e) => {
+                interrupted = Some(e.to_string());
+                break;
+            }
         }
     }
 
-    Ok(full_content)
+    request_log::info(&format!(
+        "Stream finished: chars={}, finish_reason={:?}, first_token_ms={:?}, total_tokens={:?}, interrupted={:?}",
+        full_content.chars().count(),
+        finish_reason,
+        first_token_ms,
+        usage.map(|usage| usage.total_tokens),
+        interrupted
+    ));
+    Ok(StreamOutcome {
+        content: full_content,
+        finish_reason,
+        first_token_ms,
+        usage,
+        interrupted,
+    })
+}
+
+/// generation-started 事件负载：前端用 generation_id 调用 cancel_generation 只取消这次生成
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationStartedEvent {
+    pub generation_id: String,
+    pub event_name: String,
+}
+
+// 进行中的流式生成；结束时注销并清理推送进度
+struct StreamGeneration(Generation);
+
+impl StreamGeneration {
+    async fn scope<F: std::future::Future>(&self, future: F) -> F::Output {
+        self.0.scope(future).await
+    }
+}
+
+impl Drop for StreamGeneration {
+    fn drop(&mut self) {
+        stream_progress().remove(self.0.id());
+    }
+}
+
+// 登记一次流式生成并把 generation_id 推送给前端；chapter_id 为写入草稿的章节
+fn start_generation(window: &Window, event_name: &str, chapter_id: Option<&str>) -> StreamGeneration {
+    let generation = Generation::register(chapter_id);
+    let _ = window.emit(
+        "generation-started",
+        GenerationStartedEvent {
+            generation_id: generation.id().to_string(),
+            event_name: event_name.to_string(),
+        },
+    );
+    StreamGeneration(generation)
+}
+
+// 开启串行生成时等待前一个生成结束；排队期间被取消则不再发起请求
+async fn wait_generation_turn(window: &Window) -> Result<Option<MutexGuard<'static, ()>>, String> {
+    let turn = if load_stream_settings(window).await.serialize_generations {
+        Some(GENERATION_LOCK.lock().await)
+    } else {
+        None
+    };
+    if cancellation::is_cancelled() {
+        return Err("生成已被用户中断".to_string());
+    }
+    Ok(turn)
+}
+
+/// 取消流式生成：传入 generationId 时只取消该次生成，否则取消所有进行中的生成。
+/// 返回是否有生成被取消
+#[tauri::command]
+pub fn cancel_generation(
+    #[allow(non_snake_case)] generationId: Option<String>,
+) -> Result<bool, String> {
+    Ok(match generationId {
+        Some(generation_id) => cancellation::cancel(TaskKind::Generation, &generation_id),
+        None => cancellation::cancel_all(TaskKind::Generation) > 0,
+    })
+}
+
+/// 等待被放弃的流式生成结束的最长时间
+const ABORT_RESTORE_WAIT: Duration = Duration::from_secs(10);
+
+/// 取消正在写入该章节的流式生成，把草稿还原为生成前的内容，并通过 chapter-restored
+/// 事件重新推送数据库中的正文，供编辑器丢弃已显示的部分内容。
+/// 该章节没有进行中的生成时只重新推送当前内容
+#[tauri::command]
+pub async fn abort_and_restore(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    #[allow(non_snake_case)] chapterId: String,
+) -> Result<Chapter, String> {
+    if let Some(original) = draft_buffer::discard(&chapterId) {
+        cancellation::cancel_chapter(&chapterId);
+        let started = Instant::now();
+        while draft_buffer::is_active(&chapterId) && started.elapsed() < ABORT_RESTORE_WAIT {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        if draft_buffer::is_active(&chapterId) {
+            log::warn!("Generation for chapter {} did not stop in time, restoring anyway", chapterId);
+        }
+
+        let chapter = ChapterService::get_by_id(&pool, &chapterId)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("章节不存在")?;
+        let milestones = ChapterService::update_text(&pool, &chapterId, original, chapter.final_text, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        emit_milestones(&window, milestones);
+    }
+
+    let chapter = ChapterService::get_by_id(&pool, &chapterId)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let _ = window.emit(
+        "chapter-restored",
+        ChapterSavedEvent {
+            chapter_id: chapter.id.clone(),
+            content: chapter.draft_text.clone().unwrap_or_default(),
+            word_count: chapter.word_count,
+        },
+    );
+    Ok(chapter)
 }
 
+/// 前端回报该次生成已处理的流式事件总数（跨续写轮次累计），用于推送背压；
+/// 生成已结束时忽略
 #[tauri::command]
-pub fn cancel_generation() -> Result<(), String> {
-    CANCEL_FLAG.store(true, Ordering::SeqCst);
+pub fn ack_stream_events(
+    #[allow(non_snake_case)] generationId: String,
+    received: u64,
+) -> Result<(), String> {
+    if let Some(progress) = stream_progress().get_mut(&generationId) {
+        progress.acked = Some(progress.acked.unwrap_or(0).max(received));
+    }
     Ok(())
 }
 
@@ -537,9 +1112,6 @@ pub async fn generate_prologue_stream(
     #[allow(non_snake_case)] outputLanguage: Option<String>,
     #[allow(non_snake_case)] textConfig: TextModelConfigInput,
 ) -> Result<String, String> {
-    let _lock = GENERATION_LOCK.lock().await;
-    CANCEL_FLAG.store(false, Ordering::SeqCst);
-
     let client = Client::new();
     let output_language = normalize_output_language(outputLanguage.as_deref());
 
@@ -588,125 +1160,739 @@ Outline:
         )
     };
 
-    let content = stream_generate(
-        &client,
-        &window,
-        &textConfig,
-        &system_prompt,
-        &prompt,
-        "chapter-stream",
-        2200,
-        0.7,
-    )
-    .await?;
+    let generation = start_generation(&window, "chapter-stream", None);
+    generation
+        .scope(async {
+            let _turn = wait_generation_turn(&window).await?;
+            stream_generate(
+                &client,
+                &window,
+                &textConfig,
+                &system_prompt,
+                &prompt,
+                "chapter-stream",
+                2200,
+                0.7,
+            )
+            .await
+        })
+        .await
+}
 
-    Ok(content)
+/// 设定集的输出上限；资料部分按模型上下文窗口扣除输出和提示词后裁剪
+const SERIES_BIBLE_MAX_TOKENS: u32 = 6000;
+const SERIES_BIBLE_PROMPT_RESERVE_TOKENS: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateSeriesBibleInput {
+    pub project_id: String,
+    #[serde(default)]
+    pub text_config: Option<TextModelConfigInput>,
 }
 
+/// 汇总设定、角色、时间线和章节摘要，流式生成系列设定集并保存到项目；可随写作进度重复生成
 #[tauri::command]
-pub async fn generate_chapter_stream(
+pub async fn generate_series_bible(
     window: Window,
-    #[allow(non_snake_case)] chapterTitle: String,
-    #[allow(non_snake_case)] outlineGoal: String,
-    conflict: String,
-    #[allow(non_snake_case)] previousSummary: Option<String>,
-    #[allow(non_snake_case)] currentContent: Option<String>,
-    #[allow(non_snake_case)] charactersInfo: Option<String>,
-    #[allow(non_snake_case)] worldSetting: Option<String>,
-    #[allow(non_snake_case)] timeline: Option<String>,
-    #[allow(non_snake_case)] targetWords: Option<u32>,
-    #[allow(non_snake_case)] isContinuation: Option<bool>,
-    #[allow(non_snake_case)] outputLanguage: Option<String>,
-    #[allow(non_snake_case)] textConfig: TextModelConfigInput,
-) -> Result<String, String> {
-    let _lock = GENERATION_LOCK.lock().await;
-    CANCEL_FLAG.store(false, Ordering::SeqCst);
-    textConfig.validate()?;
+    pool: State<'_, SqlitePool>,
+    input: GenerateSeriesBibleInput,
+) -> Result<ProjectBible, String> {
+    let project = ProjectService::get_by_id(&pool, &input.project_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("项目不存在")?;
+    let config = resolve_text_config(&pool, input.text_config).await?;
+    config.validate()?;
 
-    let client = Client::new();
-    let is_continue = isContinuation.unwrap_or(false);
-    let word_target = targetWords.unwrap_or(2500);
-    let output_language = normalize_output_language(outputLanguage.as_deref());
-    let api_url = textConfig.chat_completions_url();
-    let temperature = textConfig.normalized_temperature(0.7);
-    
-    let mut prompt = String::new();
-    
-    if let Some(ref world) = worldSetting {
-        if output_language == "en" {
-            prompt.push_str(&format!(
-                r#"[Important: World Building - follow strictly]
-Keep all generated content consistent with this world setting:
+    let characters = CharacterService::get_by_project(&pool, &project.id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let lore = LoreService::get_by_project(&pool, &project.id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let events = TimelineService::get_by_project(&pool, &project.id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let chapters = ChapterService::get_by_project(&pool, &project.id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 没有摘要的章节退回大纲目标，至少保留剧情走向
+    let chapter_summaries = chapters
+        .iter()
+        .filter_map(|chapter| {
+            let summary = [chapter.summary.as_deref(), chapter.outline_goal.as_deref()]
+                .into_iter()
+                .flatten()
+                .map(str::trim)
+                .find(|s| !s.is_empty())?;
+            Some(format!("{}. {}：{}", chapter.order_index, chapter.title, summary))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let characters_info = format_characters(&characters);
+    let world_setting = format_lore(&lore);
+    let timeline = format_timeline(&events);
+    if [&chapter_summaries, &characters_info, &world_setting, &timeline]
+        .iter()
+        .all(|text| text.trim().is_empty())
+    {
+        return Err("项目还没有可汇总的设定、角色、时间线或章节摘要".to_string());
+    }
 
-{}
+    let budget = context_window(&config.provider, &config.model)
+        .saturating_sub(SERIES_BIBLE_MAX_TOKENS as usize + SERIES_BIBLE_PROMPT_RESERVE_TOKENS);
+    let context = fit_chapter_context(
+        &window,
+        "series_bible",
+        budget,
+        Some(&chapter_summaries),
+        Some(&characters_info),
+        Some(&world_setting),
+        Some(&timeline),
+    );
+    let output_language = normalize_output_language(Some(&project.language));
+    let (system_prompt, user_prompt) = build_series_bible_prompt(
+        &project.title,
+        output_language,
+        [
+            ("world_setting", context.get("world_setting")),
+            ("characters", context.get("characters")),
+            ("timeline", context.get("timeline")),
+            ("chapter_summaries", context.get("previous_summary")),
+        ],
+    );
 
-"#,
-                world
-            ));
-        } else {
-            prompt.push_str(&format!(
-                r#"【重要：世界观设定 - 必须严格遵守】
-以下是本小说的世界观设定，生成内容时必须保持一致，不得与设定冲突：
+    let params = serde_json::json!({
+        "chapters": chapters.len(),
+        "characters": characters.len(),
+        "lore": lore.len(),
+        "timeline_events": events.len(),
+        "context_trimmed": context.report.has_cuts(),
+    });
+    let task = begin_stream_task(&pool, Some(&project.id), "series_bible", &config, params).await;
+    let started = Instant::now();
+    let mut stats = StreamStats::default();
+
+    let correlation_id = request_log::correlation_id(task.as_ref().map(|t| t.id.as_str()));
+    let generation = start_generation(&window, "series-bible-stream", None);
+    let content = request_log::scope(correlation_id, async {
+        let result = generation.scope(async {
+            let _turn = wait_generation_turn(&window).await?;
+
+            let outcome = stream_generate_outcome(
+                &Client::new(),
+                &window,
+                &config,
+                &system_prompt,
+                &user_prompt,
+                "series-bible-stream",
+                SERIES_BIBLE_MAX_TOKENS,
+                0.5,
+                None,
+            )
+            .await?;
+            stats.record(&outcome);
+            if outcome.content.trim().is_empty() {
+                return Err("AI 未返回设定集内容".to_string());
+            }
+            Ok(outcome.content.trim().to_string())
+        })
+        .await;
+        finish_stream_task(&pool, task, &result, started, &stats).await;
+        result
+    })
+    .await?;
 
-{}
+    ProjectService::save_bible(&pool, &project.id, &content)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-"#,
-                world
-            ));
+fn build_series_bible_prompt(
+    title: &str,
+    output_language: &str,
+    sections: [(&str, Option<&str>); 4],
+) -> (String, String) {
+    let material = sections
+        .iter()
+        .filter_map(|(label, text)| text.map(|text| wrap_user_field(label, text)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if output_language == "en" {
+        (
+            format!(
+                "You are the continuity editor of a long-running novel series. Consolidate the supplied material into a single, well-organized series bible the author can consult while writing. Only state what the material supports; do not invent new facts.\n{}",
+                data_boundary_notice("en")
+            ),
+            format!(
+                r#"Write the series bible for "{}".
+Use these Markdown sections:
+1. World: setting, rules, factions, places.
+2. Characters: each major character's role, traits, relationships, and arc so far.
+3. Story so far: the main plot by act or chapter range.
+4. Timeline: key events in order.
+5. Open threads: unresolved conflicts, mysteries, and foreshadowing still to pay off.
+
+Material:
+{}"#,
+                sanitize_inline(title),
+                material
+            ),
+        )
+    } else {
+        (
+            format!(
+                "你是一部长篇系列小说的设定编辑，负责把零散的资料整理成一份条理清晰、便于作者写作时查阅的系列设定集。只写资料能支持的内容，不要编造新设定。\n{}",
+                data_boundary_notice("zh")
+            ),
+            format!(
+                r#"请为《{}》整理系列设定集，使用以下 Markdown 小节：
+1. 世界观：背景、规则、势力、地点。
+2. 人物：主要角色的定位、性格、人物关系以及到目前为止的成长弧线。
+3. 剧情进展：按卷或章节区间概括主线。
+4. 时间线：按顺序列出关键事件。
+5. 未解线索：尚未解决的冲突、悬念和待回收的伏笔。
+
+资料：
+{}"#,
+                sanitize_inline(title),
+                material
+            ),
+        )
+    }
+}
+
+#[tauri::command]
+pub async fn generate_chapter_stream(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    #[allow(non_snake_case)] chapterTitle: String,
+    #[allow(non_snake_case)] outlineGoal: String,
+    conflict: String,
+    #[allow(non_snake_case)] previousSummary: Option<String>,
+    #[allow(non_snake_case)] currentContent: Option<String>,
+    #[allow(non_snake_case)] charactersInfo: Option<String>,
+    #[allow(non_snake_case)] worldSetting: Option<String>,
+    #[allow(non_snake_case)] timeline: Option<String>,
+    #[allow(non_snake_case)] targetWords: Option<u32>,
+    #[allow(non_snake_case)] isContinuation: Option<bool>,
+    #[allow(non_snake_case)] outputLanguage: Option<String>,
+    #[allow(non_snake_case)] autoContinue: Option<bool>,
+    #[allow(non_snake_case)] maxContinuationRounds: Option<u32>,
+    #[allow(non_snake_case)] projectId: Option<String>,
+    #[allow(non_snake_case)] chapterId: Option<String>,
+    #[allow(non_snake_case)] textConfig: TextModelConfigInput,
+) -> Result<String, String> {
+    // 章节的生成参数覆盖优先于前端传入的配置
+    let (text_config, effective) =
+        resolve_chapter_overrides(&pool, chapterId.as_deref(), &textConfig, targetWords).await?;
+    emit_chapter_settings(&window, "chapter-stream", &effective);
+    let target_words = effective.target_words;
+
+    // 传入 chapterId 时由后端定时保存草稿，前端无需在流式过程中反复调用 update_chapter
+    let draft_saver = match chapterId.as_deref() {
+        Some(chapter_id) => Some(
+            DraftSaver::start(pool.inner().clone(), chapter_id, isContinuation.unwrap_or(false)).await?,
+        ),
+        None => None,
+    };
+
+    let params = serde_json::json!({
+        "chapter_title": chapterTitle,
+        "target_words": target_words,
+        "is_continuation": isContinuation,
+    });
+    let task = begin_stream_task(&pool, projectId.as_deref(), "chapter", &text_config, params).await;
+    let started = Instant::now();
+    let mut stats = StreamStats::default();
+
+    let expected_language = normalize_output_language(outputLanguage.as_deref());
+
+    let budget = SettingsService::get(&pool)
+        .await
+        .map(|settings| settings.context_budget.injection_tokens)
+        .unwrap_or_else(|_| ContextBudgetSettings::default().injection_tokens);
+    let fit = |budget: usize| {
+        fit_chapter_context(
+            &window,
+            "chapter-stream",
+            budget,
+            previousSummary.as_deref(),
+            charactersInfo.as_deref(),
+            worldSetting.as_deref(),
+            timeline.as_deref(),
+        )
+    };
+    let mut context = fit(budget);
+
+    let generation = start_generation(&window, "chapter-stream", chapterId.as_deref());
+    let correlation_id = request_log::correlation_id(task.as_ref().map(|t| t.id.as_str()));
+    let result = request_log::scope(correlation_id, async {
+        // 请求因上下文超长被拒（尚未推送任何内容）时按更小的预算重新裁剪，重试一次
+        let mut trimmed_retry = false;
+        let result = loop {
+            let owned = |name: &str| context.get(name).map(str::to_string);
+            let result = generation.scope(run_chapter_stream(
+                &window,
+                chapterTitle.clone(),
+                outlineGoal.clone(),
+                conflict.clone(),
+                owned("previous_summary"),
+                currentContent.clone(),
+                owned("characters"),
+                owned("world_setting"),
+                owned("timeline"),
+                target_words,
+                isContinuation,
+                outputLanguage.clone(),
+                autoContinue,
+                maxContinuationRounds,
+                text_config.clone(),
+                &mut stats,
+                chapterId.as_deref(),
+            ))
+            .await;
+            match result {
+                Err(e) if is_context_length_exceeded(&e) && stats.first_token_ms.is_none() => {
+                    match context_retry_budget(&context.report).filter(|_| !trimmed_retry) {
+                        Some(budget) => {
+                            request_log::warn(&format!(
+                                "Chapter prompt exceeds model context, retrying with {} tokens",
+                                budget
+                            ));
+                            context = fit(budget);
+                            trimmed_retry = true;
+                        }
+                        None => break Err(context_too_long_error(&context.report, trimmed_retry)),
+                    }
+                }
+                result => break result,
+            }
+        };
+        finish_stream_task(&pool, task, &result, started, &stats).await;
+        result
+    })
+    .await;
+
+    // 无论成功、失败还是中断，都把已生成的内容落盘一次并重算字数
+    if let Some(saver) = draft_saver {
+        saver.finish(&window, &pool).await;
+    }
+
+    // 流式内容已推送给前端，语言不符或人称/时态偏离时只提示，不自动重试
+    if let (Ok(content), Some(project_id)) = (&result, projectId.as_deref()) {
+        spawn_narrative_check(
+            window.clone(),
+            pool.inner().clone(),
+            text_config.clone(),
+            "chapter-stream",
+            project_id.to_string(),
+            chapterId.clone(),
+            content.clone(),
+        );
+    }
+    if let Ok(ref content) = result {
+        if let Ok(settings) = SettingsService::get(&pool).await {
+            let language = settings.language_check;
+            if language.mode != "off" {
+                let check = check_language(content, expected_language, language.mismatch_threshold);
+                if check.mismatch {
+                    emit_language_mismatch(&window, "chapter-stream", check, false);
+                }
+            }
         }
     }
+    result
+}
 
-    if let Some(ref tl) = timeline {
-        if output_language == "en" {
-            prompt.push_str(&format!(
-                r#"[Important: Timeline - follow strictly]
-Keep chronology consistent with these events:
+const DRAFT_SAVE_INTERVAL: Duration = Duration::from_secs(3);
 
-{}
+/// chapter-saved 事件负载：流式生成结束后数据库中的最终草稿
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterSavedEvent {
+    pub chapter_id: String,
+    pub content: String,
+    pub word_count: i64,
+}
 
-"#,
-                tl
-            ));
+// 流式生成期间的服务端草稿保存：增量先进 draft_buffer，定时只写 draft_text，结束时完整保存一次
+struct DraftSaver {
+    chapter_id: String,
+    ticker: tokio::task::JoinHandle<()>,
+}
+
+impl DraftSaver {
+    async fn start(pool: SqlitePool, chapter_id: &str, is_continuation: bool) -> Result<Self, String> {
+        let chapter = ChapterService::get_by_id(&pool, chapter_id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("章节不存在")?;
+        // 续写时追加到已有草稿之后，否则新内容替换草稿
+        let base = if is_continuation {
+            chapter.draft_text.clone().unwrap_or_default()
         } else {
-            prompt.push_str(&format!(
-                r#"【重要：时间线事件 - 必须严格遵守】
-以下是本小说的时间线，生成内容时必须保持时间顺序一致，不得与已发生的事件冲突：
+            String::new()
+        };
+        draft_buffer::begin(chapter_id, base, chapter.draft_text);
+
+        let id = chapter_id.to_string();
+        let ticker = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DRAFT_SAVE_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Some(content) = draft_buffer::take_dirty(&id) {
+                    if let Err(e) = ChapterService::save_draft_checkpoint(&pool, &id, &content).await {
+                        log::warn!("Failed to checkpoint draft for chapter {}: {}", id, e);
+                    }
+                }
+            }
+        });
 
-{}
+        Ok(Self {
+            chapter_id: chapter_id.to_string(),
+            ticker,
+        })
+    }
 
-"#,
-                tl
-            ));
+    async fn finish(self, window: &Window, pool: &SqlitePool) {
+        self.ticker.abort();
+        let content = match draft_buffer::finish(&self.chapter_id) {
+            Some(DraftOutcome::Completed(content)) => content,
+            // abort_and_restore 负责还原生成前的草稿
+            Some(DraftOutcome::Discarded) => {
+                log::info!("Discarded streamed draft for chapter {}", self.chapter_id);
+                return;
+            }
+            None => return,
+        };
+
+        let saved = async {
+            let chapter = ChapterService::get_by_id(pool, &self.chapter_id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+            let milestones = ChapterService::update_text(pool, &self.chapter_id, Some(content.clone()), chapter.final_text, None)
+                .await?;
+            let word_count = ChapterService::get_by_id(pool, &self.chapter_id)
+                .await?
+                .map(|c| c.word_count)
+                .unwrap_or(0);
+            Ok::<_, anyhow::Error>((milestones, word_count))
+        }
+        .await;
+
+        match saved {
+            Ok((milestones, word_count)) => {
+                let _ = window.emit(
+                    "chapter-saved",
+                    ChapterSavedEvent {
+                        chapter_id: self.chapter_id.clone(),
+                        content,
+                        word_count,
+                    },
+                );
+                emit_milestones(window, milestones);
+            }
+            Err(e) => log::error!("Failed to save streamed draft for chapter {}: {}", self.chapter_id, e),
         }
     }
+}
 
-    if let Some(ref chars) = charactersInfo {
-        if output_language == "en" {
-            prompt.push_str(&format!(
-                r#"[Important: Character Bible - follow strictly]
-Keep identity/personality/background/motivation consistent:
+async fn run_chapter_stream(
+    window: &Window,
+    #[allow(non_snake_case)] chapterTitle: String,
+    #[allow(non_snake_case)] outlineGoal: String,
+    conflict: String,
+    #[allow(non_snake_case)] previousSummary: Option<String>,
+    #[allow(non_snake_case)] currentContent: Option<String>,
+    #[allow(non_snake_case)] charactersInfo: Option<String>,
+    #[allow(non_snake_case)] worldSetting: Option<String>,
+    #[allow(non_snake_case)] timeline: Option<String>,
+    #[allow(non_snake_case)] targetWords: Option<u32>,
+    #[allow(non_snake_case)] isContinuation: Option<bool>,
+    #[allow(non_snake_case)] outputLanguage: Option<String>,
+    #[allow(non_snake_case)] autoContinue: Option<bool>,
+    #[allow(non_snake_case)] maxContinuationRounds: Option<u32>,
+    #[allow(non_snake_case)] textConfig: TextModelConfigInput,
+    stats: &mut StreamStats,
+    draft_chapter_id: Option<&str>,
+) -> Result<String, String> {
+    let _turn = wait_generation_turn(window).await?;
+    textConfig.validate()?;
 
-{}
+    let client = Client::new();
+    let is_continue = isContinuation.unwrap_or(false);
+    let word_target = targetWords.unwrap_or(2500);
+    let output_language = normalize_output_language(outputLanguage.as_deref());
+    let auto_continue = autoContinue.unwrap_or(false);
+    let max_rounds = maxContinuationRounds
+        .unwrap_or(DEFAULT_CHAPTER_CONTINUATION_ROUNDS)
+        .min(MAX_CHAPTER_CONTINUATION_ROUNDS);
+    
+    let parts = build_chapter_prompt_parts(
+        &chapterTitle,
+        &outlineGoal,
+        &conflict,
+        previousSummary.as_deref(),
+        currentContent.as_deref(),
+        charactersInfo.as_deref(),
+        worldSetting.as_deref(),
+        timeline.as_deref(),
+        word_target,
+        is_continue,
+        output_language,
+    );
+    let context_prefix = parts.context_prefix();
+    let prompt = format!("{}{}", context_prefix, parts.task);
+    let segment = ChapterSegmentConfig {
+        text_config: &textConfig,
+        system_prompt: parts.system,
+        draft_chapter_id,
+        context_prefix: &context_prefix,
+        chapter_title: &chapterTitle,
+        outline_goal: &outlineGoal,
+        word_target: word_target as usize,
+        output_language,
+    };
 
-"#,
-                chars
-            ));
-        } else {
-            prompt.push_str(&format!(
-                r#"【重要：角色设定 - 必须严格遵守】
-以下是本小说的角色设定，生成内容时必须保持角色身份、性格、背景完全一致，不得擅自更改：
+    // 控制在4000 tokens以内，避免中断
+    let existing = if is_continue { currentContent.as_deref().unwrap_or("") } else { "" };
+    let first = stream_chapter_segment(&client, window, &segment, &prompt, existing, 0, stats).await?;
 
-{}
+    let mut full_content = first.content;
+    let mut finish_reason = first.finish_reason;
 
-"#,
-                chars
-            ));
+    // 因 max_tokens 截断且未达到目标字数时，以结尾内容为上下文自动续写
+    if auto_continue {
+        for _ in 0..max_rounds {
+            if finish_reason.as_deref() != Some("length") {
+                break;
+            }
+            let written = count_words(&full_content, output_language);
+            if written >= word_target as usize {
+                break;
+            }
+            if cancellation::is_cancelled() {
+                return Err("生成已被用户中断".to_string());
+            }
+
+            let continuation_prompt = build_chapter_continuation_prompt(
+                &context_prefix,
+                &chapterTitle,
+                &outlineGoal,
+                select_tail_context(&full_content, TAIL_CONTEXT_TOKENS),
+                word_target as usize - written,
+                output_language,
+            );
+
+            let outcome = stream_chapter_segment(
+                &client,
+                window,
+                &segment,
+                &continuation_prompt,
+                &full_content,
+                written,
+                stats,
+            )
+            .await?;
+
+            full_content.push_str(&outcome.content);
+            finish_reason = outcome.finish_reason;
+        }
+    }
+
+    Ok(full_content)
+}
+
+/// 注入内容占可用输入空间达到该比例时提醒
+const INJECTED_CONTEXT_WARN_RATIO: f64 = 0.8;
+/// 超出上下文时依次移除的注入内容（优先级从低到高）
+const CONTEXT_DROP_ORDER: [&str; 4] = ["timeline", "world_setting", "characters", "previous_chapter"];
+
+/// 按数据库中的角色、设定、时间线和上一章结尾构建将要发送的章节提示词，预估上下文窗口占用
+#[tauri::command]
+pub async fn preview_context_usage(
+    pool: State<'_, SqlitePool>,
+    #[allow(non_snake_case)] chapterId: String,
+    #[allow(non_snake_case)] textConfig: Option<TextModelConfigInput>,
+    #[allow(non_snake_case)] targetWords: Option<u32>,
+    #[allow(non_snake_case)] outputLanguage: Option<String>,
+) -> Result<ContextUsagePreview, String> {
+    let config = resolve_text_config(&pool, textConfig).await?;
+    let chapter = ChapterService::get_by_id(&pool, &chapterId)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("章节不存在")?;
+    let language = match outputLanguage {
+        Some(value) => normalize_output_language(Some(&value)),
+        None => {
+            let project = ProjectService::get_by_id(&pool, &chapter.project_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            normalize_output_language(project.as_ref().map(|p| p.language.as_str()))
         }
+    };
+    let context = load_chapter_context(&pool, &chapter, Some(&config))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let parts = build_chapter_prompt_parts(
+        &chapter.title,
+        chapter.outline_goal.as_deref().unwrap_or(""),
+        chapter.conflict.as_deref().unwrap_or(""),
+        context.previous_tail.as_deref(),
+        None,
+        context.characters_info.as_deref(),
+        context.world_setting.as_deref(),
+        context.timeline.as_deref(),
+        targetWords.unwrap_or(2500),
+        false,
+        language,
+    );
+
+    let mut sections: Vec<ContextSectionUsage> = parts
+        .context
+        .iter()
+        .map(|(name, block)| ContextSectionUsage {
+            name: name.to_string(),
+            tokens: estimate_tokens(block),
+        })
+        .collect();
+    // 上一章结尾写在任务提示词内，单独计量以便判断能否移除
+    let previous_tokens = context.previous_tail.as_deref().map(estimate_tokens).unwrap_or(0);
+    if previous_tokens > 0 {
+        sections.push(ContextSectionUsage {
+            name: "previous_chapter".to_string(),
+            tokens: previous_tokens,
+        });
+    }
+
+    let system_tokens = estimate_message_tokens(parts.system);
+    let injected_tokens: usize = sections.iter().map(|s| s.tokens).sum();
+    let user_tokens = estimate_message_tokens(&format!("{}{}", parts.context_prefix(), parts.task));
+    let task_tokens = user_tokens.saturating_sub(injected_tokens);
+    let total_input_tokens = system_tokens + user_tokens;
+
+    let window = context_window(&config.provider, &config.model);
+    let reserved = CHAPTER_MAX_TOKENS as usize;
+    let available_input = window.saturating_sub(reserved);
+    let overflow_tokens = total_input_tokens.saturating_sub(available_input);
+    let utilization_percent =
+        ((total_input_tokens + reserved) as f64 / window as f64 * 1000.0).round() / 10.0;
+
+    let mut warnings = Vec::new();
+    let mut dropped = Vec::new();
+    if overflow_tokens > 0 {
+        let mut remaining = overflow_tokens;
+        for name in CONTEXT_DROP_ORDER {
+            if remaining == 0 {
+                break;
+            }
+            if let Some(section) = sections.iter().find(|s| s.name == name && s.tokens > 0) {
+                dropped.push(section.name.clone());
+                remaining = remaining.saturating_sub(section.tokens);
+            }
+        }
+        warnings.push(format!(
+            "预计超出上下文窗口约 {} tokens，需要移除：{}",
+            overflow_tokens,
+            if dropped.is_empty() { "（无可移除的注入内容）".to_string() } else { dropped.join("、") }
+        ));
+        if remaining > 0 {
+            warnings.push("移除全部注入内容后仍超出上下文窗口，请缩短章节目标或更换更大窗口的模型".to_string());
+        }
+    }
+    if available_input > 0
+        && injected_tokens as f64 >= available_input as f64 * INJECTED_CONTEXT_WARN_RATIO
+    {
+        warnings.push(format!(
+            "注入的设定上下文约 {} tokens，已占可用输入空间的 {:.0}%",
+            injected_tokens,
+            injected_tokens as f64 / available_input as f64 * 100.0
+        ));
+    }
+
+    Ok(ContextUsagePreview {
+        model: config.model,
+        context_window: window,
+        reserved_output_tokens: reserved,
+        system_tokens,
+        injected_tokens,
+        task_tokens,
+        total_input_tokens,
+        utilization_percent,
+        overflow_tokens,
+        sections,
+        dropped,
+        warnings,
+    })
+}
+
+// 章节提示词的组成：系统提示词、注入的设定上下文（世界观/时间线/角色）与本次写作任务
+struct ChapterPromptParts {
+    system: &'static str,
+    context: Vec<(&'static str, String)>,
+    task: String,
+}
+
+impl ChapterPromptParts {
+    // 世界观/时间线/角色设定部分，自动续写时复用
+    fn context_prefix(&self) -> String {
+        self.context.iter().map(|(_, block)| block.as_str()).collect()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_chapter_prompt_parts(
+    chapter_title: &str,
+    outline_goal: &str,
+    conflict: &str,
+    previous_summary: Option<&str>,
+    current_content: Option<&str>,
+    characters_info: Option<&str>,
+    world_setting: Option<&str>,
+    timeline: Option<&str>,
+    word_target: u32,
+    is_continue: bool,
+    output_language: &str,
+) -> ChapterPromptParts {
+    let en = output_language == "en";
+    let mut context = Vec::new();
+
+    if let Some(world) = world_setting {
+        let header = if en {
+            "[Important: World Building - follow strictly]\nKeep all generated content consistent with this world setting:"
+        } else {
+            "【重要：世界观设定 - 必须严格遵守】\n以下是本小说的世界观设定，生成内容时必须保持一致，不得与设定冲突："
+        };
+        context.push(("world_setting", format!("{}\n\n{}\n\n", header, world)));
+    }
+
+    if let Some(tl) = timeline {
+        let header = if en {
+            "[Important: Timeline - follow strictly]\nKeep chronology consistent with these events:"
+        } else {
+            "【重要：时间线事件 - 必须严格遵守】\n以下是本小说的时间线，生成内容时必须保持时间顺序一致，不得与已发生的事件冲突："
+        };
+        context.push(("timeline", format!("{}\n\n{}\n\n", header, tl)));
     }
 
+    if let Some(chars) = characters_info {
+        let header = if en {
+            "[Important: Character Bible - follow strictly]\nKeep identity/personality/background/motivation consistent:"
+        } else {
+            "【重要：角色设定 - 必须严格遵守】\n以下是本小说的角色设定，生成内容时必须保持角色身份、性格、背景完全一致，不得擅自更改："
+        };
+        context.push(("characters", format!("{}\n\n{}\n\n", header, chars)));
+    }
+
+    let mut task = String::new();
+
     if is_continue {
         if output_language == "en" {
-            prompt.push_str(&format!(
+            task.push_str(&format!(
                 r#"Continue writing this chapter.
 
 Chapter title: {}
@@ -724,13 +1910,13 @@ Requirements:
 6. Output plain English prose only (no Markdown).
 
 Continue directly:"#,
-                chapterTitle,
-                outlineGoal,
-                currentContent.as_deref().unwrap_or("(none)"),
+                chapter_title,
+                outline_goal,
+                current_content.unwrap_or("(none)"),
                 word_target
             ));
         } else {
-            prompt.push_str(&format!(
+            task.push_str(&format!(
                 r#"请续写以下小说章节内容。
 
 章节标题：{}
@@ -748,25 +1934,25 @@ Continue directly:"#,
 6. 不要使用markdown格式，直接输出小说正文
 
 请直接续写内容，不要添加任何说明或标记："#,
-                chapterTitle,
-                outlineGoal,
-                currentContent.as_deref().unwrap_or("（无）"),
+                chapter_title,
+                outline_goal,
+                current_content.unwrap_or("（无）"),
                 word_target
             ));
         }
     } else {
         if output_language == "en" {
-            prompt.push_str(&format!(
+            task.push_str(&format!(
                 r#"Write this chapter:
 
 Chapter title: {}
 Chapter goal: {}
 Core conflict: {}
 "#,
-                chapterTitle, outlineGoal, conflict
+                chapter_title, outline_goal, conflict
             ));
-            if let Some(ref summary) = previousSummary {
-                prompt.push_str(&format!(
+            if let Some(summary) = previous_summary {
+                task.push_str(&format!(
                     r#"
 [Previous chapter tail for continuity]
 {}
@@ -775,7 +1961,7 @@ Core conflict: {}
                     summary
                 ));
             }
-            prompt.push_str(&format!(
+            task.push_str(&format!(
                 r#"
 Requirements:
 1. Write around {} words.
@@ -789,25 +1975,25 @@ Start writing the chapter content now:"#,
                 word_target
             ));
         } else {
-            prompt.push_str(&format!(
+            task.push_str(&format!(
                 r#"请撰写以下章节：
 
 章节标题：{}
 本章目标：{}
 核心冲突：{}
 "#,
-                chapterTitle, outlineGoal, conflict
+                chapter_title, outline_goal, conflict
             ));
 
-            if let Some(ref summary) = previousSummary {
-                prompt.push_str(&format!(r#"
+            if let Some(summary) = previous_summary {
+                task.push_str(&format!(r#"
 【前一章结尾内容】（请自然衔接，不要重复）
 {}
 
 "#, summary));
             }
 
-            prompt.push_str(&format!(r#"
+            task.push_str(&format!(r#"
 写作要求：
 1. 本次生成约{}字
 2. 【重要】必须严格遵守世界观设定、时间线和角色设定，不得与之冲突
@@ -820,7 +2006,7 @@ Start writing the chapter content now:"#,
         }
     }
 
-    let system_prompt = if output_language == "en" {
+    let system = if output_language == "en" {
         r#"You are a skilled fiction writer.
 
 Hard constraints:
@@ -857,63 +2043,365 @@ Style:
 - 保持语言简洁有力
 - 不要使用任何markdown格式，输出纯小说正文"#
     };
+    ChapterPromptParts { system, context, task }
+}
 
-    let request_body = serde_json::json!({
-        "model": textConfig.model,
-        "messages": [
-            {"role": "system", "content": system_prompt},
-            {"role": "user", "content": prompt}
-        ],
-        "temperature": temperature,
-        "max_tokens": 4000,  // 控制在4000 tokens以内，避免中断
-        "stream": true
-    });
+const CHAPTER_MAX_TOKENS: u32 = 4000;
+const DEFAULT_CHAPTER_CONTINUATION_ROUNDS: u32 = 3;
+const MAX_CHAPTER_CONTINUATION_ROUNDS: u32 = 10;
+/// 章节流式连接中途断开时的最大重连次数
+const MAX_STREAM_RECONNECTS: u32 = 3;
+/// 断线重连续写时至少要求的字数（已达目标字数时用于收尾）
+const MIN_RECONNECT_WORDS: usize = 300;
+
+/// stream-reconnecting 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamReconnectingEvent {
+    pub event_name: String,
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// 断开前本段已收到的字符数
+    pub received_chars: usize,
+    pub error: String,
+}
 
-    let response = client
-        .post(&api_url)
-        .header("Authorization", format!("Bearer {}", textConfig.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("请求失败: {}", e))?;
+// 同一章节各段生成共用的参数，包括断线重连时构建续写提示词所需的章节信息
+struct ChapterSegmentConfig<'a> {
+    text_config: &'a TextModelConfigInput,
+    system_prompt: &'a str,
+    draft_chapter_id: Option<&'a str>,
+    context_prefix: &'a str,
+    chapter_title: &'a str,
+    outline_goal: &'a str,
+    word_target: usize,
+    output_language: &'a str,
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API错误: {}", error_text));
+// 生成章节的一段内容；连接在结束前断开时，以已生成内容为上下文重新请求续写
+// before 为本段之前的正文（用于取结尾上下文），written_words 为本次生成已写字数
+async fn stream_chapter_segment(
+    client: &Client,
+    window: &Window,
+    segment: &ChapterSegmentConfig<'_>,
+    user_prompt: &str,
+    before: &str,
+    written_words: usize,
+    stats: &mut StreamStats,
+) -> Result<StreamOutcome, String> {
+    let mut outcome = stream_generate_attempt(
+        client,
+        window,
+        segment.text_config,
+        segment.system_prompt,
+        user_prompt,
+        "chapter-stream",
+        CHAPTER_MAX_TOKENS,
+        0.7,
+        segment.draft_chapter_id,
+    )
+    .await?;
+    stats.record(&outcome);
+
+    let mut attempt = 0;
+    while let Some(error) = outcome.interrupted.take() {
+        attempt += 1;
+        if attempt > MAX_STREAM_RECONNECTS {
+            return Err(format!("读取流失败（已重连{}次）: {}", MAX_STREAM_RECONNECTS, error));
+        }
+        if cancellation::is_cancelled() {
+            return Err("生成已被用户中断".to_string());
+        }
+        request_log::warn(&format!(
+            "Chapter stream interrupted ({}), reconnecting {}/{}",
+            error, attempt, MAX_STREAM_RECONNECTS
+        ));
+        let _ = window.emit(
+            "stream-reconnecting",
+            StreamReconnectingEvent {
+                event_name: "chapter-stream".to_string(),
+                attempt,
+                max_attempts: MAX_STREAM_RECONNECTS,
+                received_chars: outcome.content.chars().count(),
+                error,
+            },
+        );
+        tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+
+        // 尚未收到任何内容时原样重发，否则从断开处续写
+        let text_so_far = format!("{}{}", before, outcome.content);
+        let prompt = if outcome.content.trim().is_empty() {
+            user_prompt.to_string()
+        } else {
+            let written = written_words + count_words(&outcome.content, segment.output_language);
+            build_chapter_continuation_prompt(
+                segment.context_prefix,
+                segment.chapter_title,
+                segment.outline_goal,
+                select_tail_context(&text_so_far, TAIL_CONTEXT_TOKENS),
+                segment.word_target.saturating_sub(written).max(MIN_RECONNECT_WORDS),
+                segment.output_language,
+            )
+        };
+
+        let next = stream_generate_attempt(
+            client,
+            window,
+            segment.text_config,
+            segment.system_prompt,
+            &prompt,
+            "chapter-stream",
+            CHAPTER_MAX_TOKENS,
+            0.7,
+            segment.draft_chapter_id,
+        )
+        .await?;
+        stats.record(&next);
+
+        outcome.content.push_str(&next.content);
+        outcome.finish_reason = next.finish_reason;
+        outcome.interrupted = next.interrupted;
     }
 
-    let mut full_content = String::new();
-    let mut stream = response.bytes_stream();
+    Ok(outcome)
+}
 
-    while let Some(chunk_result) = stream.next().await {
-        if CANCEL_FLAG.load(Ordering::SeqCst) {
-            return Err("生成已被用户中断".to_string());
+// 构建章节被截断后的自动续写提示词
+fn build_chapter_continuation_prompt(
+    context_prefix: &str,
+    chapter_title: &str,
+    outline_goal: &str,
+    tail: &str,
+    remaining_words: usize,
+    output_language: &str,
+) -> String {
+    if output_language == "en" {
+        format!(
+            r#"{}The previous output was cut off mid-chapter. Continue seamlessly from the exact point where it stopped.
+
+Chapter title: {}
+Chapter goal: {}
+
+[Text so far (tail)]
+{}
+
+Requirements:
+1. Continue from the last sentence without repeating any existing text.
+2. Write about {} more words and bring the chapter toward its goal.
+3. Keep style, tense and pacing consistent.
+4. Output plain English prose only (no Markdown, no notes).
+
+Continue directly:"#,
+            context_prefix, chapter_title, outline_goal, tail, remaining_words
+        )
+    } else {
+        format!(
+            r#"{}上一次输出在章节中途被截断，请从断开处无缝续写。
+
+章节标题：{}
+本章目标：{}
+
+【已生成内容结尾】
+{}
+
+请注意：
+1. 紧接最后一句继续写，不要重复已有内容
+2. 再写约{}字，推进并完成本章目标
+3. 保持文风、人称和节奏一致
+4. 不要使用markdown格式，不要添加任何说明，直接输出小说正文
+
+请直接续写："#,
+            context_prefix, chapter_title, outline_goal, tail, remaining_words
+        )
+    }
+}
+
+const DEFAULT_CONTINUE_FROM_WORDS: u32 = 500;
+const CONTINUE_FROM_CONTEXT_TOKENS: usize = 2000;
+const MIN_REPEAT_OVERLAP_CHARS: usize = 4;
+
+// 将前端的 UTF-16 偏移（与 selectionStart 一致）换算为字节偏移，超出时截到末尾
+fn utf16_offset_to_byte(text: &str, offset: usize) -> usize {
+    let mut units = 0;
+    for (index, ch) in text.char_indices() {
+        if units >= offset {
+            return index;
         }
+        units += ch.len_utf16();
+    }
+    text.len()
+}
 
-        let chunk = chunk_result.map_err(|e| format!("读取流失败: {}", e))?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
+// 取光标前的最后一句（用于提示模型不要重复）
+fn last_sentence(text: &str) -> &str {
+    let trimmed = text.trim_end();
+    let body = trimmed.trim_end_matches(['。', '！', '？', '.', '!', '?', '"', '”', '」']);
+    let start = body
+        .rfind(['。', '！', '？', '.', '!', '?', '\n'])
+        .map(|index| index + body[index..].chars().next().map_or(0, char::len_utf8))
+        .unwrap_or(0);
+    trimmed[start..].trim()
+}
 
-        for line in chunk_str.lines() {
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-                if data == "[DONE]" {
-                    continue;
-                }
+// 去掉续写开头与光标前文本重叠的部分（模型常会复述最后一句）
+fn strip_repeated_prefix(before: &str, generated: &str) -> String {
+    let before = before.trim_end();
+    let candidate = generated.trim_start();
+    let boundaries: Vec<usize> = candidate
+        .char_indices()
+        .map(|(index, _)| index)
+        .skip(1)
+        .chain(std::iter::once(candidate.len()))
+        .collect();
+
+    for &end in boundaries.iter().rev() {
+        let prefix = &candidate[..end];
+        if prefix.chars().count() < MIN_REPEAT_OVERLAP_CHARS {
+            break;
+        }
+        if before.ends_with(prefix) {
+            return candidate[end..].to_string();
+        }
+    }
 
-                if let Ok(stream_response) = serde_json::from_str::<StreamResponse>(data) {
-                    if let Some(choice) = stream_response.choices.first() {
-                        if let Some(content) = &choice.delta.content {
-                            full_content.push_str(content);
-                            let _ = window.emit("chapter-stream", content.clone());
-                        }
-                    }
-                }
-            }
+    let sentence = last_sentence(before);
+    if sentence.chars().count() >= MIN_REPEAT_OVERLAP_CHARS {
+        if let Some(rest) = candidate.strip_prefix(sentence) {
+            return rest.to_string();
         }
     }
 
-    Ok(full_content)
+    generated.to_string()
+}
+
+/// 从光标位置续写：以光标前的正文为上下文生成，只返回新内容，不自动保存。
+/// 推送的 continue-from-stream 事件与返回值一样已去掉开头对前文的复述
+#[tauri::command]
+pub async fn continue_from(
+    window: Window,
+    pool: State<'_, SqlitePool>,
+    #[allow(non_snake_case)] chapterId: String,
+    #[allow(non_snake_case)] upToOffset: usize,
+    #[allow(non_snake_case)] textConfig: Option<TextModelConfigInput>,
+    #[allow(non_snake_case)] targetWords: Option<u32>,
+    #[allow(non_snake_case)] outputLanguage: Option<String>,
+) -> Result<String, String> {
+    let text_config = resolve_text_config(&pool, textConfig).await?;
+    text_config.validate()?;
+
+    let chapter = ChapterService::get_by_id(&pool, &chapterId)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "章节不存在".to_string())?;
+    let text = chapter
+        .draft_text
+        .clone()
+        .or_else(|| chapter.final_text.clone())
+        .unwrap_or_default();
+    let before = &text[..utf16_offset_to_byte(&text, upToOffset)];
+    if before.trim().is_empty() {
+        return Err("光标前没有可用于续写的内容".to_string());
+    }
+
+    let client = Client::new();
+    let output_language = normalize_output_language(outputLanguage.as_deref());
+    let word_target = targetWords.unwrap_or(DEFAULT_CONTINUE_FROM_WORDS);
+    let context = select_tail_context(before, CONTINUE_FROM_CONTEXT_TOKENS);
+    let last = last_sentence(before);
+    let outline_goal = chapter.outline_goal.as_deref().unwrap_or("");
+
+    let (system_prompt, prompt) = if output_language == "en" {
+        (
+            format!(
+                "You are a skilled fiction writer continuing an existing manuscript. Output only the new text that follows the given passage: plain prose, no Markdown, no notes.\n{}",
+                data_boundary_notice("en")
+            ),
+            format!(
+                r#"Continue the chapter from the exact end of the passage below.
+
+Chapter title: {}
+{}
+
+{}
+
+Requirements:
+1. Start right after the last sentence: "{}". Do not repeat it or any earlier text.
+2. Write about {} words.
+3. Keep style, tense, point of view and pacing consistent.
+
+Continue directly:"#,
+                sanitize_inline(&chapter.title),
+                wrap_user_field("chapter goal", outline_goal),
+                wrap_user_field("passage before the cursor", context),
+                sanitize_inline(last),
+                word_target
+            ),
+        )
+    } else {
+        (
+            format!(
+                "你是一位优秀的小说作者，正在接着已有稿件续写。只输出紧接给定段落之后的新内容：纯小说正文，不使用markdown，不添加任何说明。\n{}",
+                data_boundary_notice("zh")
+            ),
+            format!(
+                r#"请从下面这段正文的末尾处直接续写。
+
+章节标题：{}
+{}
+
+{}
+
+请注意：
+1. 紧接最后一句“{}”往下写，不要重复这句话或前文的任何内容
+2. 本次续写约{}字
+3. 保持文风、人称和节奏一致
+
+请直接续写："#,
+                sanitize_inline(&chapter.title),
+                wrap_user_field("本章目标", outline_goal),
+                wrap_user_field("光标前的正文", context),
+                sanitize_inline(last),
+                word_target
+            ),
+        )
+    };
+
+    let params = serde_json::json!({
+        "chapter_id": chapter.id,
+        "up_to_offset": upToOffset,
+        "target_words": word_target,
+    });
+    let task = begin_stream_task(&pool, Some(&chapter.project_id), "continue", &text_config, params).await;
+    let started = Instant::now();
+    let mut stats = StreamStats::default();
+
+    let correlation_id = request_log::correlation_id(task.as_ref().map(|t| t.id.as_str()));
+    let generation = start_generation(&window, "continue-from-stream", None);
+    request_log::scope(correlation_id, async {
+        let result = generation
+            .scope(REPEAT_SOURCE.scope(before.to_string(), async {
+                let _turn = wait_generation_turn(&window).await?;
+                stream_generate_outcome(
+                    &client,
+                    &window,
+                    &text_config,
+                    &system_prompt,
+                    &prompt,
+                    "continue-from-stream",
+                    word_target.saturating_mul(2).clamp(512, 4000),
+                    0.7,
+                    None,
+                )
+                .await
+            }))
+            .await
+            .map(|outcome| {
+                stats.record(&outcome);
+                strip_repeated_prefix(before, &outcome.content)
+            });
+        finish_stream_task(&pool, task, &result, started, &stats).await;
+        result
+    })
+    .await
 }
 
 /// 生成章节推文（封面图片提示词 + 摘要）
@@ -972,7 +2460,7 @@ pub async fn generate_illustration_prompt(
         clipped_text
     );
 
-    let request_body = serde_json::json!({
+    let mut request_body = serde_json::json!({
         "model": textConfig.model,
         "messages": [
             {
@@ -987,6 +2475,9 @@ pub async fn generate_illustration_prompt(
         "max_tokens": 400,
         "temperature": temperature
     });
+    if let Some(seed) = textConfig.effective_seed() {
+        request_body["seed"] = serde_json::json!(seed);
+    }
 
     let response = client
         .post(&api_url)
@@ -999,6 +2490,7 @@ pub async fn generate_illustration_prompt(
 
     if !response.status().is_success() {
         let error_text = response.text().await.unwrap_or_default();
+        let error_text = redact(&error_text);
         return Err(format!("API错误: {}", error_text));
     }
 
@@ -1011,16 +2503,7 @@ pub async fn generate_illustration_prompt(
         .as_str()
         .ok_or("无法获取AI响应内容")?;
 
-    // 清理可能的markdown包裹
-    let cleaned_content = content
-        .trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```")
-        .trim();
-
-    let result: serde_json::Value = serde_json::from_str(cleaned_content)
-        .map_err(|e| format!("解析AI返回的JSON失败: {}。原始内容: {}", e, cleaned_content))?;
+    let result = extract_json(content)?;
 
     let image_prompt = result["image_prompt"]
         .as_str()
@@ -1030,6 +2513,245 @@ pub async fn generate_illustration_prompt(
     Ok(image_prompt)
 }
 
+const MAX_ILLUSTRATION_POINTS: usize = 10;
+const ILLUSTRATION_SCAN_CHARS: usize = 12000;
+
+/// 章节内建议插图的位置
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IllustrationPoint {
+    pub paragraph_index: usize,
+    /// 段落结束处的 UTF-16 偏移，插图放在该段之后
+    pub offset: usize,
+    pub excerpt: String,
+    pub image_prompt: String,
+    /// 段落中出现的角色名
+    pub characters: Vec<String>,
+    /// 段落内首个设置了固定种子的角色的种子，生成图片时使用以保持形象一致
+    pub seed: Option<i64>,
+}
+
+// 段落中出现的角色（按首次出现位置排序）
+fn characters_in_paragraph<'a>(paragraph: &str, characters: &'a [Character]) -> Vec<&'a Character> {
+    let mut found: Vec<(usize, &Character)> = characters
+        .iter()
+        .filter_map(|c| {
+            let name = c.name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            paragraph.find(name).map(|pos| (pos, c))
+        })
+        .collect();
+    found.sort_by_key(|(pos, _)| *pos);
+    found.into_iter().map(|(_, c)| c).collect()
+}
+
+// 把角色固定外貌描述拼入提示词
+fn with_character_descriptors(image_prompt: &str, characters: &[&Character]) -> String {
+    let descriptors: Vec<String> = characters
+        .iter()
+        .filter_map(|c| {
+            c.portrait_descriptor
+                .as_deref()
+                .map(str::trim)
+                .filter(|d| !d.is_empty() && !image_prompt.contains(*d))
+                .map(|d| format!("{}: {}", c.name.trim(), d))
+        })
+        .collect();
+    if descriptors.is_empty() {
+        image_prompt.to_string()
+    } else {
+        format!("{}. Characters — {}", image_prompt.trim_end_matches('.'), descriptors.join("; "))
+    }
+}
+
+// 按非空行切分段落，返回 (段落文本, 段落结束处的 UTF-16 偏移)
+fn split_paragraphs_with_offsets(text: &str) -> Vec<(&str, usize)> {
+    let mut paragraphs = Vec::new();
+    let mut units = 0;
+    for line in text.split_inclusive('\n') {
+        let line_units: usize = line.encode_utf16().count();
+        let content = line.trim();
+        if !content.is_empty() {
+            let trailing = line.trim_end().encode_utf16().count();
+            paragraphs.push((content, units + trailing));
+        }
+        units += line_units;
+    }
+    paragraphs
+}
+
+/// 分析整章内容，挑选最有画面感的若干段落，返回插入位置与英文插图提示词
+#[tauri::command]
+pub async fn suggest_illustration_points(
+    pool: State<'_, SqlitePool>,
+    #[allow(non_snake_case)] chapterId: String,
+    count: Option<usize>,
+    style: Option<String>,
+    #[allow(non_snake_case)] textConfig: Option<TextModelConfigInput>,
+) -> Result<Vec<IllustrationPoint>, String> {
+    let text_config = resolve_text_config(&pool, textConfig).await?;
+    text_config.validate()?;
+
+    let chapter = ChapterService::get_by_id(&pool, &chapterId)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "章节不存在".to_string())?;
+    let text = chapter
+        .final_text
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| chapter.draft_text.clone())
+        .unwrap_or_default();
+
+    let paragraphs = split_paragraphs_with_offsets(&text);
+    if paragraphs.is_empty() {
+        return Err("章节内容为空".to_string());
+    }
+    let count = count.unwrap_or(3).clamp(1, MAX_ILLUSTRATION_POINTS).min(paragraphs.len());
+
+    // 按段编号发送，超出长度的段落不参与挑选
+    let mut numbered = String::new();
+    for (index, (paragraph, _)) in paragraphs.iter().enumerate() {
+        let line = format!("[P{}] {}\n", index + 1, paragraph);
+        if numbered.chars().count() + line.chars().count() > ILLUSTRATION_SCAN_CHARS {
+            break;
+        }
+        numbered.push_str(&line);
+    }
+
+    let style_text = style.unwrap_or_default();
+    let style_section = if style_text.trim().is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n用户指定的图片风格（可能包含中文，请先翻译为英文再融合到每条提示词中）：\n{}\n",
+            style_text
+        )
+    };
+
+    // 有固定外貌描述的角色：提示模型按描述刻画，避免与设定冲突
+    let characters = CharacterService::get_by_project(&pool, &chapter.project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let appearance_lines: Vec<String> = characters
+        .iter()
+        .filter_map(|c| {
+            c.portrait_descriptor
+                .as_deref()
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .map(|d| format!("- {}: {}", c.name.trim(), d))
+        })
+        .collect();
+    let appearance_section = if appearance_lines.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n角色固定外貌（段落涉及这些角色时，提示词中的外貌必须与此一致）：\n{}\n",
+            appearance_lines.join("\n")
+        )
+    };
+
+    let prompt = format!(
+        r#"你是专业的书籍插画策划。下面是小说章节《{}》的正文，每段以 [P编号] 开头。
+请挑选最有画面感、最适合配插图的 {} 个段落（尽量分散在全章不同位置），并为每个段落写一条英文插图提示词。
+
+要求：
+- image_prompt 必须是英文，包含场景、人物、氛围、构图、光线、风格
+- 每个段落只能选一次
+- 不要输出任何解释
+{}{}
+正文：
+{}
+请严格按JSON格式输出：
+{{"points": [{{"paragraph": 段落编号数字, "image_prompt": "English prompt"}}]}}"#,
+        chapter.title, count, style_section, appearance_section, numbered
+    );
+
+    let client = Client::new();
+    let mut request_body = serde_json::json!({
+        "model": text_config.model,
+        "messages": [
+            {
+                "role": "system",
+                "content": "You are a professional book illustration planner. Return only JSON."
+            },
+            {
+                "role": "user",
+                "content": prompt
+            }
+        ],
+        "max_tokens": 300 * count as u32 + 200,
+        "temperature": text_config.normalized_temperature(0.6)
+    });
+    if let Some(seed) = text_config.effective_seed() {
+        request_body["seed"] = serde_json::json!(seed);
+    }
+
+    let response = client
+        .post(text_config.chat_completions_url())
+        .header("Authorization", format!("Bearer {}", text_config.api_key))
+        .header("Content-Type", "application/json")
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        let error_text = redact(&error_text);
+        return Err(format!("API错误: {}", error_text));
+    }
+
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析响应失败: {}", e))?;
+
+    let content = response_json["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or("无法获取AI响应内容")?;
+
+    let result = extract_json(content)?;
+    let items = result["points"]
+        .as_array()
+        .or_else(|| result.as_array())
+        .ok_or("AI未返回插图位置")?;
+
+    let mut points: Vec<IllustrationPoint> = Vec::new();
+    for item in items {
+        let Some(number) = item["paragraph"].as_u64().map(|n| n as usize) else {
+            continue;
+        };
+        let Some(image_prompt) = item["image_prompt"].as_str().map(str::trim).filter(|p| !p.is_empty()) else {
+            continue;
+        };
+        if number == 0 || number > paragraphs.len() || points.iter().any(|p| p.paragraph_index == number - 1) {
+            continue;
+        }
+        let (paragraph, offset) = paragraphs[number - 1];
+        let involved = characters_in_paragraph(paragraph, &characters);
+        points.push(IllustrationPoint {
+            paragraph_index: number - 1,
+            offset,
+            excerpt: paragraph.chars().take(80).collect(),
+            image_prompt: with_character_descriptors(image_prompt, &involved),
+            characters: involved.iter().map(|c| c.name.trim().to_string()).collect(),
+            seed: involved.iter().find_map(|c| c.portrait_seed),
+        });
+        if points.len() == count {
+            break;
+        }
+    }
+
+    if points.is_empty() {
+        return Err("AI未返回有效的插图位置".to_string());
+    }
+    points.sort_by_key(|point| point.offset);
+    Ok(points)
+}
+
 /// 生成章节摘要和图片提示词（使用DeepSeek）
 #[tauri::command]
 pub async fn generate_chapter_promo(
@@ -1150,7 +2872,7 @@ Return strict JSON only:
         )
     };
 
-    let request_body = serde_json::json!({
+    let mut request_body = serde_json::json!({
         "model": textConfig.model,
         "messages": [
             {
@@ -1169,6 +2891,9 @@ Return strict JSON only:
         "max_tokens": 500,
         "temperature": temperature
     });
+    if let Some(seed) = textConfig.effective_seed() {
+        request_body["seed"] = serde_json::json!(seed);
+    }
 
     let response = client
         .post(&api_url)
@@ -1181,6 +2906,7 @@ Return strict JSON only:
 
     if !response.status().is_success() {
         let error_text = response.text().await.unwrap_or_default();
+        let error_text = redact(&error_text);
         return Err(format!("API错误: {}", error_text));
     }
 
@@ -1191,17 +2917,8 @@ Return strict JSON only:
         .as_str()
         .ok_or("无法获取AI响应内容")?;
 
-    // 清理AI返回的内容：去除可能的markdown代码块标记
-    let cleaned_content = content
-        .trim()
-        .trim_start_matches("```json")
-        .trim_start_matches("```")
-        .trim_end_matches("```")
-        .trim();
-
-    // 解析JSON响应
-    let result: serde_json::Value = serde_json::from_str(cleaned_content)
-        .map_err(|e| format!("解析AI返回的JSON失败: {}。原始内容: {}", e, cleaned_content))?;
+    // 解析JSON响应（自动去除markdown代码块标记）
+    let result = extract_json(content)?;
 
     let summary = result["summary"]
         .as_str()
@@ -1223,26 +2940,128 @@ Return strict JSON only:
 /// 使用Pollinations生成图片
 #[tauri::command]
 pub async fn generate_promo_image(
+    pool: State<'_, SqlitePool>,
     prompt: String,
     width: Option<u32>,
     height: Option<u32>,
     model: Option<String>,
     #[allow(non_snake_case)] pollinationsKey: Option<String>,
+    #[allow(non_snake_case)] projectId: Option<String>,
 ) -> Result<String, String> {
-    use crate::api::pollinations::{PollinationsClient, ImageGenerationParams};
-    
-    let client = PollinationsClient::new(pollinationsKey, None);
-    
-    let params = ImageGenerationParams {
+    use crate::api::pollinations::{is_prompt_filtered, PollinationsClient, ImageGenerationParams};
+    use crate::commands::ai::PROMPT_FILTERED_MESSAGE;
+
+    let settings = SettingsService::get(&pool).await.map_err(|e| e.to_string())?;
+    let image = SettingsService::image_settings_for(&pool, projectId.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    let client = PollinationsClient::new(pollinationsKey.or(settings.pollinations_api_key), None);
+
+    let mut params = ImageGenerationParams {
         prompt,
-        width: Some(width.unwrap_or(1200)),  // 默认3:1比例
-        height: Some(height.unwrap_or(400)),
-        seed: Some(-1),
-        model: Some(model.unwrap_or_else(|| "zimage".to_string())),
-        nologo: Some(true),
-        enhance: Some(false),
+        width,
+        height,
+        seed: None,
+        model,
+        nologo: None,
+        enhance: None,
     };
+    image.fill_params(&mut params, (image.promo_width, image.promo_height));
+
+    client.generate_image_base64(&params).await.map_err(|e| {
+        if is_prompt_filtered(&e.to_string()) {
+            PROMPT_FILTERED_MESSAGE.to_string()
+        } else {
+            format!("图片生成失败: {}", e)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gapped_outline_reports_missing_chapters() {
+        let outline = "\
+## 章节大纲
+### 第1章：启程
+### 第2章：相遇
+### 第4章：追杀
+";
+        let validation = check_outline(outline, 4);
+        assert_eq!(validation.found, vec![1, 2, 4]);
+        assert_eq!(validation.missing, vec![3]);
+        assert!(!validation.complete);
+    }
+
+    #[test]
+    fn timeline_mentions_do_not_count_as_chapters() {
+        let outline = "\
+## 时间线事件
+### 剧情时间线
+1. 【第5章】城破，主角出逃
+- 第5章：反派现身
+## 章节大纲
+### 第1章：启程
+### 第2章：相遇
+### 第3章：追杀
+### 第4章：重逢
+";
+        let validation = check_outline(outline, 5);
+        assert_eq!(validation.found, vec![1, 2, 3, 4]);
+        assert_eq!(validation.missing, vec![5]);
+        assert!(!validation.complete);
+        assert!(check_outline(outline, 4).complete);
+    }
 
-    client.generate_image_base64(&params).await
-        .map_err(|e| format!("图片生成失败: {}", e))
+    #[test]
+    fn missing_chapters_are_summarized_as_ranges() {
+        assert_eq!(format_chapter_numbers(&[3, 5, 7, 8, 9, 10]), "3, 5, 7-10");
+    }
+
+    // 逐块推送，收集实际会发出的内容
+    fn guarded_stream(before: &str, chunks: &[&str]) -> String {
+        let mut guard = Some(RepeatGuard { before: before.to_string(), held: String::new() });
+        let mut emitted = String::new();
+        for chunk in chunks {
+            match guard.as_mut() {
+                Some(active) => {
+                    if let Some(text) = active.push(chunk) {
+                        emitted.push_str(&text);
+                        guard = None;
+                    }
+                }
+                None => emitted.push_str(chunk),
+            }
+        }
+        if let Some(active) = guard {
+            emitted.push_str(&active.finish());
+        }
+        emitted
+    }
+
+    #[test]
+    fn repeated_last_sentence_is_not_streamed() {
+        let before = "夜色很深。林风推开门，走了进去。";
+        let chunks = ["林风推", "开门，走了", "进去。屋里", "一片漆黑。"];
+        let streamed = guarded_stream(before, &chunks);
+        assert_eq!(streamed, "屋里一片漆黑。");
+        assert_eq!(streamed, strip_repeated_prefix(before, &chunks.concat()));
+    }
+
+    #[test]
+    fn fresh_continuation_streams_unchanged() {
+        let before = "夜色很深。林风推开门，走了进去。";
+        let chunks = ["屋里", "一片漆黑，", "只有林风的呼吸声。"];
+        let streamed = guarded_stream(before, &chunks);
+        assert_eq!(streamed, chunks.concat());
+        assert_eq!(streamed, strip_repeated_prefix(before, &chunks.concat()));
+    }
+
+    #[test]
+    fn short_streams_are_stripped_on_finish() {
+        let before = "他说：“走吧。”林风点了点头。";
+        assert_eq!(guarded_stream(before, &["林风点了", "点头。"]), "");
+    }
 }
@@ -1,7 +1,11 @@
 use base64::{engine::general_purpose, Engine as _};
 use serde::Serialize;
+use sqlx::SqlitePool;
 use std::fs;
 use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use crate::db::{self, encryption::{self, EncryptionError, MIN_PASSPHRASE_CHARS}};
+use crate::services::font_coverage::{collect_project_chars, is_safe_file_name, measure_coverage, FontCoverage};
 
 const WINDOWS_FONTS_DIR: &str = r"C:\Windows\Fonts";
 
@@ -12,6 +16,8 @@ pub struct SystemFontOption {
     pub label: String,
     pub file_name: String,
     pub pdf_family: String,
+    /// 传入 project_id 时给出该字体对稿件用字的覆盖情况
+    pub coverage: Option<FontCoverage>,
 }
 
 fn is_font_ext(path: &Path) -> bool {
@@ -101,21 +107,37 @@ fn sanitize_pdf_family(file_name: &str) -> String {
     family
 }
 
-fn is_safe_file_name(file_name: &str) -> bool {
-    if file_name.trim().is_empty() {
-        return false;
-    }
-    if file_name.contains('/') || file_name.contains('\\') {
-        return false;
-    }
-    if file_name.contains("..") {
-        return false;
-    }
-    true
+/// 列出候选中文字体；传入 project_id 时读取各字体的 cmap，统计对项目实际用字的覆盖率
+#[tauri::command]
+pub async fn list_system_fonts(
+    pool: State<'_, SqlitePool>,
+    project_id: Option<String>,
+) -> Result<Vec<SystemFontOption>, String> {
+    let mut fonts = scan_system_fonts()?;
+    let Some(project_id) = project_id else {
+        return Ok(fonts);
+    };
+
+    let sample = collect_project_chars(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    fonts = tokio::task::spawn_blocking(move || {
+        for font in fonts.iter_mut() {
+            let path = PathBuf::from(WINDOWS_FONTS_DIR).join(&font.file_name);
+            match measure_coverage(&path, &sample) {
+                Ok(coverage) => font.coverage = Some(coverage),
+                Err(e) => log::warn!("Failed to read cmap of {}: {}", font.file_name, e),
+            }
+        }
+        fonts
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(fonts)
 }
 
-#[tauri::command]
-pub fn list_system_fonts() -> Result<Vec<SystemFontOption>, String> {
+fn scan_system_fonts() -> Result<Vec<SystemFontOption>, String> {
     let fonts_dir = PathBuf::from(WINDOWS_FONTS_DIR);
     let read_dir = fs::read_dir(&fonts_dir)
         .map_err(|error| format!("读取系统字体目录失败: {}", error))?;
@@ -145,6 +167,7 @@ pub fn list_system_fonts() -> Result<Vec<SystemFontOption>, String> {
             label,
             file_name: file_name.clone(),
             pdf_family: sanitize_pdf_family(&file_name),
+            coverage: None,
         };
         fonts.push((priority, file_name, option));
     }
@@ -165,11 +188,17 @@ pub fn list_system_fonts() -> Result<Vec<SystemFontOption>, String> {
 
 #[tauri::command]
 pub fn get_system_font_base64(file_name: String) -> Result<String, String> {
-    if !is_safe_file_name(&file_name) {
+    let bytes = read_system_font(&file_name)?;
+    Ok(general_purpose::STANDARD.encode(bytes))
+}
+
+/// 读取系统字体目录下的 TTF/OTF 文件（后端生成 PDF 时嵌入）
+pub(crate) fn read_system_font(file_name: &str) -> Result<Vec<u8>, String> {
+    if !is_safe_file_name(file_name) {
         return Err("字体文件名不合法".to_string());
     }
 
-    let path = PathBuf::from(WINDOWS_FONTS_DIR).join(&file_name);
+    let path = PathBuf::from(WINDOWS_FONTS_DIR).join(file_name);
     if !path.exists() || !path.is_file() {
         return Err(format!("字体文件不存在: {}", file_name));
     }
@@ -177,7 +206,109 @@ pub fn get_system_font_base64(file_name: String) -> Result<String, String> {
         return Err("仅支持 TTF/OTF 字体".to_string());
     }
 
-    let bytes = fs::read(&path)
-        .map_err(|error| format!("读取字体文件失败: {}", error))?;
-    Ok(general_purpose::STANDARD.encode(bytes))
+    fs::read(&path).map_err(|error| format!("读取字体文件失败: {}", error))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseEncryptionStatus {
+    /// 数据库文件已加密
+    pub encrypted: bool,
+    /// 当前版本以 SQLCipher 编译，可以设置口令
+    pub supported: bool,
+    /// 数据库已打开（未加密或已解锁）
+    pub unlocked: bool,
+}
+
+fn encryption_error(error: anyhow::Error) -> String {
+    match error.downcast_ref::<EncryptionError>() {
+        Some(EncryptionError::PassphraseRequired) => "数据库已加密，请输入口令".to_string(),
+        Some(EncryptionError::WrongPassphrase) => "数据库口令错误，无法解密".to_string(),
+        Some(EncryptionError::Unsupported) => "当前版本未启用数据库加密（SQLCipher）".to_string(),
+        None => error.to_string(),
+    }
+}
+
+#[tauri::command]
+pub async fn get_database_encryption_status(app_handle: AppHandle) -> Result<DatabaseEncryptionStatus, String> {
+    let path = db::database_path(&app_handle).map_err(|e| e.to_string())?;
+    Ok(DatabaseEncryptionStatus {
+        encrypted: encryption::is_encrypted(&path).map_err(|e| e.to_string())?,
+        supported: encryption::cipher_available().await,
+        unlocked: app_handle.try_state::<SqlitePool>().is_some(),
+    })
+}
+
+/// 数据库状态：opening / locked / ready / failed / restart_required。
+/// 前端启动时轮询该命令，不依赖可能错过的 database-locked 事件
+#[tauri::command]
+pub fn get_database_status() -> db::DatabaseStatus {
+    db::status()
+}
+
+const RESTART_REQUIRED_MESSAGE: &str = "数据库口令已变更，请重启应用后再操作";
+
+/// 启动时数据库已加密（get_database_status 为 locked）后，用口令打开数据库
+#[tauri::command]
+pub async fn unlock_database(app_handle: AppHandle, passphrase: String) -> Result<(), String> {
+    if matches!(db::status(), db::DatabaseStatus::RestartRequired) {
+        return Err(RESTART_REQUIRED_MESSAGE.to_string());
+    }
+    if app_handle.try_state::<SqlitePool>().is_some() {
+        return Err("数据库已打开".to_string());
+    }
+    db::init_database(&app_handle, Some(&passphrase))
+        .await
+        .map_err(encryption_error)?;
+    db::set_status(db::DatabaseStatus::Ready);
+    crate::on_database_ready(&app_handle).await;
+    Ok(())
+}
+
+/// 设置、更换或移除数据库口令（new_passphrase 为空表示取消加密）。
+/// 口令校验通过后连接池即被关闭且无法在运行中替换，此后数据库状态为 restart_required，
+/// 其他命令都会失败，前端必须提示用户重启应用；成功时返回该状态
+#[tauri::command]
+pub async fn set_database_passphrase(
+    app_handle: AppHandle,
+    current_passphrase: Option<String>,
+    new_passphrase: Option<String>,
+) -> Result<db::DatabaseStatus, String> {
+    if matches!(db::status(), db::DatabaseStatus::RestartRequired) {
+        return Err(RESTART_REQUIRED_MESSAGE.to_string());
+    }
+    let new_passphrase = new_passphrase.filter(|p| !p.is_empty());
+    if let Some(ref passphrase) = new_passphrase {
+        if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+            return Err(format!("口令至少需要 {} 个字符", MIN_PASSPHRASE_CHARS));
+        }
+    }
+    if !encryption::cipher_available().await {
+        return Err(encryption_error(EncryptionError::Unsupported.into()));
+    }
+    let pool = app_handle
+        .try_state::<SqlitePool>()
+        .ok_or("数据库尚未打开")?
+        .inner()
+        .clone();
+    let path = db::database_path(&app_handle).map_err(|e| e.to_string())?;
+    let encrypted = encryption::is_encrypted(&path).map_err(|e| e.to_string())?;
+    if !encrypted && new_passphrase.is_none() {
+        return Err("数据库未加密".to_string());
+    }
+    // 先校验当前口令，口令输错时不关闭连接池
+    encryption::check_passphrase(&path, current_passphrase.as_deref())
+        .await
+        .map_err(encryption_error)?;
+
+    // 所有连接归还后才能安全地重写数据库文件；关闭后无论成功与否都需要重启
+    pool.close().await;
+    db::set_status(db::DatabaseStatus::RestartRequired);
+    encryption::change_passphrase(&path, current_passphrase.as_deref(), new_passphrase.as_deref())
+        .await
+        .map_err(|e| format!("{}（{}）", encryption_error(e), RESTART_REQUIRED_MESSAGE))?;
+    log::info!(
+        "Database passphrase {}",
+        if new_passphrase.is_some() { "updated" } else { "removed" }
+    );
+    Ok(db::status())
 }
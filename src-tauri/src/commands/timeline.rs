@@ -0,0 +1,77 @@
+use tauri::State;
+use sqlx::SqlitePool;
+use crate::models::{CreateTimelineEventInput, TimelineEvent, UpdateTimelineEventInput};
+use crate::services::TimelineService;
+
+#[tauri::command]
+pub async fn create_timeline_event(
+    pool: State<'_, SqlitePool>,
+    input: CreateTimelineEventInput,
+) -> Result<TimelineEvent, String> {
+    let title = input.title.trim().to_string();
+    if title.is_empty() {
+        return Err("事件标题不能为空".to_string());
+    }
+    TimelineService::create(&pool, CreateTimelineEventInput { title, ..input })
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 项目时间线，按 order_index、event_time 排序；前端拼接后作为 generate_chapter_stream 的 timeline 参数
+#[tauri::command]
+pub async fn get_timeline_events(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+) -> Result<Vec<TimelineEvent>, String> {
+    TimelineService::get_by_project(&pool, &project_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_timeline_event(
+    pool: State<'_, SqlitePool>,
+    id: String,
+    input: UpdateTimelineEventInput,
+) -> Result<TimelineEvent, String> {
+    let title = input.title.as_deref().map(str::trim);
+    if title.is_some_and(str::is_empty) {
+        return Err("事件标题不能为空".to_string());
+    }
+    let input = UpdateTimelineEventInput {
+        title: title.map(str::to_string),
+        ..input
+    };
+    TimelineService::update(&pool, &id, input)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_timeline_event(pool: State<'_, SqlitePool>, id: String) -> Result<(), String> {
+    TimelineService::delete(&pool, &id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 批量调整事件顺序：传入 (id, order_index) 列表，全部成功或全部不变
+#[tauri::command]
+pub async fn reorder_timeline_events(
+    pool: State<'_, SqlitePool>,
+    orders: Vec<(String, i32)>,
+) -> Result<(), String> {
+    TimelineService::reorder(&pool, &orders)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn sync_outline_timeline(
+    pool: State<'_, SqlitePool>,
+    project_id: String,
+    outline: String,
+) -> Result<Vec<TimelineEvent>, String> {
+    TimelineService::sync_from_outline(&pool, &project_id, &outline)
+        .await
+        .map_err(|e| e.to_string())
+}
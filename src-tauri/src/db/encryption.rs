@@ -0,0 +1,185 @@
+//! 数据库加密（SQLCipher）
+//!
+//! 需要以 `sqlcipher` feature 编译（libsqlite3-sys 改用 SQLCipher 源码）。未设置口令的用户仍使用
+//! 普通 SQLite 文件；文件头不是 "SQLite format 3" 即视为已加密，需要口令才能打开。
+//! 加密、解密和更换口令都通过独立连接完成，完成后需重启应用以新口令重新打开连接池。
+
+use anyhow::Result;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection, SqliteConnection, SqlitePool};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+/// 口令最短字符数
+pub const MIN_PASSPHRASE_CHARS: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    #[error("Database is encrypted, a passphrase is required")]
+    PassphraseRequired,
+    #[error("Database passphrase is incorrect")]
+    WrongPassphrase,
+    #[error("This build does not support database encryption (SQLCipher)")]
+    Unsupported,
+}
+
+/// 文件存在、非空且文件头不是明文 SQLite 时视为已加密
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut header = [0u8; 16];
+    let mut read = 0;
+    while read < header.len() {
+        match file.read(&mut header[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read > 0 && header[..read] != SQLITE_HEADER[..read])
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// 连接参数；passphrase 为 None 时与未加密数据库完全一致
+pub fn connect_options(path: &Path, passphrase: Option<&str>) -> SqliteConnectOptions {
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    match passphrase {
+        Some(passphrase) => options.pragma("key", quote(passphrase)),
+        None => options,
+    }
+}
+
+/// 当前链接的 SQLite 是否为 SQLCipher
+pub async fn cipher_available() -> bool {
+    let Ok(mut conn) = SqliteConnection::connect("sqlite::memory:").await else {
+        return false;
+    };
+    let version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+        .fetch_optional(&mut conn)
+        .await
+        .ok()
+        .flatten();
+    let _ = conn.close().await;
+    version.is_some()
+}
+
+fn is_not_a_database(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(e) => e.code().as_deref() == Some("26") || e.message().contains("file is not a database"),
+        _ => false,
+    }
+}
+
+/// 读取一次 schema 确认口令正确；口令错误时 SQLCipher 报 SQLITE_NOTADB
+async fn verify<'e, E>(executor: E) -> Result<()>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    match sqlx::query_scalar::<_, i64>("SELECT count(*) FROM sqlite_master").fetch_one(executor).await {
+        Ok(_) => Ok(()),
+        Err(e) if is_not_a_database(&e) => Err(EncryptionError::WrongPassphrase.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 打开连接池：已加密的文件必须提供口令，未加密的文件忽略口令
+pub async fn open_pool(path: &Path, passphrase: Option<&str>) -> Result<SqlitePool> {
+    let key = if is_encrypted(path)? {
+        if !cipher_available().await {
+            return Err(EncryptionError::Unsupported.into());
+        }
+        Some(passphrase.ok_or(EncryptionError::PassphraseRequired)?)
+    } else {
+        None
+    };
+    let pool = SqlitePool::connect_with(connect_options(path, key)).await?;
+    if let Err(e) = verify(&pool).await {
+        pool.close().await;
+        return Err(e);
+    }
+    Ok(pool)
+}
+
+/// 用独立连接校验口令，不影响已打开的连接池
+pub async fn check_passphrase(path: &Path, passphrase: Option<&str>) -> Result<()> {
+    let key = if is_encrypted(path)? {
+        Some(passphrase.ok_or(EncryptionError::PassphraseRequired)?)
+    } else {
+        None
+    };
+    let mut conn = connect_options(path, key).connect().await?;
+    let result = verify(&mut conn).await;
+    let _ = conn.close().await;
+    result
+}
+
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 设置、更换或移除口令（new_passphrase 为 None 表示解密为普通 SQLite）。
+/// 调用前必须关闭连接池，完成后需以新口令重新打开
+pub async fn change_passphrase(path: &Path, current: Option<&str>, new_passphrase: Option<&str>) -> Result<()> {
+    if !cipher_available().await {
+        return Err(EncryptionError::Unsupported.into());
+    }
+    let encrypted = is_encrypted(path)?;
+    let current = if encrypted {
+        Some(current.ok_or(EncryptionError::PassphraseRequired)?)
+    } else {
+        None
+    };
+    if !encrypted && new_passphrase.is_none() {
+        return Err(anyhow::anyhow!("Database is not encrypted"));
+    }
+
+    let mut conn = connect_options(path, current).connect().await?;
+    verify(&mut conn).await?;
+
+    // 已加密时直接 rekey；加密或解密需要用 sqlcipher_export 导出到新文件再替换
+    if let (true, Some(new_passphrase)) = (encrypted, new_passphrase) {
+        sqlx::query(&format!("PRAGMA rekey = {}", quote(new_passphrase)))
+            .execute(&mut conn)
+            .await?;
+        conn.close().await?;
+        return Ok(());
+    }
+
+    let target = sidecar(path, ".rekey");
+    if target.exists() {
+        std::fs::remove_file(&target)?;
+    }
+    let exported = async {
+        sqlx::query(&format!(
+            "ATTACH DATABASE {} AS target KEY {}",
+            quote(&target.to_string_lossy()),
+            quote(new_passphrase.unwrap_or(""))
+        ))
+        .execute(&mut conn)
+        .await?;
+        sqlx::query("SELECT sqlcipher_export('target')").execute(&mut conn).await?;
+        sqlx::query("DETACH DATABASE target").execute(&mut conn).await?;
+        anyhow::Ok(())
+    }
+    .await;
+    conn.close().await?;
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&target);
+        return Err(e);
+    }
+
+    // 旧文件的 WAL/SHM 不能留给新文件使用
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(sidecar(path, suffix));
+    }
+    std::fs::rename(&target, path)?;
+    Ok(())
+}
@@ -1,32 +1,81 @@
-use sqlx::{sqlite::SqlitePool, migrate::MigrateDatabase, Sqlite};
+use sqlx::sqlite::SqlitePool;
+use serde::Serialize;
 use tauri::{AppHandle, Manager};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use anyhow::Result;
 
 pub mod schema;
+pub mod encryption;
 
-pub async fn init_database(app_handle: &AppHandle) -> Result<()> {
+lazy_static::lazy_static! {
+    static ref STATUS: RwLock<DatabaseStatus> = RwLock::new(DatabaseStatus::Opening);
+}
+
+/// 数据库当前状态，供前端轮询（启动时的 database-locked 事件可能早于前端注册监听）
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "snake_case")]
+pub enum DatabaseStatus {
+    /// 启动中，尚未打开
+    Opening,
+    /// 数据库已加密，等待 unlock_database 提供口令
+    Locked,
+    Ready,
+    /// 打开失败，附错误信息
+    Failed(String),
+    /// 口令已变更、连接池已关闭，需重启应用
+    RestartRequired,
+}
+
+pub fn status() -> DatabaseStatus {
+    STATUS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn set_status(status: DatabaseStatus) {
+    *STATUS.write().unwrap_or_else(|e| e.into_inner()) = status;
+}
+
+const DATABASE_FILE: &str = "novelseek.db";
+
+pub fn database_path(app_handle: &AppHandle) -> Result<PathBuf> {
     let app_dir = app_handle.path_resolver()
         .app_data_dir()
-        .expect("Failed to get app data directory");
-    
+        .ok_or_else(|| anyhow::anyhow!("Failed to get app data directory"))?;
+    Ok(app_dir.join(DATABASE_FILE))
+}
+
+/// 按应用配置计算数据库路径，与 database_path 一致；用于没有 AppHandle 的无界面模式
+#[cfg(feature = "http-server")]
+pub fn database_path_for_config(config: &tauri::Config) -> Result<PathBuf> {
+    let app_dir = tauri::api::path::app_data_dir(config)
+        .ok_or_else(|| anyhow::anyhow!("Failed to get app data directory"))?;
+    Ok(app_dir.join(DATABASE_FILE))
+}
+
+/// 打开数据库文件（不存在时创建）并执行迁移；数据库已加密时需要 passphrase
+pub async fn open_database(db_path: &Path, passphrase: Option<&str>) -> Result<SqlitePool> {
     // Ensure directory exists
-    std::fs::create_dir_all(&app_dir)?;
-    
-    let db_path = app_dir.join("novelseek.db");
-    let db_url = format!("sqlite:{}", db_path.display());
-    
-    // Create database if it doesn't exist
-    if !Sqlite::database_exists(&db_url).await? {
-        log::info!("Creating database at {}", db_url);
-        Sqlite::create_database(&db_url).await?;
+    if let Some(app_dir) = db_path.parent() {
+        std::fs::create_dir_all(app_dir)?;
     }
-    
-    // Connect to database
-    let pool = SqlitePool::connect(&db_url).await?;
+
+    if !db_path.exists() {
+        log::info!("Creating database at {}", db_path.display());
+    }
+
+    // Connect to database (created if missing)
+    let pool = encryption::open_pool(db_path, passphrase).await?;
     
     // Run migrations
     schema::run_migrations(&pool).await?;
+
+    Ok(pool)
+}
+
+/// 打开数据库并注册到应用状态；数据库已加密时需要 passphrase
+pub async fn init_database(app_handle: &AppHandle, passphrase: Option<&str>) -> Result<()> {
+    let db_path = database_path(app_handle)?;
+    let pool = open_database(&db_path, passphrase).await?;
     
     // Store pool in app state
     app_handle.manage(pool);
@@ -1,5 +1,7 @@
 use sqlx::{SqlitePool, Row};
 use anyhow::Result;
+use crate::services::genre::normalize_genre;
+use crate::services::ChatPersonaService;
 
 pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     // Enable foreign keys
@@ -59,6 +61,26 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
             .await?;
     }
 
+    // Archiving (archived projects are hidden from the default list)
+    ensure_column(pool, "projects", "archived_at", "TEXT").await?;
+    ensure_column(pool, "projects", "archive_path", "TEXT").await?;
+
+    // Canonical genre code alongside the free-text genre
+    ensure_column(pool, "projects", "genre_code", "TEXT").await?;
+    backfill_genre_codes(pool).await?;
+
+    // Project scratchpad, saved independently of updated_at
+    ensure_column(pool, "projects", "notes", "TEXT").await?;
+    ensure_column(pool, "projects", "notes_updated_at", "TEXT").await?;
+
+    // Regenerable series bible (world, characters and arcs so far)
+    ensure_column(pool, "projects", "bible", "TEXT").await?;
+    ensure_column(pool, "projects", "bible_updated_at", "TEXT").await?;
+
+    // Current outline (versions are kept as snapshots with target_type 'outline')
+    ensure_column(pool, "projects", "outline", "TEXT").await?;
+    ensure_column(pool, "projects", "outline_updated_at", "TEXT").await?;
+
     // Chapters table
     sqlx::query(
         r#"
@@ -98,6 +120,35 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
             .await?;
     }
 
+    // Per-chapter summary used for cross-chapter context
+    ensure_column(pool, "chapters", "summary", "TEXT").await?;
+
+    // Per-chapter generation overrides (NULL = use project/global settings)
+    ensure_column(pool, "chapters", "override_model", "TEXT").await?;
+    ensure_column(pool, "chapters", "override_temperature", "REAL").await?;
+    ensure_column(pool, "chapters", "override_target_words", "INTEGER").await?;
+
+    // Chapter ambiance/music prompt (JSON)
+    ensure_column(pool, "chapters", "ambiance", "TEXT").await?;
+
+    // Three-act assignment (1-3, NULL = unassigned) for pacing analysis
+    ensure_column(pool, "chapters", "act", "INTEGER").await?;
+
+    // Chapter tags (keyed by chapter id, so they follow the chapter through reordering)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS chapter_tags (
+            chapter_id TEXT NOT NULL,
+            tag TEXT NOT NULL COLLATE NOCASE,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (chapter_id, tag),
+            FOREIGN KEY (chapter_id) REFERENCES chapters(id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Characters table
     sqlx::query(
         r#"
@@ -120,6 +171,10 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Fixed image seed and canonical appearance prompt for consistent portraits
+    ensure_column(pool, "characters", "portrait_seed", "INTEGER").await?;
+    ensure_column(pool, "characters", "portrait_descriptor", "TEXT").await?;
+
     // World building / Lore table
     sqlx::query(
         r#"
@@ -138,6 +193,12 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Manual ordering within a lore category
+    ensure_column(pool, "lore", "order_index", "INTEGER NOT NULL DEFAULT 0").await?;
+
+    // Outline-synced lore entries (sync_key is NULL for manual entries)
+    ensure_column(pool, "lore", "sync_key", "TEXT").await?;
+
     // Timeline events table
     sqlx::query(
         r#"
@@ -156,6 +217,11 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Outline-synced timeline entries (sync_key is NULL for manual events)
+    ensure_column(pool, "timeline_events", "event_type", "TEXT").await?;
+    ensure_column(pool, "timeline_events", "chapter_id", "TEXT").await?;
+    ensure_column(pool, "timeline_events", "sync_key", "TEXT").await?;
+
     // Generation tasks table
     sqlx::query(
         r#"
@@ -178,6 +244,14 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    ensure_column(pool, "generation_tasks", "seed", "INTEGER").await?;
+
+    // Latency metrics per task
+    ensure_column(pool, "generation_tasks", "provider", "TEXT").await?;
+    ensure_column(pool, "generation_tasks", "model", "TEXT").await?;
+    ensure_column(pool, "generation_tasks", "latency_ms", "INTEGER").await?;
+    ensure_column(pool, "generation_tasks", "first_token_ms", "INTEGER").await?;
+
     // Snapshots table (version control)
     sqlx::query(
         r#"
@@ -195,6 +269,12 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Generation provenance for snapshots
+    ensure_column(pool, "snapshots", "source", "TEXT NOT NULL DEFAULT 'manual'").await?;
+    ensure_column(pool, "snapshots", "model", "TEXT").await?;
+    ensure_column(pool, "snapshots", "temperature", "REAL").await?;
+    ensure_column(pool, "snapshots", "prompt_template", "TEXT").await?;
+
     // Assets table (images, covers, etc.)
     sqlx::query(
         r#"
@@ -214,6 +294,135 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Word-count milestones (each threshold fires once per project)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS milestones (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            threshold INTEGER NOT NULL,
+            word_count INTEGER NOT NULL,
+            reached_at TEXT NOT NULL,
+            UNIQUE (project_id, threshold),
+            FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Daily project word-count totals (last value of each local day)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS word_count_history (
+            project_id TEXT NOT NULL,
+            day TEXT NOT NULL,
+            word_count INTEGER NOT NULL,
+            updated_at TEXT NOT NULL,
+            PRIMARY KEY (project_id, day),
+            FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Character appearances per chapter (character arc tracking)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS character_appearances (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            character_id TEXT NOT NULL,
+            chapter_id TEXT NOT NULL,
+            note TEXT,
+            source TEXT NOT NULL DEFAULT 'manual',
+            mention_count INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            UNIQUE (character_id, chapter_id),
+            FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE,
+            FOREIGN KEY (character_id) REFERENCES characters(id) ON DELETE CASCADE,
+            FOREIGN KEY (chapter_id) REFERENCES chapters(id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Operation log for debugging and undo of the last action (summaries only, no content)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS operation_log (
+            id TEXT PRIMARY KEY,
+            project_id TEXT,
+            operation TEXT NOT NULL,
+            target_type TEXT NOT NULL,
+            target_id TEXT,
+            summary TEXT NOT NULL,
+            snapshot_id TEXT,
+            undone_at TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Assistant personas for the freeform chat (built-ins are re-seeded on startup)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS chat_personas (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT,
+            system_prompt TEXT NOT NULL,
+            builtin INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+    ChatPersonaService::seed_builtin(pool).await?;
+
+    // Cached embedding vectors of lore/character entries (little-endian f32 blobs)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS embeddings (
+            id TEXT PRIMARY KEY,
+            project_id TEXT NOT NULL,
+            source_type TEXT NOT NULL,
+            source_id TEXT NOT NULL,
+            model TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            dimensions INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            UNIQUE (source_type, source_id, model),
+            FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Application settings (JSON blob per key)
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Create indexes
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_chapters_project ON chapters(project_id);")
         .execute(pool)
@@ -227,6 +436,74 @@ pub async fn run_migrations(pool: &SqlitePool) -> Result<()> {
         .execute(pool)
         .await?;
 
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_snapshots_target ON snapshots(target_type, target_id);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_timeline_sync ON timeline_events(project_id, sync_key);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_lore_sync ON lore(project_id, sync_key);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_lore_category ON lore(project_id, category);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_operation_log_created ON operation_log(created_at);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_chapter_tags_tag ON chapter_tags(tag);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_appearances_chapter ON character_appearances(chapter_id);")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_embeddings_project ON embeddings(project_id, model);")
+        .execute(pool)
+        .await?;
+
     log::info!("Database migrations completed");
     Ok(())
 }
+
+/// 为已有项目补算规范题材代码
+async fn backfill_genre_codes(pool: &SqlitePool) -> Result<()> {
+    let rows = sqlx::query(
+        "SELECT id, genre FROM projects WHERE genre_code IS NULL AND genre IS NOT NULL AND TRIM(genre) != ''"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in rows {
+        let id: String = row.get("id");
+        let genre: String = row.get("genre");
+        sqlx::query("UPDATE projects SET genre_code = ? WHERE id = ?")
+            .bind(normalize_genre(&genre))
+            .bind(&id)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 为旧数据库补充缺失的列
+async fn ensure_column(pool: &SqlitePool, table: &str, column: &str, definition: &str) -> Result<()> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({});", table))
+        .fetch_all(pool)
+        .await?;
+    let exists = columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == column);
+    if !exists {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
@@ -6,20 +6,68 @@ mod api;
 mod services;
 mod models;
 mod commands;
+#[cfg(feature = "http-server")]
+mod server;
 
 use tauri::Manager;
 
+/// 加载依赖数据库的全局设置（日志、重试策略）
+pub(crate) async fn apply_database_settings(pool: &sqlx::SqlitePool) {
+    match services::SettingsService::get(pool).await {
+        Ok(settings) => {
+            services::log_redaction::set_verbose(settings.verbose_request_logging);
+            api::retry::set_policy(settings.retry);
+        }
+        Err(e) => log::warn!("Failed to load logging settings: {}", e),
+    }
+}
+
+/// 数据库打开后（启动时或解锁后）加载依赖数据库的设置
+pub(crate) async fn on_database_ready(app_handle: &tauri::AppHandle) {
+    apply_database_settings(&db::get_pool(app_handle)).await;
+
+    #[cfg(feature = "http-server")]
+    server::spawn_from_env(db::get_pool(app_handle));
+}
+
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    services::log_redaction::init_logger();
+    let context = tauri::generate_context!();
+
+    // --serve：不创建窗口，只运行 HTTP 服务
+    #[cfg(feature = "http-server")]
+    if std::env::args().any(|arg| arg == server::SERVE_FLAG) {
+        if let Err(e) = server::run_headless(context.config()).await {
+            log::error!("Headless HTTP server failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     tauri::Builder::default()
         .setup(|app| {
             // Initialize database
             let app_handle = app.handle();
             tauri::async_runtime::spawn(async move {
-                if let Err(e) = db::init_database(&app_handle).await {
-                    log::error!("Failed to initialize database: {}", e);
+                match db::init_database(&app_handle, None).await {
+                    Ok(()) => {
+                        db::set_status(db::DatabaseStatus::Ready);
+                        on_database_ready(&app_handle).await;
+                    }
+                    // 加密数据库等待前端调用 unlock_database 提供口令；事件可能早于前端监听，前端另需轮询 get_database_status
+                    Err(e) if matches!(
+                        e.downcast_ref::<db::encryption::EncryptionError>(),
+                        Some(db::encryption::EncryptionError::PassphraseRequired)
+                    ) => {
+                        log::info!("Database is encrypted, waiting for passphrase");
+                        db::set_status(db::DatabaseStatus::Locked);
+                        let _ = app_handle.emit_all("database-locked", ());
+                    }
+                    Err(e) => {
+                        log::error!("Failed to initialize database: {}", e);
+                        db::set_status(db::DatabaseStatus::Failed(e.to_string()));
+                    }
                 }
             });
             Ok(())
@@ -28,34 +76,163 @@ async fn main() {
             commands::project::create_project,
             commands::project::get_projects,
             commands::project::get_project,
+            commands::project::get_project_dashboard,
             commands::project::update_project,
+            commands::project::update_project_partial,
             commands::project::delete_project,
+            commands::project::archive_project,
+            commands::project::unarchive_project,
+            commands::project::get_archived_projects,
+            commands::project::list_genres,
+            commands::project::get_project_notes,
+            commands::project::save_project_notes,
+            commands::project::get_series_bible,
+            commands::project::get_project_outline,
+            commands::project::save_project_outline,
+            commands::project::extract_outline_entities,
+            commands::project::list_outline_versions,
+            commands::project::restore_outline_version,
+            commands::project::validate_project_structure,
+            commands::project::get_act_distribution,
+            commands::project::analyze_vocabulary,
+            commands::project::search_all_projects,
             commands::chapter::create_chapter,
             commands::chapter::get_chapters,
+            commands::chapter::get_chapter_outlines,
+            commands::chapter::get_chapter_tags,
+            commands::chapter::add_chapter_tag,
+            commands::chapter::remove_chapter_tag,
+            commands::chapter::get_chapters_by_tag,
             commands::chapter::update_chapter,
+            commands::chapter::reflow_chapter,
+            commands::chapter::renumber_chapters,
             commands::chapter::update_chapter_meta,
+            commands::chapter::apply_chapter_title,
+            commands::chapter::set_chapters_status,
+            commands::chapter::set_chapters_act,
             commands::chapter::delete_chapter,
             commands::chapter::recalculate_project_word_count,
+            commands::chapter::find_duplicate_chapters,
             commands::ai::generate_outline,
             commands::ai::generate_chapter,
+            commands::ai::get_generation_modes,
+            commands::ai::generate_chapter_mode,
             commands::ai::generate_image,
+            commands::ai::cancel_image_generation,
+            commands::ai::generate_illustrations_batch,
             commands::ai::generate_prologue,
             commands::ai::generate_revision,
+            commands::ai::generate_chapter_variants,
+            commands::ai::promote_chapter_variant,
             commands::ai::generate_character_appearance,
             commands::ai::generate_character_portrait_prompt,
+            commands::ai::regenerate_character_field,
+            commands::ai::regenerate_outline_chapter,
+            commands::ai::check_timeline_consistency,
+            commands::ai::find_foreshadowing,
+            commands::ai::check_narrative_consistency,
+            commands::ai::regenerate_chapter_with_locks,
+            commands::ai::suggest_chapter_title,
+            commands::ai::generate_transition,
+            commands::ai::generate_ambiance_prompt,
+            commands::ai::get_chat_personas,
+            commands::ai::chat,
+            commands::ai::detect_language,
+            commands::ai::summarize_all_chapters,
+            commands::ai::generate_all_chapters,
+            commands::ai::retry_failed_chapters,
             commands::ai::test_deepseek_connection,
             commands::ai::test_text_connection,
             commands::ai::test_pollinations_connection,
+            commands::ai::list_pollinations_models,
             commands::stream::generate_outline_stream,
             commands::stream::generate_prologue_stream,
+            commands::stream::generate_series_bible,
             commands::stream::generate_chapter_stream,
+            commands::stream::continue_from,
+            commands::stream::validate_outline,
+            commands::stream::preview_context_usage,
             commands::stream::cancel_generation,
+            commands::stream::abort_and_restore,
+            commands::stream::ack_stream_events,
             commands::stream::generate_illustration_prompt,
+            commands::stream::suggest_illustration_points,
             commands::stream::generate_chapter_promo,
             commands::stream::generate_promo_image,
             commands::system::list_system_fonts,
             commands::system::get_system_font_base64,
+            commands::system::get_database_encryption_status,
+            commands::system::unlock_database,
+            commands::system::set_database_passphrase,
+            commands::system::get_database_status,
+            commands::snapshot::create_snapshot,
+            commands::snapshot::list_snapshots,
+            commands::snapshot::create_chapter_snapshot,
+            commands::snapshot::restore_snapshot,
+            commands::snapshot::diff_snapshots,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
+            commands::settings::validate_text_config,
+            commands::settings::get_project_image_config,
+            commands::settings::set_project_image_config,
+            commands::settings::get_project_narrative_settings,
+            commands::settings::set_project_narrative_settings,
+            commands::milestone::get_milestones,
+            commands::milestone::get_word_count_history,
+            commands::milestone::estimate_completion_date,
+            commands::timeline::create_timeline_event,
+            commands::timeline::get_timeline_events,
+            commands::timeline::update_timeline_event,
+            commands::timeline::delete_timeline_event,
+            commands::timeline::reorder_timeline_events,
+            commands::timeline::sync_outline_timeline,
+            commands::export::export_project_docx,
+            commands::export::export_docx,
+            commands::export::export_pdf,
+            commands::export::export_markdown,
+            commands::export::export_txt,
+            commands::export::export_story_bible_pdf,
+            commands::export::cancel_export,
+            commands::export::get_export_chapters,
+            commands::export::get_last_export_settings,
+            commands::export::save_last_export_settings,
+            commands::generation_task::get_generation_tasks,
+            commands::generation_task::get_project_cost_summary,
+            commands::generation_task::get_generation_metrics,
+            commands::generation_task::export_tasks_csv,
+            commands::generation_task::export_all_tasks_csv,
+            commands::generation_task::get_logs,
+            commands::lore::create_lore,
+            commands::lore::get_lore,
+            commands::lore::update_lore,
+            commands::lore::delete_lore,
+            commands::lore::compose_world_setting,
+            commands::lore::get_lore_by_category,
+            commands::lore::reorder_lore,
+            commands::lore::get_lore_categories,
+            commands::lore::sync_outline_lore,
+            commands::lore::refresh_embeddings,
+            commands::character::create_character,
+            commands::character::get_characters,
+            commands::character::get_character,
+            commands::character::update_character,
+            commands::character::delete_character,
+            commands::character::set_character_appearance,
+            commands::character::remove_character_appearance,
+            commands::character::detect_character_appearances,
+            commands::character::get_character_arc,
+            commands::character::set_character_portrait_seed,
+            commands::character::find_duplicate_characters,
+            commands::character::merge_characters,
+            commands::character::import_characters,
+            commands::character::export_characters,
+            commands::operation::get_recent_operations,
+            commands::operation::undo_last_operation,
+            commands::asset::register_asset,
+            commands::asset::get_project_assets,
+            commands::asset::get_linked_assets,
+            commands::asset::delete_asset,
         ])
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
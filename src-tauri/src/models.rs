@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use crate::api::pollinations::ImageGenerationParams;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Project {
@@ -7,6 +8,8 @@ pub struct Project {
     pub title: String,
     pub author: Option<String>,
     pub genre: Option<String>,
+    /// 规范题材代码（由 genre 归一化得到，见 services::genre）
+    pub genre_code: Option<String>,
     pub description: Option<String>,
     pub language: String,
     pub target_word_count: Option<i64>,
@@ -16,6 +19,73 @@ pub struct Project {
     pub updated_at: String,
     pub cover_images: Option<String>,
     pub default_cover_id: Option<String>,
+    pub archived_at: Option<String>,
+    pub archive_path: Option<String>,
+}
+
+/// 项目首页概览（get_project_dashboard），一次取齐首页所需的统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectDashboard {
+    pub project: Project,
+    pub chapter_count: i64,
+    pub chapters_by_status: Vec<ChapterStatusCount>,
+    /// 各章字数之和
+    pub total_words: i64,
+    pub target_word_count: Option<i64>,
+    /// 完成百分比（保留一位小数），未设置目标字数时为空
+    pub progress_percent: Option<f64>,
+    pub recent_tasks: Vec<DashboardTask>,
+    /// 已记录费用的任务合计，没有任何费用记录时为空
+    pub total_cost: Option<f64>,
+    pub total_tokens: i64,
+    pub character_count: i64,
+    pub lore_count: i64,
+    /// 项目、章节或角色最近一次快照的时间
+    pub latest_snapshot_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChapterStatusCount {
+    pub status: String,
+    pub count: i64,
+}
+
+/// 概览中的生成任务（不含输入参数和输出内容）
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DashboardTask {
+    pub id: String,
+    pub task_type: String,
+    pub status: String,
+    pub model: Option<String>,
+    pub token_count: Option<i64>,
+    pub cost: Option<f64>,
+    pub error_message: Option<String>,
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+/// 项目笔记（与项目元数据分开保存，不影响 updated_at 排序）
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProjectNotes {
+    pub project_id: String,
+    pub notes: Option<String>,
+    pub notes_updated_at: Option<String>,
+}
+
+/// 项目当前大纲；历史版本保存在 target_type 为 outline 的快照中
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProjectOutline {
+    pub project_id: String,
+    pub outline: Option<String>,
+    pub outline_updated_at: Option<String>,
+}
+
+/// 系列设定集：汇总世界观、角色和已有剧情的参考文档，可随写作进度重新生成
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ProjectBible {
+    pub project_id: String,
+    pub bible: Option<String>,
+    pub bible_updated_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +100,19 @@ pub struct CreateProjectInput {
     pub default_cover_id: Option<String>,
 }
 
+/// 项目部分更新：只修改传入的字段，未传字段保持原值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateProjectInput {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub genre: Option<String>,
+    pub description: Option<String>,
+    pub language: Option<String>,
+    pub target_word_count: Option<i64>,
+    pub cover_images: Option<String>,
+    pub default_cover_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Chapter {
     pub id: String,
@@ -45,8 +128,31 @@ pub struct Chapter {
     pub illustrations: Option<String>,
     pub word_count: i64,
     pub status: String, // draft, review, final
+    /// 用于跨章节上下文注入的摘要
+    pub summary: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// 本章生成时优先使用的模型、温度和目标字数，为空时沿用项目/全局设置
+    pub override_model: Option<String>,
+    pub override_temperature: Option<f32>,
+    pub override_target_words: Option<i64>,
+    /// 配乐/氛围提示词（ChapterAmbiance 的 JSON）
+    pub ambiance: Option<String>,
+    /// 所属幕（1-3），为空表示未分配
+    pub act: Option<i64>,
+}
+
+/// 章节配乐/氛围建议，供 AI 音乐生成工具使用
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterAmbiance {
+    /// 英文描述性提示词
+    pub prompt: String,
+    pub tempo: Option<String>,
+    pub instrumentation: Vec<String>,
+    pub mood: Vec<String>,
+    /// 简短英文标签
+    pub tags: Vec<String>,
+    pub generated_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,7 +164,7 @@ pub struct CreateChapterInput {
     pub conflict: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UpdateChapterMetaInput {
     pub title: Option<String>,
     pub order_index: Option<i32>,
@@ -66,6 +172,95 @@ pub struct UpdateChapterMetaInput {
     pub conflict: Option<String>,
     pub twist: Option<String>,
     pub cliffhanger: Option<String>,
+    #[serde(default)]
+    pub override_model: Option<String>,
+    #[serde(default)]
+    pub override_temperature: Option<f32>,
+    #[serde(default)]
+    pub override_target_words: Option<i64>,
+    /// 为 true 时先清空本章的全部生成参数覆盖，再写入上面提供的值
+    #[serde(default)]
+    pub clear_overrides: bool,
+}
+
+/// 一次章节生成实际使用的参数；overridden 列出取自章节覆盖设置的字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveChapterSettings {
+    pub chapter_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub temperature: f32,
+    pub target_words: Option<u32>,
+    pub overridden: Vec<String>,
+}
+
+impl EffectiveChapterSettings {
+    /// 合并章节的覆盖设置：覆盖项优先于请求或项目/全局传入的配置，返回实际使用的配置
+    pub fn resolve(
+        chapter: Option<&Chapter>,
+        config: &TextModelConfigInput,
+        target_words: Option<u32>,
+    ) -> (TextModelConfigInput, Self) {
+        let mut config = config.clone();
+        let mut target_words = target_words;
+        let mut overridden = Vec::new();
+        if let Some(chapter) = chapter {
+            if let Some(model) = chapter.override_model.as_deref().filter(|m| !m.trim().is_empty()) {
+                config.model = model.trim().to_string();
+                overridden.push("model".to_string());
+            }
+            if let Some(temperature) = chapter.override_temperature {
+                config.temperature = temperature;
+                overridden.push("temperature".to_string());
+            }
+            if let Some(words) = chapter.override_target_words.and_then(|w| u32::try_from(w).ok()) {
+                target_words = Some(words);
+                overridden.push("target_words".to_string());
+            }
+        }
+        let effective = Self {
+            chapter_id: chapter.map(|c| c.id.clone()),
+            provider: config.provider.clone(),
+            model: config.model.clone(),
+            temperature: config.normalized_temperature(0.7),
+            target_words,
+            overridden,
+        };
+        (config, effective)
+    }
+}
+
+/// 侧边栏使用的精简章节列表项（不含正文）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterOutlineItem {
+    pub id: String,
+    pub title: String,
+    pub order_index: i32,
+    pub status: String,
+    pub word_count: i64,
+    pub tags: Vec<String>,
+}
+
+/// 疑似重复的两个章节（a 的 order_index 不大于 b）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateChapterPair {
+    pub chapter_a_id: String,
+    pub chapter_a_title: String,
+    pub chapter_a_order: i32,
+    pub chapter_b_id: String,
+    pub chapter_b_title: String,
+    pub chapter_b_order: i32,
+    pub similarity: f64,
+}
+
+/// 疑似重复的两个角色（a 创建得更早）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCharacterPair {
+    pub character_a_id: String,
+    pub character_a_name: String,
+    pub character_b_id: String,
+    pub character_b_name: String,
+    pub similarity: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -81,6 +276,149 @@ pub struct Character {
     pub voice_style: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// 插图中固定使用的图片种子
+    pub portrait_seed: Option<i64>,
+    /// 英文外貌描述，生成插图时原样拼入提示词
+    pub portrait_descriptor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCharacterInput {
+    pub project_id: String,
+    pub name: String,
+    pub role: Option<String>,
+    pub description: Option<String>,
+    pub personality: Option<String>,
+    pub background: Option<String>,
+    pub motivation: Option<String>,
+    pub voice_style: Option<String>,
+}
+
+/// 角色设定的部分更新：为 None 的字段保持不变
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateCharacterInput {
+    pub name: Option<String>,
+    pub role: Option<String>,
+    pub description: Option<String>,
+    pub personality: Option<String>,
+    pub background: Option<String>,
+    pub motivation: Option<String>,
+    pub voice_style: Option<String>,
+}
+
+/// 导入/导出用的角色表格行（不含 id 与时间戳）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CharacterSheet {
+    pub name: String,
+    pub role: Option<String>,
+    pub description: Option<String>,
+    pub personality: Option<String>,
+    pub background: Option<String>,
+    pub motivation: Option<String>,
+    pub voice_style: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterImportRowError {
+    /// CSV 为行号（含表头），JSON 为数组下标（从 1 开始）
+    pub row: usize,
+    pub message: String,
+}
+
+/// 从大纲提取设定、时间线和角色的结果；大纲缺少的部分对应列表为空
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineEntitiesReport {
+    pub lore: Vec<Lore>,
+    pub timeline_events: Vec<TimelineEvent>,
+    pub created_characters: Vec<Character>,
+    /// 与已有角色同名、补全了空字段的角色
+    pub updated_characters: Vec<Character>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterImportReport {
+    pub created: Vec<Character>,
+    /// 与已有角色同名并合并了非空字段的角色
+    pub merged: Vec<Character>,
+    /// 同名而跳过的角色名（包括导入数据内部重复）
+    pub skipped_duplicates: Vec<String>,
+    pub errors: Vec<CharacterImportRowError>,
+    /// 无法识别而忽略的表头
+    pub ignored_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CharacterAppearance {
+    pub id: String,
+    pub project_id: String,
+    pub character_id: String,
+    pub chapter_id: String,
+    pub note: Option<String>,
+    pub source: String, // manual, detected
+    pub mention_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 角色弧线中的一章
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CharacterArcEntry {
+    pub chapter_id: String,
+    pub chapter_title: String,
+    pub order_index: i32,
+    pub note: Option<String>,
+    pub source: String,
+    pub mention_count: i64,
+    /// 与上一次出场之间缺席的章节数
+    pub chapters_absent_before: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterArc {
+    pub character_id: String,
+    pub character_name: String,
+    pub total_chapters: i64,
+    pub appearances: Vec<CharacterArcEntry>,
+    /// 两次出场之间最长的缺席章节数
+    pub longest_absence: i64,
+    /// 最后一次出场之后已有多少章未出现
+    pub chapters_since_last: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Lore {
+    pub id: String,
+    pub project_id: String,
+    pub category: String,
+    pub title: String,
+    pub content: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub order_index: i32,
+    pub sync_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateLoreInput {
+    pub project_id: String,
+    pub category: String,
+    pub title: String,
+    pub content: Option<String>,
+}
+
+/// 设定条目的部分更新：为 None 的字段保持不变
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateLoreInput {
+    pub category: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LoreCategory {
+    pub category: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -96,6 +434,69 @@ pub struct GenerationTask {
     pub cost: Option<f64>,
     pub created_at: String,
     pub completed_at: Option<String>,
+    pub seed: Option<i64>,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub latency_ms: Option<i64>,
+    pub first_token_ms: Option<i64>, // 仅流式生成
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TaskTypeCost {
+    pub task_type: String,
+    pub task_count: i64,
+    pub token_count: i64,
+    pub cost: f64,
+}
+
+/// 项目生成费用汇总；费用按任务完成时的计费单价估算
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectCostSummary {
+    pub project_id: String,
+    pub currency: String,
+    pub total_tokens: i64,
+    pub total_cost: f64,
+    pub by_task_type: Vec<TaskTypeCost>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelLatencyMetrics {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    pub total: usize,
+    pub failed: usize,
+    pub p50_latency_ms: Option<i64>,
+    pub p95_latency_ms: Option<i64>,
+}
+
+/// 按本地日期筛选任务（闭区间，YYYY-MM-DD），为空表示不限
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TaskDateRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskCsvExport {
+    pub path: String,
+    pub rows: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationMetrics {
+    pub window_hours: i64,
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub failure_rate: f64,
+    pub p50_latency_ms: Option<i64>,
+    pub p95_latency_ms: Option<i64>,
+    pub p50_first_token_ms: Option<i64>,
+    pub p95_first_token_ms: Option<i64>,
+    /// 仅统计有 token 用量的已完成任务
+    pub avg_tokens_per_sec: Option<f64>,
+    pub by_model: Vec<ModelLatencyMetrics>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -107,6 +508,61 @@ pub struct Snapshot {
     pub content_hash: String,
     pub note: Option<String>,
     pub created_at: String,
+    pub source: String, // manual, generated
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub prompt_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct OperationLogEntry {
+    pub id: String,
+    pub project_id: Option<String>,
+    pub operation: String, // 命令名，如 update_chapter
+    pub target_type: String, // project, chapter, character
+    pub target_id: Option<String>,
+    pub summary: String,
+    /// 操作前状态的快照，用于撤销
+    pub snapshot_id: Option<String>,
+    pub undone_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSnapshotInput {
+    pub target_type: String,
+    pub target_id: String,
+    pub content: String,
+    pub note: Option<String>,
+    /// 生成该版本时使用的模型；为空表示手动编辑
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub prompt_template: Option<String>,
+}
+
+/// 生成的图片等文件，可关联到章节或角色
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Asset {
+    pub id: String,
+    pub project_id: String,
+    pub asset_type: String, // illustration, cover, portrait
+    pub file_path: String,
+    pub linked_to_type: Option<String>, // chapter, character
+    pub linked_to_id: Option<String>,
+    /// 生成参数等附加信息（JSON）
+    pub metadata: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateAssetInput {
+    pub project_id: String,
+    pub asset_type: String,
+    pub file_path: String,
+    pub linked_to_type: Option<String>,
+    pub linked_to_id: Option<String>,
+    pub metadata: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,6 +586,526 @@ impl Default for ApiConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TimelineEvent {
+    pub id: String,
+    pub project_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub event_time: Option<String>,
+    pub order_index: Option<i32>,
+    pub event_type: Option<String>, // history, story
+    pub chapter_id: Option<String>,
+    pub sync_key: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTimelineEventInput {
+    pub project_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub event_time: Option<String>,
+    /// 不传时排在最后
+    pub order_index: Option<i32>,
+    pub event_type: Option<String>,
+    pub chapter_id: Option<String>,
+}
+
+/// 时间线事件的部分更新：为 None 的字段保持不变
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateTimelineEventInput {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub event_time: Option<String>,
+    pub order_index: Option<i32>,
+    pub event_type: Option<String>,
+    pub chapter_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Milestone {
+    pub id: String,
+    pub project_id: String,
+    pub threshold: i64,
+    pub word_count: i64,
+    pub reached_at: String,
+}
+
+/// 某天结束时的项目总字数
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DailyWordCount {
+    pub project_id: String,
+    pub day: String, // YYYY-MM-DD（本地日期）
+    pub word_count: i64,
+    pub updated_at: String,
+}
+
+/// 按近期写作速度推算的完成日期；无法推算时 estimated_date 为空并给出 reason
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionEstimate {
+    pub project_id: String,
+    pub target_word_count: Option<i64>,
+    pub current_word_count: i64,
+    pub remaining_words: i64,
+    /// 统计窗口内的日均净增字数
+    pub daily_average: Option<f64>,
+    /// 统计窗口实际覆盖的天数
+    pub window_days: i64,
+    pub estimated_date: Option<String>,
+    pub days_remaining: Option<i64>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppSettings {
+    pub text_config: TextModelConfigInput,
+    pub pollinations_api_key: Option<String>,
+    pub pdf_font_file_name: Option<String>,
+    pub export: ExportSettings,
+    pub image: ImageSettings,
+    pub stream: StreamSettings,
+    pub language_check: LanguageCheckSettings,
+    pub context_budget: ContextBudgetSettings,
+    pub generation_modes: GenerationModeSettings,
+    /// 字数里程碑间隔（每达到该倍数触发一次）
+    pub milestone_interval: i64,
+    /// 详细请求日志：在日志中记录提示词片段（仍会去除密钥），仅建议本地调试时开启
+    pub verbose_request_logging: bool,
+    pub vocabulary: VocabularySettings,
+    /// 生成或修改大纲时自动保存版本快照
+    pub outline_snapshots: bool,
+    pub retrieval: RetrievalSettings,
+    pub pricing: PricingConfig,
+    pub retry: RetrySettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            text_config: TextModelConfigInput::default(),
+            pollinations_api_key: None,
+            pdf_font_file_name: None,
+            export: ExportSettings::default(),
+            image: ImageSettings::default(),
+            stream: StreamSettings::default(),
+            language_check: LanguageCheckSettings::default(),
+            context_budget: ContextBudgetSettings::default(),
+            generation_modes: GenerationModeSettings::default(),
+            milestone_interval: 10_000,
+            verbose_request_logging: false,
+            vocabulary: VocabularySettings::default(),
+            outline_snapshots: true,
+            retrieval: RetrievalSettings::default(),
+            pricing: PricingConfig::default(),
+            retry: RetrySettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportSettings {
+    pub format: String, // pdf, epub, txt, mobi
+    pub include_cover: bool,
+    pub page_size: String,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: "pdf".to_string(),
+            include_cover: true,
+            page_size: "A4".to_string(),
+        }
+    }
+}
+
+/// 自由对话的助手人设
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatPersona {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub system_prompt: String,
+    pub builtin: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 对话中的一轮消息，role 为 user 或 assistant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: String,
+    pub content: String,
+}
+
+/// 段落整理规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReflowRules {
+    /// 首行缩进：fullwidth（两个全角空格）/ none / keep（保留原有缩进）
+    pub indent: String,
+    /// 段落之间空一行
+    pub blank_line_between: bool,
+    /// 合并段落中被硬换行切断的行
+    pub join_wrapped_lines: bool,
+}
+
+impl Default for ReflowRules {
+    fn default() -> Self {
+        Self {
+            indent: "fullwidth".to_string(),
+            blank_line_between: false,
+            join_wrapped_lines: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReflowResult {
+    pub chapter: Chapter,
+    pub paragraphs: usize,
+    /// 被合并进上一行的硬换行数
+    pub joined_lines: usize,
+    /// 整理前的章节快照，可用于撤销
+    pub snapshot_id: Option<String>,
+}
+
+/// 章节编号方案
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChapterNumberingScheme {
+    /// 编号格式，{n} 替换为章节号，如“第{n}章”“Chapter {n}”
+    pub format: String,
+    /// 数字写法：arabic / chinese
+    pub numeral: String,
+    /// 编号与自定义标题之间的分隔符
+    pub separator: String,
+    /// 第一章（order_index 最小的章节）的编号
+    pub start: u32,
+}
+
+impl Default for ChapterNumberingScheme {
+    fn default() -> Self {
+        Self {
+            format: "第{n}章".to_string(),
+            numeral: "chinese".to_string(),
+            separator: " ".to_string(),
+            start: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterRenumberItem {
+    pub chapter_id: String,
+    pub order_index: i32,
+    pub number: u32,
+    pub old_title: String,
+    pub new_title: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterRenumberResult {
+    /// dry_run 时为 false，只返回预览
+    pub applied: bool,
+    pub items: Vec<ChapterRenumberItem>,
+    pub changed_count: usize,
+    /// 改名前各章节的快照
+    pub snapshot_ids: Vec<String>,
+}
+
+/// 导出的章节范围：给出 chapter_ids 时按 id 选取，否则按 order_index 闭区间选取；都为空时导出全部
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportChapterSelection {
+    pub chapter_ids: Option<Vec<String>>,
+    pub from_order: Option<i32>,
+    pub to_order: Option<i32>,
+}
+
+/// Markdown / TXT 导出结果：path 为输出目录或文件，files 为写入的全部文件，word_count 为导出正文的字数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextExportResult {
+    pub path: String,
+    pub files: Vec<String>,
+    pub chapter_count: usize,
+    pub word_count: i64,
+}
+
+/// 项目最近一次导出使用的参数，未保存过时取全局导出设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProjectExportSettings {
+    pub format: String, // pdf, epub, txt, mobi, docx
+    pub font_file_name: Option<String>,
+    pub include_cover: bool,
+    pub page_size: String,
+    /// 页边距（毫米），为空时使用导出器默认值
+    pub margin_mm: Option<f64>,
+}
+
+impl Default for ProjectExportSettings {
+    fn default() -> Self {
+        let export = ExportSettings::default();
+        Self {
+            format: export.format,
+            font_file_name: None,
+            include_cover: export.include_cover,
+            page_size: export.page_size,
+            margin_mm: None,
+        }
+    }
+}
+
+/// 流式输出推送粒度：累计到 flush_chars 个字符或距上次推送超过 flush_interval_ms 时推送一次
+///
+/// 背压：前端通过 ack_stream_events 按 generation_id 回报已处理的事件数后，已推送未确认的事件达到
+/// max_unacked_events 时暂停推送，新内容先进入本地缓冲；缓冲批次超过
+/// max_buffered_events 时把最早的两批合并，只减少事件数量、不丢内容。
+/// 默认 16 个未确认事件（默认推送粒度下约 0.4k 字）、64 个缓冲批次。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StreamSettings {
+    /// 不大于 1 时每个增量立即推送
+    pub flush_chars: usize,
+    pub flush_interval_ms: u64,
+    /// 为 0 时不做背压，始终立即推送
+    pub max_unacked_events: u64,
+    pub max_buffered_events: usize,
+    /// 同一时间只运行一个流式生成，后开始的排队等待；关闭后可并行生成，按 generation_id 分别取消
+    pub serialize_generations: bool,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            flush_chars: 24,
+            flush_interval_ms: 80,
+            max_unacked_events: 16,
+            max_buffered_events: 64,
+            serialize_generations: true,
+        }
+    }
+}
+
+/// 章节生成时注入上下文（前情提要、角色、设定、时间线）的 token 预算
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ContextBudgetSettings {
+    pub injection_tokens: usize,
+}
+
+impl Default for ContextBudgetSettings {
+    fn default() -> Self {
+        Self { injection_tokens: 8000 }
+    }
+}
+
+/// 生成模式使用的模型；为空时沿用当前文本模型
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GenerationModeSettings {
+    /// 快速草稿用的较便宜模型
+    pub fast_draft_model: String,
+    /// 精修用的较强模型
+    pub polished_model: String,
+}
+
+/// 章节生成后的语言检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LanguageCheckSettings {
+    /// off：不检查；flag：不符时发出 language-mismatch 事件；retry：非流式生成额外以更强的语言要求重试一次
+    pub mode: String,
+    /// 不符段落占比达到该值视为不符
+    pub mismatch_threshold: f64,
+}
+
+impl Default for LanguageCheckSettings {
+    fn default() -> Self {
+        Self {
+            mode: "flag".to_string(),
+            mismatch_threshold: 0.15,
+        }
+    }
+}
+
+/// 词频统计（analyze_vocabulary）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct VocabularySettings {
+    /// 自定义停用词，不区分大小写
+    pub stopwords: Vec<String>,
+    /// 是否同时使用内置的中英文停用词表
+    pub use_default_stopwords: bool,
+    /// 报告中列出的高频词数量
+    pub top_n: usize,
+    /// 每千词出现次数达到该值视为用得过多
+    pub overuse_per_thousand: f64,
+}
+
+impl Default for VocabularySettings {
+    fn default() -> Self {
+        Self {
+            stopwords: Vec::new(),
+            use_default_stopwords: true,
+            top_n: 50,
+            overuse_per_thousand: 5.0,
+        }
+    }
+}
+
+/// 章节生成时按与章节大纲的相关度挑选注入的设定和角色
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetrievalSettings {
+    /// 向量模型，走当前文本模型地址的 /embeddings 接口；为空时按关键词匹配
+    pub embedding_model: String,
+    /// 设定、角色各自最多注入的条目数；条目不超过该数时全部注入，0 表示不筛选
+    pub top_k: usize,
+    /// 单次向量请求包含的条目数
+    pub batch_size: usize,
+}
+
+impl Default for RetrievalSettings {
+    fn default() -> Self {
+        Self {
+            embedding_model: String::new(),
+            top_k: 12,
+            batch_size: 32,
+        }
+    }
+}
+
+/// 文本生成的计费单价（每 1K tokens），用于估算生成任务的费用；默认为 DeepSeek 官方价格（缓存未命中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PricingConfig {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+    pub currency: String,
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            input_per_1k: 0.002,
+            output_per_1k: 0.008,
+            currency: "CNY".to_string(),
+        }
+    }
+}
+
+/// 文本和图片请求遇到超时、连接失败或 429/500/502/503 时的重试：
+/// 共请求 max_attempts 次，第 n 次重试前等待 base_delay_ms × 2^(n-1)（上下浮动 jitter 比例，不超过 max_delay_ms）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetrySettings {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: f64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl PricingConfig {
+    pub fn estimate_cost(&self, prompt_tokens: i64, completion_tokens: i64) -> f64 {
+        (prompt_tokens as f64 * self.input_per_1k + completion_tokens as f64 * self.output_per_1k) / 1000.0
+    }
+}
+
+/// 图片生成默认参数，单次调用显式传入的值优先
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ImageSettings {
+    pub model: String,
+    pub nologo: bool,
+    pub enhance: bool,
+    pub width: u32,
+    pub height: u32,
+    pub promo_width: u32,
+    pub promo_height: u32,
+    /// 固定种子；为空时每次随机
+    pub seed: Option<i64>,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            model: "zimage".to_string(),
+            nologo: true,
+            enhance: false,
+            width: 1024,
+            height: 1024,
+            promo_width: 1200,  // 推文横幅默认3:1
+            promo_height: 400,
+            seed: None,
+        }
+    }
+}
+
+impl ImageSettings {
+    /// 用项目级配置覆盖全局默认值
+    pub fn for_project(&self, project: &ProjectImageConfig) -> Self {
+        let mut settings = self.clone();
+        if let Some(model) = project.model.as_deref().filter(|m| !m.trim().is_empty()) {
+            settings.model = model.trim().to_string();
+        }
+        settings.width = project.width.unwrap_or(settings.width);
+        settings.height = project.height.unwrap_or(settings.height);
+        settings.promo_width = project.promo_width.unwrap_or(settings.promo_width);
+        settings.promo_height = project.promo_height.unwrap_or(settings.promo_height);
+        settings
+    }
+
+    /// 补全未指定的参数，size 为该场景的默认尺寸
+    pub fn fill_params(&self, params: &mut ImageGenerationParams, size: (u32, u32)) {
+        params.width.get_or_insert(size.0);
+        params.height.get_or_insert(size.1);
+        params.seed.get_or_insert(self.seed.unwrap_or(-1));
+        params.model.get_or_insert_with(|| self.model.clone());
+        params.nologo.get_or_insert(self.nologo);
+        params.enhance.get_or_insert(self.enhance);
+    }
+}
+
+/// 项目级图片默认参数，未设置的项沿用全局图片设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProjectImageConfig {
+    pub model: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub promo_width: Option<u32>,
+    pub promo_height: Option<u32>,
+}
+
+/// 项目的叙述人称与时态，检查生成内容是否偏离；为空的项不检查
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProjectNarrativeSettings {
+    /// first / third
+    pub pov: Option<String>,
+    /// past / present
+    pub tense: Option<String>,
+    /// 章节生成完成后自动检查（额外调用一次模型），发现偏离时发出 narrative-drift 事件
+    pub auto_check: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TextModelConfigInput {
@@ -138,6 +1114,8 @@ pub struct TextModelConfigInput {
     pub api_url: String,
     pub model: String,
     pub temperature: f32,
+    #[serde(default)]
+    pub seed: Option<i64>,
 }
 
 impl Default for TextModelConfigInput {
@@ -148,6 +1126,7 @@ impl Default for TextModelConfigInput {
             api_url: "https://api.deepseek.com/v1".to_string(),
             model: "deepseek-chat".to_string(),
             temperature: 0.7,
+            seed: None,
         }
     }
 }
@@ -177,6 +1156,15 @@ impl TextModelConfigInput {
         }
     }
 
+    /// 仅对接受 `seed` 参数的服务商返回种子。
+    /// OpenAI / DeepSeek / OpenRouter / 自定义兼容接口会随请求发送；Gemini 兼容层不支持，直接忽略。
+    pub fn effective_seed(&self) -> Option<i64> {
+        match self.provider.trim().to_ascii_lowercase().as_str() {
+            "gemini" => None,
+            _ => self.seed,
+        }
+    }
+
     pub fn normalized_api_base_url(&self) -> String {
         self.api_url.trim().trim_end_matches('/').to_string()
     }
@@ -190,3 +1178,46 @@ impl TextModelConfigInput {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFieldIssue {
+    pub field: String, // provider, api_key, api_url, model, temperature
+    pub message: String,
+}
+
+/// 文本模型配置的结构检查结果（不发起网络请求）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextConfigValidation {
+    pub valid: bool,
+    pub errors: Vec<ConfigFieldIssue>,
+    /// 不影响使用但可能是误填的项
+    pub warnings: Vec<ConfigFieldIssue>,
+    /// 实际请求的 chat/completions 地址
+    pub chat_completions_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextSectionUsage {
+    pub name: String, // world_setting, timeline, characters, previous_chapter
+    pub tokens: usize,
+}
+
+/// 生成前的上下文窗口占用预估
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextUsagePreview {
+    pub model: String,
+    pub context_window: usize,
+    /// 为模型输出预留的 tokens（即 max_tokens）
+    pub reserved_output_tokens: usize,
+    pub system_tokens: usize,
+    pub injected_tokens: usize,
+    pub task_tokens: usize,
+    pub total_input_tokens: usize,
+    /// (输入 + 预留输出) / 上下文窗口
+    pub utilization_percent: f64,
+    pub overflow_tokens: usize,
+    pub sections: Vec<ContextSectionUsage>,
+    /// 超出时为腾出空间需要移除的注入内容（按移除顺序）
+    pub dropped: Vec<String>,
+    pub warnings: Vec<String>,
+}
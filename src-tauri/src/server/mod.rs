@@ -0,0 +1,341 @@
+//! 无界面 HTTP 服务模式（feature = "http-server"）
+//!
+//! 复用现有 Service 层，将项目/章节 CRUD 与文本生成暴露为 JSON 接口，便于脚本调用。
+//! 仅监听 127.0.0.1，且必须通过环境变量 `NOVELSEEK_HTTP_TOKEN` 配置访问令牌才会启动。
+//! 以 `--serve` 启动时不创建窗口，只打开数据库并在前台运行服务，可在没有桌面会话的环境使用；
+//! 否则随桌面应用在后台启动。生成接口与 Tauri 命令走同一流程（生成任务记录、用量统计、上下文裁剪）。
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use crate::commands::ai::{
+    resolve_text_config, run_chapter, run_outline, run_prologue, run_revision, GenerateChapterInput,
+    GenerateOutlineInput, GeneratePrologueInput, GenerateRevisionInput,
+};
+use crate::db;
+use crate::models::{
+    Chapter, CreateChapterInput, CreateProjectInput, Project, TextModelConfigInput, UpdateChapterMetaInput,
+};
+use crate::services::{ChapterService, ProjectService};
+
+/// 以无界面模式启动的命令行参数
+pub const SERVE_FLAG: &str = "--serve";
+
+const TOKEN_ENV: &str = "NOVELSEEK_HTTP_TOKEN";
+const PASSPHRASE_ENV: &str = "NOVELSEEK_DB_PASSPHRASE";
+const PORT_ENV: &str = "NOVELSEEK_HTTP_PORT";
+const DEFAULT_PORT: u16 = 17321;
+
+#[derive(Clone)]
+struct ServerState {
+    pool: SqlitePool,
+    token: Arc<String>,
+}
+
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    fn internal(error: impl std::fmt::Display) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    }
+
+    fn bad_request(error: impl std::fmt::Display) -> Self {
+        Self(StatusCode::BAD_REQUEST, error.to_string())
+    }
+
+    fn not_found(message: &str) -> Self {
+        Self(StatusCode::NOT_FOUND, message.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct UpdateChapterTextBody {
+    draft_text: Option<String>,
+    final_text: Option<String>,
+    illustrations: Option<String>,
+}
+
+// 访问令牌和端口；未设置令牌时返回 None
+fn config_from_env() -> Option<(String, u16)> {
+    let token = std::env::var(TOKEN_ENV)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())?;
+    let port = std::env::var(PORT_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<u16>().ok())
+        .unwrap_or(DEFAULT_PORT);
+    Some((token, port))
+}
+
+/// 若设置了访问令牌则在后台启动 HTTP 服务；未设置时保持关闭
+pub fn spawn_from_env(pool: SqlitePool) {
+    let Some((token, port)) = config_from_env() else {
+        log::info!("HTTP server disabled: {} is not set", TOKEN_ENV);
+        return;
+    };
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(pool, token, port).await {
+            log::error!("HTTP server stopped: {}", e);
+        }
+    });
+}
+
+/// 无界面模式：打开数据库（加密时口令取自 NOVELSEEK_DB_PASSPHRASE）后在前台运行服务，直到出错退出
+pub async fn run_headless(config: &tauri::Config) -> anyhow::Result<()> {
+    let (token, port) = config_from_env()
+        .ok_or_else(|| anyhow::anyhow!("{} must be set to run the HTTP server", TOKEN_ENV))?;
+    let passphrase = std::env::var(PASSPHRASE_ENV).ok().filter(|value| !value.is_empty());
+    let path = db::database_path_for_config(config)?;
+    let pool = db::open_database(&path, passphrase.as_deref()).await?;
+    crate::apply_database_settings(&pool).await;
+    serve(pool, token, port).await
+}
+
+pub async fn serve(pool: SqlitePool, token: String, port: u16) -> anyhow::Result<()> {
+    let state = ServerState {
+        pool,
+        token: Arc::new(token),
+    };
+
+    let app = Router::new()
+        .route("/api/projects", get(list_projects).post(create_project))
+        .route(
+            "/api/projects/:id",
+            get(get_project).put(update_project).delete(delete_project),
+        )
+        .route("/api/projects/:id/chapters", get(list_chapters))
+        .route("/api/chapters", post(create_chapter))
+        .route("/api/chapters/:id", put(update_chapter_text).delete(delete_chapter))
+        .route("/api/chapters/:id/meta", put(update_chapter_meta))
+        .route("/api/generate/outline", post(generate_outline))
+        .route("/api/generate/chapter", post(generate_chapter))
+        .route("/api/generate/prologue", post(generate_prologue))
+        .route("/api/generate/revision", post(generate_revision))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state);
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    log::info!("HTTP server listening on http://{}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+fn tokens_match(expected: &str, provided: &str) -> bool {
+    if expected.len() != provided.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(provided.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+async fn require_token(
+    State(state): State<ServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+
+    if !tokens_match(&state.token, provided) {
+        return ApiError(StatusCode::UNAUTHORIZED, "invalid or missing token".to_string())
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn list_projects(State(state): State<ServerState>) -> ApiResult<Vec<Project>> {
+    ProjectService::get_all(&state.pool)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn create_project(
+    State(state): State<ServerState>,
+    Json(input): Json<CreateProjectInput>,
+) -> ApiResult<Project> {
+    ProjectService::create(&state.pool, input)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn get_project(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> ApiResult<Project> {
+    ProjectService::get_by_id(&state.pool, &id)
+        .await
+        .map_err(ApiError::internal)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("project not found"))
+}
+
+async fn update_project(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Json(input): Json<CreateProjectInput>,
+) -> ApiResult<Project> {
+    ProjectService::update(&state.pool, &id, input)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn delete_project(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    ProjectService::delete(&state.pool, &id)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_chapters(
+    State(state): State<ServerState>,
+    Path(project_id): Path<String>,
+) -> ApiResult<Vec<Chapter>> {
+    ChapterService::get_by_project(&state.pool, &project_id)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn create_chapter(
+    State(state): State<ServerState>,
+    Json(input): Json<CreateChapterInput>,
+) -> ApiResult<Chapter> {
+    ChapterService::create(&state.pool, input)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn update_chapter_text(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateChapterTextBody>,
+) -> ApiResult<Chapter> {
+    ChapterService::update_text(
+        &state.pool,
+        &id,
+        body.draft_text,
+        body.final_text,
+        body.illustrations,
+    )
+    .await
+    .map_err(ApiError::internal)?;
+
+    ChapterService::get_by_id(&state.pool, &id)
+        .await
+        .map_err(ApiError::internal)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("chapter not found"))
+}
+
+async fn update_chapter_meta(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateChapterMetaInput>,
+) -> ApiResult<Chapter> {
+    ChapterService::update_meta(&state.pool, &id, input)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn delete_chapter(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    ChapterService::delete(&state.pool, &id)
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 解析并校验文本模型配置，配置问题按 400 返回
+async fn text_config(
+    state: &ServerState,
+    config: Option<TextModelConfigInput>,
+) -> Result<TextModelConfigInput, ApiError> {
+    let config = resolve_text_config(&state.pool, config)
+        .await
+        .map_err(ApiError::bad_request)?;
+    config.validate().map_err(ApiError::bad_request)?;
+    Ok(config)
+}
+
+async fn generate_outline(
+    State(state): State<ServerState>,
+    Json(input): Json<GenerateOutlineInput>,
+) -> ApiResult<String> {
+    let config = text_config(&state, input.text_config.clone()).await?;
+    run_outline(&state.pool, &input, &config)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn generate_chapter(
+    State(state): State<ServerState>,
+    Json(input): Json<GenerateChapterInput>,
+) -> ApiResult<String> {
+    let config = text_config(&state, input.text_config.clone()).await?;
+    run_chapter(&state.pool, None, &input, &config)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn generate_prologue(
+    State(state): State<ServerState>,
+    Json(input): Json<GeneratePrologueInput>,
+) -> ApiResult<String> {
+    let config = text_config(&state, input.text_config.clone()).await?;
+    run_prologue(&state.pool, &input, &config)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
+async fn generate_revision(
+    State(state): State<ServerState>,
+    Json(input): Json<GenerateRevisionInput>,
+) -> ApiResult<String> {
+    let config = text_config(&state, input.text_config.clone()).await?;
+    run_revision(&state.pool, &input, &config)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
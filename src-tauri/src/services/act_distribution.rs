@@ -0,0 +1,122 @@
+//! 三幕字数分布
+//!
+//! 大纲按 20/60/20 规划三幕篇幅，这里按章节所属的幕（chapters.act）汇总实际字数，
+//! 与目标比例对照，偏差超过容差的幕标记为过长或过短。未分配幕的章节不计入比例。
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use super::ProjectService;
+use super::chapter_service::ACT_COUNT;
+
+/// 各幕目标占比（%），与大纲提示词中的三幕规划一致
+pub const ACT_TARGET_PERCENTS: [f64; 3] = [20.0, 60.0, 20.0];
+/// 实际占比与目标相差超过该百分点时视为失衡
+const IMBALANCE_TOLERANCE: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActShare {
+    pub act: i64,
+    pub chapter_count: i64,
+    pub words: i64,
+    /// 占已分配章节总字数的百分比
+    pub percent: f64,
+    pub target_percent: f64,
+    /// 按项目目标字数折算的本幕目标字数；项目未设目标字数时为空
+    pub target_words: Option<i64>,
+    /// 实际占比减目标占比（百分点）
+    pub deviation: f64,
+    /// balanced / over / under；没有已分配字数时为 no_data
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActDistributionReport {
+    pub project_id: String,
+    pub acts: Vec<ActShare>,
+    pub assigned_words: i64,
+    pub unassigned_chapters: i64,
+    pub unassigned_words: i64,
+    pub tolerance: f64,
+    /// 有已分配字数且各幕都在容差内
+    pub balanced: bool,
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+pub async fn act_distribution(pool: &SqlitePool, project_id: &str) -> Result<ActDistributionReport> {
+    let project = ProjectService::get_by_id(pool, project_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+
+    let rows: Vec<(Option<i64>, i64, i64)> = sqlx::query_as(
+        "SELECT act, COUNT(*), COALESCE(SUM(word_count), 0) FROM chapters WHERE project_id = ? GROUP BY act"
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut counts = [(0i64, 0i64); ACT_COUNT as usize];
+    let (mut unassigned_chapters, mut unassigned_words) = (0, 0);
+    for (act, chapters, words) in rows {
+        match act.filter(|act| (1..=ACT_COUNT).contains(act)) {
+            Some(act) => {
+                let entry = &mut counts[(act - 1) as usize];
+                entry.0 += chapters;
+                entry.1 += words;
+            }
+            None => {
+                unassigned_chapters += chapters;
+                unassigned_words += words;
+            }
+        }
+    }
+
+    let assigned_words: i64 = counts.iter().map(|(_, words)| words).sum();
+    let target_total = project.target_word_count.filter(|target| *target > 0);
+    let acts: Vec<ActShare> = counts
+        .iter()
+        .zip(ACT_TARGET_PERCENTS)
+        .enumerate()
+        .map(|(index, ((chapter_count, words), target_percent))| {
+            let percent = if assigned_words > 0 {
+                *words as f64 * 100.0 / assigned_words as f64
+            } else {
+                0.0
+            };
+            let deviation = percent - target_percent;
+            let status = if assigned_words == 0 {
+                "no_data"
+            } else if deviation > IMBALANCE_TOLERANCE {
+                "over"
+            } else if deviation < -IMBALANCE_TOLERANCE {
+                "under"
+            } else {
+                "balanced"
+            };
+            ActShare {
+                act: index as i64 + 1,
+                chapter_count: *chapter_count,
+                words: *words,
+                percent: round1(percent),
+                target_percent,
+                target_words: target_total.map(|total| (total as f64 * target_percent / 100.0).round() as i64),
+                deviation: round1(deviation),
+                status: status.to_string(),
+            }
+        })
+        .collect();
+
+    let balanced = assigned_words > 0 && acts.iter().all(|act| act.status == "balanced");
+    Ok(ActDistributionReport {
+        project_id: project.id,
+        acts,
+        assigned_words,
+        unassigned_chapters,
+        unassigned_words,
+        tolerance: IMBALANCE_TOLERANCE,
+        balanced,
+    })
+}
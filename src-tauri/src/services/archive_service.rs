@@ -0,0 +1,150 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::models::{Chapter, Character, Lore, Project, TimelineEvent};
+use super::{ChapterService, ProjectService};
+
+const BUNDLE_ENTRY: &str = "project.json";
+const BUNDLE_VERSION: u32 = 1;
+
+/// 归档包内容：项目及其章节、角色、设定、时间线的完整副本
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveBundle {
+    version: u32,
+    archived_at: String,
+    project: Project,
+    chapters: Vec<Chapter>,
+    characters: Vec<Character>,
+    lore: Vec<Lore>,
+    timeline_events: Vec<TimelineEvent>,
+}
+
+pub struct ArchiveService;
+
+impl ArchiveService {
+    /// 将项目打包为压缩归档并标记为已归档；purge_text 为 true 时清空数据库中的章节正文（字数保留）
+    pub async fn archive(
+        pool: &SqlitePool,
+        archive_dir: &Path,
+        project_id: &str,
+        purge_text: bool,
+    ) -> Result<Project> {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        if project.archived_at.is_some() {
+            return Err(anyhow::anyhow!("Project is already archived"));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let bundle = ArchiveBundle {
+            version: BUNDLE_VERSION,
+            archived_at: now.clone(),
+            chapters: ChapterService::get_by_project(pool, project_id).await?,
+            characters: sqlx::query_as::<_, Character>("SELECT * FROM characters WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_all(pool)
+                .await?,
+            lore: sqlx::query_as::<_, Lore>("SELECT * FROM lore WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_all(pool)
+                .await?,
+            timeline_events: sqlx::query_as::<_, TimelineEvent>("SELECT * FROM timeline_events WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_all(pool)
+                .await?,
+            project,
+        };
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(BUNDLE_ENTRY, options)?;
+        zip.write_all(serde_json::to_string(&bundle)?.as_bytes())?;
+        let bytes = zip.finish()?.into_inner();
+
+        tokio::fs::create_dir_all(archive_dir).await?;
+        let archive_path = archive_dir.join(format!("{}.zip", project_id));
+        tokio::fs::write(&archive_path, bytes).await?;
+        let archive_path = archive_path.to_string_lossy().to_string();
+
+        // 归档文件写入成功后再修改数据库
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE projects SET archived_at = ?, archive_path = ? WHERE id = ?")
+            .bind(&now)
+            .bind(&archive_path)
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await?;
+        if purge_text {
+            sqlx::query("UPDATE chapters SET draft_text = NULL, final_text = NULL WHERE project_id = ?")
+                .bind(project_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        log::info!("Archived project {} to {}", project_id, archive_path);
+        ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found after archive"))
+    }
+
+    /// 从归档包恢复被清空的章节正文，并取消归档标记
+    pub async fn unarchive(pool: &SqlitePool, project_id: &str) -> Result<Project> {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        if project.archived_at.is_none() {
+            return Err(anyhow::anyhow!("Project is not archived"));
+        }
+
+        let mut tx = pool.begin().await?;
+        if let Some(ref archive_path) = project.archive_path {
+            let bytes = tokio::fs::read(archive_path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to read archive {}: {}", archive_path, e))?;
+            let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+            let mut json = String::new();
+            archive.by_name(BUNDLE_ENTRY)?.read_to_string(&mut json)?;
+            let bundle: ArchiveBundle = serde_json::from_str(&json)?;
+
+            // 只回填仍为空的正文，归档后手动写入的内容不会被覆盖
+            for chapter in &bundle.chapters {
+                sqlx::query(
+                    r#"
+                    UPDATE chapters SET draft_text = ?, final_text = ?
+                    WHERE id = ? AND project_id = ? AND draft_text IS NULL AND final_text IS NULL
+                    "#
+                )
+                .bind(&chapter.draft_text)
+                .bind(&chapter.final_text)
+                .bind(&chapter.id)
+                .bind(project_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        sqlx::query("UPDATE projects SET archived_at = NULL, archive_path = NULL, updated_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(project_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        if let Some(ref archive_path) = project.archive_path {
+            if let Err(e) = tokio::fs::remove_file(archive_path).await {
+                log::warn!("Failed to remove archive {}: {}", archive_path, e);
+            }
+        }
+
+        ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found after unarchive"))
+    }
+}
@@ -0,0 +1,110 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{Asset, CreateAssetInput};
+
+pub struct AssetService;
+
+impl AssetService {
+    pub async fn create(pool: &SqlitePool, input: CreateAssetInput) -> Result<Asset> {
+        let asset = Asset {
+            id: Uuid::new_v4().to_string(),
+            project_id: input.project_id,
+            asset_type: input.asset_type,
+            file_path: input.file_path,
+            linked_to_type: input.linked_to_type,
+            linked_to_id: input.linked_to_id,
+            metadata: input.metadata,
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO assets (id, project_id, asset_type, file_path, linked_to_type, linked_to_id, metadata, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&asset.id)
+        .bind(&asset.project_id)
+        .bind(&asset.asset_type)
+        .bind(&asset.file_path)
+        .bind(&asset.linked_to_type)
+        .bind(&asset.linked_to_id)
+        .bind(&asset.metadata)
+        .bind(&asset.created_at)
+        .execute(pool)
+        .await?;
+
+        Ok(asset)
+    }
+
+    /// 项目中关联到某类对象（如 character / chapter）的资源，按创建时间倒序
+    pub async fn get_linked(pool: &SqlitePool, project_id: &str, linked_to_type: &str) -> Result<Vec<Asset>> {
+        let assets = sqlx::query_as::<_, Asset>(
+            "SELECT * FROM assets WHERE project_id = ? AND linked_to_type = ? ORDER BY created_at DESC"
+        )
+        .bind(project_id)
+        .bind(linked_to_type)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(assets)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<Asset>> {
+        let asset = sqlx::query_as::<_, Asset>("SELECT * FROM assets WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(asset)
+    }
+
+    /// 项目的全部资源，可按类型过滤，最新的在前
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str, asset_type: Option<&str>) -> Result<Vec<Asset>> {
+        let assets = sqlx::query_as::<_, Asset>(
+            "SELECT * FROM assets WHERE project_id = ? AND (? IS NULL OR asset_type = ?) ORDER BY created_at DESC"
+        )
+        .bind(project_id)
+        .bind(asset_type)
+        .bind(asset_type)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(assets)
+    }
+
+    /// 关联到某个章节或角色的资源，最新的在前
+    pub async fn get_by_link(pool: &SqlitePool, linked_to_type: &str, linked_to_id: &str) -> Result<Vec<Asset>> {
+        let assets = sqlx::query_as::<_, Asset>(
+            "SELECT * FROM assets WHERE linked_to_type = ? AND linked_to_id = ? ORDER BY created_at DESC"
+        )
+        .bind(linked_to_type)
+        .bind(linked_to_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(assets)
+    }
+
+    /// 删除资源记录及其文件；文件已不存在时只删除记录
+    pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
+        let asset = Self::get_by_id(pool, id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Asset not found"))?;
+
+        if let Err(e) = tokio::fs::remove_file(&asset.file_path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(anyhow::anyhow!("Failed to remove {}: {}", asset.file_path, e));
+            }
+        }
+
+        sqlx::query("DELETE FROM assets WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! 可取消任务的统一登记
+//!
+//! 流式生成、图片请求和导出各自登记一个 id 和取消标志，cancel 只影响对应的任务。
+//! 流式生成在 scope 中执行，期间 is_cancelled 读取当前生成的标志，不在 scope 内时始终为 false。
+//! 写入章节草稿的生成同时按章节登记，供 abort_and_restore 找到要取消的生成。
+
+use dashmap::DashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+lazy_static::lazy_static! {
+    static ref TASKS: DashMap<(TaskKind, String), Arc<AtomicBool>> = DashMap::new();
+    // chapter_id -> generation_id
+    static ref CHAPTER_GENERATIONS: DashMap<String, String> = DashMap::new();
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<AtomicBool>;
+    static CURRENT_ID: String;
+}
+
+/// 可取消任务的类别；不同类别的 id 互不影响
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskKind {
+    Generation,
+    Image,
+    Export,
+}
+
+/// 一次登记中的任务；drop 时注销
+pub struct CancelToken {
+    kind: TaskKind,
+    id: String,
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// 同类别下 id 重复时，后登记的任务取代之前的登记
+    pub fn register(kind: TaskKind, id: String) -> Self {
+        let flag = Arc::new(AtomicBool::new(false));
+        TASKS.insert((kind, id.clone()), flag.clone());
+        Self { kind, id, flag }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 传给按 &AtomicBool 检查取消的服务
+    pub fn flag(&self) -> &AtomicBool {
+        &self.flag
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for CancelToken {
+    fn drop(&mut self) {
+        // 同一 id 已被更新的任务登记时保留它
+        TASKS.remove_if(&(self.kind, self.id.clone()), |_, flag| Arc::ptr_eq(flag, &self.flag));
+    }
+}
+
+/// 取消指定的任务；id 不存在（未登记或已结束）时返回 false
+pub fn cancel(kind: TaskKind, id: &str) -> bool {
+    match TASKS.get(&(kind, id.to_string())) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 取消该类别所有进行中的任务，返回取消的数量
+pub fn cancel_all(kind: TaskKind) -> usize {
+    let mut cancelled = 0;
+    for entry in TASKS.iter().filter(|entry| entry.key().0 == kind) {
+        entry.value().store(true, Ordering::SeqCst);
+        cancelled += 1;
+    }
+    cancelled
+}
+
+/// 一次登记中的流式生成；drop 时注销
+pub struct Generation {
+    token: CancelToken,
+    chapter_id: Option<String>,
+}
+
+impl Generation {
+    pub fn register(chapter_id: Option<&str>) -> Self {
+        let token = CancelToken::register(TaskKind::Generation, uuid::Uuid::new_v4().to_string());
+        if let Some(chapter_id) = chapter_id {
+            CHAPTER_GENERATIONS.insert(chapter_id.to_string(), token.id.clone());
+        }
+        Self {
+            token,
+            chapter_id: chapter_id.map(str::to_string),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        self.token.id()
+    }
+
+    /// 在本次生成的上下文中执行 future
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT
+            .scope(self.token.flag.clone(), CURRENT_ID.scope(self.token.id.clone(), future))
+            .await
+    }
+}
+
+impl Drop for Generation {
+    fn drop(&mut self) {
+        if let Some(chapter_id) = &self.chapter_id {
+            // 同一章节已有更新的生成登记时保留它
+            CHAPTER_GENERATIONS.remove_if(chapter_id, |_, id| *id == self.token.id);
+        }
+    }
+}
+
+/// 当前生成是否已被取消
+pub fn is_cancelled() -> bool {
+    CURRENT.try_with(|flag| flag.load(Ordering::SeqCst)).unwrap_or(false)
+}
+
+/// 当前生成的 generation_id；不在 scope 内时为 None
+pub fn current_id() -> Option<String> {
+    CURRENT_ID.try_with(|id| id.clone()).ok()
+}
+
+/// 取消正在写入该章节的生成
+pub fn cancel_chapter(chapter_id: &str) -> bool {
+    let generation_id = CHAPTER_GENERATIONS.get(chapter_id).map(|entry| entry.value().clone());
+    generation_id.is_some_and(|id| cancel(TaskKind::Generation, &id))
+}
@@ -0,0 +1,133 @@
+//! 从数据库组装章节生成时注入的设定上下文
+//!
+//! 前端生成章节时自行拼接角色/世界观/时间线文本；后端需要同样的上下文时
+//! （如预估上下文占用）从这里按相同结构生成。角色和设定按与本章的相关度筛选（见 retrieval）。
+
+use sqlx::SqlitePool;
+use anyhow::Result;
+use crate::models::{Chapter, Character, Lore, TextModelConfigInput, TimelineEvent};
+use super::{ChapterService, CharacterService, LoreService, SettingsService, TimelineService};
+use super::context_budget::select_tail_context;
+use super::retrieval::{chapter_query, select_relevant};
+
+/// 衔接上一章时截取结尾的 token 预算
+pub const PREVIOUS_TAIL_TOKENS: usize = 1500;
+
+#[derive(Debug, Clone, Default)]
+pub struct ChapterContext {
+    pub characters_info: Option<String>,
+    pub world_setting: Option<String>,
+    pub timeline: Option<String>,
+    pub previous_tail: Option<String>,
+}
+
+/// config 用于向量检索（沿用文本模型的地址和 Key），为空时按关键词筛选
+pub async fn load_chapter_context(
+    pool: &SqlitePool,
+    chapter: &Chapter,
+    config: Option<&TextModelConfigInput>,
+) -> Result<ChapterContext> {
+    let settings = SettingsService::get(pool).await?;
+    let characters = CharacterService::get_by_project(pool, &chapter.project_id).await?;
+    let lore = LoreService::get_by_project(pool, &chapter.project_id).await?;
+    let (characters, lore) = select_relevant(
+        pool,
+        config,
+        &settings.retrieval,
+        &chapter.project_id,
+        &chapter_query(chapter),
+        characters,
+        lore,
+    )
+    .await;
+    let events = TimelineService::get_by_project(pool, &chapter.project_id).await?;
+    let chapters = ChapterService::get_by_project(pool, &chapter.project_id).await?;
+
+    let previous_tail = chapters
+        .iter()
+        .filter(|c| c.order_index < chapter.order_index)
+        .max_by_key(|c| c.order_index)
+        .and_then(chapter_text)
+        .map(|text| select_tail_context(text, PREVIOUS_TAIL_TOKENS).to_string());
+
+    Ok(ChapterContext {
+        characters_info: non_empty(format_characters(&characters)),
+        world_setting: non_empty(format_lore(&lore)),
+        timeline: non_empty(format_timeline(&events)),
+        previous_tail,
+    })
+}
+
+/// 章节正文：定稿优先，其次草稿，全为空白时为 None；导出和上下文注入共用
+pub fn chapter_text(chapter: &Chapter) -> Option<&str> {
+    chapter
+        .final_text
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .or(chapter.draft_text.as_deref())
+        .filter(|t| !t.trim().is_empty())
+}
+
+/// 字段为空或只有空白
+pub fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().is_none_or(|v| v.trim().is_empty())
+}
+
+pub fn format_characters(characters: &[Character]) -> String {
+    characters
+        .iter()
+        .map(|c| {
+            let mut line = c.name.clone();
+            if let Some(role) = c.role.as_deref().filter(|v| !v.trim().is_empty()) {
+                line.push_str(&format!("（{}）", role.trim()));
+            }
+            let fields = [
+                ("简介", &c.description),
+                ("性格", &c.personality),
+                ("背景", &c.background),
+                ("动机", &c.motivation),
+                ("说话风格", &c.voice_style),
+            ];
+            for (label, value) in fields {
+                if let Some(value) = value.as_deref().filter(|v| !v.trim().is_empty()) {
+                    line.push_str(&format!("\n  {}：{}", label, value.trim()));
+                }
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn format_lore(entries: &[Lore]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let content = entry.content.as_deref().unwrap_or("").trim();
+            format!("【{}】{}：{}", entry.category, entry.title, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn format_timeline(events: &[TimelineEvent]) -> String {
+    events
+        .iter()
+        .map(|event| {
+            let mut line = String::new();
+            if let Some(time) = event.event_time.as_deref().filter(|v| !v.trim().is_empty()) {
+                line.push_str(&format!("[{}] ", time.trim()));
+            }
+            line.push_str(&event.title);
+            if let Some(desc) = event.description.as_deref().filter(|v| !v.trim().is_empty()) {
+                line.push_str(&format!("：{}", desc.trim()));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn non_empty(text: String) -> Option<String> {
+    (!text.trim().is_empty()).then_some(text)
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+// 等待章节写锁的最长时间，超时返回 ChapterBusy
+const CHAPTER_LOCK_TIMEOUT: Duration = Duration::from_secs(3);
+// 锁表超过该数量时清理无人持有的条目
+const PRUNE_THRESHOLD: usize = 256;
+
+lazy_static::lazy_static! {
+    static ref CHAPTER_LOCKS: StdMutex<HashMap<String, Arc<Mutex<()>>>> = StdMutex::new(HashMap::new());
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Chapter {0} is busy, please retry later")]
+pub struct ChapterBusy(pub String);
+
+/// 获取单个章节的写锁：同一章节的写入串行执行，不同章节互不影响
+pub async fn lock_chapter(chapter_id: &str) -> Result<OwnedMutexGuard<()>, ChapterBusy> {
+    let lock = {
+        let mut locks = CHAPTER_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        if locks.len() > PRUNE_THRESHOLD {
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        }
+        locks
+            .entry(chapter_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone()
+    };
+
+    tokio::time::timeout(CHAPTER_LOCK_TIMEOUT, lock.lock_owned())
+        .await
+        .map_err(|_| ChapterBusy(chapter_id.to_string()))
+}
@@ -0,0 +1,332 @@
+//! 章节号识别
+//!
+//! 大纲中的章节标题可能写成“第12章”“第十二章”“第１２章”或“Chapter 12”。
+//! 只有位于行首的标题才算章节，正文里提到的“第三章的伏笔”不计入。
+//! 重新编号时按编号方案生成“第十二章 标题”这类显示标题，保留原标题中的自定义部分。
+
+use anyhow::Result;
+use regex::Regex;
+use std::collections::BTreeSet;
+
+lazy_static::lazy_static! {
+    // ### 第十二章血色黎明 / **第12章** / Chapter 12: Title。带 # 或 ** 标记的行章号后可直接接标题；
+    // 无标记的行章号后须是空白、分隔符或行尾，“第三章的伏笔”这类句子不算
+    static ref CHAPTER_HEADING: Regex = Regex::new(
+        r"(?mi)^[ \t]*(?:(?:#{1,4}[ \t]*(?:\*\*)?|\*\*)[ \t]*(?:第[ \t]*([0-9０-９零〇一二两三四五六七八九十百千]+)[ \t]*章|chapter[ \t]*(\d+))|(?:第[ \t]*([0-9０-９零〇一二两三四五六七八九十百千]+)[ \t]*章|chapter[ \t]+(\d+))(?:[ \t]|[:：、.．\-—*]|$))"
+    ).unwrap();
+    // 不限位置的章节引用
+    static ref CHAPTER_REF: Regex = Regex::new(
+        r"第\s*([0-9０-９零〇一二两三四五六七八九十百千]+)\s*章|(?i:chapter)\s*(\d+)"
+    ).unwrap();
+    // 标题开头的常见章节号：第十二章 / 第12回 / Chapter 12
+    static ref TITLE_NUMBER_PREFIX: Regex = Regex::new(
+        r"^\s*(?:第\s*[0-9０-９零〇一二两三四五六七八九十百千]+\s*[章回节]|(?i:chapter)\s*\d+)"
+    ).unwrap();
+}
+
+/// 编号与标题之间可能出现的分隔符
+const TITLE_SEPARATORS: &[char] = &[':', '：', '、', '.', '．', '-', '—', '·', '|'];
+const NUMERAL_PATTERN: &str = "[0-9０-９零〇一二两三四五六七八九十百千]+";
+
+/// 解析阿拉伯数字（含全角）或中文数字，如“十二”“一百零五”“二〇三”
+pub fn parse_chapter_numeral(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let ascii: String = text
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+            _ => c,
+        })
+        .collect();
+    if ascii.chars().all(|c| c.is_ascii_digit()) {
+        return ascii.parse().ok().filter(|n| *n > 0);
+    }
+
+    parse_chinese_numeral(text).filter(|n| *n > 0)
+}
+
+fn chinese_digit(c: char) -> Option<u32> {
+    match c {
+        '零' | '〇' => Some(0),
+        '一' => Some(1),
+        '二' | '两' => Some(2),
+        '三' => Some(3),
+        '四' => Some(4),
+        '五' => Some(5),
+        '六' => Some(6),
+        '七' => Some(7),
+        '八' => Some(8),
+        '九' => Some(9),
+        _ => None,
+    }
+}
+
+fn parse_chinese_numeral(text: &str) -> Option<u32> {
+    let has_unit = text.chars().any(|c| matches!(c, '十' | '百' | '千'));
+
+    // 不带单位的逐位写法：二〇三 → 203
+    if !has_unit {
+        return text
+            .chars()
+            .try_fold(0u32, |acc, c| acc.checked_mul(10)?.checked_add(chinese_digit(c)?));
+    }
+
+    let mut total = 0u32;
+    // 尚未乘单位的数字；“零”只作占位，后面只能再接数字
+    let mut pending: Option<u32> = None;
+    let mut last_unit = u32::MAX;
+    for c in text.chars() {
+        if let Some(digit) = chinese_digit(c) {
+            // 非零数字后不能直接再接数字（“一二十”）
+            if pending.is_some_and(|d| d != 0) {
+                return None;
+            }
+            pending = Some(digit);
+            continue;
+        }
+        let unit = match c {
+            '十' => 10,
+            '百' => 100,
+            '千' => 1000,
+            _ => return None,
+        };
+        // 单位必须从大到小出现（“十百”“十十”之类不是合法数字），且不能跟在“零”后
+        if unit >= last_unit || pending == Some(0) {
+            return None;
+        }
+        last_unit = unit;
+        // “十二”省略了前面的“一”
+        total += pending.take().unwrap_or(1) * unit;
+    }
+
+    match pending {
+        Some(0) => None,
+        digit => Some(total + digit.unwrap_or(0)),
+    }
+}
+
+/// 以章节号开头的标题行中出现的全部章节号
+pub fn chapter_heading_numbers(content: &str) -> BTreeSet<u32> {
+    CHAPTER_HEADING
+        .captures_iter(content)
+        .filter_map(|caps| (1..=4).find_map(|group| caps.get(group)))
+        .filter_map(|m| parse_chapter_numeral(m.as_str()))
+        .collect()
+}
+
+/// 文本中提到的第一个章节号（不要求位于行首，用于“【第三章】”这类时间标注）
+pub fn first_chapter_reference(text: &str) -> Option<u32> {
+    CHAPTER_REF
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .find_map(|m| parse_chapter_numeral(m.as_str()))
+}
+
+/// 逐位以外的中文数字写法：12 → 十二，105 → 一百零五；一万及以上退回阿拉伯数字
+pub fn format_chinese_numeral(n: u32) -> String {
+    const DIGITS: [char; 10] = ['零', '一', '二', '三', '四', '五', '六', '七', '八', '九'];
+    if n == 0 {
+        return "零".to_string();
+    }
+    if n >= 10_000 {
+        return n.to_string();
+    }
+
+    let mut out = String::new();
+    let mut rest = n;
+    let mut zero_pending = false;
+    for (unit, unit_char) in [(1000, '千'), (100, '百'), (10, '十')] {
+        let digit = rest / unit;
+        rest %= unit;
+        if digit == 0 {
+            zero_pending = !out.is_empty();
+            continue;
+        }
+        if zero_pending {
+            out.push('零');
+            zero_pending = false;
+        }
+        // “十二”而不是“一十二”
+        if !(unit == 10 && digit == 1 && out.is_empty()) {
+            out.push(DIGITS[digit as usize]);
+        }
+        out.push(unit_char);
+    }
+    if rest > 0 {
+        if zero_pending {
+            out.push('零');
+        }
+        out.push(DIGITS[rest as usize]);
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumeralStyle {
+    Arabic,
+    Chinese,
+}
+
+impl NumeralStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "arabic" => Some(Self::Arabic),
+            "chinese" => Some(Self::Chinese),
+            _ => None,
+        }
+    }
+
+    pub fn format(self, n: u32) -> String {
+        match self {
+            Self::Arabic => n.to_string(),
+            Self::Chinese => format_chinese_numeral(n),
+        }
+    }
+}
+
+/// 章节编号格式，如“第{n}章”“Chapter {n}”，{n} 为章节号
+#[derive(Debug, Clone)]
+pub struct ChapterNumbering {
+    format: String,
+    numeral: NumeralStyle,
+    separator: String,
+    /// 匹配按本格式生成过的编号（任意数字写法），重复编号时能识别自定义前缀
+    own_prefix: Regex,
+}
+
+impl ChapterNumbering {
+    pub fn new(format: &str, numeral: NumeralStyle, separator: &str) -> Result<Self> {
+        let format = format.trim();
+        if !format.contains("{n}") {
+            return Err(anyhow::anyhow!("Numbering format must contain {{n}}"));
+        }
+        let pattern = format
+            .split("{n}")
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(&format!(r"\s*{}\s*", NUMERAL_PATTERN));
+        let own_prefix = Regex::new(&format!(r"(?i)^\s*{}", pattern))?;
+
+        Ok(Self {
+            format: format.to_string(),
+            numeral,
+            separator: separator.to_string(),
+            own_prefix,
+        })
+    }
+
+    /// 去掉标题开头已有的章节编号（本格式或常见格式）及其后的分隔符，返回自定义部分
+    pub fn custom_title<'a>(&self, title: &'a str) -> &'a str {
+        let rest = match self.own_prefix.find(title).or_else(|| TITLE_NUMBER_PREFIX.find(title)) {
+            Some(prefix) => &title[prefix.end()..],
+            None => title,
+        };
+        let separator = self.separator.trim();
+        rest.trim_start_matches(|c: char| {
+            c.is_whitespace() || TITLE_SEPARATORS.contains(&c) || separator.contains(c)
+        })
+        .trim_end()
+    }
+
+    /// 按章节号生成显示标题；自定义部分为空时只有编号
+    pub fn title(&self, number: u32, custom: &str) -> String {
+        let prefix = self.format.replace("{n}", &self.numeral.format(number));
+        if custom.is_empty() {
+            prefix
+        } else {
+            format!("{}{}{}", prefix, self.separator, custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(content: &str) -> Vec<u32> {
+        chapter_heading_numbers(content).into_iter().collect()
+    }
+
+    #[test]
+    fn parses_chinese_and_full_width_numerals() {
+        assert_eq!(parse_chapter_numeral("十"), Some(10));
+        assert_eq!(parse_chapter_numeral("十二"), Some(12));
+        assert_eq!(parse_chapter_numeral("二十"), Some(20));
+        assert_eq!(parse_chapter_numeral("一百零五"), Some(105));
+        assert_eq!(parse_chapter_numeral("一千零二十"), Some(1020));
+        assert_eq!(parse_chapter_numeral("两百"), Some(200));
+        assert_eq!(parse_chapter_numeral("二〇三"), Some(203));
+        assert_eq!(parse_chapter_numeral("１２"), Some(12));
+        assert_eq!(parse_chapter_numeral("0"), None);
+        assert_eq!(parse_chapter_numeral("零"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_chinese_numerals() {
+        assert_eq!(parse_chapter_numeral("一二十"), None);
+        assert_eq!(parse_chapter_numeral("十十"), None);
+        assert_eq!(parse_chapter_numeral("十百"), None);
+        assert_eq!(parse_chapter_numeral("一百零十"), None);
+        assert_eq!(parse_chapter_numeral("一百零"), None);
+        assert_eq!(parse_chapter_numeral("第十"), None);
+    }
+
+    #[test]
+    fn chinese_numerals_round_trip() {
+        for n in [1, 10, 12, 20, 105, 110, 203, 1000, 1020, 9999] {
+            assert_eq!(parse_chapter_numeral(&format_chinese_numeral(n)), Some(n), "{}", n);
+        }
+    }
+
+    #[test]
+    fn prose_mentions_are_not_headings() {
+        let outline = "第三章的伏笔在这里回收。\n主角想起第5章发生的事。\n- 第7章：反派登场\n1. 【第8章】决战";
+        assert!(numbers(outline).is_empty());
+    }
+
+    #[test]
+    fn recognizes_heading_variants() {
+        let outline = "## 第十章\n### 第一百零五章：归来\n**第二〇三章** 尾声\n第１２章 重逢\nChapter 12: The Return\n### chapter 7\n";
+        assert_eq!(numbers(outline), vec![7, 10, 12, 105, 203]);
+    }
+
+    #[test]
+    fn marked_heading_number_may_be_followed_directly_by_text() {
+        assert_eq!(numbers("### 第3章血色黎明"), vec![3]);
+        assert_eq!(numbers("**第十一章风起云涌**"), vec![11]);
+        assert_eq!(numbers("## Chapter4"), vec![4]);
+        // 无标记的行仍要求章号后有分隔符
+        assert!(numbers("第四章风起云涌").is_empty());
+    }
+
+    #[test]
+    fn gapped_outline_reports_last_and_missing_chapters() {
+        let outline = "\
+## 章节大纲
+### 第1章：启程
+- **时间**：第一天
+### 第2章：相遇
+回忆第三章的伏笔时要呼应开头。
+### 第4章：追杀
+### 第六章 决战
+## 时间线事件
+1. 【第5章】城破
+";
+        let found = chapter_heading_numbers(outline);
+        assert_eq!(found.iter().copied().collect::<Vec<_>>(), vec![1, 2, 4, 6]);
+        assert_eq!(found.last(), Some(&6));
+        let missing: Vec<u32> = (1..=6).filter(|n| !found.contains(n)).collect();
+        assert_eq!(missing, vec![3, 5]);
+    }
+
+    #[test]
+    fn first_reference_finds_chapter_anywhere() {
+        assert_eq!(first_chapter_reference("【第三章】 城破"), Some(3));
+        assert_eq!(first_chapter_reference("see chapter 9"), Some(9));
+        assert_eq!(first_chapter_reference("序幕"), None);
+    }
+}
@@ -2,10 +2,41 @@ use sqlx::SqlitePool;
 use chrono::Utc;
 use uuid::Uuid;
 use anyhow::Result;
-use crate::models::{Chapter, CreateChapterInput, UpdateChapterMetaInput};
+use std::collections::HashMap;
+use crate::models::{
+    Chapter, ChapterOutlineItem, CreateChapterInput, DuplicateChapterPair, Milestone,
+    UpdateChapterMetaInput,
+};
+use super::chapter_lock::lock_chapter;
+use super::similarity::{jaccard, jaccard_upper_bound, shingles};
+use super::{MilestoneService, WordCountHistoryService};
 
 pub struct ChapterService;
 
+/// 章节可用的状态
+pub const CHAPTER_STATUSES: [&str; 3] = ["draft", "review", "final"];
+
+/// 三幕结构的幕数（chapters.act 取 1..=ACT_COUNT）
+pub const ACT_COUNT: i64 = 3;
+
+const MAX_TAG_CHARS: usize = 32;
+
+/// 章节覆盖设置中目标字数的允许范围
+const MIN_OVERRIDE_TARGET_WORDS: i64 = 100;
+const MAX_OVERRIDE_TARGET_WORDS: i64 = 20000;
+
+/// 规范化标签：去除首尾空白，内部空白替换为 -
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.split_whitespace().collect::<Vec<_>>().join("-");
+    if tag.is_empty() {
+        return Err(anyhow::anyhow!("Tag cannot be empty"));
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        return Err(anyhow::anyhow!("Tag cannot exceed {} characters", MAX_TAG_CHARS));
+    }
+    Ok(tag)
+}
+
 impl ChapterService {
     pub async fn create(pool: &SqlitePool, input: CreateChapterInput) -> Result<Chapter> {
         let now = Utc::now().to_rfc3339();
@@ -23,8 +54,14 @@ impl ChapterService {
             illustrations: None,
             word_count: 0,
             status: "draft".to_string(),
+            summary: None,
             created_at: now.clone(),
             updated_at: now,
+            override_model: None,
+            override_temperature: None,
+            override_target_words: None,
+            ambiance: None,
+            act: None,
         };
 
         sqlx::query(
@@ -76,13 +113,161 @@ impl ChapterService {
         Ok(chapter)
     }
 
+    /// 精简章节列表，附带每章的标签
+    pub async fn get_outlines(pool: &SqlitePool, project_id: &str) -> Result<Vec<ChapterOutlineItem>> {
+        let rows: Vec<(String, String, i32, String, i64)> = sqlx::query_as(
+            "SELECT id, title, order_index, status, word_count FROM chapters WHERE project_id = ? ORDER BY order_index ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        let tag_rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT t.chapter_id, t.tag FROM chapter_tags t
+            JOIN chapters c ON c.id = t.chapter_id
+            WHERE c.project_id = ?
+            ORDER BY t.tag ASC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for (chapter_id, tag) in tag_rows {
+            tags.entry(chapter_id).or_default().push(tag);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, title, order_index, status, word_count)| ChapterOutlineItem {
+                tags: tags.remove(&id).unwrap_or_default(),
+                id,
+                title,
+                order_index,
+                status,
+                word_count,
+            })
+            .collect())
+    }
+
+    pub async fn get_tags(pool: &SqlitePool, chapter_id: &str) -> Result<Vec<String>> {
+        let tags = sqlx::query_scalar::<_, String>(
+            "SELECT tag FROM chapter_tags WHERE chapter_id = ? ORDER BY tag ASC"
+        )
+        .bind(chapter_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tags)
+    }
+
+    /// 添加标签（不区分大小写，已存在时忽略），返回该章全部标签
+    pub async fn add_tag(pool: &SqlitePool, chapter_id: &str, tag: &str) -> Result<Vec<String>> {
+        let tag = normalize_tag(tag)?;
+        if Self::get_by_id(pool, chapter_id).await?.is_none() {
+            return Err(anyhow::anyhow!("Chapter not found"));
+        }
+
+        sqlx::query("INSERT OR IGNORE INTO chapter_tags (chapter_id, tag, created_at) VALUES (?, ?, ?)")
+            .bind(chapter_id)
+            .bind(&tag)
+            .bind(Utc::now().to_rfc3339())
+            .execute(pool)
+            .await?;
+
+        Self::get_tags(pool, chapter_id).await
+    }
+
+    pub async fn remove_tag(pool: &SqlitePool, chapter_id: &str, tag: &str) -> Result<Vec<String>> {
+        let tag = normalize_tag(tag)?;
+        sqlx::query("DELETE FROM chapter_tags WHERE chapter_id = ? AND tag = ?")
+            .bind(chapter_id)
+            .bind(&tag)
+            .execute(pool)
+            .await?;
+
+        Self::get_tags(pool, chapter_id).await
+    }
+
+    pub async fn get_by_tag(pool: &SqlitePool, project_id: &str, tag: &str) -> Result<Vec<Chapter>> {
+        let tag = normalize_tag(tag)?;
+        let chapters = sqlx::query_as::<_, Chapter>(
+            r#"
+            SELECT c.* FROM chapters c
+            JOIN chapter_tags t ON t.chapter_id = c.id
+            WHERE c.project_id = ? AND t.tag = ?
+            ORDER BY c.order_index ASC
+            "#
+        )
+        .bind(project_id)
+        .bind(&tag)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(chapters)
+    }
+
+    /// 两两比较章节正文（优先定稿），返回相似度不低于阈值的章节对，按相似度降序
+    pub async fn find_duplicates(
+        pool: &SqlitePool,
+        project_id: &str,
+        threshold: f64,
+    ) -> Result<Vec<DuplicateChapterPair>> {
+        let chapters = Self::get_by_project(pool, project_id).await?;
+
+        let pairs = tokio::task::spawn_blocking(move || {
+            let prepared: Vec<_> = chapters
+                .iter()
+                .filter_map(|chapter| {
+                    let text = chapter
+                        .final_text
+                        .as_deref()
+                        .filter(|t| !t.trim().is_empty())
+                        .or(chapter.draft_text.as_deref())?;
+                    let set = shingles(text);
+                    (!set.is_empty()).then_some((chapter, set))
+                })
+                .collect();
+
+            let mut pairs = Vec::new();
+            for (i, (a, set_a)) in prepared.iter().enumerate() {
+                for (b, set_b) in prepared.iter().skip(i + 1) {
+                    if jaccard_upper_bound(set_a, set_b) < threshold {
+                        continue;
+                    }
+                    let similarity = jaccard(set_a, set_b);
+                    if similarity >= threshold {
+                        pairs.push(DuplicateChapterPair {
+                            chapter_a_id: a.id.clone(),
+                            chapter_a_title: a.title.clone(),
+                            chapter_a_order: a.order_index,
+                            chapter_b_id: b.id.clone(),
+                            chapter_b_title: b.title.clone(),
+                            chapter_b_order: b.order_index,
+                            similarity: (similarity * 1000.0).round() / 1000.0,
+                        });
+                    }
+                }
+            }
+            pairs.sort_by(|x, y| y.similarity.total_cmp(&x.similarity));
+            pairs
+        })
+        .await?;
+
+        Ok(pairs)
+    }
+
+    /// 保存正文并重算项目总字数；返回新达成的字数里程碑
     pub async fn update_text(
         pool: &SqlitePool,
         id: &str,
         draft_text: Option<String>,
         final_text: Option<String>,
         illustrations: Option<String>,
-    ) -> Result<()> {
+    ) -> Result<Vec<Milestone>> {
+        // 自动保存、生成结果保存等写入同一章节时串行执行，避免互相覆盖
+        let _guard = lock_chapter(id).await?;
         let now = Utc::now().to_rfc3339();
         
         // Calculate word count from final_text or draft_text
@@ -114,14 +299,129 @@ impl ChapterService {
         .bind(id)
         .fetch_optional(pool)
         .await? {
-            Self::update_project_word_count(pool, &chapter.project_id).await?;
+            return Self::update_project_word_count(pool, &chapter.project_id).await;
         }
 
+        Ok(Vec::new())
+    }
+
+    /// 流式生成期间的阶段性保存：只写草稿，不重算字数（生成结束时由 update_text 统一计算）
+    pub async fn save_draft_checkpoint(pool: &SqlitePool, id: &str, draft_text: &str) -> Result<()> {
+        let _guard = lock_chapter(id).await?;
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query("UPDATE chapters SET draft_text = ?, updated_at = ? WHERE id = ?")
+            .bind(draft_text)
+            .bind(&now)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
         Ok(())
     }
 
-    /// 重新计算并更新项目的总字数
-    pub async fn update_project_word_count(pool: &SqlitePool, project_id: &str) -> Result<()> {
+    /// 按快照恢复整章（撤销删除时使用，章节已存在时覆盖）；返回新达成的字数里程碑
+    pub async fn restore(pool: &SqlitePool, chapter: &Chapter) -> Result<Vec<Milestone>> {
+        // 与生成、自动保存写入同一章节时串行执行，撤销不会覆盖进行中的写入
+        let _guard = lock_chapter(&chapter.id).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO chapters (id, project_id, title, order_index, outline_goal, conflict, twist, cliffhanger, draft_text, final_text, illustrations, word_count, status, summary, act, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title,
+                order_index = excluded.order_index,
+                outline_goal = excluded.outline_goal,
+                conflict = excluded.conflict,
+                twist = excluded.twist,
+                cliffhanger = excluded.cliffhanger,
+                draft_text = excluded.draft_text,
+                final_text = excluded.final_text,
+                illustrations = excluded.illustrations,
+                word_count = excluded.word_count,
+                status = excluded.status,
+                summary = excluded.summary,
+                act = excluded.act,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(&chapter.id)
+        .bind(&chapter.project_id)
+        .bind(&chapter.title)
+        .bind(chapter.order_index)
+        .bind(&chapter.outline_goal)
+        .bind(&chapter.conflict)
+        .bind(&chapter.twist)
+        .bind(&chapter.cliffhanger)
+        .bind(&chapter.draft_text)
+        .bind(&chapter.final_text)
+        .bind(&chapter.illustrations)
+        .bind(chapter.word_count)
+        .bind(&chapter.status)
+        .bind(&chapter.summary)
+        .bind(chapter.act)
+        .bind(&chapter.created_at)
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+
+        Self::update_project_word_count(pool, &chapter.project_id).await
+    }
+
+    /// 恢复章节的标题、顺序和大纲字段（撤销 update_meta 时使用）
+    pub async fn restore_meta(pool: &SqlitePool, chapter: &Chapter) -> Result<()> {
+        let _guard = lock_chapter(&chapter.id).await?;
+        let result = sqlx::query(
+            r#"
+            UPDATE chapters
+            SET title = ?, order_index = ?, outline_goal = ?, conflict = ?, twist = ?, cliffhanger = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&chapter.title)
+        .bind(chapter.order_index)
+        .bind(&chapter.outline_goal)
+        .bind(&chapter.conflict)
+        .bind(&chapter.twist)
+        .bind(&chapter.cliffhanger)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&chapter.id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Chapter not found"));
+        }
+        Ok(())
+    }
+
+    /// 保存章节摘要（由正文派生，不更新 updated_at）
+    pub async fn update_summary(pool: &SqlitePool, id: &str, summary: &str) -> Result<()> {
+        sqlx::query("UPDATE chapters SET summary = ? WHERE id = ?")
+            .bind(summary)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 保存章节的配乐/氛围提示词（JSON）
+    pub async fn update_ambiance(pool: &SqlitePool, id: &str, ambiance: &str) -> Result<()> {
+        let result = sqlx::query("UPDATE chapters SET ambiance = ?, updated_at = ? WHERE id = ?")
+            .bind(ambiance)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Chapter {} not found", id));
+        }
+        Ok(())
+    }
+
+    /// 重新计算并更新项目的总字数，返回因此新达成的字数里程碑（由调用方推送 milestone-reached）
+    pub async fn update_project_word_count(pool: &SqlitePool, project_id: &str) -> Result<Vec<Milestone>> {
         let total: i64 = sqlx::query_scalar(
             "SELECT COALESCE(SUM(word_count), 0) FROM chapters WHERE project_id = ?"
         )
@@ -139,7 +439,9 @@ impl ChapterService {
         .execute(pool)
         .await?;
 
-        Ok(())
+        WordCountHistoryService::record(pool, project_id, total).await;
+
+        Ok(Self::reached_milestones(pool, project_id).await)
     }
 
     pub async fn update_meta(
@@ -147,6 +449,25 @@ impl ChapterService {
         id: &str,
         input: UpdateChapterMetaInput,
     ) -> Result<Chapter> {
+        if let Some(temperature) = input.override_temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(anyhow::anyhow!("Override temperature must be between 0 and 2"));
+            }
+        }
+        if let Some(words) = input.override_target_words {
+            if !(MIN_OVERRIDE_TARGET_WORDS..=MAX_OVERRIDE_TARGET_WORDS).contains(&words) {
+                return Err(anyhow::anyhow!(
+                    "Override target words must be between {} and {}",
+                    MIN_OVERRIDE_TARGET_WORDS,
+                    MAX_OVERRIDE_TARGET_WORDS
+                ));
+            }
+        }
+        // 空白模型名视为未设置
+        let override_model = input
+            .override_model
+            .map(|model| model.trim().to_string())
+            .filter(|model| !model.is_empty());
         let now = Utc::now().to_rfc3339();
 
         sqlx::query(
@@ -158,6 +479,9 @@ impl ChapterService {
                 conflict = COALESCE(?, conflict),
                 twist = COALESCE(?, twist),
                 cliffhanger = COALESCE(?, cliffhanger),
+                override_model = COALESCE(?, CASE WHEN ? THEN NULL ELSE override_model END),
+                override_temperature = COALESCE(?, CASE WHEN ? THEN NULL ELSE override_temperature END),
+                override_target_words = COALESCE(?, CASE WHEN ? THEN NULL ELSE override_target_words END),
                 updated_at = ?
             WHERE id = ?
             "#
@@ -168,6 +492,12 @@ impl ChapterService {
         .bind(input.conflict)
         .bind(input.twist)
         .bind(input.cliffhanger)
+        .bind(override_model)
+        .bind(input.clear_overrides)
+        .bind(input.override_temperature)
+        .bind(input.clear_overrides)
+        .bind(input.override_target_words)
+        .bind(input.clear_overrides)
         .bind(&now)
         .bind(id)
         .execute(pool)
@@ -177,8 +507,94 @@ impl ChapterService {
             .ok_or_else(|| anyhow::anyhow!("Chapter not found after update"))
     }
 
-    /// 仅更新项目总字数（不修改 updated_at）
-    pub async fn update_project_word_count_only(pool: &SqlitePool, project_id: &str) -> Result<()> {
+    /// 批量设置章节状态（同一事务）；所有 id 必须存在且属于同一项目
+    pub async fn set_status_bulk(pool: &SqlitePool, ids: &[String], status: &str) -> Result<Vec<Chapter>> {
+        if !CHAPTER_STATUSES.contains(&status) {
+            return Err(anyhow::anyhow!("Invalid chapter status: {}", status));
+        }
+        let sql = "UPDATE chapters SET status = ?, updated_at = ? WHERE id = ?";
+        Self::update_bulk(pool, ids, sql, status.to_string()).await
+    }
+
+    /// 批量设置章节所属的幕（1-3，None 表示取消分配），规则同 set_status_bulk
+    pub async fn set_act_bulk(pool: &SqlitePool, ids: &[String], act: Option<i64>) -> Result<Vec<Chapter>> {
+        if let Some(act) = act {
+            if !(1..=ACT_COUNT).contains(&act) {
+                return Err(anyhow::anyhow!("Act must be between 1 and {}", ACT_COUNT));
+            }
+        }
+        Self::update_bulk(pool, ids, "UPDATE chapters SET act = ?, updated_at = ? WHERE id = ?", act).await
+    }
+
+    // 在同一事务中对每个章节执行 sql（依次绑定 value、更新时间和 id），返回更新后的章节
+    async fn update_bulk<T>(pool: &SqlitePool, ids: &[String], sql: &str, value: T) -> Result<Vec<Chapter>>
+    where
+        T: for<'q> sqlx::Encode<'q, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite> + Clone + Send,
+    {
+        let mut unique: Vec<&String> = Vec::with_capacity(ids.len());
+        for id in ids {
+            if !unique.contains(&id) {
+                unique.push(id);
+            }
+        }
+        if unique.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+        let mut project_id: Option<String> = None;
+        for id in &unique {
+            let owner: Option<String> = sqlx::query_scalar("SELECT project_id FROM chapters WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            match (owner, &project_id) {
+                (None, _) => return Err(anyhow::anyhow!("Chapter {} not found", id)),
+                (Some(owner), Some(expected)) if owner != *expected => {
+                    return Err(anyhow::anyhow!("Chapters belong to different projects"));
+                }
+                (Some(owner), None) => project_id = Some(owner),
+                _ => {}
+            }
+
+            sqlx::query(sql)
+                .bind(value.clone())
+                .bind(&now)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        let mut chapters = Vec::with_capacity(unique.len());
+        for id in unique {
+            chapters.extend(Self::get_by_id(pool, id).await?);
+        }
+        Ok(chapters)
+    }
+
+    /// 批量修改章节标题（同一事务）
+    pub async fn update_titles(pool: &SqlitePool, titles: &[(String, String)]) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+        for (id, title) in titles {
+            let result = sqlx::query("UPDATE chapters SET title = ?, updated_at = ? WHERE id = ?")
+                .bind(title)
+                .bind(&now)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 0 {
+                return Err(anyhow::anyhow!("Chapter {} not found", id));
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 仅更新项目总字数（不修改 updated_at），返回新达成的字数里程碑
+    pub async fn update_project_word_count_only(pool: &SqlitePool, project_id: &str) -> Result<Vec<Milestone>> {
         let total: i64 = sqlx::query_scalar(
             "SELECT COALESCE(SUM(word_count), 0) FROM chapters WHERE project_id = ?"
         )
@@ -194,7 +610,19 @@ impl ChapterService {
         .execute(pool)
         .await?;
 
-        Ok(())
+        WordCountHistoryService::record(pool, project_id, total).await;
+
+        Ok(Self::reached_milestones(pool, project_id).await)
+    }
+
+    // 字数变化后记录新达成的里程碑；检查失败只记录日志，不影响保存
+    async fn reached_milestones(pool: &SqlitePool, project_id: &str) -> Vec<Milestone> {
+        MilestoneService::record_reached(pool, project_id)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Failed to check milestones for {}: {}", project_id, e);
+                Vec::new()
+            })
     }
 
     pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
@@ -211,7 +639,7 @@ impl ChapterService {
             .execute(pool)
             .await?;
 
-        // 删除后更新项目总字数
+        // 删除后更新项目总字数（只会减少，不会达成新的里程碑）
         if let Some(pid) = project_id {
             Self::update_project_word_count(pool, &pid).await?;
         }
@@ -0,0 +1,203 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{CharacterAppearance, CharacterArc, CharacterArcEntry};
+use super::chapter_context::chapter_text;
+use super::{ChapterService, CharacterService};
+
+pub struct CharacterArcService;
+
+impl CharacterArcService {
+    /// 手动记录角色在某章的出场及状态备注（已存在时只更新备注）
+    pub async fn set_appearance(
+        pool: &SqlitePool,
+        character_id: &str,
+        chapter_id: &str,
+        note: Option<String>,
+    ) -> Result<CharacterAppearance> {
+        let character = CharacterService::get_by_id(pool, character_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Character not found"))?;
+        let chapter = ChapterService::get_by_id(pool, chapter_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+        if chapter.project_id != character.project_id {
+            return Err(anyhow::anyhow!("Character and chapter belong to different projects"));
+        }
+
+        let note = note.filter(|n| !n.trim().is_empty());
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"
+            INSERT INTO character_appearances (id, project_id, character_id, chapter_id, note, source, mention_count, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, 'manual', 0, ?, ?)
+            ON CONFLICT(character_id, chapter_id) DO UPDATE SET
+                note = excluded.note,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&character.project_id)
+        .bind(character_id)
+        .bind(chapter_id)
+        .bind(&note)
+        .bind(&now)
+        .bind(&now)
+        .execute(pool)
+        .await?;
+
+        let appearance = sqlx::query_as::<_, CharacterAppearance>(
+            "SELECT * FROM character_appearances WHERE character_id = ? AND chapter_id = ?"
+        )
+        .bind(character_id)
+        .bind(chapter_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(appearance)
+    }
+
+    pub async fn remove_appearance(pool: &SqlitePool, character_id: &str, chapter_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM character_appearances WHERE character_id = ? AND chapter_id = ?")
+            .bind(character_id)
+            .bind(chapter_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 在章节正文中查找项目角色的名字并记录出场。
+    /// 自动检测的记录会随正文重新计算；手动记录和已填写的备注不会被删除。
+    pub async fn detect_appearances(pool: &SqlitePool, chapter_id: &str) -> Result<Vec<CharacterAppearance>> {
+        let chapter = ChapterService::get_by_id(pool, chapter_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+        let text = chapter_text(&chapter).unwrap_or("");
+        let characters = CharacterService::get_by_project(pool, &chapter.project_id).await?;
+
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+        for character in &characters {
+            let name = character.name.trim();
+            let mentions = if name.is_empty() { 0 } else { text.matches(name).count() as i64 };
+
+            if mentions > 0 {
+                sqlx::query(
+                    r#"
+                    INSERT INTO character_appearances (id, project_id, character_id, chapter_id, note, source, mention_count, created_at, updated_at)
+                    VALUES (?, ?, ?, ?, NULL, 'detected', ?, ?, ?)
+                    ON CONFLICT(character_id, chapter_id) DO UPDATE SET
+                        mention_count = excluded.mention_count,
+                        updated_at = excluded.updated_at
+                    "#
+                )
+                .bind(Uuid::new_v4().to_string())
+                .bind(&chapter.project_id)
+                .bind(&character.id)
+                .bind(chapter_id)
+                .bind(mentions)
+                .bind(&now)
+                .bind(&now)
+                .execute(&mut *tx)
+                .await?;
+            } else {
+                // 正文中已不再出现：删除没有备注的自动记录，其余只把提及次数归零
+                sqlx::query(
+                    r#"
+                    DELETE FROM character_appearances
+                    WHERE character_id = ? AND chapter_id = ? AND source = 'detected' AND (note IS NULL OR TRIM(note) = '')
+                    "#
+                )
+                .bind(&character.id)
+                .bind(chapter_id)
+                .execute(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "UPDATE character_appearances SET mention_count = 0 WHERE character_id = ? AND chapter_id = ?"
+                )
+                .bind(&character.id)
+                .bind(chapter_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+
+        let appearances = sqlx::query_as::<_, CharacterAppearance>(
+            r#"
+            SELECT a.* FROM character_appearances a
+            JOIN characters c ON c.id = a.character_id
+            WHERE a.chapter_id = ?
+            ORDER BY a.mention_count DESC, c.name ASC
+            "#
+        )
+        .bind(chapter_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(appearances)
+    }
+
+    /// 按章节顺序返回角色的出场记录，并统计缺席间隔
+    pub async fn get_arc(pool: &SqlitePool, character_id: &str) -> Result<CharacterArc> {
+        let character = CharacterService::get_by_id(pool, character_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Character not found"))?;
+
+        let chapter_ids: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM chapters WHERE project_id = ? ORDER BY order_index ASC"
+        )
+        .bind(&character.project_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut appearances = sqlx::query_as::<_, CharacterArcEntry>(
+            r#"
+            SELECT a.chapter_id, ch.title AS chapter_title, ch.order_index, a.note, a.source, a.mention_count,
+                   0 AS chapters_absent_before
+            FROM character_appearances a
+            JOIN chapters ch ON ch.id = a.chapter_id
+            WHERE a.character_id = ?
+            ORDER BY ch.order_index ASC
+            "#
+        )
+        .bind(character_id)
+        .fetch_all(pool)
+        .await?;
+
+        // 按章节在书中的位置计算间隔，order_index 可能不连续
+        let position = |chapter_id: &str| {
+            chapter_ids.iter().position(|id| id == chapter_id).unwrap_or(0) as i64
+        };
+        let mut previous: Option<i64> = None;
+        let mut longest_absence = 0;
+        for entry in &mut appearances {
+            let current = position(&entry.chapter_id);
+            entry.chapters_absent_before = match previous {
+                Some(prev) => (current - prev - 1).max(0),
+                None => current,
+            };
+            if previous.is_some() {
+                longest_absence = longest_absence.max(entry.chapters_absent_before);
+            }
+            previous = Some(current);
+        }
+
+        let total_chapters = chapter_ids.len() as i64;
+        let chapters_since_last = match previous {
+            Some(last) => total_chapters - last - 1,
+            None => total_chapters,
+        };
+
+        Ok(CharacterArc {
+            character_id: character.id,
+            character_name: character.name,
+            total_chapters,
+            appearances,
+            longest_absence,
+            chapters_since_last,
+        })
+    }
+}
@@ -0,0 +1,319 @@
+//! 角色表格导入/导出（CSV 或 JSON）
+//!
+//! CSV 表头可以用字段名（name、role…）或常见中文列名（姓名、定位…），
+//! 无法识别的列会被忽略并在报告中列出；缺少 name 列时整体拒绝。
+
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::models::{Character, CharacterImportReport, CharacterImportRowError, CharacterSheet};
+use super::csv::{parse_csv, write_row};
+use super::CharacterService;
+
+const SHEET_COLUMNS: [&str; 7] = [
+    "name",
+    "role",
+    "description",
+    "personality",
+    "background",
+    "motivation",
+    "voice_style",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetFormat {
+    Csv,
+    Json,
+}
+
+impl SheetFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+// 表头 → 字段名（也用于解析大纲中的角色字段）
+pub(crate) fn column_key(header: &str) -> Option<&'static str> {
+    let header = header.trim().to_lowercase().replace([' ', '-'], "_");
+    let key = match header.as_str() {
+        "name" | "姓名" | "名字" | "角色名" => "name",
+        "role" | "定位" | "身份" | "角色定位" => "role",
+        "description" | "简介" | "描述" => "description",
+        "personality" | "性格" => "personality",
+        "background" | "背景" => "background",
+        "motivation" | "动机" => "motivation",
+        "voice_style" | "voice" | "说话风格" | "语言风格" => "voice_style",
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim()).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+fn set_field(sheet: &mut CharacterSheet, key: &str, value: &str) {
+    match key {
+        "name" => sheet.name = value.trim().to_string(),
+        "role" => sheet.role = non_empty(value),
+        "description" => sheet.description = non_empty(value),
+        "personality" => sheet.personality = non_empty(value),
+        "background" => sheet.background = non_empty(value),
+        "motivation" => sheet.motivation = non_empty(value),
+        "voice_style" => sheet.voice_style = non_empty(value),
+        _ => {}
+    }
+}
+
+type ParsedRows = Vec<(usize, std::result::Result<CharacterSheet, String>)>;
+
+/// 解析 CSV，返回 (每行结果, 被忽略的列)
+fn parse_csv_sheets(data: &str) -> Result<(ParsedRows, Vec<String>)> {
+    let mut rows = parse_csv(data)?.into_iter();
+    let (_, header) = rows.next().ok_or_else(|| anyhow::anyhow!("CSV is empty"))?;
+
+    let keys: Vec<Option<&'static str>> = header.iter().map(|h| column_key(h)).collect();
+    if !keys.contains(&Some("name")) {
+        return Err(anyhow::anyhow!("CSV header must contain a name column"));
+    }
+    let ignored: Vec<String> = header
+        .iter()
+        .zip(&keys)
+        .filter(|(h, key)| key.is_none() && !h.trim().is_empty())
+        .map(|(h, _)| h.trim().to_string())
+        .collect();
+
+    let parsed = rows
+        .map(|(line, fields)| {
+            if fields.len() > header.len() {
+                return (line, Err(format!("列数 {} 多于表头的 {} 列", fields.len(), header.len())));
+            }
+            let mut sheet = CharacterSheet::default();
+            for (key, value) in keys.iter().zip(&fields) {
+                if let Some(key) = key {
+                    set_field(&mut sheet, key, value);
+                }
+            }
+            (line, Ok(sheet))
+        })
+        .collect();
+    Ok((parsed, ignored))
+}
+
+/// 解析 JSON：角色数组，或 {"characters": [...]}
+fn parse_json_sheets(data: &str) -> Result<ParsedRows> {
+    let value: serde_json::Value = serde_json::from_str(data.trim_start_matches('\u{feff}'))?;
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut map) => match map.remove("characters") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => return Err(anyhow::anyhow!("JSON must be an array of characters")),
+        },
+        _ => return Err(anyhow::anyhow!("JSON must be an array of characters")),
+    };
+
+    Ok(items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let sheet = serde_json::from_value::<CharacterSheet>(item)
+                .map(|mut sheet| {
+                    sheet.name = sheet.name.trim().to_string();
+                    sheet
+                })
+                .map_err(|e| format!("字段格式错误: {}", e));
+            (index + 1, sheet)
+        })
+        .collect())
+}
+
+fn name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+// 导入的非空字段覆盖原值，空字段保留原值
+fn merge_sheet(character: &mut Character, sheet: &CharacterSheet) {
+    let fields = [
+        (&mut character.role, &sheet.role),
+        (&mut character.description, &sheet.description),
+        (&mut character.personality, &sheet.personality),
+        (&mut character.background, &sheet.background),
+        (&mut character.motivation, &sheet.motivation),
+        (&mut character.voice_style, &sheet.voice_style),
+    ];
+    for (target, value) in fields {
+        if value.is_some() {
+            *target = value.clone();
+        }
+    }
+}
+
+/// 导入角色。同名（忽略大小写和首尾空白）的角色在 merge_duplicates 为 true 时合并，否则跳过；
+/// 单行错误只记入报告，不影响其他行
+pub async fn import_characters(
+    pool: &SqlitePool,
+    project_id: &str,
+    data: &str,
+    format: SheetFormat,
+    merge_duplicates: bool,
+) -> Result<CharacterImportReport> {
+    let (rows, ignored_columns) = match format {
+        SheetFormat::Csv => parse_csv_sheets(data)?,
+        SheetFormat::Json => (parse_json_sheets(data)?, Vec::new()),
+    };
+
+    let mut existing: HashMap<String, Character> = CharacterService::get_by_project(pool, project_id)
+        .await?
+        .into_iter()
+        .map(|c| (name_key(&c.name), c))
+        .collect();
+
+    let mut report = CharacterImportReport {
+        created: Vec::new(),
+        merged: Vec::new(),
+        skipped_duplicates: Vec::new(),
+        errors: Vec::new(),
+        ignored_columns,
+    };
+    let now = Utc::now().to_rfc3339();
+    let mut created_keys: Vec<String> = Vec::new();
+    let mut merged_keys: Vec<String> = Vec::new();
+    let mut tx = pool.begin().await?;
+
+    for (row, sheet) in rows {
+        let sheet = match sheet {
+            Ok(sheet) if sheet.name.is_empty() => {
+                report.errors.push(CharacterImportRowError { row, message: "缺少角色名".to_string() });
+                continue;
+            }
+            Ok(sheet) => sheet,
+            Err(message) => {
+                report.errors.push(CharacterImportRowError { row, message });
+                continue;
+            }
+        };
+        let key = name_key(&sheet.name);
+
+        if let Some(character) = existing.get_mut(&key) {
+            if !merge_duplicates {
+                report.skipped_duplicates.push(sheet.name);
+                continue;
+            }
+            merge_sheet(character, &sheet);
+            character.updated_at = now.clone();
+            sqlx::query(
+                r#"
+                UPDATE characters
+                SET role = ?, description = ?, personality = ?, background = ?, motivation = ?, voice_style = ?, updated_at = ?
+                WHERE id = ?
+                "#
+            )
+            .bind(&character.role)
+            .bind(&character.description)
+            .bind(&character.personality)
+            .bind(&character.background)
+            .bind(&character.motivation)
+            .bind(&character.voice_style)
+            .bind(&now)
+            .bind(&character.id)
+            .execute(&mut *tx)
+            .await?;
+            if !created_keys.contains(&key) && !merged_keys.contains(&key) {
+                merged_keys.push(key);
+            }
+            continue;
+        }
+
+        let character = Character {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            name: sheet.name,
+            role: sheet.role,
+            description: sheet.description,
+            personality: sheet.personality,
+            background: sheet.background,
+            motivation: sheet.motivation,
+            voice_style: sheet.voice_style,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            portrait_seed: None,
+            portrait_descriptor: None,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO characters (id, project_id, name, role, description, personality, background, motivation, voice_style, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&character.id)
+        .bind(&character.project_id)
+        .bind(&character.name)
+        .bind(&character.role)
+        .bind(&character.description)
+        .bind(&character.personality)
+        .bind(&character.background)
+        .bind(&character.motivation)
+        .bind(&character.voice_style)
+        .bind(&character.created_at)
+        .bind(&character.updated_at)
+        .execute(&mut *tx)
+        .await?;
+
+        created_keys.push(key.clone());
+        existing.insert(key, character);
+    }
+
+    tx.commit().await?;
+
+    for key in created_keys {
+        report.created.extend(existing.get(&key).cloned());
+    }
+    for key in merged_keys {
+        report.merged.extend(existing.get(&key).cloned());
+    }
+    Ok(report)
+}
+
+/// 导出项目角色，CSV 使用字段名作表头
+pub async fn export_characters(pool: &SqlitePool, project_id: &str, format: SheetFormat) -> Result<String> {
+    let sheets: Vec<CharacterSheet> = CharacterService::get_by_project(pool, project_id)
+        .await?
+        .into_iter()
+        .map(|c| CharacterSheet {
+            name: c.name,
+            role: c.role,
+            description: c.description,
+            personality: c.personality,
+            background: c.background,
+            motivation: c.motivation,
+            voice_style: c.voice_style,
+        })
+        .collect();
+
+    match format {
+        SheetFormat::Json => Ok(serde_json::to_string_pretty(&sheets)?),
+        SheetFormat::Csv => {
+            let mut out = String::from("\u{feff}");
+            out.push_str(&write_row(&SHEET_COLUMNS));
+            for sheet in &sheets {
+                let fields = [
+                    Some(sheet.name.as_str()),
+                    sheet.role.as_deref(),
+                    sheet.description.as_deref(),
+                    sheet.personality.as_deref(),
+                    sheet.background.as_deref(),
+                    sheet.motivation.as_deref(),
+                    sheet.voice_style.as_deref(),
+                ];
+                out.push_str(&write_row(&fields.map(|f| f.unwrap_or_default())));
+            }
+            Ok(out)
+        }
+    }
+}
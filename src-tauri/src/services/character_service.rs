@@ -0,0 +1,583 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use regex::Regex;
+use crate::models::{Character, CreateCharacterInput, DuplicateCharacterPair, UpdateCharacterInput};
+use super::chapter_context::is_blank;
+use super::character_io::column_key;
+use super::lore_service::labeled_item;
+use super::similarity::name_similarity;
+
+lazy_static::lazy_static! {
+    // ### 1. 角色名
+    static ref NUMBERED: Regex = Regex::new(r"^\d+\s*[.、)）]\s*").unwrap();
+}
+
+pub struct CharacterService;
+
+fn is_character_section(heading: &str) -> bool {
+    heading.contains("角色") || heading.contains("人物") || heading.to_lowercase().contains("character")
+}
+
+// “林风（男主角）”拆成名称和括号内的身份
+fn split_character_heading(heading: &str) -> (String, Option<String>) {
+    let heading = NUMBERED.replace(heading.trim(), "");
+    let heading = heading.trim().trim_matches('*').trim();
+    match heading.split_once(['（', '(']) {
+        Some((name, rest)) => {
+            let role = rest.trim_end_matches(['）', ')']).trim();
+            (name.trim().to_string(), Some(role.to_string()).filter(|role| !role.is_empty()))
+        }
+        None => (heading.to_string(), None),
+    }
+}
+
+fn push_text(target: &mut Option<String>, text: &str) {
+    match target {
+        Some(current) if !current.is_empty() => {
+            current.push('\n');
+            current.push_str(text);
+        }
+        _ => *target = Some(text.to_string()),
+    }
+}
+
+fn character_field<'a>(input: &'a mut CreateCharacterInput, key: &str) -> &'a mut Option<String> {
+    match key {
+        "role" => &mut input.role,
+        "personality" => &mut input.personality,
+        "background" => &mut input.background,
+        "motivation" => &mut input.motivation,
+        "voice_style" => &mut input.voice_style,
+        _ => &mut input.description,
+    }
+}
+
+/// 解析大纲“主要角色”：每个 ### 小节一个角色，“- **字段**：内容”按字段名归入对应列，
+/// 无法识别的字段和续行并入简介（续行跟随上一字段）
+fn parse_outline_characters(project_id: &str, outline: &str) -> Vec<CreateCharacterInput> {
+    let mut characters: Vec<CreateCharacterInput> = Vec::new();
+    let mut in_section = false;
+    let mut last_key = "description";
+
+    for line in outline.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            in_section = is_character_section(heading);
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        if let Some(heading) = trimmed.strip_prefix("### ") {
+            let (name, role) = split_character_heading(heading);
+            characters.push(CreateCharacterInput {
+                project_id: project_id.to_string(),
+                name,
+                role,
+                description: None,
+                personality: None,
+                background: None,
+                motivation: None,
+                voice_style: None,
+            });
+            last_key = "description";
+            continue;
+        }
+        let Some(character) = characters.last_mut() else {
+            continue;
+        };
+        if trimmed.is_empty() || (trimmed.starts_with('（') && trimmed.ends_with('）')) {
+            continue;
+        }
+
+        // - **性格**：内容  /  - Personality: 内容
+        if let Some((label, value)) = labeled_item(line) {
+            match column_key(label).filter(|key| *key != "name") {
+                Some(key) => {
+                    last_key = key;
+                    if !value.is_empty() {
+                        push_text(character_field(character, key), value);
+                    }
+                }
+                None => {
+                    last_key = "description";
+                    push_text(&mut character.description, &format!("{}：{}", label, value));
+                }
+            }
+            continue;
+        }
+        let text = trimmed.trim_start_matches(['-', '*', ' ']).trim();
+        if !text.is_empty() {
+            push_text(character_field(character, last_key), text);
+        }
+    }
+
+    characters.retain(|character| !character.name.is_empty());
+    characters
+}
+
+/// 允许单独重写的角色字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterField {
+    Personality,
+    Background,
+    Motivation,
+    Description,
+}
+
+impl CharacterField {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "personality" => Some(Self::Personality),
+            "background" => Some(Self::Background),
+            "motivation" => Some(Self::Motivation),
+            "description" => Some(Self::Description),
+            _ => None,
+        }
+    }
+
+    /// 对应的数据库列名（固定白名单，可安全拼入 SQL）
+    pub fn column(self) -> &'static str {
+        match self {
+            Self::Personality => "personality",
+            Self::Background => "background",
+            Self::Motivation => "motivation",
+            Self::Description => "description",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Personality => "性格",
+            Self::Background => "背景",
+            Self::Motivation => "动机",
+            Self::Description => "简介",
+        }
+    }
+}
+
+impl CharacterService {
+    pub async fn create(pool: &SqlitePool, input: CreateCharacterInput) -> Result<Character> {
+        let now = Utc::now().to_rfc3339();
+        let character = Character {
+            id: Uuid::new_v4().to_string(),
+            project_id: input.project_id,
+            name: input.name,
+            role: input.role,
+            description: input.description,
+            personality: input.personality,
+            background: input.background,
+            motivation: input.motivation,
+            voice_style: input.voice_style,
+            created_at: now.clone(),
+            updated_at: now,
+            portrait_seed: None,
+            portrait_descriptor: None,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO characters (id, project_id, name, role, description, personality, background, motivation, voice_style, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&character.id)
+        .bind(&character.project_id)
+        .bind(&character.name)
+        .bind(&character.role)
+        .bind(&character.description)
+        .bind(&character.personality)
+        .bind(&character.background)
+        .bind(&character.motivation)
+        .bind(&character.voice_style)
+        .bind(&character.created_at)
+        .bind(&character.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(character)
+    }
+
+    /// 从大纲“主要角色”创建角色，返回 (新建的角色, 补全了字段的已有角色)。
+    /// 同名（忽略大小写和首尾空白）的已有角色只补全空字段，不覆盖手动编辑的内容
+    pub async fn sync_from_outline(
+        pool: &SqlitePool,
+        project_id: &str,
+        outline: &str,
+    ) -> Result<(Vec<Character>, Vec<Character>)> {
+        let parsed = parse_outline_characters(project_id, outline);
+        let mut existing = Self::get_by_project(pool, project_id).await?;
+        let mut created = Vec::new();
+        let mut updated: Vec<Character> = Vec::new();
+
+        for input in parsed {
+            let key = input.name.trim().to_lowercase();
+            let Some(character) = existing.iter_mut().find(|c| c.name.trim().to_lowercase() == key) else {
+                let character = Self::create(pool, input).await?;
+                existing.push(character.clone());
+                created.push(character);
+                continue;
+            };
+
+            let fill = [
+                (&mut character.role, input.role),
+                (&mut character.description, input.description),
+                (&mut character.personality, input.personality),
+                (&mut character.background, input.background),
+                (&mut character.motivation, input.motivation),
+                (&mut character.voice_style, input.voice_style),
+            ];
+            let mut changed = false;
+            for (target, value) in fill {
+                if is_blank(target) && !is_blank(&value) {
+                    *target = value;
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            character.updated_at = Utc::now().to_rfc3339();
+            Self::restore(pool, character).await?;
+            // 大纲中重复出现的角色只在结果里出现一次
+            if let Some(entry) = created.iter_mut().chain(updated.iter_mut()).find(|c| c.id == character.id) {
+                *entry = character.clone();
+            } else {
+                updated.push(character.clone());
+            }
+        }
+
+        Ok((created, updated))
+    }
+
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str) -> Result<Vec<Character>> {
+        let characters = sqlx::query_as::<_, Character>(
+            "SELECT * FROM characters WHERE project_id = ? ORDER BY created_at ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(characters)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<Character>> {
+        let character = sqlx::query_as::<_, Character>(
+            "SELECT * FROM characters WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(character)
+    }
+
+    /// 按快照恢复角色设定
+    pub async fn restore(pool: &SqlitePool, character: &Character) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE characters
+            SET name = ?, role = ?, description = ?, personality = ?, background = ?, motivation = ?, voice_style = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&character.name)
+        .bind(&character.role)
+        .bind(&character.description)
+        .bind(&character.personality)
+        .bind(&character.background)
+        .bind(&character.motivation)
+        .bind(&character.voice_style)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&character.id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Character not found"));
+        }
+        Ok(())
+    }
+
+    pub async fn update(pool: &SqlitePool, id: &str, input: UpdateCharacterInput) -> Result<Character> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE characters
+            SET name = COALESCE(?, name),
+                role = COALESCE(?, role),
+                description = COALESCE(?, description),
+                personality = COALESCE(?, personality),
+                background = COALESCE(?, background),
+                motivation = COALESCE(?, motivation),
+                voice_style = COALESCE(?, voice_style),
+                updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(input.name)
+        .bind(input.role)
+        .bind(input.description)
+        .bind(input.personality)
+        .bind(input.background)
+        .bind(input.motivation)
+        .bind(input.voice_style)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Character not found"));
+        }
+
+        Self::get_by_id(pool, id).await?
+            .ok_or_else(|| anyhow::anyhow!("Character not found after update"))
+    }
+
+    /// 删除角色；出场记录随外键级联删除
+    pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM characters WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 撤销删除：按快照重新插入角色（出场记录无法恢复）
+    pub async fn reinsert(pool: &SqlitePool, character: &Character) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO characters (id, project_id, name, role, description, personality, background, motivation, voice_style, portrait_seed, portrait_descriptor, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&character.id)
+        .bind(&character.project_id)
+        .bind(&character.name)
+        .bind(&character.role)
+        .bind(&character.description)
+        .bind(&character.personality)
+        .bind(&character.background)
+        .bind(&character.motivation)
+        .bind(&character.voice_style)
+        .bind(character.portrait_seed)
+        .bind(&character.portrait_descriptor)
+        .bind(&character.created_at)
+        .bind(&character.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 只更新单个字段，其余字段保持不变
+    pub async fn update_field(
+        pool: &SqlitePool,
+        id: &str,
+        field: CharacterField,
+        value: &str,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(&format!(
+            "UPDATE characters SET {} = ?, updated_at = ? WHERE id = ?",
+            field.column()
+        ))
+        .bind(value)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Character not found"));
+        }
+
+        Ok(())
+    }
+
+    /// 设置插图用的固定种子和外貌描述；传 None 表示清除
+    pub async fn set_portrait(
+        pool: &SqlitePool,
+        id: &str,
+        seed: Option<i64>,
+        descriptor: Option<String>,
+    ) -> Result<Character> {
+        let descriptor = descriptor
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+
+        let result = sqlx::query(
+            "UPDATE characters SET portrait_seed = ?, portrait_descriptor = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(seed)
+        .bind(descriptor)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Character not found"));
+        }
+
+        Self::get_by_id(pool, id).await?
+            .ok_or_else(|| anyhow::anyhow!("Character not found after update"))
+    }
+
+    /// 按名字相似度查找疑似重复的角色（忽略括注、空白、标点和大小写）
+    pub async fn find_duplicates(
+        pool: &SqlitePool,
+        project_id: &str,
+        threshold: f64,
+    ) -> Result<Vec<DuplicateCharacterPair>> {
+        let characters = Self::get_by_project(pool, project_id).await?;
+
+        let mut pairs = Vec::new();
+        for (i, a) in characters.iter().enumerate() {
+            for b in characters.iter().skip(i + 1) {
+                let similarity = name_similarity(&a.name, &b.name);
+                if similarity >= threshold {
+                    pairs.push(DuplicateCharacterPair {
+                        character_a_id: a.id.clone(),
+                        character_a_name: a.name.clone(),
+                        character_b_id: b.id.clone(),
+                        character_b_name: b.name.clone(),
+                        similarity: (similarity * 1000.0).round() / 1000.0,
+                    });
+                }
+            }
+        }
+        pairs.sort_by(|x, y| y.similarity.total_cmp(&x.similarity));
+        Ok(pairs)
+    }
+
+    /// 把 merge_id 合并进 keep_id（同一事务）：字段以保留的角色为准，空字段用被合并角色补齐，
+    /// 背景和动机不同时追加在后面；出场记录转到保留的角色（同一章都有记录时合并提及次数），
+    /// 最后删除被合并的角色
+    pub async fn merge(pool: &SqlitePool, keep_id: &str, merge_id: &str) -> Result<Character> {
+        if keep_id == merge_id {
+            return Err(anyhow::anyhow!("Cannot merge a character into itself"));
+        }
+        let mut keep = Self::get_by_id(pool, keep_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Character {} not found", keep_id))?;
+        let merged = Self::get_by_id(pool, merge_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Character {} not found", merge_id))?;
+        if keep.project_id != merged.project_id {
+            return Err(anyhow::anyhow!("Characters belong to different projects"));
+        }
+
+        merge_fields(&mut keep, &merged);
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE characters
+            SET role = ?, description = ?, personality = ?, background = ?, motivation = ?, voice_style = ?,
+                portrait_seed = ?, portrait_descriptor = ?, updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(&keep.role)
+        .bind(&keep.description)
+        .bind(&keep.personality)
+        .bind(&keep.background)
+        .bind(&keep.motivation)
+        .bind(&keep.voice_style)
+        .bind(keep.portrait_seed)
+        .bind(&keep.portrait_descriptor)
+        .bind(&now)
+        .bind(&keep.id)
+        .execute(&mut *tx)
+        .await?;
+
+        // 两人在同一章都有出场记录：并入保留角色的记录，再删除被合并角色的那条
+        sqlx::query(
+            r#"
+            UPDATE character_appearances
+            SET mention_count = mention_count + (
+                    SELECT m.mention_count FROM character_appearances m
+                    WHERE m.character_id = ?1 AND m.chapter_id = character_appearances.chapter_id
+                ),
+                note = COALESCE(note, (
+                    SELECT m.note FROM character_appearances m
+                    WHERE m.character_id = ?1 AND m.chapter_id = character_appearances.chapter_id
+                )),
+                updated_at = ?3
+            WHERE character_id = ?2
+              AND chapter_id IN (SELECT chapter_id FROM character_appearances WHERE character_id = ?1)
+            "#
+        )
+        .bind(&merged.id)
+        .bind(&keep.id)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            DELETE FROM character_appearances
+            WHERE character_id = ?1
+              AND chapter_id IN (SELECT chapter_id FROM character_appearances WHERE character_id = ?2)
+            "#
+        )
+        .bind(&merged.id)
+        .bind(&keep.id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE character_appearances SET character_id = ?, updated_at = ? WHERE character_id = ?")
+            .bind(&keep.id)
+            .bind(&now)
+            .bind(&merged.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM characters WHERE id = ?")
+            .bind(&merged.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Self::get_by_id(pool, &keep.id).await?
+            .ok_or_else(|| anyhow::anyhow!("Character not found after merge"))
+    }
+}
+
+// 背景、动机：两边都有且内容不同时把被合并角色的追加在后面
+fn append_note(target: &mut Option<String>, extra: &Option<String>) {
+    let Some(extra) = extra.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+        return;
+    };
+    match target.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        Some(current) if current.contains(extra) => {}
+        Some(current) => *target = Some(format!("{}\n\n{}", current, extra)),
+        None => *target = Some(extra.to_string()),
+    }
+}
+
+fn merge_fields(keep: &mut Character, merged: &Character) {
+    let fill = [
+        (&mut keep.role, &merged.role),
+        (&mut keep.description, &merged.description),
+        (&mut keep.personality, &merged.personality),
+        (&mut keep.voice_style, &merged.voice_style),
+        (&mut keep.portrait_descriptor, &merged.portrait_descriptor),
+    ];
+    for (target, value) in fill {
+        if is_blank(target) && !is_blank(value) {
+            *target = value.clone();
+        }
+    }
+    if keep.portrait_seed.is_none() {
+        keep.portrait_seed = merged.portrait_seed;
+    }
+    append_note(&mut keep.background, &merged.background);
+    append_note(&mut keep.motivation, &merged.motivation);
+}
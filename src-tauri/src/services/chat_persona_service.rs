@@ -0,0 +1,84 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use anyhow::Result;
+use crate::models::ChatPersona;
+
+/// 内置人设：(id, 名称, 简介, 系统提示词)
+const BUILTIN_PERSONAS: [(&str, &str, &str, &str); 4] = [
+    (
+        "assistant",
+        "写作助手",
+        "中立、务实的写作顾问",
+        "你是一位经验丰富的小说写作助手，回答具体、务实，优先给出可以直接用在稿子里的建议。",
+    ),
+    (
+        "harsh_editor",
+        "严厉编辑",
+        "直指问题，不留情面",
+        "你是一位以严厉著称的资深文学编辑。直接指出情节漏洞、节奏拖沓、人物扁平和文字上的毛病，不说客套话；每个问题都给出明确的修改方向。",
+    ),
+    (
+        "encouraging_coach",
+        "鼓励型教练",
+        "先肯定再建议，帮作者保持动力",
+        "你是一位温和的写作教练。先指出作者做得好的地方，再用鼓励的语气提出一到两条最关键的改进建议，并帮助作者拆解下一步可以马上动手的小目标。",
+    ),
+    (
+        "worldbuilding_expert",
+        "世界观专家",
+        "推敲设定的一致性与深度",
+        "你是一位世界观构建专家，熟悉历史、社会、经济、魔法/科技体系的设计。帮助作者推敲设定的内在逻辑和一致性，提出能深化世界观、同时服务于情节的细节。",
+    ),
+];
+
+pub struct ChatPersonaService;
+
+impl ChatPersonaService {
+    /// 写入内置人设；已存在的按当前版本更新文案，不影响用户自建的人设
+    pub async fn seed_builtin(pool: &SqlitePool) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        for (id, name, description, system_prompt) in BUILTIN_PERSONAS {
+            sqlx::query(
+                r#"
+                INSERT INTO chat_personas (id, name, description, system_prompt, builtin, created_at, updated_at)
+                VALUES (?, ?, ?, ?, 1, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    description = excluded.description,
+                    system_prompt = excluded.system_prompt,
+                    builtin = 1
+                "#
+            )
+            .bind(id)
+            .bind(name)
+            .bind(description)
+            .bind(system_prompt)
+            .bind(&now)
+            .bind(&now)
+            .execute(pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_all(pool: &SqlitePool) -> Result<Vec<ChatPersona>> {
+        let personas = sqlx::query_as::<_, ChatPersona>(
+            "SELECT * FROM chat_personas ORDER BY builtin DESC, created_at ASC"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(personas)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<ChatPersona>> {
+        let persona = sqlx::query_as::<_, ChatPersona>(
+            "SELECT * FROM chat_personas WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(persona)
+    }
+}
@@ -0,0 +1,315 @@
+//! 提示词 token 估算与上下文窗口
+//!
+//! 不引入分词器，按字符类别粗略估算：中日韩字符约 1 token/字，
+//! 其余字符约 4 字符/token。估算偏保守，用于提前预警而非精确计费。
+
+use serde::Serialize;
+
+/// 每条消息的格式开销（role、分隔符等）
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// 未识别模型时假定的上下文窗口
+const DEFAULT_CONTEXT_WINDOW: usize = 32_000;
+/// 剩余预算低于该值时不再截取，直接移除该部分
+const MIN_TRIMMED_SECTION_TOKENS: usize = 100;
+
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut cjk = 0usize;
+    let mut other = 0usize;
+    for c in text.chars() {
+        if is_cjk(c) {
+            cjk += 1;
+        } else {
+            other += 1;
+        }
+    }
+    cjk + other.div_ceil(4)
+}
+
+/// 按 chat 消息估算（含每条消息的格式开销）
+pub fn estimate_message_tokens(text: &str) -> usize {
+    estimate_tokens(text) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// 在 token 预算内选取文本结尾作为续写上下文。
+///
+/// 优先取完整段落；最后一段本身超出预算时改为在段内按整句截取，
+/// 单句仍超出时才按字符截断。结果总是从段落或句子开头开始，避免从半句话、半段对话切入。
+pub fn select_tail_context(text: &str, budget_tokens: usize) -> &str {
+    let text = text.trim_end();
+    if estimate_tokens(text) <= budget_tokens {
+        return text;
+    }
+
+    let paragraph_starts = paragraph_starts(text);
+    if let Some(start) = fit_from_end(text, &paragraph_starts, budget_tokens) {
+        return text[start..].trim_start();
+    }
+
+    let last_paragraph = paragraph_starts.last().copied().unwrap_or(0);
+    let sentence_starts = sentence_starts(text, last_paragraph);
+    if let Some(start) = fit_from_end(text, &sentence_starts, budget_tokens) {
+        return text[start..].trim_start();
+    }
+
+    &text[char_tail_start(text, budget_tokens)..]
+}
+
+/// 待注入的一段上下文
+pub struct ContextSection {
+    pub name: &'static str,
+    /// 数值越小越优先保留
+    pub priority: u8,
+    /// 超出预算时保留结尾（前情提要）还是开头（角色、设定列表）
+    pub keep_tail: bool,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FittedSectionReport {
+    pub name: String,
+    pub original_tokens: usize,
+    pub included_tokens: usize,
+    /// included, trimmed, dropped
+    pub status: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextFitReport {
+    pub budget_tokens: usize,
+    pub used_tokens: usize,
+    pub sections: Vec<FittedSectionReport>,
+}
+
+impl ContextFitReport {
+    pub fn has_cuts(&self) -> bool {
+        self.sections.iter().any(|s| s.status != "included")
+    }
+}
+
+/// 裁剪后的上下文，按名称取用
+pub struct FittedContext {
+    sections: Vec<(&'static str, String)>,
+    pub report: ContextFitReport,
+}
+
+impl FittedContext {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .find(|(section, _)| *section == name)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+impl ContextSection {
+    /// 空内容返回 None，便于直接用可选参数构建
+    pub fn new(name: &'static str, priority: u8, keep_tail: bool, text: Option<&str>) -> Option<Self> {
+        let text = text.map(str::trim).filter(|t| !t.is_empty())?;
+        Some(Self {
+            name,
+            priority,
+            keep_tail,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// 按优先级把各段上下文装入 token 预算：高优先级完整保留，
+/// 放不下的部分按段落截取，剩余预算过少时整段移除。
+pub fn fit_context(sections: Vec<ContextSection>, budget_tokens: usize) -> FittedContext {
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+    order.sort_by_key(|&index| sections[index].priority);
+
+    let mut fitted: Vec<(usize, &'static str, String)> = Vec::new();
+    let mut reports: Vec<(usize, FittedSectionReport)> = Vec::new();
+    let mut remaining = budget_tokens;
+
+    for index in order {
+        let section = &sections[index];
+        let original_tokens = estimate_tokens(&section.text);
+        let (text, status) = if original_tokens <= remaining {
+            (Some(section.text.as_str()), "included")
+        } else if remaining >= MIN_TRIMMED_SECTION_TOKENS {
+            let trimmed = if section.keep_tail {
+                select_tail_context(&section.text, remaining)
+            } else {
+                select_head_context(&section.text, remaining)
+            };
+            if trimmed.trim().is_empty() {
+                (None, "dropped")
+            } else {
+                (Some(trimmed), "trimmed")
+            }
+        } else {
+            (None, "dropped")
+        };
+
+        let included_tokens = text.map(estimate_tokens).unwrap_or(0);
+        remaining = remaining.saturating_sub(included_tokens);
+        if let Some(text) = text {
+            fitted.push((index, section.name, text.to_string()));
+        }
+        reports.push((
+            index,
+            FittedSectionReport {
+                name: section.name.to_string(),
+                original_tokens,
+                included_tokens,
+                status,
+            },
+        ));
+    }
+
+    // 按输入顺序返回
+    fitted.sort_by_key(|(index, _, _)| *index);
+    reports.sort_by_key(|(index, _)| *index);
+    FittedContext {
+        sections: fitted.into_iter().map(|(_, name, text)| (name, text)).collect(),
+        report: ContextFitReport {
+            budget_tokens,
+            used_tokens: budget_tokens - remaining,
+            sections: reports.into_iter().map(|(_, report)| report).collect(),
+        },
+    }
+}
+
+/// 在 token 预算内从开头选取整行（角色、设定等按条目分行的列表），单行超出时按字符截断
+pub fn select_head_context(text: &str, budget_tokens: usize) -> &str {
+    let text = text.trim();
+    if estimate_tokens(text) <= budget_tokens {
+        return text;
+    }
+
+    let mut end = 0;
+    let mut used = 0;
+    for (index, _) in text.match_indices('\n') {
+        used += estimate_tokens(&text[end..index]);
+        if used > budget_tokens {
+            break;
+        }
+        end = index;
+    }
+    if end > 0 {
+        return text[..end].trim_end();
+    }
+
+    let budget_units = budget_tokens * 4;
+    let mut used = 0;
+    let mut cut = 0;
+    for (index, c) in text.char_indices() {
+        used += if is_cjk(c) { 4 } else { 1 };
+        if used > budget_units {
+            break;
+        }
+        cut = index + c.len_utf8();
+    }
+    &text[..cut]
+}
+
+/// 常见模型的上下文窗口（tokens），按模型名匹配，未知模型取保守默认值
+pub fn context_window(provider: &str, model: &str) -> usize {
+    let model = model.to_ascii_lowercase();
+    let known: &[(&str, usize)] = &[
+        ("deepseek", 64_000),
+        ("gpt-4o", 128_000),
+        ("gpt-4.1", 1_000_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-3.5", 16_000),
+        ("gemini", 1_000_000),
+        ("claude", 200_000),
+        ("qwen", 32_000),
+        ("llama", 128_000),
+        ("mistral", 32_000),
+    ];
+    if let Some((_, window)) = known.iter().find(|(prefix, _)| model.contains(prefix)) {
+        return *window;
+    }
+
+    match provider.to_ascii_lowercase().as_str() {
+        "deepseek" => 64_000,
+        "gemini" => 1_000_000,
+        _ => DEFAULT_CONTEXT_WINDOW,
+    }
+}
+
+// 每个换行之后的位置（含开头），升序
+fn paragraph_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        text.match_indices('\n')
+            .map(|(index, _)| index + 1)
+            .filter(|&next| next < text.len()),
+    );
+    starts
+}
+
+// 段内每个句末标点（及其后的右引号）之后的位置（含段首），升序
+fn sentence_starts(text: &str, from: usize) -> Vec<usize> {
+    const TERMINATORS: &[char] = &['。', '！', '？', '!', '?', '.', '…', '；', ';'];
+    const CLOSERS: &[char] = &['”', '’', '」', '』', '"', '\'', '）', ')'];
+
+    let mut starts = vec![from];
+    let mut chars = text[from..].char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if !TERMINATORS.contains(&c) {
+            continue;
+        }
+        let mut next = from + index + c.len_utf8();
+        while let Some(&(following_index, following)) = chars.peek() {
+            if TERMINATORS.contains(&following) || CLOSERS.contains(&following) {
+                next = from + following_index + following.len_utf8();
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if next < text.len() && starts.last() != Some(&next) {
+            starts.push(next);
+        }
+    }
+    starts
+}
+
+// 从末尾向前累加各片段，返回仍在预算内的最早起点
+fn fit_from_end(text: &str, starts: &[usize], budget_tokens: usize) -> Option<usize> {
+    let mut used = 0;
+    let mut end = text.len();
+    let mut best = None;
+    for &start in starts.iter().rev() {
+        used += estimate_tokens(&text[start..end]);
+        if used > budget_tokens {
+            break;
+        }
+        best = Some(start);
+        end = start;
+    }
+    best
+}
+
+fn char_tail_start(text: &str, budget_tokens: usize) -> usize {
+    // 以 1/4 token 为单位累加，与 estimate_tokens 的估算口径一致
+    let budget_units = budget_tokens * 4;
+    let mut used = 0;
+    let mut start = text.len();
+    for (index, c) in text.char_indices().rev() {
+        used += if is_cjk(c) { 4 } else { 1 };
+        if used > budget_units {
+            break;
+        }
+        start = index;
+    }
+    start
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F      // CJK 标点
+        | 0x3040..=0x30FF    // 日文假名
+        | 0x3400..=0x4DBF    // 扩展 A
+        | 0x4E00..=0x9FFF    // 基本汉字
+        | 0xAC00..=0xD7AF    // 韩文
+        | 0xF900..=0xFAFF    // 兼容汉字
+        | 0xFF00..=0xFFEF    // 全角符号
+        | 0x20000..=0x2FA1F  // 扩展 B 及以后
+    )
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+
+// 流式生成期间按章节缓存正文，由定时任务批量落盘，避免前端每个增量都整章保存
+lazy_static::lazy_static! {
+    static ref DRAFTS: StdMutex<HashMap<String, PendingDraft>> = StdMutex::new(HashMap::new());
+}
+
+struct PendingDraft {
+    content: String,
+    dirty: bool,
+    /// 生成开始前数据库中的草稿，放弃本次生成时用于还原
+    original: Option<String>,
+    discarded: bool,
+}
+
+/// 缓存结束时的结果
+pub enum DraftOutcome {
+    Completed(String),
+    /// 已调用 discard，不应再保存生成内容
+    Discarded,
+}
+
+/// 开始缓存：base 为生成前已有的正文（续写时），新生成的内容追加在其后；original 为生成前的草稿
+pub fn begin(chapter_id: &str, base: String, original: Option<String>) {
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    drafts.insert(
+        chapter_id.to_string(),
+        PendingDraft {
+            content: base,
+            dirty: false,
+            original,
+            discarded: false,
+        },
+    );
+}
+
+pub fn append(chapter_id: &str, delta: &str) {
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(draft) = drafts.get_mut(chapter_id) {
+        draft.content.push_str(delta);
+        draft.dirty = true;
+    }
+}
+
+/// 自上次落盘后有新内容时返回当前全文并标记为已保存
+pub fn take_dirty(chapter_id: &str) -> Option<String> {
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    let draft = drafts.get_mut(chapter_id)?;
+    if !draft.dirty || draft.discarded {
+        return None;
+    }
+    draft.dirty = false;
+    Some(draft.content.clone())
+}
+
+/// 放弃本次生成：之后不再落盘，返回生成前的草稿；该章节没有进行中的生成时返回 None
+pub fn discard(chapter_id: &str) -> Option<Option<String>> {
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    let draft = drafts.get_mut(chapter_id)?;
+    draft.discarded = true;
+    Some(draft.original.clone())
+}
+
+pub fn is_active(chapter_id: &str) -> bool {
+    DRAFTS.lock().unwrap_or_else(|e| e.into_inner()).contains_key(chapter_id)
+}
+
+/// 结束缓存并返回最终全文
+pub fn finish(chapter_id: &str) -> Option<DraftOutcome> {
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    drafts.remove(chapter_id).map(|draft| {
+        if draft.discarded {
+            DraftOutcome::Discarded
+        } else {
+            DraftOutcome::Completed(draft.content)
+        }
+    })
+}
@@ -0,0 +1,101 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+
+/// 缓存的条目向量；content_hash 对应生成向量时的条目文本，文本变化后需重新计算
+#[derive(Debug, Clone)]
+pub struct StoredEmbedding {
+    pub source_type: String,
+    pub source_id: String,
+    pub content_hash: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(sqlx::FromRow)]
+struct EmbeddingRow {
+    source_type: String,
+    source_id: String,
+    content_hash: String,
+    vector: Vec<u8>,
+}
+
+fn encode(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+pub struct EmbeddingService;
+
+impl EmbeddingService {
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str, model: &str) -> Result<Vec<StoredEmbedding>> {
+        let rows = sqlx::query_as::<_, EmbeddingRow>(
+            "SELECT source_type, source_id, content_hash, vector FROM embeddings WHERE project_id = ? AND model = ?"
+        )
+        .bind(project_id)
+        .bind(model)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| StoredEmbedding {
+                vector: decode(&row.vector),
+                source_type: row.source_type,
+                source_id: row.source_id,
+                content_hash: row.content_hash,
+            })
+            .collect())
+    }
+
+    pub async fn upsert(
+        pool: &SqlitePool,
+        project_id: &str,
+        model: &str,
+        embedding: &StoredEmbedding,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"
+            INSERT INTO embeddings (id, project_id, source_type, source_id, model, content_hash, dimensions, vector, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(source_type, source_id, model) DO UPDATE SET
+                content_hash = excluded.content_hash,
+                dimensions = excluded.dimensions,
+                vector = excluded.vector,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(project_id)
+        .bind(&embedding.source_type)
+        .bind(&embedding.source_id)
+        .bind(model)
+        .bind(&embedding.content_hash)
+        .bind(embedding.vector.len() as i64)
+        .bind(encode(&embedding.vector))
+        .bind(&now)
+        .bind(&now)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 删除条目已不存在的向量（所有模型）
+    pub async fn delete(pool: &SqlitePool, source_type: &str, source_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM embeddings WHERE source_type = ? AND source_id = ?")
+            .bind(source_type)
+            .bind(source_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
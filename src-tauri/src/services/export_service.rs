@@ -0,0 +1,442 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use anyhow::Result;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use crate::models::{Chapter, ExportChapterSelection, Project, TextExportResult};
+use super::chapter_context::chapter_text;
+use super::pdf_layout::PdfLayout;
+use super::{ChapterService, ProjectService};
+
+/// PDF 正文字号（磅）与行距（字号的倍数）的允许范围
+pub const PDF_FONT_SIZE_RANGE: (f32, f32) = (8.0, 24.0);
+pub const PDF_LINE_SPACING_RANGE: (f32, f32) = (1.0, 3.0);
+
+pub struct ExportService;
+
+/// 导出进度：phase 为 layout（逐章排版）/ rendering（打包文档）/ writing（写入文件）
+#[derive(Debug, Clone, Copy)]
+pub struct ExportProgress {
+    pub phase: &'static str,
+    pub current: usize,
+    pub total: usize,
+}
+
+// 与章节保存时的字数统计一致：不计空白字符
+fn body_word_count(text: &str) -> i64 {
+    text.chars().filter(|c| !c.is_whitespace()).count() as i64
+}
+
+// 章节标题转为文件名：去掉各平台不允许的字符，过长时截断
+fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(80)
+        .collect();
+    let stem = stem.trim_end_matches(['.', ' ']).trim_start_matches('.');
+    if stem.is_empty() {
+        "chapter".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 不允许的控制字符直接丢弃
+            c if c.is_control() && c != '\t' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn docx_paragraph(style: &str, text: &str) -> String {
+    format!(
+        r#"<w:p><w:pPr><w:pStyle w:val="{}"/></w:pPr><w:r><w:t xml:space="preserve">{}</w:t></w:r></w:p>"#,
+        style,
+        xml_escape(text)
+    )
+}
+
+fn docx_page_break() -> &'static str {
+    r#"<w:p><w:r><w:br w:type="page"/></w:r></w:p>"#
+}
+
+// 扉页
+fn docx_title_page(project: &Project) -> String {
+    let mut body = docx_paragraph("Title", &project.title);
+    if let Some(author) = project.author.as_deref().filter(|a| !a.trim().is_empty()) {
+        body.push_str(&docx_paragraph("Subtitle", author));
+    }
+    body
+}
+
+fn docx_chapter(chapter: &Chapter, text: &str) -> String {
+    let mut body = String::from(docx_page_break());
+    body.push_str(&docx_paragraph("Heading1", &chapter.title));
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        body.push_str(&docx_paragraph("Normal", line));
+    }
+    body
+}
+
+fn docx_document(body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{}<w:sectPr><w:pgSz w:w="11906" w:h="16838"/><w:pgMar w:top="1440" w:right="1800" w:bottom="1440" w:left="1800" w:header="851" w:footer="992" w:gutter="0"/></w:sectPr></w:body></w:document>"#,
+        body
+    )
+}
+
+// 默认字体随项目语言：中文用宋体/黑体并首行缩进两字，英文用 Times New Roman
+fn docx_styles(language: &str) -> String {
+    let (body_font, heading_font, east_asia, lang, indent) = if language == "en" {
+        ("Times New Roman", "Times New Roman", "SimSun", "en-US", "")
+    } else {
+        ("Times New Roman", "SimHei", "SimSun", "zh-CN", r#"<w:ind w:firstLineChars="200" w:firstLine="420"/>"#)
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="{body}" w:hAnsi="{body}" w:eastAsia="{east}" w:cs="{body}"/><w:sz w:val="24"/><w:szCs w:val="24"/><w:lang w:val="{lang}" w:eastAsia="zh-CN"/></w:rPr></w:rPrDefault><w:pPrDefault><w:pPr><w:spacing w:after="120" w:line="360" w:lineRule="auto"/></w:pPr></w:pPrDefault></w:docDefaults>
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/><w:pPr><w:jc w:val="both"/>{indent}</w:pPr></w:style>
+<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:before="2400" w:after="480"/><w:ind w:firstLine="0" w:firstLineChars="0"/><w:jc w:val="center"/></w:pPr><w:rPr><w:rFonts w:ascii="{heading}" w:hAnsi="{heading}" w:eastAsia="{heading}"/><w:b/><w:sz w:val="52"/><w:szCs w:val="52"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Subtitle"><w:name w:val="Subtitle"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:ind w:firstLine="0" w:firstLineChars="0"/><w:jc w:val="center"/></w:pPr><w:rPr><w:sz w:val="30"/><w:szCs w:val="30"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="480" w:after="360"/><w:ind w:firstLine="0" w:firstLineChars="0"/><w:jc w:val="center"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:rFonts w:ascii="{heading}" w:hAnsi="{heading}" w:eastAsia="{heading}"/><w:b/><w:sz w:val="36"/><w:szCs w:val="36"/></w:rPr></w:style>
+</w:styles>"#,
+        body = body_font,
+        heading = heading_font,
+        east = east_asia,
+        lang = lang,
+        indent = indent
+    )
+}
+
+fn docx_core_properties(project: &Project) -> String {
+    let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>{}</dc:title><dc:creator>{}</dc:creator><dc:language>{}</dc:language><dcterms:created xsi:type="dcterms:W3CDTF">{}</dcterms:created><dcterms:modified xsi:type="dcterms:W3CDTF">{}</dcterms:modified></cp:coreProperties>"#,
+        xml_escape(&project.title),
+        xml_escape(project.author.as_deref().unwrap_or("")),
+        if project.language == "en" { "en-US" } else { "zh-CN" },
+        now,
+        now
+    )
+}
+
+const DOCX_CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/></Types>"#;
+
+const DOCX_ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#;
+
+const DOCX_DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#;
+
+// 整本书的 PDF 排版：扉页（书名、作者、题材）后每章另起一页，章节标题加入书签
+fn pdf_book(
+    project: &Project,
+    chapters: &[(&Chapter, &str)],
+    font_bytes: Vec<u8>,
+    font_size: f32,
+    line_spacing: f32,
+) -> Result<Vec<u8>> {
+    let mut pdf = PdfLayout::new(&project.title, font_bytes)?;
+    pdf.set_line_spacing(line_spacing);
+
+    pdf.spacing(60.0);
+    pdf.heading(&project.title, font_size * 2.2, false);
+    for line in [project.author.as_deref(), project.genre.as_deref()].into_iter().flatten() {
+        if !line.trim().is_empty() {
+            pdf.paragraph(line, font_size * 1.1, 0.0);
+        }
+    }
+
+    for (chapter, text) in chapters {
+        pdf.page_break();
+        pdf.heading(&chapter.title, font_size * 1.6, true);
+        pdf.spacing(font_size * 0.5);
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            pdf.paragraph(line, font_size, 0.0);
+        }
+    }
+    pdf.finish()
+}
+
+/// 先写临时文件再改名，失败时不留下写了一半的文件、不覆盖已有的同名导出
+pub(crate) async fn write_export_file(output_path: &str, bytes: Vec<u8>) -> Result<()> {
+    if let Some(parent) = Path::new(output_path).parent() {
+        if !parent.as_os_str().is_empty() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+    }
+    let partial_path = format!("{}.part", output_path);
+    tokio::fs::write(&partial_path, bytes).await?;
+    if let Err(e) = tokio::fs::rename(&partial_path, output_path).await {
+        let _ = tokio::fs::remove_file(&partial_path).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+impl ExportService {
+    /// 按选择范围取出要导出的章节（按 order_index 排序）。
+    /// 指定的 id 必须都属于该项目，范围内没有章节时报错。
+    pub async fn select_chapters(
+        pool: &SqlitePool,
+        project_id: &str,
+        selection: &ExportChapterSelection,
+    ) -> Result<Vec<Chapter>> {
+        let chapters = ChapterService::get_by_project(pool, project_id).await?;
+
+        let selected: Vec<Chapter> = match selection.chapter_ids.as_deref() {
+            Some(ids) => {
+                if ids.is_empty() {
+                    return Err(anyhow::anyhow!("No chapters selected for export"));
+                }
+                if let Some(missing) = ids.iter().find(|id| !chapters.iter().any(|c| &c.id == *id)) {
+                    return Err(anyhow::anyhow!("Chapter {} does not belong to this project", missing));
+                }
+                chapters.into_iter().filter(|c| ids.contains(&c.id)).collect()
+            }
+            None => {
+                let from = selection.from_order.unwrap_or(i32::MIN);
+                let to = selection.to_order.unwrap_or(i32::MAX);
+                if from > to {
+                    return Err(anyhow::anyhow!("Invalid chapter range: {} > {}", from, to));
+                }
+                chapters
+                    .into_iter()
+                    .filter(|c| (from..=to).contains(&c.order_index))
+                    .collect()
+            }
+        };
+
+        if selected.is_empty() {
+            return Err(anyhow::anyhow!("No chapters in the selected range"));
+        }
+        Ok(selected)
+    }
+
+    /// 导出为 Word 文档（扉页 + 每章一级标题 + 正文段落），跳过没有正文的章节；
+    /// 章节保留原标题，部分导出时编号与全书一致。
+    /// 各阶段通过 `on_progress` 回报进度；`cancel` 置位后尽快中止，不会留下写了一半的文件
+    pub async fn export_project_docx(
+        pool: &SqlitePool,
+        project_id: &str,
+        output_path: &str,
+        selection: &ExportChapterSelection,
+        cancel: &AtomicBool,
+        on_progress: impl Fn(ExportProgress),
+    ) -> Result<String> {
+        let cancelled = || anyhow::anyhow!("Export cancelled");
+
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        let chapters = Self::select_chapters(pool, project_id, selection).await?;
+        let exported: Vec<(&Chapter, &str)> = chapters
+            .iter()
+            .filter_map(|chapter| chapter_text(chapter).map(|text| (chapter, text)))
+            .collect();
+        if exported.is_empty() {
+            return Err(anyhow::anyhow!("No chapter content to export"));
+        }
+
+        let total = exported.len();
+        let mut body = docx_title_page(&project);
+        for (index, (chapter, text)) in exported.iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(cancelled());
+            }
+            body.push_str(&docx_chapter(chapter, text));
+            on_progress(ExportProgress { phase: "layout", current: index + 1, total });
+            // 让出执行权，长篇导出时取消请求能及时生效
+            tokio::task::yield_now().await;
+        }
+
+        let parts = [
+            ("[Content_Types].xml", DOCX_CONTENT_TYPES.to_string()),
+            ("_rels/.rels", DOCX_ROOT_RELS.to_string()),
+            ("word/_rels/document.xml.rels", DOCX_DOCUMENT_RELS.to_string()),
+            ("word/document.xml", docx_document(&body)),
+            ("word/styles.xml", docx_styles(&project.language)),
+            ("docProps/core.xml", docx_core_properties(&project)),
+        ];
+        drop(body);
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let part_count = parts.len();
+        for (index, (name, content)) in parts.into_iter().enumerate() {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(cancelled());
+            }
+            zip.start_file(name, options)?;
+            zip.write_all(content.as_bytes())?;
+            on_progress(ExportProgress { phase: "rendering", current: index + 1, total: part_count });
+        }
+        let bytes = zip.finish()?.into_inner();
+
+        if cancel.load(Ordering::SeqCst) {
+            return Err(cancelled());
+        }
+        on_progress(ExportProgress { phase: "writing", current: 0, total: 1 });
+        if let Some(parent) = Path::new(output_path).parent() {
+            if !parent.as_os_str().is_empty() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+        // 先写临时文件再改名：取消或失败时删除临时文件，不覆盖已有的同名导出
+        let partial_path = format!("{}.part", output_path);
+        let written = async {
+            tokio::fs::write(&partial_path, bytes).await?;
+            if cancel.load(Ordering::SeqCst) {
+                return Err(cancelled());
+            }
+            tokio::fs::rename(&partial_path, output_path).await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            if let Err(remove_error) = tokio::fs::remove_file(&partial_path).await {
+                if remove_error.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to remove partial export {}: {}", partial_path, remove_error);
+                }
+            }
+            return Err(e);
+        }
+        on_progress(ExportProgress { phase: "writing", current: 1, total: 1 });
+
+        log::info!(
+            "Exported {} chapters of project {} to {}",
+            exported.len(),
+            project_id,
+            output_path
+        );
+        Ok(output_path.to_string())
+    }
+
+    // 按 order_index 取出有正文的章节，都没有正文时报错
+    async fn chapters_with_text(pool: &SqlitePool, project_id: &str) -> Result<(Project, Vec<(Chapter, String)>)> {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        let chapters: Vec<(Chapter, String)> = ChapterService::get_by_project(pool, project_id)
+            .await?
+            .into_iter()
+            .filter_map(|chapter| {
+                let text = chapter_text(&chapter)?.trim().to_string();
+                Some((chapter, text))
+            })
+            .collect();
+        if chapters.is_empty() {
+            return Err(anyhow::anyhow!("No chapter content to export"));
+        }
+        Ok((project, chapters))
+    }
+
+    /// 每章一个 Markdown 文件（{序号:03}-{标题}.md），另写 index.md 列出各章链接；跳过没有正文的章节
+    pub async fn export_markdown(pool: &SqlitePool, project_id: &str, output_dir: &str) -> Result<TextExportResult> {
+        let (project, chapters) = Self::chapters_with_text(pool, project_id).await?;
+        tokio::fs::create_dir_all(output_dir).await?;
+
+        let mut index = format!("# {}\n\n", project.title);
+        if let Some(author) = project.author.as_deref().filter(|a| !a.trim().is_empty()) {
+            index.push_str(&format!("{}\n\n", author.trim()));
+        }
+        let mut files = Vec::with_capacity(chapters.len() + 1);
+        let mut word_count = 0;
+        for (chapter, text) in &chapters {
+            let file_name = format!("{:03}-{}.md", chapter.order_index, file_stem(&chapter.title));
+            let path = Path::new(output_dir).join(&file_name);
+            tokio::fs::write(&path, format!("# {}\n\n{}\n", chapter.title.trim(), text)).await?;
+            index.push_str(&format!("- [{}]({})\n", chapter.title.trim(), urlencoding::encode(&file_name)));
+            files.push(path.to_string_lossy().to_string());
+            word_count += body_word_count(text);
+        }
+
+        let index_path = Path::new(output_dir).join("index.md");
+        tokio::fs::write(&index_path, index).await?;
+        files.push(index_path.to_string_lossy().to_string());
+
+        log::info!("Exported {} chapters of project {} to Markdown in {}", chapters.len(), project_id, output_dir);
+        Ok(TextExportResult {
+            path: output_dir.to_string(),
+            files,
+            chapter_count: chapters.len(),
+            word_count,
+        })
+    }
+
+    /// 全部章节合并为一个 TXT 文件：每章以标题开头，章节之间空一行；跳过没有正文的章节
+    pub async fn export_txt(pool: &SqlitePool, project_id: &str, output_path: &str) -> Result<TextExportResult> {
+        let (_, chapters) = Self::chapters_with_text(pool, project_id).await?;
+        let content = chapters
+            .iter()
+            .map(|(chapter, text)| format!("{}\n\n{}", chapter.title.trim(), text))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let word_count = chapters.iter().map(|(_, text)| body_word_count(text)).sum();
+
+        write_export_file(output_path, format!("{}\n", content).into_bytes()).await?;
+        log::info!("Exported {} chapters of project {} to TXT {}", chapters.len(), project_id, output_path);
+        Ok(TextExportResult {
+            path: output_path.to_string(),
+            files: vec![output_path.to_string()],
+            chapter_count: chapters.len(),
+            word_count,
+        })
+    }
+
+    /// 导出整本书为 PDF（嵌入 font_bytes 字体，中日韩文字逐字断行，页脚加页码），跳过没有正文的章节
+    pub async fn export_project_pdf(
+        pool: &SqlitePool,
+        project_id: &str,
+        output_path: &str,
+        font_bytes: Vec<u8>,
+        font_size: f32,
+        line_spacing: f32,
+    ) -> Result<String> {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        let chapters = ChapterService::get_by_project(pool, project_id).await?;
+        if !chapters.iter().any(|chapter| chapter_text(chapter).is_some()) {
+            return Err(anyhow::anyhow!("No chapter content to export"));
+        }
+
+        // printpdf 的文档对象不是 Send，整个排版放在阻塞线程里完成
+        let bytes = tokio::task::spawn_blocking(move || {
+            let exported: Vec<(&Chapter, &str)> = chapters
+                .iter()
+                .filter_map(|chapter| chapter_text(chapter).map(|text| (chapter, text)))
+                .collect();
+            pdf_book(&project, &exported, font_bytes, font_size, line_spacing)
+        })
+        .await??;
+
+        write_export_file(output_path, bytes).await?;
+        log::info!("Exported project {} to PDF {}", project_id, output_path);
+        Ok(output_path.to_string())
+    }
+}
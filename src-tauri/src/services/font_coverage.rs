@@ -0,0 +1,225 @@
+//! 字体字形覆盖检测
+//!
+//! 只读取字体文件的表目录和 cmap 表，判断稿件中实际用到的字符是否都有字形，
+//! 避免导出 PDF 时生僻字（常见于奇幻人名、地名）显示为方框。
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use super::{ChapterService, CharacterService, LoreService, ProjectService};
+
+/// 参与检测的不同字符数上限
+const MAX_SAMPLE_CHARS: usize = 8000;
+/// 返回的缺字示例数量
+const MAX_MISSING_EXAMPLES: usize = 30;
+
+/// 字体文件名只能是字体目录下的单个文件，不能带路径或 ..
+pub(crate) fn is_safe_file_name(file_name: &str) -> bool {
+    if file_name.trim().is_empty() {
+        return false;
+    }
+    if file_name.contains('/') || file_name.contains('\\') {
+        return false;
+    }
+    if file_name.contains("..") {
+        return false;
+    }
+    true
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontCoverage {
+    pub sampled_chars: usize,
+    pub covered_chars: usize,
+    pub coverage_percent: f64,
+    /// 缺少字形的字符示例
+    pub missing_chars: Vec<String>,
+}
+
+/// 收集项目中出现过的非 ASCII 字符（书名、章节正文与标题、角色、设定）
+pub async fn collect_project_chars(pool: &SqlitePool, project_id: &str) -> Result<Vec<char>> {
+    let mut chars = BTreeSet::new();
+    let mut add = |text: &str| {
+        chars.extend(text.chars().filter(|c| !c.is_ascii() && !c.is_whitespace() && !c.is_control()));
+    };
+
+    if let Some(project) = ProjectService::get_by_id(pool, project_id).await? {
+        add(&project.title);
+    }
+    for chapter in ChapterService::get_by_project(pool, project_id).await? {
+        add(&chapter.title);
+        for text in [&chapter.draft_text, &chapter.final_text].into_iter().flatten() {
+            add(text);
+        }
+    }
+    for character in CharacterService::get_by_project(pool, project_id).await? {
+        add(&character.name);
+    }
+    for entry in LoreService::get_by_project(pool, project_id).await? {
+        add(&entry.title);
+        if let Some(ref content) = entry.content {
+            add(content);
+        }
+    }
+
+    Ok(chars.into_iter().take(MAX_SAMPLE_CHARS).collect())
+}
+
+/// 计算字体对给定字符的覆盖情况
+pub fn measure_coverage(font_path: &Path, sample: &[char]) -> Result<FontCoverage> {
+    let cmap = read_cmap_table(font_path)?;
+    let subtable = CmapSubtable::select(&cmap)
+        .ok_or_else(|| anyhow::anyhow!("Font has no supported Unicode cmap subtable"))?;
+
+    let mut covered = 0;
+    let mut missing = Vec::new();
+    for &c in sample {
+        if subtable.has_glyph(c as u32) {
+            covered += 1;
+        } else if missing.len() < MAX_MISSING_EXAMPLES {
+            missing.push(c.to_string());
+        }
+    }
+
+    let coverage_percent = if sample.is_empty() {
+        100.0
+    } else {
+        (covered as f64 / sample.len() as f64 * 1000.0).round() / 10.0
+    };
+
+    Ok(FontCoverage {
+        sampled_chars: sample.len(),
+        covered_chars: covered,
+        coverage_percent,
+        missing_chars: missing,
+    })
+}
+
+// 只读取表目录和 cmap，CJK 字体通常有十几 MB，不必整个读入
+fn read_cmap_table(path: &Path) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    let num_tables = read_u16(&header, 4).unwrap_or(0) as usize;
+
+    let mut records = vec![0u8; num_tables * 16];
+    file.read_exact(&mut records)?;
+    for record in records.chunks_exact(16) {
+        if &record[0..4] == b"cmap" {
+            let offset = read_u32(record, 8).unwrap_or(0) as u64;
+            let length = read_u32(record, 12).unwrap_or(0) as usize;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut table = vec![0u8; length];
+            file.read_exact(&mut table)?;
+            return Ok(table);
+        }
+    }
+
+    Err(anyhow::anyhow!("Font has no cmap table"))
+}
+
+enum CmapSubtable<'a> {
+    /// 分段映射，仅覆盖 BMP
+    Format4(&'a [u8]),
+    /// 分组映射，覆盖全部 Unicode（含扩展区汉字）
+    Format12(&'a [u8]),
+}
+
+impl<'a> CmapSubtable<'a> {
+    // 优先选完整 Unicode 的 format 12，其次 BMP 的 format 4
+    fn select(cmap: &'a [u8]) -> Option<Self> {
+        let num_tables = read_u16(cmap, 2)? as usize;
+        let mut bmp = None;
+        for index in 0..num_tables {
+            let record = 4 + index * 8;
+            let platform = read_u16(cmap, record)?;
+            let encoding = read_u16(cmap, record + 2)?;
+            let offset = read_u32(cmap, record + 4)? as usize;
+            let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+            if !unicode || offset >= cmap.len() {
+                continue;
+            }
+            let data = &cmap[offset..];
+            match read_u16(data, 0)? {
+                12 => return Some(Self::Format12(data)),
+                4 if bmp.is_none() => bmp = Some(Self::Format4(data)),
+                _ => {}
+            }
+        }
+        bmp
+    }
+
+    fn has_glyph(&self, code: u32) -> bool {
+        match self {
+            Self::Format4(data) => format4_glyph(data, code).unwrap_or(0) != 0,
+            Self::Format12(data) => format12_glyph(data, code).unwrap_or(0) != 0,
+        }
+    }
+}
+
+fn format4_glyph(data: &[u8], code: u32) -> Option<u32> {
+    if code > 0xFFFF {
+        return Some(0);
+    }
+    let seg_count = read_u16(data, 6)? as usize / 2;
+    let end_codes = 14;
+    let start_codes = end_codes + seg_count * 2 + 2;
+    let id_deltas = start_codes + seg_count * 2;
+    let id_range_offsets = id_deltas + seg_count * 2;
+
+    for segment in 0..seg_count {
+        let end = read_u16(data, end_codes + segment * 2)? as u32;
+        if end < code {
+            continue;
+        }
+        let start = read_u16(data, start_codes + segment * 2)? as u32;
+        if start > code {
+            return Some(0);
+        }
+        let delta = read_u16(data, id_deltas + segment * 2)? as u32;
+        let range_offset_pos = id_range_offsets + segment * 2;
+        let range_offset = read_u16(data, range_offset_pos)? as usize;
+        if range_offset == 0 {
+            return Some((code + delta) & 0xFFFF);
+        }
+        let glyph_pos = range_offset_pos + range_offset + (code - start) as usize * 2;
+        let glyph = read_u16(data, glyph_pos)? as u32;
+        return Some(if glyph == 0 { 0 } else { (glyph + delta) & 0xFFFF });
+    }
+    Some(0)
+}
+
+fn format12_glyph(data: &[u8], code: u32) -> Option<u32> {
+    let groups = read_u32(data, 12)? as usize;
+    // 分组按起始码点升序排列，二分查找
+    let (mut low, mut high) = (0, groups);
+    while low < high {
+        let mid = (low + high) / 2;
+        let group = 16 + mid * 12;
+        let start = read_u32(data, group)?;
+        let end = read_u32(data, group + 4)?;
+        if code < start {
+            high = mid;
+        } else if code > end {
+            low = mid + 1;
+        } else {
+            return Some(read_u32(data, group + 8)? + (code - start));
+        }
+    }
+    Some(0)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
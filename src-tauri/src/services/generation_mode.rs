@@ -0,0 +1,69 @@
+//! 章节生成模式
+//!
+//! 把模型、温度、篇幅和生成后的润色/设定检查打包成一键可选的预设：
+//! 快速草稿追求产量，精修追求质量。
+
+use serde::Serialize;
+use crate::models::{GenerationModeSettings, TextModelConfigInput};
+
+/// 精修模式润色时使用的修订目标
+pub const POLISH_REVISION_GOALS: &str =
+    "润色文字，增强场景画面感和人物对话的表现力，修正不通顺的句子；保持情节、人物设定和章节结构不变";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationMode {
+    pub key: &'static str,
+    pub label: &'static str,
+    /// 覆盖文本模型配置中的模型，None 表示沿用
+    pub model: Option<String>,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    /// 生成后追加一轮润色
+    pub revise: bool,
+    /// 最终正文做一次设定一致性检查
+    pub consistency_check: bool,
+}
+
+impl GenerationMode {
+    /// 应用到文本模型配置（模型和温度）
+    pub fn apply(&self, config: &TextModelConfigInput) -> TextModelConfigInput {
+        let mut config = config.clone();
+        if let Some(model) = self.model.as_deref() {
+            config.model = model.to_string();
+        }
+        config.temperature = self.temperature;
+        config
+    }
+}
+
+fn model_override(model: &str) -> Option<String> {
+    Some(model.trim()).filter(|m| !m.is_empty()).map(str::to_string)
+}
+
+pub fn generation_modes(settings: &GenerationModeSettings) -> Vec<GenerationMode> {
+    vec![
+        GenerationMode {
+            key: "fast_draft",
+            label: "快速草稿",
+            model: model_override(&settings.fast_draft_model),
+            temperature: 0.95,
+            max_tokens: 3500,
+            revise: false,
+            consistency_check: false,
+        },
+        GenerationMode {
+            key: "polished",
+            label: "精修",
+            model: model_override(&settings.polished_model),
+            temperature: 0.7,
+            max_tokens: 6000,
+            revise: true,
+            consistency_check: true,
+        },
+    ]
+}
+
+pub fn find_generation_mode(key: &str, settings: &GenerationModeSettings) -> Option<GenerationMode> {
+    let key = key.trim().to_ascii_lowercase().replace('-', "_");
+    generation_modes(settings).into_iter().find(|mode| mode.key == key)
+}
@@ -1,12 +1,30 @@
 use anyhow::Result;
 use crate::api::{DeepSeekClient, PollinationsClient};
-use crate::api::deepseek::{GenerationParams, prompts as deepseek_prompts};
-use crate::api::pollinations::ImageGenerationParams;
-
+use crate::api::deepseek::{ChatMessage, GenerationParams, prompts as deepseek_prompts};
+use crate::api::pollinations::{ImageDownloadProgress, ImageGenerationParams};
+use crate::models::{Chapter, Character, ChatTurn};
+use super::character_service::CharacterField;
+use super::narrative_check::{pov_label, tense_label};
+use super::prompt_guard::{data_boundary_notice, sanitize_inline, wrap_user_field};
+use std::sync::atomic::AtomicBool;
+
+/// 生成摘要时送入模型的正文上限（字符）
+const SUMMARY_INPUT_CHARS: usize = 20000;
+/// 拟定章节标题时送入模型的正文上限（字符）
+const TITLE_INPUT_CHARS: usize = 8000;
+
+#[derive(Clone)]
 pub struct GenerationService {
     deepseek: Option<DeepSeekClient>,
     pollinations: Option<PollinationsClient>,
     text_temperature: Option<f32>,
+    text_seed: Option<i64>,
+    /// 追加到章节系统提示词的语言要求（语言检查重试时使用）
+    language_notice: Option<String>,
+    /// 章节生成的 max_tokens，未设置时为 6000（生成模式调整篇幅时使用）
+    chapter_max_tokens: Option<u32>,
+    /// 章节目标字数，未设置时提示词要求 3000-5000 字（章节覆盖设置中的目标字数）
+    chapter_target_words: Option<u32>,
 }
 
 impl GenerationService {
@@ -14,7 +32,7 @@ impl GenerationService {
         deepseek_key: Option<String>,
         pollinations_key: Option<String>,
     ) -> Self {
-        Self::new_with_text_config(deepseek_key, None, None, None, pollinations_key)
+        Self::new_with_text_config(deepseek_key, None, None, None, None, pollinations_key)
     }
 
     pub fn new_with_text_config(
@@ -22,6 +40,7 @@ impl GenerationService {
         deepseek_base_url: Option<String>,
         deepseek_model: Option<String>,
         text_temperature: Option<f32>,
+        text_seed: Option<i64>,
         pollinations_key: Option<String>,
     ) -> Self {
         let deepseek = deepseek_key.map(|key| {
@@ -33,6 +52,45 @@ impl GenerationService {
             deepseek,
             pollinations,
             text_temperature: text_temperature.map(|v| v.clamp(0.0, 2.0)),
+            text_seed,
+            language_notice: None,
+            chapter_max_tokens: None,
+            chapter_target_words: None,
+        }
+    }
+
+    /// 覆盖文本生成的 Temperature 和 seed（复用缓存的服务时按次设置）
+    pub fn with_sampling(mut self, temperature: f32, seed: Option<i64>) -> Self {
+        self.text_temperature = Some(temperature.clamp(0.0, 2.0));
+        self.text_seed = seed;
+        self
+    }
+
+    /// 关闭相同请求合并（生成多个变体时每次都要独立采样）
+    pub fn without_request_dedup(mut self) -> Self {
+        self.deepseek = self.deepseek.map(DeepSeekClient::without_request_dedup);
+        self
+    }
+
+    pub fn with_language_notice(mut self, notice: &str) -> Self {
+        self.language_notice = Some(notice.to_string());
+        self
+    }
+
+    pub fn with_chapter_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.chapter_max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_chapter_target_words(mut self, target_words: Option<u32>) -> Self {
+        self.chapter_target_words = target_words;
+        self
+    }
+
+    fn chapter_system_prompt(&self) -> String {
+        match self.language_notice {
+            Some(ref notice) => format!("{}\n\n{}", deepseek_prompts::chapter_system_prompt(), notice),
+            None => deepseek_prompts::chapter_system_prompt(),
         }
     }
 
@@ -71,7 +129,8 @@ impl GenerationService {
 
 书名：{}
 题材：{}
-简介：{}
+简介：
+{}
 目标章节数：{}
 
 请生成包含以下内容的大纲：
@@ -82,13 +141,21 @@ impl GenerationService {
 5. 每章大纲（包含章节标题、目标、冲突点、信息增量）
 
 请以结构化的方式输出，便于后续处理。"#,
-            title, genre, description, target_chapters
+            sanitize_inline(title),
+            sanitize_inline(genre),
+            wrap_user_field("简介", description),
+            target_chapters
         );
 
         let params = GenerationParams {
             temperature: Some(self.effective_temperature(0.8)),
             max_tokens: Some(4000),
-            system_prompt: Some(deepseek_prompts::outline_system_prompt()),
+            system_prompt: Some(format!(
+                "{}\n\n{}",
+                deepseek_prompts::outline_system_prompt(),
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
         };
 
         let (content, usage) = client.generate_text(&prompt, Some(params)).await?;
@@ -134,7 +201,11 @@ impl GenerationService {
             prompt.push_str(&format!("\n世界观：\n{}\n", world));
         }
 
-        prompt.push_str("\n请撰写完整章节内容（3000-5000字），注意：\n");
+        let length = match self.chapter_target_words {
+            Some(words) => format!("约{}字", words),
+            None => "3000-5000字".to_string(),
+        };
+        prompt.push_str(&format!("\n请撰写完整章节内容（{}），注意：\n", length));
         prompt.push_str("1. 保持人物性格一致\n");
         prompt.push_str("2. 场景描写要有画面感\n");
         prompt.push_str("3. 对话要自然生动\n");
@@ -142,8 +213,13 @@ impl GenerationService {
 
         let params = GenerationParams {
             temperature: Some(self.effective_temperature(0.7)),
-            max_tokens: Some(6000),
-            system_prompt: Some(deepseek_prompts::chapter_system_prompt()),
+            max_tokens: Some(
+                self.chapter_max_tokens
+                    .or(self.chapter_target_words.map(|words| (words * 2).clamp(2000, 8000)))
+                    .unwrap_or(6000),
+            ),
+            system_prompt: Some(self.chapter_system_prompt()),
+            seed: self.text_seed,
         };
 
         let (content, usage) = client.generate_text(&prompt, Some(params)).await?;
@@ -183,6 +259,7 @@ impl GenerationService {
             temperature: Some(self.effective_temperature(0.7)),
             max_tokens: Some(2000),
             system_prompt: Some(deepseek_prompts::chapter_system_prompt()),
+            seed: self.text_seed,
         };
 
         let (content, _) = client.generate_text(&prompt, Some(params)).await?;
@@ -210,6 +287,619 @@ impl GenerationService {
             temperature: Some(self.effective_temperature(0.5)),
             max_tokens: Some(6000),
             system_prompt: Some(deepseek_prompts::revision_system_prompt()),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 按指示只重写角色的某一个字段，其余设定作为上下文保持不变
+    pub async fn regenerate_character_field(
+        &self,
+        character: &Character,
+        field: CharacterField,
+        instructions: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let sheet_field = |value: Option<&str>| {
+            value.map(str::trim).filter(|v| !v.is_empty()).unwrap_or("（未填写）").to_string()
+        };
+        let sheet = format!(
+            "姓名：{}\n身份：{}\n简介：{}\n性格：{}\n背景：{}\n动机：{}\n说话风格：{}",
+            sheet_field(Some(&character.name)),
+            sheet_field(character.role.as_deref()),
+            sheet_field(character.description.as_deref()),
+            sheet_field(character.personality.as_deref()),
+            sheet_field(character.background.as_deref()),
+            sheet_field(character.motivation.as_deref()),
+            sheet_field(character.voice_style.as_deref()),
+        );
+
+        let prompt = format!(
+            r#"以下是一个小说角色的完整设定：
+
+{}
+
+请只重写该角色的「{}」字段，其余设定视为既定事实，新内容不得与之矛盾。
+
+修改要求：
+{}
+
+只输出新的「{}」内容本身，不要输出字段名、其他字段或任何解释。"#,
+            wrap_user_field("角色设定", &sheet),
+            field.label(),
+            wrap_user_field("修改要求", instructions),
+            field.label()
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.7)),
+            max_tokens: Some(1000),
+            system_prompt: Some(format!(
+                "你是一位专业的小说人物设定师。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        let value = strip_field_label(&content, field.label());
+        if value.is_empty() {
+            return Err(anyhow::anyhow!("Model returned an empty {}", field.column()));
+        }
+        Ok(value)
+    }
+
+    /// 按修改要求重写单章大纲（目标/冲突/结尾钩子），前后章节大纲作为上下文；
+    /// 返回模型原始 JSON 回复
+    pub async fn regenerate_outline_entry(
+        &self,
+        chapter: &Chapter,
+        surrounding: &[&Chapter],
+        instructions: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let field = |value: Option<&str>| {
+            value.map(str::trim).filter(|v| !v.is_empty()).unwrap_or("（未填写）").to_string()
+        };
+        let context = surrounding
+            .iter()
+            .map(|c| {
+                format!(
+                    "- [{}] {}：{}（冲突：{}）",
+                    c.order_index,
+                    sanitize_inline(&c.title),
+                    field(c.outline_goal.as_deref()),
+                    field(c.conflict.as_deref())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let current = format!(
+            "章节：[{}] {}\n本章目标：{}\n核心冲突：{}\n结尾钩子：{}",
+            chapter.order_index,
+            sanitize_inline(&chapter.title),
+            field(chapter.outline_goal.as_deref()),
+            field(chapter.conflict.as_deref()),
+            field(chapter.cliffhanger.as_deref())
+        );
+
+        let prompt = format!(
+            r#"下面是小说中某一章的大纲，以及它前后章节的大纲。请只重写这一章的大纲，使其与前后章节自然衔接、不重复前后章节的情节。
+
+前后章节大纲：
+{}
+
+需要重写的章节：
+{}
+
+修改要求：
+{}
+
+严格按JSON格式输出：
+{{"outline_goal": "本章目标", "conflict": "核心冲突", "cliffhanger": "结尾钩子"}}"#,
+            wrap_user_field("前后章节", if context.is_empty() { "（无）" } else { &context }),
+            wrap_user_field("当前章节", &current),
+            wrap_user_field("修改要求", instructions)
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.7)),
+            max_tokens: Some(1000),
+            system_prompt: Some(format!(
+                "你是一位专业的小说大纲策划，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 多轮自由对话，system_prompt 由调用方拼好（人设与项目资料）
+    pub async fn chat(&self, system_prompt: &str, turns: &[ChatTurn]) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let messages = turns
+            .iter()
+            .map(|turn| ChatMessage {
+                role: turn.role.clone(),
+                content: turn.content.clone(),
+            })
+            .collect();
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.8)),
+            max_tokens: Some(2000),
+            system_prompt: Some(system_prompt.to_string()),
+            seed: self.text_seed,
+        };
+
+        let response = client.chat_completion(messages, Some(params)).await?;
+        let content = response.choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No choices in response"))?
+            .message
+            .content;
+        Ok(content)
+    }
+
+    /// 对照时间线与各章摘要检查时序漏洞，返回模型原始 JSON 回复。
+    /// 章节以 [C序号] 标记，时间线事件以 [E序号] 标记
+    pub async fn check_timeline(
+        &self,
+        timeline: &str,
+        chapter_summaries: &str,
+        character_names: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let prompt = format!(
+            r#"请检查这部小说的时间线与章节内容是否存在连贯性问题：
+1. order：事件在章节中出现的顺序与时间线不符（如提前引用尚未发生的事件）
+2. contradiction：章节内容与时间线事件互相矛盾
+3. character：角色在被引入之前就已出场或行动
+4. other：其他时序或因果漏洞
+
+时间线（按时间顺序）：
+{}
+
+章节摘要（按章节顺序）：
+{}
+
+主要角色：
+{}
+
+只报告确实存在的问题，没有问题时返回空列表。chapters 填写涉及的章节序号（[C序号] 中的数字），events 填写涉及的事件序号（[E序号] 中的数字）。严格按JSON格式输出：
+{{"issues": [{{"type": "order|contradiction|character|other", "description": "问题说明", "chapters": [1], "events": [1]}}]}}"#,
+            wrap_user_field("时间线", timeline),
+            wrap_user_field("章节摘要", chapter_summaries),
+            wrap_user_field("角色", if character_names.is_empty() { "（无）" } else { character_names })
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.2)),
+            max_tokens: Some(2000),
+            system_prompt: Some(format!(
+                "你是一位严谨的小说连贯性审校编辑，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 根据章节摘要找出伏笔及其回收章节
+    pub async fn find_foreshadowing(&self, chapter_summaries: &str) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let prompt = format!(
+            r#"请根据以下各章摘要，找出小说中埋下的伏笔（setup）及其回收（payoff）：
+- 伏笔：刻意提及、后文可能兑现的物品、承诺、预言、秘密、异常细节或未解之谜
+- 回收：后续章节中兑现、揭示或呼应该伏笔的情节
+- 只列出有明确文本依据的伏笔，不要把普通情节当作伏笔
+- 尚未回收的伏笔 payoffs 留空
+
+章节摘要（按章节顺序）：
+{}
+
+chapter 与 payoffs 填写章节序号（[C序号] 中的数字）。严格按JSON格式输出：
+{{"foreshadowing": [{{"element": "伏笔内容", "setup": 1, "payoffs": [5], "note": "如何回收或为何尚未回收"}}]}}"#,
+            wrap_user_field("章节摘要", chapter_summaries)
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.3)),
+            max_tokens: Some(3000),
+            system_prompt: Some(format!(
+                "你是一位擅长分析长篇小说结构的编辑，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 检查编号段落是否偏离期望的叙述人称/时态；rewrite 为 true 时附带修正后的段落
+    pub async fn check_narrative(
+        &self,
+        numbered_paragraphs: &str,
+        pov: Option<&str>,
+        tense: Option<&str>,
+        rewrite: bool,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let mut expectations = Vec::new();
+        if let Some(pov) = pov {
+            expectations.push(format!("- 叙述人称（kind 为 pov）：{}", pov_label(pov)));
+        }
+        if let Some(tense) = tense {
+            expectations.push(format!("- 叙述时态（kind 为 tense）：{}", tense_label(tense)));
+        }
+        let rewrite_rule = if rewrite {
+            "\n- rewrite 给出修正后的整段文字：只改人称或时态，保留原有情节、对白和文风"
+        } else {
+            ""
+        };
+
+        let prompt = format!(
+            r#"下面是小说正文，每段以 [P编号] 开头。本书应保持：
+{}
+
+请找出叙述部分偏离上述要求的段落：
+- 只看叙述，对白、引语、书信和角色内心独白中的人称与时态不算偏离
+- 中文没有动词时态变化，时态以时间词和“了/着/正在”等体现，只标出明显混用的段落
+- 没有问题时 issues 为空数组{}
+
+正文：
+{}
+
+paragraph 填写段落编号数字，detected 为实际使用的人称或时态。严格按JSON格式输出：
+{{"issues": [{{"paragraph": 1, "kind": "pov", "detected": "第一人称", "reason": "偏离原因"{}}}]}}"#,
+            expectations.join("\n"),
+            rewrite_rule,
+            wrap_user_field("正文", numbered_paragraphs),
+            if rewrite { r#", "rewrite": "修正后的段落""# } else { "" }
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.2)),
+            max_tokens: Some(if rewrite { 6000 } else { 2000 }),
+            system_prompt: Some(format!(
+                "你是一位严谨的小说校对编辑，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 重写章节中未锁定的部分：annotated 为按 [GAP编号]/[LOCK编号] 标注的原文，
+    /// 返回 JSON，gaps 依次为每个间隙的新内容
+    pub async fn regenerate_with_locks(
+        &self,
+        chapter_title: &str,
+        outline_goal: &str,
+        conflict: &str,
+        annotated: &str,
+        gap_count: usize,
+        instructions: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let instructions = if instructions.trim().is_empty() {
+            String::new()
+        } else {
+            format!("\n修改要求：\n{}\n", wrap_user_field("修改要求", instructions))
+        };
+        let prompt = format!(
+            r#"请重写小说章节《{}》中未锁定的部分。
+本章目标：{}
+核心冲突：{}
+{}
+下面的原文按顺序分为间隙 [GAP编号] 和锁定段落 [LOCK编号]：
+- 锁定段落是固定锚点，会原样保留在最终正文中，你不能修改、删除或复述它们
+- 重写每个间隙，使其与前后的锁定段落自然衔接，不与锁定段落的情节矛盾
+- 间隙可以比原文长或短；原文为空的间隙可以留空，也可以补写过渡
+- 保持原文的人称、时态和文风，不要使用markdown，不要添加说明
+
+{}
+
+gaps 必须恰好包含 {} 个字符串，依次对应 GAP1 到 GAP{}。严格按JSON格式输出：
+{{"gaps": ["GAP1 的新内容", "..."]}}"#,
+            sanitize_inline(chapter_title),
+            sanitize_inline(outline_goal),
+            sanitize_inline(conflict),
+            instructions,
+            wrap_user_field("章节原文", annotated),
+            gap_count,
+            gap_count
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.7)),
+            max_tokens: Some(self.chapter_max_tokens.unwrap_or(8000)),
+            system_prompt: Some(format!(
+                "你是一位专业的小说作者，擅长在保留指定段落的前提下改写章节，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 根据上一章结尾和下一章开头生成过渡段落，以及下一章开头的改写建议
+    pub async fn generate_transition(
+        &self,
+        prev_title: &str,
+        prev_tail: &str,
+        next_title: &str,
+        next_head: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let prompt = format!(
+            r#"下面是相邻两章的衔接处，两章之间的过渡显得生硬。请给出两种处理方式：
+1. bridge：一段可以插在上一章结尾之后的过渡文字（100-300字），承接上一章的情绪和场景，自然引出下一章的开头
+2. opening_rewrite：改写下一章的开头段落，使其与上一章结尾顺畅衔接，保留原有情节信息
+
+上一章《{}》结尾：
+{}
+
+下一章《{}》开头：
+{}
+
+保持原文的叙述视角、人称和文风，不要引入新的情节或角色。严格按JSON格式输出：
+{{"bridge": "过渡段落", "opening_rewrite": "改写后的开头"}}"#,
+            sanitize_inline(prev_title),
+            wrap_user_field("上一章结尾", prev_tail),
+            sanitize_inline(next_title),
+            wrap_user_field("下一章开头", next_head)
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.7)),
+            max_tokens: Some(2000),
+            system_prompt: Some(format!(
+                "你是一位擅长处理章节衔接的小说编辑，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 分析章节情绪，生成适用于 AI 音乐生成工具的英文配乐提示词
+    pub async fn generate_ambiance(&self, chapter_title: &str, text: &str) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let prompt = format!(
+            r#"请分析下面这一章的情绪基调和场景氛围，为它设计一段配乐/环境音，用于 AI 音乐生成工具（如 Suno、Udio）。
+
+章节标题：{}
+
+{}
+
+要求：
+- prompt：一段英文描述（40-80词），包含曲风、节奏、配器、情绪走向和环境音，不要出现人名和剧情细节
+- tempo：英文节奏描述，如 "slow, around 70 BPM"
+- instrumentation：英文乐器列表
+- mood：英文情绪关键词
+- tags：3-8 个简短英文标签（小写）
+
+严格按JSON格式输出：
+{{"prompt": "...", "tempo": "...", "instrumentation": ["..."], "mood": ["..."], "tags": ["..."]}}"#,
+            sanitize_inline(chapter_title),
+            wrap_user_field("章节正文", text)
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.7)),
+            max_tokens: Some(800),
+            system_prompt: Some(format!(
+                "你是一位影视与游戏配乐设计师，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 生成章节摘要，供后续章节作为前情提要注入
+    pub async fn summarize_chapter(&self, chapter_title: &str, text: &str, output_language: &str) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let text: String = text.chars().take(SUMMARY_INPUT_CHARS).collect();
+        let (prompt, system_prompt) = if output_language == "en" {
+            (
+                format!(
+                    "Summarize the following chapter in 100-200 words. Cover the key events, character changes and any unresolved threads, in the order they happen.\n\nChapter title: {}\n\n{}\n\nOutput the summary only.",
+                    sanitize_inline(chapter_title),
+                    wrap_user_field("chapter", &text)
+                ),
+                format!("You are an editor who writes precise plot summaries.\n\n{}", data_boundary_notice("en")),
+            )
+        } else {
+            (
+                format!(
+                    "请用150-300字概括以下章节，按发生顺序写清关键事件、人物状态变化和尚未解决的悬念。\n\n章节标题：{}\n\n{}\n\n只输出摘要本身。",
+                    sanitize_inline(chapter_title),
+                    wrap_user_field("章节正文", &text)
+                ),
+                format!("你是一位擅长提炼剧情的小说编辑。\n\n{}", data_boundary_notice("zh")),
+            )
+        };
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.3)),
+            max_tokens: Some(800),
+            system_prompt: Some(system_prompt),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        let summary = content.trim().to_string();
+        if summary.is_empty() {
+            return Err(anyhow::anyhow!("Model returned an empty summary"));
+        }
+        Ok(summary)
+    }
+
+    /// 根据章节内容拟定 count 个候选标题，返回 JSON {"titles": [...]}
+    pub async fn suggest_chapter_titles(
+        &self,
+        current_title: &str,
+        text: &str,
+        count: usize,
+        output_language: &str,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let text: String = text.chars().take(TITLE_INPUT_CHARS).collect();
+        let (prompt, system_prompt) = if output_language == "en" {
+            (
+                format!(
+                    "Suggest {} candidate titles for the following chapter. Each title should be short (2-8 words), hint at the chapter's key event or image without spoiling the ending, and differ from the others in angle. Do not include chapter numbers.\n\nCurrent title: {}\n\n{}\n\nOutput strictly in JSON:\n{{\"titles\": [\"Title\"]}}",
+                    count,
+                    sanitize_inline(current_title),
+                    wrap_user_field("chapter", &text)
+                ),
+                format!("You are a novel editor who writes evocative chapter titles. Output JSON only.\n\n{}", data_boundary_notice("en")),
+            )
+        } else {
+            (
+                format!(
+                    "请为以下章节拟定 {} 个候选标题。每个标题简短（2-10字），点出本章关键事件或意象但不剧透结局，各标题角度不同，不要带“第N章”之类的序号。\n\n当前标题：{}\n\n{}\n\n严格按JSON格式输出：\n{{\"titles\": [\"标题\"]}}",
+                    count,
+                    sanitize_inline(current_title),
+                    wrap_user_field("章节正文", &text)
+                ),
+                format!("你是一位擅长拟定章节标题的小说编辑，只输出JSON。\n\n{}", data_boundary_notice("zh")),
+            )
+        };
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.8)),
+            max_tokens: Some(600),
+            system_prompt: Some(system_prompt),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 为章节挑选最有画面感的场景并写成英文插图提示词，返回 JSON {"image_prompt": "..."}
+    pub async fn generate_chapter_illustration_prompt(
+        &self,
+        chapter_title: &str,
+        text: &str,
+        style: Option<&str>,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let style_section = match style.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(style) => format!("\n用户指定的图片风格（可能是中文，请翻译为英文后使用）：{}\n", sanitize_inline(style)),
+            None => String::new(),
+        };
+        let prompt = format!(
+            r#"请从下面的章节中挑选最有代表性、最有画面感的一个场景，写成一条英文插图提示词（image prompt），用于章节配图。
+
+要求：
+- 必须是英文
+- 包含场景、人物外观、氛围、构图、光线和风格
+- 人物用外观描述代替姓名，不要输出任何解释
+{}
+章节标题：{}
+
+{}
+
+严格按JSON格式输出：
+{{"image_prompt": "your English prompt here"}}"#,
+            style_section,
+            sanitize_inline(chapter_title),
+            wrap_user_field("章节正文", text)
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.6)),
+            max_tokens: Some(400),
+            system_prompt: Some(format!(
+                "You are a professional image prompt engineer. Return only JSON with an English image_prompt.\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
+        };
+
+        let (content, _) = client.generate_text(&prompt, Some(params)).await?;
+        Ok(content)
+    }
+
+    /// 对照角色、世界观和时间线检查章节中的设定冲突，返回模型原始 JSON 回复
+    pub async fn check_consistency(
+        &self,
+        chapter_title: &str,
+        text: &str,
+        character_info: Option<&str>,
+        world_info: Option<&str>,
+        timeline: Option<&str>,
+    ) -> Result<String> {
+        let client = self.deepseek.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("DeepSeek not configured"))?;
+
+        let mut prompt = format!(
+            "请检查下面的章节是否与已有设定矛盾：人物性格/身份/能力、世界观规则、时间线顺序，以及章节内部的前后逻辑。\n\n章节标题：{}\n\n{}\n",
+            sanitize_inline(chapter_title),
+            wrap_user_field("章节正文", text)
+        );
+        for (label, value) in [("人物设定", character_info), ("世界观", world_info), ("时间线", timeline)] {
+            if let Some(value) = value.filter(|v| !v.trim().is_empty()) {
+                prompt.push_str(&format!("\n{}\n", wrap_user_field(label, value)));
+            }
+        }
+        prompt.push_str(
+            r#"
+只报告确实存在的冲突，没有问题时返回空列表。严格按JSON格式输出：
+{"issues": [{"type": "character|world|timeline|logic", "description": "问题说明", "excerpt": "相关原文片段"}]}"#,
+        );
+
+        let params = GenerationParams {
+            temperature: Some(self.effective_temperature(0.2)),
+            max_tokens: Some(1500),
+            system_prompt: Some(format!(
+                "你是一位严谨的小说设定校对编辑，只输出JSON。\n\n{}",
+                data_boundary_notice("zh")
+            )),
+            seed: self.text_seed,
         };
 
         let (content, _) = client.generate_text(&prompt, Some(params)).await?;
@@ -238,17 +928,30 @@ impl GenerationService {
             temperature: Some(self.effective_temperature(0.8)),
             max_tokens: Some(1000),
             system_prompt: Some(deepseek_prompts::tweet_system_prompt()),
+            seed: self.text_seed,
         };
 
         let (content, _) = client.generate_text(&prompt, Some(params)).await?;
         Ok(content)
     }
 
-    pub async fn generate_image(&self, params: ImageGenerationParams, save_path: &str) -> Result<String> {
+    /// 生成图片并定期回调下载进度，`cancel` 置位时中止
+    pub async fn generate_image<F>(
+        &self,
+        params: ImageGenerationParams,
+        save_path: &str,
+        cancel: &AtomicBool,
+        on_progress: F,
+    ) -> Result<String>
+    where
+        F: FnMut(ImageDownloadProgress),
+    {
         let client = self.pollinations.as_ref()
             .ok_or_else(|| anyhow::anyhow!("Pollinations not configured"))?;
 
-        client.generate_and_download(&params, save_path).await
+        client
+            .generate_and_download(&params, save_path, cancel, on_progress)
+            .await
     }
 
     pub fn generate_image_url(&self, params: &ImageGenerationParams) -> Result<String> {
@@ -258,3 +961,17 @@ impl GenerationService {
         client.generate_image_url(params)
     }
 }
+
+/// 去掉模型偶尔附带的“性格：”前缀和首尾引号
+fn strip_field_label(content: &str, label: &str) -> String {
+    let mut value = content.trim();
+    for prefix in [format!("{}：", label), format!("{}:", label), format!("「{}」：", label)] {
+        if let Some(rest) = value.strip_prefix(prefix.as_str()) {
+            value = rest.trim_start();
+        }
+    }
+    value
+        .trim_matches(|c| matches!(c, '"' | '“' | '”'))
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,413 @@
+use sqlx::SqlitePool;
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use std::path::Path;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{
+    GenerationMetrics, GenerationTask, ModelLatencyMetrics, ProjectCostSummary, TaskDateRange, TaskTypeCost,
+};
+use super::SettingsService;
+use super::csv::write_row;
+use super::log_redaction::redact;
+use super::token_usage::TokenUsage;
+
+const TASK_CSV_COLUMNS: [&str; 14] = [
+    "id",
+    "project_id",
+    "project_title",
+    "task_type",
+    "status",
+    "provider",
+    "model",
+    "token_count",
+    "cost",
+    "latency_ms",
+    "first_token_ms",
+    "created_at",
+    "completed_at",
+    "error_message",
+];
+
+pub struct GenerationTaskService;
+
+/// 一次生成的耗时（毫秒）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TaskTiming {
+    pub latency_ms: i64,
+    pub first_token_ms: Option<i64>,
+}
+
+#[derive(sqlx::FromRow)]
+struct TaskMetricRow {
+    status: String,
+    provider: Option<String>,
+    model: Option<String>,
+    latency_ms: Option<i64>,
+    first_token_ms: Option<i64>,
+    token_count: Option<i64>,
+}
+
+// 按模型分组统计时的键：(provider, model)
+type ModelKey = (Option<String>, Option<String>);
+
+// 最近秩法取百分位
+fn percentile(sorted: &[i64], pct: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[derive(sqlx::FromRow)]
+struct TaskExportRow {
+    #[sqlx(flatten)]
+    task: GenerationTask,
+    project_title: Option<String>,
+}
+
+// 本地日期零点对应的 UTC 时间（与 created_at 同为 RFC 3339，可直接按字符串比较）
+fn local_day_start(day: &str) -> Result<String> {
+    let date = NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid date: {}", day))?;
+    let start = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let local = Local
+        .from_local_datetime(&start)
+        .earliest()
+        .ok_or_else(|| anyhow::anyhow!("Invalid local date: {}", day))?;
+    Ok(DateTime::<Utc>::from(local).to_rfc3339())
+}
+
+fn optional_field<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+fn sorted_values(rows: &[&TaskMetricRow], pick: impl Fn(&TaskMetricRow) -> Option<i64>) -> Vec<i64> {
+    let mut values: Vec<i64> = rows.iter().filter_map(|row| pick(row)).collect();
+    values.sort_unstable();
+    values
+}
+
+impl GenerationTaskService {
+    /// 记录一个开始执行的生成任务（status = running）
+    pub async fn start(
+        pool: &SqlitePool,
+        project_id: &str,
+        task_type: &str,
+        input_params: &serde_json::Value,
+        seed: Option<i64>,
+    ) -> Result<GenerationTask> {
+        let task = GenerationTask {
+            id: Uuid::new_v4().to_string(),
+            project_id: project_id.to_string(),
+            task_type: task_type.to_string(),
+            status: "running".to_string(),
+            input_params: input_params.to_string(),
+            output_result: None,
+            error_message: None,
+            token_count: None,
+            cost: None,
+            created_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+            seed,
+            provider: input_params["provider"].as_str().map(str::to_string),
+            model: input_params["model"].as_str().map(str::to_string),
+            latency_ms: None,
+            first_token_ms: None,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO generation_tasks (id, project_id, task_type, status, input_params, created_at, seed, provider, model)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&task.id)
+        .bind(&task.project_id)
+        .bind(&task.task_type)
+        .bind(&task.status)
+        .bind(&task.input_params)
+        .bind(&task.created_at)
+        .bind(task.seed)
+        .bind(&task.provider)
+        .bind(&task.model)
+        .execute(pool)
+        .await?;
+
+        Ok(task)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<GenerationTask>> {
+        let task = sqlx::query_as::<_, GenerationTask>(
+            "SELECT * FROM generation_tasks WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(task)
+    }
+
+    /// 项目的生成记录，最新的在前；可按任务类型过滤
+    pub async fn get_by_project(
+        pool: &SqlitePool,
+        project_id: &str,
+        task_type: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<GenerationTask>> {
+        let tasks = sqlx::query_as::<_, GenerationTask>(
+            r#"
+            SELECT * FROM generation_tasks
+            WHERE project_id = ? AND (? IS NULL OR task_type = ?)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(project_id)
+        .bind(task_type)
+        .bind(task_type)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(tasks)
+    }
+
+    /// 按任务类型汇总项目的 token 用量和估算费用（只统计已完成的任务）
+    pub async fn cost_summary(pool: &SqlitePool, project_id: &str) -> Result<ProjectCostSummary> {
+        let by_task_type = sqlx::query_as::<_, TaskTypeCost>(
+            r#"
+            SELECT task_type,
+                   COUNT(*) AS task_count,
+                   COALESCE(SUM(token_count), 0) AS token_count,
+                   COALESCE(SUM(cost), 0.0) AS cost
+            FROM generation_tasks
+            WHERE project_id = ? AND status = 'completed'
+            GROUP BY task_type
+            ORDER BY cost DESC, token_count DESC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ProjectCostSummary {
+            project_id: project_id.to_string(),
+            currency: SettingsService::get(pool).await?.pricing.currency,
+            total_tokens: by_task_type.iter().map(|row| row.token_count).sum(),
+            total_cost: by_task_type.iter().map(|row| row.cost).sum(),
+            by_task_type,
+        })
+    }
+
+    /// 批量任务执行中保存阶段性结果，并把状态置回 running（重试已结束的批次时）
+    pub async fn update_progress(pool: &SqlitePool, id: &str, output_result: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE generation_tasks SET status = 'running', output_result = ?, error_message = NULL WHERE id = ?"
+        )
+        .bind(output_result)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 标记任务完成；有用量时按设置中的计费单价估算费用
+    pub async fn complete(
+        pool: &SqlitePool,
+        id: &str,
+        output_result: Option<&str>,
+        usage: Option<TokenUsage>,
+        timing: TaskTiming,
+    ) -> Result<()> {
+        let cost = match usage {
+            Some(usage) => {
+                let pricing = SettingsService::get(pool).await?.pricing;
+                Some(pricing.estimate_cost(usage.prompt_tokens, usage.completion_tokens))
+            }
+            None => None,
+        };
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"
+            UPDATE generation_tasks
+            SET status = 'completed', output_result = ?, token_count = ?, cost = ?, completed_at = ?, latency_ms = ?, first_token_ms = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(output_result)
+        .bind(usage.map(|usage| usage.total_tokens))
+        .bind(cost)
+        .bind(&now)
+        .bind(timing.latency_ms)
+        .bind(timing.first_token_ms)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn fail(pool: &SqlitePool, id: &str, error_message: &str, timing: TaskTiming) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query(
+            r#"
+            UPDATE generation_tasks
+            SET status = 'failed', error_message = ?, completed_at = ?, latency_ms = ?, first_token_ms = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(redact(error_message))
+        .bind(&now)
+        .bind(timing.latency_ms)
+        .bind(timing.first_token_ms)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 把生成任务记录（不含输入输出正文）导出为 CSV；project_id 为空时导出所有项目，返回写入的行数
+    pub async fn export_csv(
+        pool: &SqlitePool,
+        project_id: Option<&str>,
+        range: &TaskDateRange,
+        output_path: &str,
+    ) -> Result<usize> {
+        let from = range.from.as_deref().filter(|d| !d.trim().is_empty()).map(local_day_start).transpose()?;
+        let until = range
+            .to
+            .as_deref()
+            .filter(|d| !d.trim().is_empty())
+            .map(|day| {
+                let next = NaiveDate::parse_from_str(day.trim(), "%Y-%m-%d")
+                    .map_err(|_| anyhow::anyhow!("Invalid date: {}", day))?
+                    + Duration::days(1);
+                local_day_start(&next.to_string())
+            })
+            .transpose()?;
+        if let (Some(from), Some(until)) = (&from, &until) {
+            if from >= until {
+                return Err(anyhow::anyhow!("Date range start is after its end"));
+            }
+        }
+
+        let rows = sqlx::query_as::<_, TaskExportRow>(
+            r#"
+            SELECT t.*, p.title AS project_title
+            FROM generation_tasks t
+            LEFT JOIN projects p ON p.id = t.project_id
+            WHERE (?1 IS NULL OR t.project_id = ?1)
+              AND (?2 IS NULL OR t.created_at >= ?2)
+              AND (?3 IS NULL OR t.created_at < ?3)
+            ORDER BY t.created_at ASC
+            "#
+        )
+        .bind(project_id)
+        .bind(from)
+        .bind(until)
+        .fetch_all(pool)
+        .await?;
+
+        let mut out = String::from("\u{feff}");
+        out.push_str(&write_row(&TASK_CSV_COLUMNS));
+        for row in &rows {
+            let task = &row.task;
+            out.push_str(&write_row(&[
+                task.id.clone(),
+                task.project_id.clone(),
+                optional_field(&row.project_title),
+                task.task_type.clone(),
+                task.status.clone(),
+                optional_field(&task.provider),
+                optional_field(&task.model),
+                optional_field(&task.token_count),
+                optional_field(&task.cost),
+                optional_field(&task.latency_ms),
+                optional_field(&task.first_token_ms),
+                task.created_at.clone(),
+                optional_field(&task.completed_at),
+                // 早期记录写入时未脱敏，导出时再处理一次
+                redact(&optional_field(&task.error_message)).into_owned(),
+            ]));
+        }
+
+        if let Some(parent) = Path::new(output_path).parent() {
+            if !parent.as_os_str().is_empty() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+        tokio::fs::write(output_path, out).await?;
+        Ok(rows.len())
+    }
+
+    /// 统计最近 window_hours 小时内已结束任务的延迟、吞吐与失败率
+    pub async fn metrics(pool: &SqlitePool, project_id: &str, window_hours: i64) -> Result<GenerationMetrics> {
+        let since = (Utc::now() - Duration::hours(window_hours)).to_rfc3339();
+        let rows = sqlx::query_as::<_, TaskMetricRow>(
+            r#"
+            SELECT status, provider, model, latency_ms, first_token_ms, token_count
+            FROM generation_tasks
+            WHERE project_id = ? AND created_at >= ? AND status IN ('completed', 'failed')
+            "#
+        )
+        .bind(project_id)
+        .bind(&since)
+        .fetch_all(pool)
+        .await?;
+
+        let all: Vec<&TaskMetricRow> = rows.iter().collect();
+        let failed = all.iter().filter(|row| row.status == "failed").count();
+        let latencies = sorted_values(&all, |row| row.latency_ms);
+        let first_tokens = sorted_values(&all, |row| row.first_token_ms);
+
+        let (tokens, millis) = all
+            .iter()
+            .filter(|row| row.status == "completed")
+            .filter_map(|row| match (row.token_count, row.latency_ms) {
+                (Some(tokens), Some(ms)) if ms > 0 => Some((tokens, ms)),
+                _ => None,
+            })
+            .fold((0i64, 0i64), |(t, m), (tokens, ms)| (t + tokens, m + ms));
+        let avg_tokens_per_sec = (millis > 0).then(|| tokens as f64 * 1000.0 / millis as f64);
+
+        let mut groups: Vec<(ModelKey, Vec<&TaskMetricRow>)> = Vec::new();
+        for row in &rows {
+            let key = (row.provider.clone(), row.model.clone());
+            match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+                Some((_, members)) => members.push(row),
+                None => groups.push((key, vec![row])),
+            }
+        }
+        let by_model = groups
+            .into_iter()
+            .map(|((provider, model), members)| {
+                let latencies = sorted_values(&members, |row| row.latency_ms);
+                ModelLatencyMetrics {
+                    provider,
+                    model,
+                    total: members.len(),
+                    failed: members.iter().filter(|row| row.status == "failed").count(),
+                    p50_latency_ms: percentile(&latencies, 50.0),
+                    p95_latency_ms: percentile(&latencies, 95.0),
+                }
+            })
+            .collect();
+
+        Ok(GenerationMetrics {
+            window_hours,
+            total: all.len(),
+            completed: all.len() - failed,
+            failed,
+            failure_rate: if all.is_empty() { 0.0 } else { failed as f64 / all.len() as f64 },
+            p50_latency_ms: percentile(&latencies, 50.0),
+            p95_latency_ms: percentile(&latencies, 95.0),
+            p50_first_token_ms: percentile(&first_tokens, 50.0),
+            p95_first_token_ms: percentile(&first_tokens, 95.0),
+            avg_tokens_per_sec,
+            by_model,
+        })
+    }
+}
@@ -0,0 +1,178 @@
+//! 题材规范化
+//!
+//! `genre` 为自由文本，"玄幻"、"玄幻小说"、"Xuanhuan" 会被视为不同题材。
+//! 这里维护一份规范题材表及别名，创建/更新项目时计算 `genre_code`，
+//! 原始输入仍保存在 `genre` 中用于展示。
+
+use serde::Serialize;
+
+/// 无法识别的题材统一归入此代码
+pub const OTHER_GENRE: &str = "other";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GenreInfo {
+    pub code: &'static str,
+    pub name_zh: &'static str,
+    pub name_en: &'static str,
+}
+
+struct GenreEntry {
+    info: GenreInfo,
+    aliases: &'static [&'static str],
+}
+
+const GENRES: &[GenreEntry] = &[
+    GenreEntry {
+        info: GenreInfo { code: "xuanhuan", name_zh: "玄幻", name_en: "Xuanhuan" },
+        aliases: &["玄幻", "东方玄幻", "异世大陆", "xuanhuan", "eastern fantasy"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "xianxia", name_zh: "仙侠", name_en: "Xianxia" },
+        aliases: &["仙侠", "修仙", "修真", "古典仙侠", "xianxia", "cultivation"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "wuxia", name_zh: "武侠", name_en: "Wuxia" },
+        aliases: &["武侠", "传统武侠", "wuxia", "martial arts"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "fantasy", name_zh: "奇幻", name_en: "Fantasy" },
+        aliases: &["奇幻", "西方奇幻", "魔幻", "fantasy", "high fantasy", "epic fantasy"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "scifi", name_zh: "科幻", name_en: "Science Fiction" },
+        aliases: &["科幻", "硬科幻", "软科幻", "星际", "scifi", "sci-fi", "sci fi", "science fiction"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "urban", name_zh: "都市", name_en: "Urban" },
+        aliases: &["都市", "都市生活", "都市异能", "现代都市", "urban", "urban fantasy"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "romance", name_zh: "言情", name_en: "Romance" },
+        aliases: &["言情", "现代言情", "古代言情", "古言", "现言", "爱情", "romance", "love story"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "history", name_zh: "历史", name_en: "Historical" },
+        aliases: &["历史", "架空历史", "穿越", "history", "historical", "historical fiction"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "mystery", name_zh: "悬疑", name_en: "Mystery" },
+        aliases: &["悬疑", "推理", "侦探", "mystery", "detective", "crime"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "thriller", name_zh: "惊悚", name_en: "Thriller" },
+        aliases: &["惊悚", "灵异", "thriller", "suspense"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "horror", name_zh: "恐怖", name_en: "Horror" },
+        aliases: &["恐怖", "horror"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "game", name_zh: "游戏", name_en: "Gaming" },
+        aliases: &["游戏", "网游", "电竞", "游戏竞技", "game", "gaming", "litrpg"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "military", name_zh: "军事", name_en: "Military" },
+        aliases: &["军事", "战争", "military", "war"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: "literary", name_zh: "文学", name_en: "Literary Fiction" },
+        aliases: &["文学", "纯文学", "现实", "现实主义", "literary", "literary fiction"],
+    },
+    GenreEntry {
+        info: GenreInfo { code: OTHER_GENRE, name_zh: "其他", name_en: "Other" },
+        aliases: &["其他", "other"],
+    },
+];
+
+/// 规范题材列表（用于前端下拉框）
+pub fn list_genres() -> Vec<GenreInfo> {
+    GENRES.iter().map(|entry| entry.info.clone()).collect()
+}
+
+/// 将用户输入映射为规范题材代码
+///
+/// 先按别名/代码/名称精确匹配（忽略大小写与空白），再去掉“小说”“类”等后缀重试，
+/// 最后尝试包含匹配（如“东方玄幻修仙”），仍无法识别则返回 `other`。
+pub fn normalize_genre(input: &str) -> &'static str {
+    let key = normalize_key(input);
+    if key.is_empty() {
+        return OTHER_GENRE;
+    }
+
+    if let Some(code) = match_exact(&key) {
+        return code;
+    }
+
+    let stripped = strip_suffixes(&key);
+    if stripped != key {
+        if let Some(code) = match_exact(&stripped) {
+            return code;
+        }
+    }
+
+    // 包含匹配：优先最长别名，避免“都市”抢先匹配“都市异能”之类的组合
+    let mut best: Option<(&'static str, usize)> = None;
+    for entry in GENRES {
+        for alias in entry.aliases {
+            let alias_key = normalize_key(alias);
+            // 过短的英文别名容易误中（如 "war" 命中 "warrior"）
+            if alias_key.is_ascii() && alias_key.len() < 5 {
+                continue;
+            }
+            if key.contains(&alias_key) {
+                let len = alias_key.chars().count();
+                if best.is_none_or(|(_, best_len)| len > best_len) {
+                    best = Some((entry.info.code, len));
+                }
+            }
+        }
+    }
+
+    best.map(|(code, _)| code).unwrap_or(OTHER_GENRE)
+}
+
+/// 规范化可选题材：空值保持为空
+pub fn normalize_optional_genre(input: Option<&str>) -> Option<String> {
+    input
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| normalize_genre(value).to_string())
+}
+
+fn match_exact(key: &str) -> Option<&'static str> {
+    GENRES.iter().find_map(|entry| {
+        let matched = normalize_key(entry.info.code) == key
+            || normalize_key(entry.info.name_zh) == key
+            || normalize_key(entry.info.name_en) == key
+            || entry.aliases.iter().any(|alias| normalize_key(alias) == key);
+        matched.then_some(entry.info.code)
+    })
+}
+
+fn normalize_key(value: &str) -> String {
+    value
+        .trim()
+        .to_lowercase()
+        .replace(['-', '_'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn strip_suffixes(key: &str) -> String {
+    const SUFFIXES: &[&str] = &["小说", "文", "类", "题材", " novel", " fiction", " story"];
+    let mut current = key.to_string();
+    loop {
+        let before = current.clone();
+        for suffix in SUFFIXES {
+            if let Some(rest) = current.strip_suffix(suffix) {
+                if !rest.trim().is_empty() {
+                    current = rest.trim().to_string();
+                }
+            }
+        }
+        if current == before {
+            return current;
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! 生成结果的语言检测
+//!
+//! 按段落统计汉字与拉丁字母判断主要文字，用于发现中文项目里混入英文段落
+//! （或反之）的情况。只区分 zh / en，其余视为 unknown。
+
+use serde::Serialize;
+
+/// 段落中可判定文字少于该数量时不参与统计（如纯标点、分隔线）
+const MIN_CLASSIFIABLE_UNITS: usize = 4;
+/// 约多少个拉丁字母相当于一个汉字的信息量
+const LATIN_LETTERS_PER_UNIT: usize = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageDetection {
+    /// zh, en, unknown
+    pub language: &'static str,
+    pub cjk_chars: usize,
+    pub latin_letters: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanguageCheck {
+    pub expected: String,
+    pub detected: &'static str,
+    /// 与期望语言不符的段落所占比例（按段落长度加权）
+    pub mismatch_ratio: f64,
+    /// 不符段落的序号（按非空段落计，从 0 开始）
+    pub mismatched_paragraphs: Vec<usize>,
+    pub mismatch: bool,
+}
+
+pub fn detect_language(text: &str) -> LanguageDetection {
+    let (cjk_chars, latin_letters) = count_scripts(text);
+    LanguageDetection {
+        language: classify(cjk_chars, latin_letters),
+        cjk_chars,
+        latin_letters,
+    }
+}
+
+/// 检查文本是否符合期望语言；不符段落占比达到 threshold 即判定为不符
+pub fn check_language(text: &str, expected: &str, threshold: f64) -> LanguageCheck {
+    let expected = if expected == "en" { "en" } else { "zh" };
+    let detected = detect_language(text).language;
+
+    let mut total_units = 0usize;
+    let mut mismatched_units = 0usize;
+    let mut mismatched_paragraphs = Vec::new();
+    let paragraphs = text.split('\n').filter(|p| !p.trim().is_empty());
+    for (index, paragraph) in paragraphs.enumerate() {
+        let (cjk, latin) = count_scripts(paragraph);
+        let units = cjk + latin / LATIN_LETTERS_PER_UNIT;
+        let language = classify(cjk, latin);
+        if language == "unknown" {
+            continue;
+        }
+        total_units += units;
+        if language != expected {
+            mismatched_units += units;
+            mismatched_paragraphs.push(index);
+        }
+    }
+
+    let mismatch_ratio = if total_units == 0 {
+        0.0
+    } else {
+        mismatched_units as f64 / total_units as f64
+    };
+
+    LanguageCheck {
+        expected: expected.to_string(),
+        detected,
+        mismatch_ratio: (mismatch_ratio * 1000.0).round() / 1000.0,
+        mismatched_paragraphs,
+        mismatch: total_units > 0 && mismatch_ratio >= threshold,
+    }
+}
+
+/// 语言不符时追加到系统提示词的强调说明
+pub fn language_notice(expected: &str) -> &'static str {
+    if expected == "en" {
+        "IMPORTANT: Write the entire output in English only. Do not switch to Chinese or any other language for any paragraph, including dialogue."
+    } else {
+        "【重要】全文必须使用简体中文写作，任何段落（包括对话）都不得改用英文或其他语言；专有名词如无必要也不要使用英文。"
+    }
+}
+
+/// 是否为 CJK 统一表意文字（含扩展区和兼容区）
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F)
+}
+
+fn count_scripts(text: &str) -> (usize, usize) {
+    let mut cjk = 0;
+    let mut latin = 0;
+    for c in text.chars() {
+        if is_cjk(c) {
+            cjk += 1;
+        } else if c.is_ascii_alphabetic() {
+            latin += 1;
+        }
+    }
+    (cjk, latin)
+}
+
+fn classify(cjk: usize, latin: usize) -> &'static str {
+    let latin_units = latin / LATIN_LETTERS_PER_UNIT;
+    if cjk + latin_units < MIN_CLASSIFIABLE_UNITS {
+        "unknown"
+    } else if cjk >= latin_units {
+        "zh"
+    } else {
+        "en"
+    }
+}
@@ -0,0 +1,84 @@
+//! 从模型回复中提取 JSON
+//!
+//! 模型经常在 JSON 外包裹 ```json 代码块或附带解释文字，这里统一剥离后再解析。
+
+use regex::Regex;
+use serde_json::Value;
+
+// 去掉 markdown 代码块标记，只保留第一个代码块内的内容
+fn strip_code_fence(content: &str) -> &str {
+    let trimmed = content.trim();
+    let Some(start) = trimmed.find("```") else {
+        return trimmed;
+    };
+    let after_fence = &trimmed[start + 3..];
+    let body_start = after_fence.find('\n').map(|i| i + 1).unwrap_or(0);
+    let body = &after_fence[body_start..];
+    match body.find("```") {
+        Some(end) => body[..end].trim(),
+        None => body.trim(),
+    }
+}
+
+// 找到第一个完整的 JSON 对象或数组（跳过字符串内的括号）
+fn find_json_span(text: &str) -> Option<&str> {
+    let start = text.find(['{', '['])?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, ch) in text[start..].char_indices() {
+        if in_string {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return Some(&text[start..start + offset + ch.len_utf8()]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 解析模型返回的 JSON；失败时错误信息附带清理后的原文便于排查
+pub(crate) fn extract_json(content: &str) -> Result<Value, String> {
+    let cleaned = strip_code_fence(content);
+    match serde_json::from_str::<Value>(cleaned) {
+        Ok(value) => Ok(value),
+        Err(e) => find_json_span(cleaned)
+            .and_then(|span| serde_json::from_str::<Value>(span).ok())
+            .ok_or_else(|| format!("解析AI返回的JSON失败: {}。原始内容: {}", e, cleaned)),
+    }
+}
+
+/// JSON 整体解析失败时，按字段名提取字符串值。
+/// 值内出现未转义的引号时，以“引号后紧跟下一个字段或右括号”作为结束位置；回复被截断时取到末尾。
+pub(crate) fn extract_string_field(content: &str, field: &str) -> Option<String> {
+    let pattern = format!(
+        r#"(?s)"{}"\s*:\s*"(.*?)(?:"\s*(?:,\s*"[A-Za-z_]+"\s*:|\}}|\z)|\z)"#,
+        regex::escape(field)
+    );
+    let re = Regex::new(&pattern).ok()?;
+    let raw = re.captures(content)?.get(1)?.as_str();
+
+    let value = serde_json::from_str::<String>(&format!("\"{}\"", raw)).unwrap_or_else(|_| {
+        raw.replace("\\n", "\n")
+            .replace("\\t", "\t")
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\")
+    });
+    let value = value.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
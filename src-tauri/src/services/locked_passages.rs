@@ -0,0 +1,105 @@
+//! 保留锁定段落重写章节时的文本切分与拼装
+//!
+//! 偏移均为 UTF-16（与前端 selectionStart 一致）。锁定段落把正文切成 n+1 个可重写的间隙，
+//! 模型只重写间隙，拼装时原样放回锁定段落，因此锁定内容不会被改动。
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 按锁定范围切分后的正文：gaps 比 locks 多一个（首尾间隙可能为空）
+#[derive(Debug)]
+pub struct LockedSegments<'a> {
+    pub gaps: Vec<&'a str>,
+    pub locks: Vec<&'a str>,
+}
+
+fn utf16_to_byte(text: &str, offset: usize) -> Result<usize> {
+    let mut units = 0;
+    for (index, ch) in text.char_indices() {
+        if units == offset {
+            return Ok(index);
+        }
+        if units > offset {
+            break;
+        }
+        units += ch.len_utf16();
+    }
+    if units == offset {
+        return Ok(text.len());
+    }
+    Err(anyhow::anyhow!("Offset {} is out of range or splits a character", offset))
+}
+
+/// 排序并校验锁定范围（不能为空、越界或相互重叠），再切分正文
+pub fn split_locked<'a>(text: &'a str, ranges: &[LockedRange]) -> Result<LockedSegments<'a>> {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut gaps = Vec::with_capacity(ranges.len() + 1);
+    let mut locks = Vec::with_capacity(ranges.len());
+    let mut cursor = 0;
+    let mut previous_end = 0;
+    for (index, range) in ranges.iter().enumerate() {
+        if range.start >= range.end {
+            return Err(anyhow::anyhow!("Locked range {}..{} is empty", range.start, range.end));
+        }
+        if index > 0 && range.start < previous_end {
+            return Err(anyhow::anyhow!("Locked ranges overlap at offset {}", range.start));
+        }
+        let start = utf16_to_byte(text, range.start)?;
+        let end = utf16_to_byte(text, range.end)?;
+        gaps.push(&text[cursor..start]);
+        locks.push(&text[start..end]);
+        cursor = end;
+        previous_end = range.end;
+    }
+    gaps.push(&text[cursor..]);
+    Ok(LockedSegments { gaps, locks })
+}
+
+// 沿用原间隙首尾的空白（换行），避免重写内容与锁定段落粘连；
+// 原本没有内容的间隙新增文字时，用换行与相邻的锁定段落隔开（正文首尾除外）
+fn fit_gap(original: &str, rewritten: &str, first: bool, last: bool) -> String {
+    let rewritten = rewritten.trim();
+    if rewritten.is_empty() {
+        return if original.trim().is_empty() { original.to_string() } else { String::new() };
+    }
+    if original.trim().is_empty() {
+        let separator = if original.is_empty() { "\n" } else { original };
+        let leading = if first { "" } else { separator };
+        let trailing = if last { "" } else { separator };
+        return format!("{}{}{}", leading, rewritten, trailing);
+    }
+    let leading = &original[..original.len() - original.trim_start().len()];
+    let trailing = &original[original.trim_end().len()..];
+    format!("{}{}{}", leading, rewritten, trailing)
+}
+
+/// 把重写后的间隙与锁定段落依次拼回，返回新正文和锁定段落在新正文中的范围
+pub fn reassemble(segments: &LockedSegments, rewritten_gaps: &[String]) -> (String, Vec<LockedRange>) {
+    let mut text = String::new();
+    let mut units = 0;
+    let mut ranges = Vec::with_capacity(segments.locks.len());
+    for (index, original) in segments.gaps.iter().enumerate() {
+        let last = index + 1 == segments.gaps.len();
+        let gap = match rewritten_gaps.get(index) {
+            Some(rewritten) => fit_gap(original, rewritten, index == 0, last),
+            None => original.to_string(),
+        };
+        units += gap.encode_utf16().count();
+        text.push_str(&gap);
+        if let Some(lock) = segments.locks.get(index) {
+            let start = units;
+            units += lock.encode_utf16().count();
+            text.push_str(lock);
+            ranges.push(LockedRange { start, end: units });
+        }
+    }
+    (text, ranges)
+}
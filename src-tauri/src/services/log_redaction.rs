@@ -0,0 +1,108 @@
+//! 日志脱敏
+//!
+//! 用户反馈问题时会附上日志，所有日志输出（env_logger 与按请求缓存的 get_logs）都先经过这里：
+//! 去掉 Bearer token、sk- 开头的密钥和 key=… 之类的参数值。
+//! 提示词正文默认只记录长度，开启详细日志（verboseRequestLogging）后才截取片段，仅用于本地调试。
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 详细日志下提示词最多记录的字符数
+const VERBOSE_PROMPT_CHARS: usize = 2000;
+const REDACTED: &str = "[REDACTED]";
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref BEARER_TOKEN: Regex = Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]+").unwrap();
+    static ref SECRET_KEY: Regex = Regex::new(r"\b(?:sk|pk|rk)-[A-Za-z0-9_-]{8,}").unwrap();
+    // "api_key": "…" / apiKey=… / Authorization: … / ?key=…
+    static ref SECRET_FIELD: Regex = Regex::new(
+        r#"(?i)\b((?:api[_-]?key|apikey|access[_-]?token|secret|authorization|token|key)["']?\s*[:=]\s*["']?)([^\s"'&,;}]+)"#
+    ).unwrap();
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// 去掉文本中的密钥；没有需要替换的内容时不分配新字符串
+pub fn redact(text: &str) -> Cow<'_, str> {
+    if !BEARER_TOKEN.is_match(text) && !SECRET_KEY.is_match(text) && !SECRET_FIELD.is_match(text) {
+        return Cow::Borrowed(text);
+    }
+    let text = BEARER_TOKEN.replace_all(text, format!("${{1}}{}", REDACTED));
+    let text = SECRET_KEY.replace_all(&text, REDACTED).into_owned();
+    let text = SECRET_FIELD.replace_all(&text, |caps: &regex::Captures| {
+        // Bearer 已在上一步替换，避免再把 “Bearer” 当成值
+        if caps[2].eq_ignore_ascii_case("bearer") || &caps[2] == REDACTED {
+            caps[0].to_string()
+        } else {
+            format!("{}{}", &caps[1], REDACTED)
+        }
+    });
+    Cow::Owned(text.into_owned())
+}
+
+/// 提示词在日志中的表示：默认只有字符数，详细日志下截取开头（同样经过脱敏）
+pub fn prompt_for_log(prompt: &str) -> String {
+    let chars = prompt.chars().count();
+    if !verbose() {
+        return format!("<{} chars omitted>", chars);
+    }
+    let excerpt: String = prompt.chars().take(VERBOSE_PROMPT_CHARS).collect();
+    let suffix = if chars > VERBOSE_PROMPT_CHARS {
+        format!("…<{} more chars>", chars - VERBOSE_PROMPT_CHARS)
+    } else {
+        String::new()
+    };
+    format!("{}{}", redact(&excerpt), suffix)
+}
+
+/// 包装 env_logger，输出前对每条日志脱敏
+struct RedactingLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for RedactingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        let message = record.args().to_string();
+        match redact(&message) {
+            Cow::Borrowed(_) => self.inner.log(record),
+            Cow::Owned(redacted) => self.inner.log(
+                &log::Record::builder()
+                    .args(format_args!("{}", redacted))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// 取代 env_logger::init()：仍按 RUST_LOG 过滤，输出经过脱敏
+pub fn init_logger() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max_level = inner.filter();
+    if log::set_boxed_logger(Box::new(RedactingLogger { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
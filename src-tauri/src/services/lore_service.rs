@@ -0,0 +1,415 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use regex::Regex;
+use crate::models::{CreateLoreInput, Lore, LoreCategory, UpdateLoreInput};
+
+lazy_static::lazy_static! {
+    // - **时代背景**：内容  /  - Era: 内容
+    static ref LABELED_ITEM: Regex = Regex::new(r"^\s*[-*]\s*(?:\*\*)?([^*:：]{1,20}?)(?:\*\*)?\s*[:：]\s*(.*)$").unwrap();
+    // 1. **势力名**：描述  /  - 势力名：描述
+    static ref FACTION_ITEM: Regex = Regex::new(r"^(?:\d+\s*[.、)）]|[-*])\s*(.+)$").unwrap();
+}
+
+/// 从大纲“世界观设定”中解析出的设定条目
+#[derive(Debug)]
+struct OutlineLoreEntry {
+    category: &'static str,
+    title: String,
+    content: String,
+}
+
+pub struct LoreService;
+
+/// 解析“- **标签**：内容”形式的列表项，返回去掉首尾空白的标签和内容；大纲里的设定和角色字段都用这种写法
+pub(crate) fn labeled_item(line: &str) -> Option<(&str, &str)> {
+    let caps = LABELED_ITEM.captures(line)?;
+    Some((caps.get(1)?.as_str().trim(), caps.get(2)?.as_str().trim()))
+}
+
+// 世界观小节/条目名称对应的 lore 分类
+fn world_category(label: &str) -> Option<&'static str> {
+    let label = label.trim().trim_matches('*').trim();
+    let lower = label.to_lowercase();
+    if label.contains("时代") || lower.starts_with("era") || lower.contains("time period") {
+        Some("era")
+    } else if label.contains("地理") || lower.contains("geograph") {
+        Some("geography")
+    } else if label.contains("社会") || lower.contains("societ") || lower.contains("social") {
+        Some("society")
+    } else if label.contains("规则") || label.contains("力量体系") || lower.contains("rule") || lower.contains("power system") {
+        Some("rules")
+    } else if label.contains("势力") || lower.contains("faction") {
+        Some("faction")
+    } else {
+        None
+    }
+}
+
+fn is_world_section(heading: &str) -> bool {
+    heading.contains("世界观") || heading.to_lowercase().contains("world building")
+}
+
+// 拆分“名称：描述”，名称过长时视为没有名称
+fn split_name(text: &str) -> (String, String) {
+    let text = text.trim();
+    if let Some(rest) = text.strip_prefix("**") {
+        if let Some((name, desc)) = rest.split_once("**") {
+            let desc = desc.trim_start_matches([':', '：', '-', '—', ' ']).trim();
+            return (name.trim().to_string(), desc.to_string());
+        }
+    }
+    match text.split_once(['：', ':']) {
+        Some((name, desc)) if name.chars().count() <= 20 => {
+            (name.trim().to_string(), desc.trim().to_string())
+        }
+        _ => (text.chars().take(20).collect(), text.to_string()),
+    }
+}
+
+/// 解析“世界观设定”：基础设定按条目归入 era/geography/society/rules，重要势力每个势力一条
+fn parse_outline_world(outline: &str) -> Vec<OutlineLoreEntry> {
+    let mut entries: Vec<OutlineLoreEntry> = Vec::new();
+    let mut in_world = false;
+    // 当前 ### 小节对应的分类（如“重要势力”→ faction，“时代背景”→ era）
+    let mut section: Option<&'static str> = None;
+    // 续行内容追加到最近一条
+    let mut open_entry = false;
+
+    for line in outline.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            in_world = is_world_section(heading);
+            section = None;
+            open_entry = false;
+            continue;
+        }
+        if !in_world {
+            continue;
+        }
+        if let Some(heading) = trimmed.strip_prefix("### ") {
+            section = world_category(heading);
+            open_entry = false;
+            // 直接以小节形式给出的基础设定（如 ### 时代背景）
+            if let Some(category) = section.filter(|c| *c != "faction") {
+                entries.push(OutlineLoreEntry {
+                    category,
+                    title: heading.trim().to_string(),
+                    content: String::new(),
+                });
+                open_entry = true;
+            }
+            continue;
+        }
+        if let Some(heading) = trimmed.strip_prefix("#### ") {
+            if section == Some("faction") {
+                let (name, desc) = split_name(heading);
+                entries.push(OutlineLoreEntry { category: "faction", title: name, content: desc });
+                open_entry = true;
+            }
+            continue;
+        }
+        if trimmed.is_empty() || (trimmed.starts_with('（') && trimmed.ends_with('）')) {
+            continue;
+        }
+
+        let indented = line.starts_with("  ") || line.starts_with('\t');
+        if section == Some("faction") {
+            if !indented {
+                if let Some(caps) = FACTION_ITEM.captures(trimmed) {
+                    let (name, desc) = split_name(&caps[1]);
+                    if !name.is_empty() {
+                        entries.push(OutlineLoreEntry { category: "faction", title: name, content: desc });
+                        open_entry = true;
+                        continue;
+                    }
+                }
+            }
+        } else if let Some((label, value)) = labeled_item(line) {
+            if let Some(category) = world_category(label) {
+                entries.push(OutlineLoreEntry {
+                    category,
+                    title: label.to_string(),
+                    content: value.to_string(),
+                });
+                open_entry = true;
+                continue;
+            }
+        }
+
+        if open_entry {
+            if let Some(entry) = entries.last_mut() {
+                let text = trimmed.trim_start_matches(['-', '*', ' ']).trim();
+                if !entry.content.is_empty() {
+                    entry.content.push('\n');
+                }
+                entry.content.push_str(text);
+            }
+        }
+    }
+
+    entries.retain(|entry| !entry.title.is_empty() && !entry.content.trim().is_empty());
+    entries
+}
+
+impl LoreService {
+    /// 大纲中是否有可同步的世界观设定
+    pub fn outline_has_entries(outline: &str) -> bool {
+        !parse_outline_world(outline).is_empty()
+    }
+
+    /// 新建设定条目，排在所属分类末尾
+    pub async fn create(pool: &SqlitePool, input: CreateLoreInput) -> Result<Lore> {
+        let order_index: i32 = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(order_index) + 1, 0) FROM lore WHERE project_id = ? AND category = ?"
+        )
+        .bind(&input.project_id)
+        .bind(&input.category)
+        .fetch_one(pool)
+        .await?;
+
+        let now = Utc::now().to_rfc3339();
+        let lore = Lore {
+            id: Uuid::new_v4().to_string(),
+            project_id: input.project_id,
+            category: input.category,
+            title: input.title,
+            content: input.content,
+            created_at: now.clone(),
+            updated_at: now,
+            order_index,
+            sync_key: None,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO lore (id, project_id, category, title, content, order_index, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&lore.id)
+        .bind(&lore.project_id)
+        .bind(&lore.category)
+        .bind(&lore.title)
+        .bind(&lore.content)
+        .bind(lore.order_index)
+        .bind(&lore.created_at)
+        .bind(&lore.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(lore)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<Lore>> {
+        let lore = sqlx::query_as::<_, Lore>("SELECT * FROM lore WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(lore)
+    }
+
+    pub async fn update(pool: &SqlitePool, id: &str, input: UpdateLoreInput) -> Result<Lore> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE lore
+            SET category = COALESCE(?, category),
+                title = COALESCE(?, title),
+                content = COALESCE(?, content),
+                updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(input.category)
+        .bind(input.title)
+        .bind(input.content)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Lore entry not found"));
+        }
+
+        Self::get_by_id(pool, id).await?
+            .ok_or_else(|| anyhow::anyhow!("Lore entry not found after update"))
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM lore WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str) -> Result<Vec<Lore>> {
+        let entries = sqlx::query_as::<_, Lore>(
+            "SELECT * FROM lore WHERE project_id = ? ORDER BY category ASC, order_index ASC, title ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    pub async fn get_by_category(pool: &SqlitePool, project_id: &str, category: &str) -> Result<Vec<Lore>> {
+        let entries = sqlx::query_as::<_, Lore>(
+            "SELECT * FROM lore WHERE project_id = ? AND category = ? ORDER BY order_index ASC, title ASC"
+        )
+        .bind(project_id)
+        .bind(category)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    pub async fn get_categories(pool: &SqlitePool, project_id: &str) -> Result<Vec<LoreCategory>> {
+        let categories = sqlx::query_as::<_, LoreCategory>(
+            r#"
+            SELECT category, COUNT(*) AS count
+            FROM lore
+            WHERE project_id = ?
+            GROUP BY category
+            ORDER BY category ASC
+            "#
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(categories)
+    }
+
+    /// 按给定顺序重排同一分类下的条目；所有 id 必须属于该分类且来自同一项目
+    pub async fn reorder(pool: &SqlitePool, category: &str, ordered_ids: &[String]) -> Result<()> {
+        if ordered_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = pool.begin().await?;
+        let mut project_id: Option<String> = None;
+
+        for id in ordered_ids {
+            let owner: Option<String> = sqlx::query_scalar(
+                "SELECT project_id FROM lore WHERE id = ? AND category = ?"
+            )
+            .bind(id)
+            .bind(category)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            match (owner, &project_id) {
+                (None, _) => {
+                    return Err(anyhow::anyhow!("Lore entry {} not found in category {}", id, category));
+                }
+                (Some(owner), Some(expected)) if owner != *expected => {
+                    return Err(anyhow::anyhow!("Lore entries belong to different projects"));
+                }
+                (Some(owner), None) => project_id = Some(owner),
+                _ => {}
+            }
+        }
+
+        let now = Utc::now().to_rfc3339();
+        for (index, id) in ordered_ids.iter().enumerate() {
+            sqlx::query("UPDATE lore SET order_index = ?, updated_at = ? WHERE id = ?")
+                .bind(index as i32)
+                .bind(&now)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 将大纲“世界观设定”同步为分类设定条目。
+    /// 基础设定按分类各一条（sync_key 为 outline:era 等），势力按出现顺序 outline:faction:N；
+    /// 重复同步只会更新，大纲中已删除的条目一并移除，手动添加的设定不受影响。
+    pub async fn sync_from_outline(pool: &SqlitePool, project_id: &str, outline: &str) -> Result<Vec<Lore>> {
+        let entries = parse_outline_world(outline);
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!("No world-building entries found in outline"));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+        let mut sync_keys: Vec<String> = Vec::with_capacity(entries.len());
+        let mut faction_count = 0;
+
+        for entry in &entries {
+            let (sync_key, order_index) = if entry.category == "faction" {
+                faction_count += 1;
+                (format!("outline:faction:{}", faction_count), faction_count - 1)
+            } else {
+                (format!("outline:{}", entry.category), 0)
+            };
+            // 同一分类在大纲中出现多次时合并到一条
+            if sync_keys.contains(&sync_key) {
+                sqlx::query(
+                    "UPDATE lore SET content = content || ? WHERE project_id = ? AND sync_key = ?"
+                )
+                .bind(format!("\n{}", entry.content))
+                .bind(project_id)
+                .bind(&sync_key)
+                .execute(&mut *tx)
+                .await?;
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO lore (id, project_id, category, title, content, order_index, sync_key, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(project_id, sync_key) DO UPDATE SET
+                    category = excluded.category,
+                    title = excluded.title,
+                    content = excluded.content,
+                    updated_at = excluded.updated_at
+                "#
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(project_id)
+            .bind(entry.category)
+            .bind(&entry.title)
+            .bind(&entry.content)
+            .bind(order_index)
+            .bind(&sync_key)
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+            sync_keys.push(sync_key);
+        }
+
+        // 清理大纲中已不存在的同步条目
+        let stale: Vec<String> = sqlx::query_scalar(
+            "SELECT sync_key FROM lore WHERE project_id = ? AND sync_key LIKE 'outline:%'"
+        )
+        .bind(project_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for key in stale.iter().filter(|key| !sync_keys.contains(key)) {
+            sqlx::query("DELETE FROM lore WHERE project_id = ? AND sync_key = ?")
+                .bind(project_id)
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Self::get_by_project(pool, project_id).await
+    }
+}
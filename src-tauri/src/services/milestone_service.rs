@@ -0,0 +1,73 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::Milestone;
+use super::SettingsService;
+
+pub struct MilestoneService;
+
+impl MilestoneService {
+    /// 按当前字数记录新跨过的里程碑，只返回本次新增的（已记录过的阈值不会重复触发）
+    pub async fn record_reached(pool: &SqlitePool, project_id: &str) -> Result<Vec<Milestone>> {
+        let interval = SettingsService::get(pool).await?.milestone_interval;
+        if interval <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let word_count: Option<i64> = sqlx::query_scalar(
+            "SELECT current_word_count FROM projects WHERE id = ?"
+        )
+        .bind(project_id)
+        .fetch_optional(pool)
+        .await?;
+        let word_count = match word_count {
+            Some(count) if count >= interval => count,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut reached = Vec::new();
+        let now = Utc::now().to_rfc3339();
+        for step in 1..=(word_count / interval) {
+            let milestone = Milestone {
+                id: Uuid::new_v4().to_string(),
+                project_id: project_id.to_string(),
+                threshold: step * interval,
+                word_count,
+                reached_at: now.clone(),
+            };
+
+            let inserted = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO milestones (id, project_id, threshold, word_count, reached_at)
+                VALUES (?, ?, ?, ?, ?)
+                "#
+            )
+            .bind(&milestone.id)
+            .bind(&milestone.project_id)
+            .bind(milestone.threshold)
+            .bind(milestone.word_count)
+            .bind(&milestone.reached_at)
+            .execute(pool)
+            .await?
+            .rows_affected();
+
+            if inserted > 0 {
+                reached.push(milestone);
+            }
+        }
+
+        Ok(reached)
+    }
+
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str) -> Result<Vec<Milestone>> {
+        let milestones = sqlx::query_as::<_, Milestone>(
+            "SELECT * FROM milestones WHERE project_id = ? ORDER BY threshold ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(milestones)
+    }
+}
@@ -1,7 +1,67 @@
 pub mod project_service;
 pub mod chapter_service;
+pub mod chapter_lock;
 pub mod generation_service;
+pub mod snapshot_service;
+pub mod settings_service;
+pub mod generation_task_service;
+pub mod milestone_service;
+pub mod timeline_service;
+pub mod prompt_guard;
+pub mod llm_json;
+pub mod export_service;
+pub mod lore_service;
+pub mod archive_service;
+pub mod genre;
+pub mod character_service;
+pub mod similarity;
+pub mod context_budget;
+pub mod chapter_context;
+pub mod language_check;
+pub mod draft_buffer;
+pub mod font_coverage;
+pub mod character_arc_service;
+pub mod chapter_number;
+pub mod operation_log_service;
+pub mod structure_check;
+pub mod word_count_history;
+pub mod generation_mode;
+pub mod csv;
+pub mod character_io;
+pub mod text_service_cache;
+pub mod request_log;
+pub mod reflow;
+pub mod search;
+pub mod chat_persona_service;
+pub mod log_redaction;
+pub mod vocabulary;
+pub mod embedding_service;
+pub mod retrieval;
+pub mod narrative_check;
+pub mod locked_passages;
+pub mod asset_service;
+pub mod act_distribution;
+pub mod pdf_layout;
+pub mod story_bible;
+pub mod text_diff;
+pub mod token_usage;
+pub mod cancellation;
 
 pub use project_service::ProjectService;
 pub use chapter_service::ChapterService;
 pub use generation_service::GenerationService;
+pub use snapshot_service::SnapshotService;
+pub use settings_service::SettingsService;
+pub use generation_task_service::GenerationTaskService;
+pub use milestone_service::MilestoneService;
+pub use timeline_service::TimelineService;
+pub use export_service::ExportService;
+pub use lore_service::LoreService;
+pub use archive_service::ArchiveService;
+pub use character_service::CharacterService;
+pub use character_arc_service::CharacterArcService;
+pub use operation_log_service::OperationLogService;
+pub use word_count_history::WordCountHistoryService;
+pub use chat_persona_service::ChatPersonaService;
+pub use embedding_service::EmbeddingService;
+pub use asset_service::AssetService;
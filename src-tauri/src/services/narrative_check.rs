@@ -0,0 +1,169 @@
+//! 叙述人称与时态一致性检查
+//!
+//! 是否偏离由模型判断；这里负责按段切分并计算 UTF-16 偏移（与前端编辑器一致）、
+//! 把段落编号分批、解析模型返回的问题，以及把改写后的段落替换回原文。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+pub const POVS: [&str; 2] = ["first", "third"];
+pub const TENSES: [&str; 2] = ["past", "present"];
+
+/// 摘录长度（字符）
+const EXCERPT_CHARS: usize = 80;
+
+pub fn pov_label(pov: &str) -> &'static str {
+    match pov {
+        "first" => "第一人称（以“我”叙述）",
+        _ => "第三人称（以“他/她”或角色名叙述）",
+    }
+}
+
+pub fn tense_label(tense: &str) -> &'static str {
+    match tense {
+        "present" => "现在时",
+        _ => "过去时",
+    }
+}
+
+/// 非空段落及其在原文中的位置
+#[derive(Debug, Clone, Copy)]
+pub struct ParagraphSpan<'a> {
+    pub text: &'a str,
+    byte_start: usize,
+    byte_end: usize,
+    /// 段落首尾的 UTF-16 偏移（不含首尾空白）
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NarrativeDrift {
+    pub paragraph_index: usize,
+    pub start: usize,
+    pub end: usize,
+    /// pov / tense
+    pub kind: String,
+    /// 模型判断的实际人称或时态
+    pub detected: String,
+    pub excerpt: String,
+    pub reason: String,
+    /// 修正后的整段文字（要求改写时提供）
+    pub rewrite: Option<String>,
+}
+
+pub fn paragraph_spans(text: &str) -> Vec<ParagraphSpan<'_>> {
+    let mut spans = Vec::new();
+    let mut byte_offset = 0;
+    let mut units = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim();
+        if !content.is_empty() {
+            let leading = line.len() - line.trim_start().len();
+            let byte_start = byte_offset + leading;
+            let start = units + line[..leading].encode_utf16().count();
+            spans.push(ParagraphSpan {
+                text: content,
+                byte_start,
+                byte_end: byte_start + content.len(),
+                start,
+                end: start + content.encode_utf16().count(),
+            });
+        }
+        byte_offset += line.len();
+        units += line.encode_utf16().count();
+    }
+    spans
+}
+
+/// 按字符数把段落分批，每段以 [P编号] 开头（全章统一编号，从 1 开始）；超长段落单独成批
+pub fn numbered_batches(spans: &[ParagraphSpan], max_chars: usize) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for (index, span) in spans.iter().enumerate() {
+        let line = format!("[P{}] {}\n", index + 1, span.text);
+        let line_chars = line.chars().count();
+        if current_chars > 0 && current_chars + line_chars > max_chars {
+            batches.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        current.push_str(&line);
+        current_chars += line_chars;
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// 解析模型返回的 {"issues": [...]}；只保留正在检查的类型，同一段同一类型只取一条
+pub fn parse_drifts(
+    value: &Value,
+    spans: &[ParagraphSpan],
+    check_pov: bool,
+    check_tense: bool,
+) -> Vec<NarrativeDrift> {
+    let items = value["issues"].as_array().or_else(|| value.as_array());
+    let mut seen = HashSet::new();
+    let mut drifts = Vec::new();
+    for item in items.into_iter().flatten() {
+        let Some(number) = item["paragraph"].as_u64().map(|n| n as usize) else {
+            continue;
+        };
+        if number == 0 || number > spans.len() {
+            continue;
+        }
+        let kind = match item["kind"].as_str().map(str::trim) {
+            Some("pov") if check_pov => "pov",
+            Some("tense") if check_tense => "tense",
+            _ => continue,
+        };
+        if !seen.insert((number, kind)) {
+            continue;
+        }
+        let text = |key: &str| item[key].as_str().map(str::trim).unwrap_or_default().to_string();
+        let span = spans[number - 1];
+        drifts.push(NarrativeDrift {
+            paragraph_index: number - 1,
+            start: span.start,
+            end: span.end,
+            kind: kind.to_string(),
+            detected: text("detected"),
+            excerpt: span.text.chars().take(EXCERPT_CHARS).collect(),
+            reason: text("reason"),
+            rewrite: item["rewrite"]
+                .as_str()
+                .map(str::trim)
+                .filter(|r| !r.is_empty() && *r != span.text)
+                .map(str::to_string),
+        });
+    }
+    drifts.sort_by_key(|drift| (drift.paragraph_index, drift.kind.clone()));
+    drifts
+}
+
+/// 用改写替换对应段落（同一段有多条时取第一条改写），返回新文本和替换的段落数
+pub fn apply_rewrites(text: &str, spans: &[ParagraphSpan], drifts: &[NarrativeDrift]) -> (String, usize) {
+    let mut replacements: Vec<(usize, &str)> = Vec::new();
+    for drift in drifts {
+        if let Some(rewrite) = drift.rewrite.as_deref() {
+            if !replacements.iter().any(|(index, _)| *index == drift.paragraph_index) {
+                replacements.push((drift.paragraph_index, rewrite));
+            }
+        }
+    }
+    replacements.sort_by_key(|(index, _)| *index);
+
+    let mut output = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (index, rewrite) in &replacements {
+        let span = spans[*index];
+        output.push_str(&text[cursor..span.byte_start]);
+        output.push_str(rewrite);
+        cursor = span.byte_end;
+    }
+    output.push_str(&text[cursor..]);
+    (output, replacements.len())
+}
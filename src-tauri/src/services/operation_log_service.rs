@@ -0,0 +1,281 @@
+//! 操作日志
+//!
+//! 记录增删改和生成操作的命令名、目标与简短说明（不含正文），用于排查问题和撤销最近一次操作。
+//! 同一目标的连续保存（如自动保存）在合并窗口内只更新同一条记录，避免写放大。
+
+use sqlx::SqlitePool;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{Chapter, Character, CreateSnapshotInput, Milestone, OperationLogEntry};
+use super::{ChapterService, CharacterService, SnapshotService};
+
+/// 同一命令、同一目标的连续操作在该时间内合并为一条
+const COALESCE_WINDOW_MINUTES: i64 = 10;
+/// 最多保留的日志条数
+const MAX_LOG_ENTRIES: i64 = 1000;
+/// 撤销用的整章快照类型（与章节正文版本快照区分）
+const CHAPTER_STATE_SNAPSHOT: &str = "chapter_state";
+
+pub struct OperationRecord<'a> {
+    pub project_id: Option<&'a str>,
+    pub operation: &'a str,
+    pub target_type: &'a str,
+    pub target_id: Option<&'a str>,
+    pub summary: String,
+    pub snapshot_id: Option<String>,
+}
+
+pub struct OperationLogService;
+
+impl OperationLogService {
+    /// 写入操作日志；失败只写日志，不影响操作本身
+    pub async fn record(pool: &SqlitePool, record: OperationRecord<'_>) {
+        if let Err(e) = Self::try_record(pool, &record).await {
+            log::warn!("Failed to record operation {}: {}", record.operation, e);
+        }
+    }
+
+    /// 本次操作会与最近一条合并时返回 false，调用方据此跳过撤销快照
+    pub async fn needs_snapshot(pool: &SqlitePool, operation: &str, target_id: &str) -> bool {
+        match Self::coalescible(pool, operation, Some(target_id)).await {
+            Ok(last) => last.is_none(),
+            Err(_) => true,
+        }
+    }
+
+    // 最近一条是同一目标的同一操作、未撤销且在合并窗口内时返回该条
+    async fn coalescible(
+        pool: &SqlitePool,
+        operation: &str,
+        target_id: Option<&str>,
+    ) -> Result<Option<OperationLogEntry>> {
+        let Some(last) = Self::latest(pool).await? else {
+            return Ok(None);
+        };
+        let recent = DateTime::parse_from_rfc3339(&last.updated_at)
+            .map(|at| Utc::now().signed_duration_since(at) < Duration::minutes(COALESCE_WINDOW_MINUTES))
+            .unwrap_or(false);
+        let same = last.undone_at.is_none()
+            && last.operation == operation
+            && last.target_id.as_deref() == target_id;
+        Ok((recent && same).then_some(last))
+    }
+
+    async fn try_record(pool: &SqlitePool, record: &OperationRecord<'_>) -> Result<()> {
+        let now = Utc::now();
+
+        // 合并到最近一条时只刷新时间和说明，保留最早的快照以便撤销到这一连串操作之前
+        if let Some(last) = Self::coalescible(pool, record.operation, record.target_id).await? {
+            sqlx::query("UPDATE operation_log SET summary = ?, updated_at = ? WHERE id = ?")
+                .bind(&record.summary)
+                .bind(now.to_rfc3339())
+                .bind(&last.id)
+                .execute(pool)
+                .await?;
+            if let Some(ref unused) = record.snapshot_id {
+                Self::delete_snapshot(pool, unused).await?;
+            }
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO operation_log (id, project_id, operation, target_type, target_id, summary, snapshot_id, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(record.project_id)
+        .bind(record.operation)
+        .bind(record.target_type)
+        .bind(record.target_id)
+        .bind(&record.summary)
+        .bind(&record.snapshot_id)
+        .bind(now.to_rfc3339())
+        .bind(now.to_rfc3339())
+        .execute(pool)
+        .await?;
+
+        Self::prune(pool).await
+    }
+
+    // 超出保留条数时删除最久未更新的记录（合并写入会刷新 updated_at）及其撤销快照
+    async fn prune(pool: &SqlitePool) -> Result<()> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM operation_log")
+            .fetch_one(pool)
+            .await?;
+        if count <= MAX_LOG_ENTRIES {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM operation_log WHERE id NOT IN (
+                SELECT id FROM operation_log ORDER BY updated_at DESC LIMIT ?
+            )
+            "#
+        )
+        .bind(MAX_LOG_ENTRIES)
+        .execute(pool)
+        .await?;
+        sqlx::query(
+            r#"
+            DELETE FROM snapshots
+            WHERE target_type = ? AND id NOT IN (
+                SELECT snapshot_id FROM operation_log WHERE snapshot_id IS NOT NULL
+            )
+            "#
+        )
+        .bind(CHAPTER_STATE_SNAPSHOT)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_snapshot(pool: &SqlitePool, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM snapshots WHERE id = ? AND target_type = ?")
+            .bind(id)
+            .bind(CHAPTER_STATE_SNAPSHOT)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// 保存章节操作前的完整状态，供撤销使用；失败时返回 None
+    pub async fn snapshot_chapter(pool: &SqlitePool, chapter: &Chapter) -> Option<String> {
+        let content = serde_json::to_string(chapter)
+            .map_err(|e| log::warn!("Failed to serialize chapter {}: {}", chapter.id, e))
+            .ok()?;
+        SnapshotService::create(
+            pool,
+            CreateSnapshotInput {
+                target_type: CHAPTER_STATE_SNAPSHOT.to_string(),
+                target_id: chapter.id.clone(),
+                content,
+                note: None,
+                model: None,
+                temperature: None,
+                prompt_template: None,
+            },
+        )
+        .await
+        .map(|snapshot| snapshot.id)
+        .map_err(|e| log::warn!("Failed to snapshot chapter {}: {}", chapter.id, e))
+        .ok()
+    }
+
+    /// 保存角色修改前的设定（普通 character 快照，会出现在角色的历史版本里）；失败时返回 None
+    pub async fn snapshot_character(pool: &SqlitePool, character: &Character, note: &str) -> Option<String> {
+        let content = serde_json::to_string(character)
+            .map_err(|e| log::warn!("Failed to serialize character {}: {}", character.id, e))
+            .ok()?;
+        SnapshotService::create(
+            pool,
+            CreateSnapshotInput {
+                target_type: "character".to_string(),
+                target_id: character.id.clone(),
+                content,
+                note: Some(note.to_string()),
+                model: None,
+                temperature: None,
+                prompt_template: None,
+            },
+        )
+        .await
+        .map(|snapshot| snapshot.id)
+        .map_err(|e| log::warn!("Failed to snapshot character {}: {}", character.id, e))
+        .ok()
+    }
+
+    async fn latest(pool: &SqlitePool) -> Result<Option<OperationLogEntry>> {
+        let entry = sqlx::query_as::<_, OperationLogEntry>(
+            "SELECT * FROM operation_log ORDER BY updated_at DESC LIMIT 1"
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    pub async fn get_recent(pool: &SqlitePool, limit: i64) -> Result<Vec<OperationLogEntry>> {
+        let entries = sqlx::query_as::<_, OperationLogEntry>(
+            "SELECT * FROM operation_log ORDER BY updated_at DESC LIMIT ?"
+        )
+        .bind(limit.clamp(1, MAX_LOG_ENTRIES))
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// 撤销最近一次未撤销的操作。
+    /// 支持：新建章节、修改章节正文/信息、删除章节、恢复章节快照、新建/修改/删除角色、重写角色字段；其余操作只记录不可撤销。
+    /// 同时返回撤销后新达成的字数里程碑
+    pub async fn undo_last(pool: &SqlitePool) -> Result<(OperationLogEntry, Vec<Milestone>)> {
+        let entry = sqlx::query_as::<_, OperationLogEntry>(
+            "SELECT * FROM operation_log WHERE undone_at IS NULL ORDER BY updated_at DESC LIMIT 1"
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("No operation to undo"))?;
+
+        let target_id = entry.target_id.as_deref().unwrap_or_default();
+        let mut milestones = Vec::new();
+        match entry.operation.as_str() {
+            "create_chapter" => ChapterService::delete(pool, target_id).await?,
+            "update_chapter" | "reflow_chapter" | "restore_snapshot" => {
+                let chapter: Chapter = Self::load_snapshot(pool, &entry).await?;
+                milestones = ChapterService::update_text(pool, &chapter.id, chapter.draft_text, chapter.final_text, None).await?;
+            }
+            "update_chapter_meta" | "regenerate_outline_chapter" => {
+                let chapter: Chapter = Self::load_snapshot(pool, &entry).await?;
+                ChapterService::restore_meta(pool, &chapter).await?;
+            }
+            "delete_chapter" => {
+                let chapter: Chapter = Self::load_snapshot(pool, &entry).await?;
+                milestones = ChapterService::restore(pool, &chapter).await?;
+            }
+            "create_character" => CharacterService::delete(pool, target_id).await?,
+            "delete_character" => {
+                let character: Character = Self::load_snapshot(pool, &entry).await?;
+                CharacterService::reinsert(pool, &character).await?;
+            }
+            "regenerate_character_field" | "update_character" => {
+                let character: Character = Self::load_snapshot(pool, &entry).await?;
+                CharacterService::restore(pool, &character).await?;
+            }
+            other => return Err(anyhow::anyhow!("Operation {} cannot be undone", other)),
+        }
+
+        let now = Utc::now().to_rfc3339();
+        sqlx::query("UPDATE operation_log SET undone_at = ? WHERE id = ?")
+            .bind(&now)
+            .bind(&entry.id)
+            .execute(pool)
+            .await?;
+
+        Ok((
+            OperationLogEntry {
+                undone_at: Some(now),
+                ..entry
+            },
+            milestones,
+        ))
+    }
+
+    async fn load_snapshot<T: serde::de::DeserializeOwned>(
+        pool: &SqlitePool,
+        entry: &OperationLogEntry,
+    ) -> Result<T> {
+        let snapshot_id = entry
+            .snapshot_id
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Operation {} has no snapshot to restore", entry.operation))?;
+        let snapshot = SnapshotService::get_by_id(pool, snapshot_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Snapshot {} not found", snapshot_id))?;
+        Ok(serde_json::from_str(&snapshot.content)?)
+    }
+}
@@ -0,0 +1,259 @@
+//! 基于 printpdf 的简单流式排版
+//!
+//! printpdf 只能在指定坐标输出单行文字，这里按字体的字宽自动换行和分页，提供标题、段落、
+//! 图片等基本块。中日韩文字逐字断行，西文单词尽量在空格处断开，句读符号不放在行首。
+//! 字体整体嵌入 PDF（TTF/OTF），页脚统一加页码。
+
+use anyhow::Result;
+use owned_ttf_parser::{AsFaceRef, GlyphId, OwnedFace};
+use printpdf::image_crate::DynamicImage;
+use printpdf::{
+    Image, ImageTransform, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerIndex,
+    PdfLayerReference, PdfPageIndex,
+};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// A4 纸张与页边距（毫米）
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+/// 默认行高为字号的倍数
+const LINE_SPACING: f32 = 1.5;
+const FOOTER_SIZE: f32 = 9.0;
+const PT_TO_MM: f32 = 25.4 / 72.0;
+const LAYER_NAME: &str = "Layer 1";
+
+/// 不放在行首的标点：放不下时挤在上一行末尾
+fn is_closing_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        '，' | '。' | '、' | '；' | '：' | '！' | '？' | '）' | '》' | '」' | '』' | '”' | '’' | '…' | ','
+            | '.' | ';' | ':' | '!' | '?' | ')'
+    )
+}
+
+// 断行单位：连续的西文字母数字为一个单词，其余每个字符单独成为一个单位
+fn break_units(text: &str) -> Vec<&str> {
+    let mut units = Vec::new();
+    let mut word_start: Option<usize> = None;
+    for (index, c) in text.char_indices() {
+        if c.is_ascii_alphanumeric() || (word_start.is_some() && matches!(c, '\'' | '-')) {
+            word_start.get_or_insert(index);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            units.push(&text[start..index]);
+        }
+        units.push(&text[index..index + c.len_utf8()]);
+    }
+    if let Some(start) = word_start {
+        units.push(&text[start..]);
+    }
+    units
+}
+
+pub struct PdfLayout {
+    doc: PdfDocumentReference,
+    font: IndirectFontRef,
+    face: OwnedFace,
+    /// 字符宽度（以 em 为单位）缓存
+    widths: HashMap<char, f32>,
+    pages: Vec<(PdfPageIndex, PdfLayerIndex)>,
+    layer: PdfLayerReference,
+    /// 当前可写位置距页面底部的距离（毫米）
+    cursor: f32,
+    line_spacing: f32,
+}
+
+impl PdfLayout {
+    pub fn new(title: &str, font_bytes: Vec<u8>) -> Result<Self> {
+        let face = OwnedFace::from_vec(font_bytes.clone(), 0)
+            .map_err(|e| anyhow::anyhow!("Failed to parse font: {}", e))?;
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), LAYER_NAME);
+        let font = doc.add_external_font(Cursor::new(font_bytes))?;
+        let layer_ref = doc.get_page(page).get_layer(layer);
+        Ok(Self {
+            doc,
+            font,
+            face,
+            widths: HashMap::new(),
+            pages: vec![(page, layer)],
+            layer: layer_ref,
+            cursor: PAGE_HEIGHT - MARGIN,
+            line_spacing: LINE_SPACING,
+        })
+    }
+
+    /// 行高（字号的倍数），影响之后写入的内容
+    pub fn set_line_spacing(&mut self, line_spacing: f32) {
+        self.line_spacing = line_spacing;
+    }
+
+    fn content_width() -> f32 {
+        PAGE_WIDTH - MARGIN * 2.0
+    }
+
+    fn char_em(&mut self, c: char) -> f32 {
+        if let Some(width) = self.widths.get(&c) {
+            return *width;
+        }
+        let face = self.face.as_face_ref();
+        let width = face
+            .glyph_index(c)
+            .and_then(|GlyphId(id)| face.glyph_hor_advance(GlyphId(id)))
+            .map(|advance| advance as f32 / face.units_per_em() as f32)
+            // 字体缺字时按常见字宽估算
+            .unwrap_or(if c.is_ascii() { 0.5 } else { 1.0 });
+        self.widths.insert(c, width);
+        width
+    }
+
+    fn text_width(&mut self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.char_em(c)).sum::<f32>() * size * PT_TO_MM
+    }
+
+    /// 按宽度把一行文字折成多行
+    fn wrap(&mut self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut width = 0.0;
+        for unit in break_units(text) {
+            let unit_width = self.text_width(unit, size);
+            let first_char = unit.chars().next().unwrap_or(' ');
+            let fits = width + unit_width <= max_width || line.is_empty() || is_closing_punctuation(first_char);
+            if fits && !(line.is_empty() && unit_width > max_width && unit.chars().count() > 1) {
+                line.push_str(unit);
+                width += unit_width;
+                continue;
+            }
+            if !line.trim().is_empty() {
+                lines.push(line.trim_end().to_string());
+            }
+            line = String::new();
+            width = 0.0;
+            if first_char.is_whitespace() {
+                continue;
+            }
+            if unit_width <= max_width {
+                line.push_str(unit);
+                width = unit_width;
+            } else {
+                // 超长单词逐字断开
+                for c in unit.chars() {
+                    let char_width = self.text_width(c.encode_utf8(&mut [0; 4]), size);
+                    if width + char_width > max_width && !line.is_empty() {
+                        lines.push(std::mem::take(&mut line));
+                        width = 0.0;
+                    }
+                    line.push(c);
+                    width += char_width;
+                }
+            }
+        }
+        if !line.trim().is_empty() {
+            lines.push(line.trim_end().to_string());
+        }
+        lines
+    }
+
+    /// 剩余空间不足 height 时换页
+    fn ensure_space(&mut self, height: f32) {
+        if self.cursor - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), LAYER_NAME);
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.pages.push((page, layer));
+        self.cursor = PAGE_HEIGHT - MARGIN;
+    }
+
+    fn write_lines(&mut self, lines: &[String], size: f32, indent: f32) {
+        let line_height = size * self.line_spacing * PT_TO_MM;
+        for line in lines {
+            self.ensure_space(line_height);
+            // 基线放在行框内略低于字号的位置
+            let baseline = self.cursor - size * PT_TO_MM * 1.1;
+            self.layer.use_text(line.as_str(), size, Mm(MARGIN + indent), Mm(baseline), &self.font);
+            self.cursor -= line_height;
+        }
+    }
+
+    /// 另起一页（当前页还是空白时不重复换页）
+    pub fn page_break(&mut self) {
+        if self.cursor < PAGE_HEIGHT - MARGIN {
+            self.new_page();
+        }
+    }
+
+    pub fn spacing(&mut self, height: f32) {
+        self.cursor -= height;
+        if self.cursor < MARGIN {
+            self.new_page();
+        }
+    }
+
+    /// 标题；bookmark 为 true 时加入 PDF 书签（目录）
+    pub fn heading(&mut self, text: &str, size: f32, bookmark: bool) {
+        let lines = self.wrap(text.trim(), size, Self::content_width());
+        // 标题至少和下一行正文留在同一页
+        self.ensure_space(size * self.line_spacing * PT_TO_MM * (lines.len() as f32 + 1.0) + size * 0.4);
+        self.spacing(size * 0.4);
+        if bookmark {
+            if let Some((page, _)) = self.pages.last() {
+                self.doc.add_bookmark(text.trim(), *page);
+            }
+        }
+        self.write_lines(&lines, size, 0.0);
+    }
+
+    /// 段落：按原有换行分段，段内自动折行；indent 为整段左缩进（毫米）
+    pub fn paragraph(&mut self, text: &str, size: f32, indent: f32) {
+        let max_width = Self::content_width() - indent;
+        for raw_line in text.lines() {
+            let raw_line = raw_line.trim();
+            if raw_line.is_empty() {
+                continue;
+            }
+            let lines = self.wrap(raw_line, size, max_width);
+            self.write_lines(&lines, size, indent);
+        }
+        self.spacing(size * 0.3 * PT_TO_MM);
+    }
+
+    /// 插入图片，按原始比例缩放到不超过 max_width × max_height（毫米）
+    pub fn image(&mut self, image: &DynamicImage, max_width: f32, max_height: f32) {
+        let (pixel_width, pixel_height) = (image.width().max(1) as f32, image.height().max(1) as f32);
+        let scale = (max_width.min(Self::content_width()) / pixel_width)
+            .min(max_height.min(PAGE_HEIGHT - MARGIN * 2.0) / pixel_height);
+        let (width, height) = (pixel_width * scale, pixel_height * scale);
+        self.ensure_space(height);
+        // 去掉透明通道，避免部分阅读器把透明区域显示为黑色
+        let image = DynamicImage::ImageRgb8(image.to_rgb8());
+        Image::from_dynamic_image(&image).add_to_layer(
+            self.layer.clone(),
+            ImageTransform {
+                translate_x: Some(Mm(MARGIN)),
+                translate_y: Some(Mm(self.cursor - height)),
+                dpi: Some(pixel_width * 25.4 / width),
+                ..Default::default()
+            },
+        );
+        self.cursor -= height + 3.0;
+    }
+
+    /// 加页码并输出 PDF 字节
+    pub fn finish(self) -> Result<Vec<u8>> {
+        let total = self.pages.len();
+        for (index, (page, layer)) in self.pages.iter().enumerate() {
+            let label = format!("{} / {}", index + 1, total);
+            let layer = self.doc.get_page(*page).get_layer(*layer);
+            let x = (PAGE_WIDTH - label.len() as f32 * FOOTER_SIZE * 0.5 * PT_TO_MM) / 2.0;
+            layer.use_text(label, FOOTER_SIZE, Mm(x), Mm(MARGIN / 2.0), &self.font);
+        }
+        Ok(self.doc.save_to_bytes()?)
+    }
+}
@@ -2,7 +2,15 @@ use sqlx::SqlitePool;
 use chrono::Utc;
 use uuid::Uuid;
 use anyhow::Result;
-use crate::models::{Project, CreateProjectInput};
+use crate::models::{
+    ChapterStatusCount, CreateProjectInput, DashboardTask, Project, ProjectBible, ProjectDashboard, ProjectNotes,
+    ProjectOutline, UpdateProjectInput,
+};
+use crate::services::genre::normalize_optional_genre;
+use crate::services::{SettingsService, WordCountHistoryService};
+
+/// 概览中列出的最近任务数
+const DASHBOARD_RECENT_TASKS: i64 = 10;
 
 pub struct ProjectService;
 
@@ -17,11 +25,13 @@ impl ProjectService {
     pub async fn create(pool: &SqlitePool, input: CreateProjectInput) -> Result<Project> {
         let now = Utc::now().to_rfc3339();
         let language = normalize_project_language(input.language.as_deref());
+        let genre_code = normalize_optional_genre(input.genre.as_deref());
         let project = Project {
             id: Uuid::new_v4().to_string(),
             title: input.title,
             author: input.author,
             genre: input.genre,
+            genre_code,
             description: input.description,
             language,
             target_word_count: input.target_word_count,
@@ -31,18 +41,21 @@ impl ProjectService {
             updated_at: now,
             cover_images: input.cover_images,
             default_cover_id: input.default_cover_id,
+            archived_at: None,
+            archive_path: None,
         };
 
         sqlx::query(
             r#"
-            INSERT INTO projects (id, title, author, genre, description, language, target_word_count, current_word_count, status, cover_images, default_cover_id, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO projects (id, title, author, genre, genre_code, description, language, target_word_count, current_word_count, status, cover_images, default_cover_id, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#
         )
         .bind(&project.id)
         .bind(&project.title)
         .bind(&project.author)
         .bind(&project.genre)
+        .bind(&project.genre_code)
         .bind(&project.description)
         .bind(&project.language)
         .bind(project.target_word_count)
@@ -60,7 +73,17 @@ impl ProjectService {
 
     pub async fn get_all(pool: &SqlitePool) -> Result<Vec<Project>> {
         let projects = sqlx::query_as::<_, Project>(
-            "SELECT * FROM projects ORDER BY updated_at DESC"
+            "SELECT * FROM projects WHERE archived_at IS NULL ORDER BY updated_at DESC"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(projects)
+    }
+
+    pub async fn get_archived(pool: &SqlitePool) -> Result<Vec<Project>> {
+        let projects = sqlx::query_as::<_, Project>(
+            "SELECT * FROM projects WHERE archived_at IS NOT NULL ORDER BY archived_at DESC"
         )
         .fetch_all(pool)
         .await?;
@@ -88,17 +111,19 @@ impl ProjectService {
             Some(value) => normalize_project_language(Some(value)),
             None => existing.language,
         };
+        let genre_code = normalize_optional_genre(input.genre.as_deref());
         
         sqlx::query(
             r#"
             UPDATE projects 
-            SET title = ?, author = ?, genre = ?, description = ?, language = ?, target_word_count = ?, cover_images = ?, default_cover_id = ?, updated_at = ?
+            SET title = ?, author = ?, genre = ?, genre_code = ?, description = ?, language = ?, target_word_count = ?, cover_images = ?, default_cover_id = ?, updated_at = ?
             WHERE id = ?
             "#
         )
         .bind(&input.title)
         .bind(&input.author)
         .bind(&input.genre)
+        .bind(&genre_code)
         .bind(&input.description)
         .bind(&language)
         .bind(input.target_word_count)
@@ -113,15 +138,256 @@ impl ProjectService {
             .ok_or_else(|| anyhow::anyhow!("Project not found after update"))
     }
 
+    /// 部分更新：只覆盖传入的字段；题材变化时同步重算 genre_code
+    pub async fn update_partial(pool: &SqlitePool, id: &str, input: UpdateProjectInput) -> Result<Project> {
+        let now = Utc::now().to_rfc3339();
+        let language = input.language.as_deref().map(|value| normalize_project_language(Some(value)));
+        let genre_changed = input.genre.is_some();
+        let genre_code = normalize_optional_genre(input.genre.as_deref());
+
+        let result = sqlx::query(
+            r#"
+            UPDATE projects
+            SET title = COALESCE(?, title),
+                author = COALESCE(?, author),
+                genre = COALESCE(?, genre),
+                genre_code = CASE WHEN ? THEN ? ELSE genre_code END,
+                description = COALESCE(?, description),
+                language = COALESCE(?, language),
+                target_word_count = COALESCE(?, target_word_count),
+                cover_images = COALESCE(?, cover_images),
+                default_cover_id = COALESCE(?, default_cover_id),
+                updated_at = ?
+            WHERE id = ?
+            "#
+        )
+        .bind(input.title)
+        .bind(input.author)
+        .bind(input.genre)
+        .bind(genre_changed)
+        .bind(genre_code)
+        .bind(input.description)
+        .bind(language)
+        .bind(input.target_word_count)
+        .bind(input.cover_images)
+        .bind(input.default_cover_id)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Project not found"));
+        }
+
+        Self::get_by_id(pool, id).await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found after update"))
+    }
+
     pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
         sqlx::query("DELETE FROM projects WHERE id = ?")
             .bind(id)
             .execute(pool)
             .await?;
 
+        // 项目级设置不受外键约束，需要单独清理
+        SettingsService::delete_project_settings(pool, id).await?;
+
         Ok(())
     }
 
+    /// 首页概览；所有统计在同一个读事务里完成，数字之间互相一致
+    pub async fn dashboard(pool: &SqlitePool, id: &str) -> Result<Option<ProjectDashboard>> {
+        let mut tx = pool.begin().await?;
+        let Some(project) = sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let chapters_by_status = sqlx::query_as::<_, ChapterStatusCount>(
+            "SELECT status, COUNT(*) AS count FROM chapters WHERE project_id = ? GROUP BY status ORDER BY status"
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let total_words: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(word_count), 0) FROM chapters WHERE project_id = ?"
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let recent_tasks = sqlx::query_as::<_, DashboardTask>(
+            r#"
+            SELECT id, task_type, status, model, token_count, cost, error_message, created_at, completed_at
+            FROM generation_tasks
+            WHERE project_id = ?
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(id)
+        .bind(DASHBOARD_RECENT_TASKS)
+        .fetch_all(&mut *tx)
+        .await?;
+        let (total_cost, total_tokens): (Option<f64>, i64) = sqlx::query_as(
+            "SELECT SUM(cost), COALESCE(SUM(token_count), 0) FROM generation_tasks WHERE project_id = ?"
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let character_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM characters WHERE project_id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let lore_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM lore WHERE project_id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        // 快照按目标 id 关联：大纲快照的目标是项目本身
+        let latest_snapshot_at: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(created_at) FROM snapshots
+            WHERE target_id = ?
+               OR target_id IN (SELECT id FROM chapters WHERE project_id = ?)
+               OR target_id IN (SELECT id FROM characters WHERE project_id = ?)
+            "#
+        )
+        .bind(id)
+        .bind(id)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let target_word_count = project.target_word_count.filter(|target| *target > 0);
+        let progress_percent = target_word_count
+            .map(|target| (total_words as f64 * 1000.0 / target as f64).round() / 10.0);
+        Ok(Some(ProjectDashboard {
+            chapter_count: chapters_by_status.iter().map(|row| row.count).sum(),
+            chapters_by_status,
+            total_words,
+            target_word_count,
+            progress_percent,
+            recent_tasks,
+            total_cost,
+            total_tokens,
+            character_count,
+            lore_count,
+            latest_snapshot_at,
+            project,
+        }))
+    }
+
+    pub async fn get_notes(pool: &SqlitePool, id: &str) -> Result<Option<ProjectNotes>> {
+        let notes = sqlx::query_as::<_, ProjectNotes>(
+            "SELECT id AS project_id, notes, notes_updated_at FROM projects WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(notes)
+    }
+
+    /// 只写笔记列，不更新 updated_at，避免自动保存打乱项目列表排序
+    pub async fn save_notes(pool: &SqlitePool, id: &str, notes: &str) -> Result<ProjectNotes> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            "UPDATE projects SET notes = ?, notes_updated_at = ? WHERE id = ?"
+        )
+        .bind(notes)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Project not found"));
+        }
+
+        Ok(ProjectNotes {
+            project_id: id.to_string(),
+            notes: Some(notes.to_string()),
+            notes_updated_at: Some(now),
+        })
+    }
+
+    pub async fn get_bible(pool: &SqlitePool, id: &str) -> Result<Option<ProjectBible>> {
+        let bible = sqlx::query_as::<_, ProjectBible>(
+            "SELECT id AS project_id, bible, bible_updated_at FROM projects WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(bible)
+    }
+
+    /// 设定集是派生内容，与笔记一样不更新 updated_at
+    pub async fn save_bible(pool: &SqlitePool, id: &str, bible: &str) -> Result<ProjectBible> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            "UPDATE projects SET bible = ?, bible_updated_at = ? WHERE id = ?"
+        )
+        .bind(bible)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Project not found"));
+        }
+
+        Ok(ProjectBible {
+            project_id: id.to_string(),
+            bible: Some(bible.to_string()),
+            bible_updated_at: Some(now),
+        })
+    }
+
+    pub async fn get_outline(pool: &SqlitePool, id: &str) -> Result<Option<ProjectOutline>> {
+        let outline = sqlx::query_as::<_, ProjectOutline>(
+            "SELECT id AS project_id, outline, outline_updated_at FROM projects WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(outline)
+    }
+
+    pub async fn save_outline(pool: &SqlitePool, id: &str, outline: &str) -> Result<ProjectOutline> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            "UPDATE projects SET outline = ?, outline_updated_at = ?, updated_at = ? WHERE id = ?"
+        )
+        .bind(outline)
+        .bind(&now)
+        .bind(&now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Project not found"));
+        }
+
+        Ok(ProjectOutline {
+            project_id: id.to_string(),
+            outline: Some(outline.to_string()),
+            outline_updated_at: Some(now),
+        })
+    }
+
     pub async fn update_word_count(pool: &SqlitePool, id: &str, count: i64) -> Result<()> {
         let now = Utc::now().to_rfc3339();
         
@@ -134,6 +400,8 @@ impl ProjectService {
         .execute(pool)
         .await?;
 
+        WordCountHistoryService::record(pool, id, count).await;
+
         Ok(())
     }
 }
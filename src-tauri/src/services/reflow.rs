@@ -0,0 +1,114 @@
+//! 章节段落整理
+//!
+//! 导入或粘贴的正文常带有段中硬换行、缩进混乱的问题。这里按空行、缩进和
+//! 句末标点重新切分段落，再按规则统一缩进和段间空行。只处理文本，不写库。
+
+use anyhow::Result;
+use crate::models::ReflowRules;
+
+const FULLWIDTH_INDENT: &str = "\u{3000}\u{3000}";
+
+/// 句末标点：行尾是这些字符时视为段落结束，不与下一行合并
+const PARAGRAPH_END: &[char] = &[
+    '。', '！', '？', '…', '”', '」', '』', '）', '：', '.', '!', '?', '"', ')', ':', '—', '~', '～',
+];
+
+/// 行首是这些字符时视为新段落（对话）
+const PARAGRAPH_START: &[char] = &['“', '「', '『', '"'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    FullWidth,
+    None,
+    Keep,
+}
+
+impl IndentStyle {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fullwidth" | "full_width" | "全角空格" => Some(Self::FullWidth),
+            "none" | "无" => Some(Self::None),
+            "keep" => Some(Self::Keep),
+            _ => None,
+        }
+    }
+}
+
+pub struct ReflowOutput {
+    pub text: String,
+    pub paragraphs: usize,
+    pub joined_lines: usize,
+}
+
+fn is_indent(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\u{3000}'
+}
+
+fn is_indented(line: &str) -> bool {
+    line.starts_with(['\u{3000}', '\t']) || line.starts_with("  ")
+}
+
+// 拉丁字母/数字之间补一个空格，中文直接拼接
+fn join_line(paragraph: &mut String, line: &str) {
+    let needs_space = paragraph.chars().last().is_some_and(|c| c.is_ascii_alphanumeric() || c == ',')
+        && line.chars().next().is_some_and(|c| c.is_ascii_alphanumeric());
+    if needs_space {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(line);
+}
+
+/// 按规则重排段落；缩进规则无法识别时报错
+pub fn reflow_text(text: &str, rules: &ReflowRules) -> Result<ReflowOutput> {
+    let indent = IndentStyle::parse(&rules.indent)
+        .ok_or_else(|| anyhow::anyhow!("Unknown indent style: {}", rules.indent))?;
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    // (原始缩进, 段落正文)
+    let mut paragraphs: Vec<(String, String)> = Vec::new();
+    let mut open = false;
+    let mut joined_lines = 0;
+
+    for raw in text.lines() {
+        let line = raw.trim_end();
+        let body = line.trim_start_matches(is_indent);
+        if body.is_empty() {
+            open = false;
+            continue;
+        }
+
+        let continues = rules.join_wrapped_lines
+            && open
+            && !is_indented(line)
+            && !body.starts_with(PARAGRAPH_START)
+            && paragraphs
+                .last()
+                .is_some_and(|(_, p)| !p.ends_with(PARAGRAPH_END));
+        if continues {
+            if let Some((_, paragraph)) = paragraphs.last_mut() {
+                join_line(paragraph, body);
+                joined_lines += 1;
+            }
+        } else {
+            let original_indent = &line[..line.len() - body.len()];
+            paragraphs.push((original_indent.to_string(), body.to_string()));
+        }
+        open = true;
+    }
+
+    let separator = if rules.blank_line_between { "\n\n" } else { "\n" };
+    let text = paragraphs
+        .iter()
+        .map(|(original, body)| match indent {
+            IndentStyle::FullWidth => format!("{}{}", FULLWIDTH_INDENT, body),
+            IndentStyle::None => body.clone(),
+            IndentStyle::Keep => format!("{}{}", original, body),
+        })
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    Ok(ReflowOutput {
+        text,
+        paragraphs: paragraphs.len(),
+        joined_lines,
+    })
+}
@@ -0,0 +1,94 @@
+//! 按生成请求归集的日志
+//!
+//! 每次生成分配一个关联 id（有任务记录时即任务 id），请求内的日志都带上该 id，
+//! 并在内存中按 id 保留最近的若干行，供 get_logs 取回附到问题反馈里。
+//! 写入前先脱敏（见 log_redaction），取回的日志可以直接分享。
+
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use uuid::Uuid;
+use super::log_redaction::redact;
+
+// 保留日志的请求数，超出时丢弃最早的请求
+const MAX_TRACKED_REQUESTS: usize = 200;
+// 单个请求保留的日志行数
+const MAX_LINES_PER_REQUEST: usize = 200;
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestLogLine {
+    pub timestamp: String,
+    pub level: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct RequestLogs {
+    order: VecDeque<String>,
+    lines: HashMap<String, VecDeque<RequestLogLine>>,
+}
+
+lazy_static::lazy_static! {
+    static ref REQUEST_LOGS: Mutex<RequestLogs> = Mutex::new(RequestLogs::default());
+}
+
+/// 有任务 id 时沿用任务 id，否则生成新 id
+pub fn correlation_id(task_id: Option<&str>) -> String {
+    task_id.map_or_else(|| Uuid::new_v4().to_string(), str::to_string)
+}
+
+/// 在关联 id 的作用域内执行 future，其中的 info/warn 都会归到该 id 下
+pub async fn scope<F: Future>(correlation_id: String, future: F) -> F::Output {
+    CORRELATION_ID.scope(correlation_id, future).await
+}
+
+fn record(level: log::Level, message: &str) {
+    let message = &*redact(message);
+    let Ok(id) = CORRELATION_ID.try_with(|id| id.clone()) else {
+        log::log!(level, "{}", message);
+        return;
+    };
+    log::log!(level, "[{}] {}", id, message);
+
+    let mut logs = REQUEST_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    if !logs.lines.contains_key(&id) {
+        if logs.order.len() >= MAX_TRACKED_REQUESTS {
+            if let Some(oldest) = logs.order.pop_front() {
+                logs.lines.remove(&oldest);
+            }
+        }
+        logs.order.push_back(id.clone());
+    }
+    let lines = logs.lines.entry(id).or_default();
+    if lines.len() >= MAX_LINES_PER_REQUEST {
+        lines.pop_front();
+    }
+    lines.push_back(RequestLogLine {
+        timestamp: Utc::now().to_rfc3339(),
+        level: level.to_string(),
+        message: message.to_string(),
+    });
+}
+
+pub fn info(message: &str) {
+    record(log::Level::Info, message);
+}
+
+pub fn warn(message: &str) {
+    record(log::Level::Warn, message);
+}
+
+/// 取回某个请求（任务）缓存的日志行，未找到时返回空列表
+pub fn get_lines(correlation_id: &str) -> Vec<RequestLogLine> {
+    let logs = REQUEST_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    logs.lines
+        .get(correlation_id)
+        .map(|lines| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,288 @@
+//! 章节生成时挑选相关的设定和角色
+//!
+//! 设定集很大时全部注入既占上下文又冲淡重点。配置了向量模型时，经当前文本模型地址的
+//! /embeddings 接口为每个条目计算向量并缓存到 embeddings 表（条目文本变化后重新计算），
+//! 按与章节标题、目标、冲突的余弦相似度取前 K 条；未配置向量模型或接口出错时退回关键词匹配。
+//! 选中的条目保持原有顺序。
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use crate::api::DeepSeekClient;
+use crate::models::{Chapter, Character, Lore, RetrievalSettings, TextModelConfigInput};
+use super::{CharacterService, EmbeddingService, LoreService};
+use super::chapter_context::{format_characters, format_lore};
+use super::context_budget::select_head_context;
+use super::embedding_service::StoredEmbedding;
+use super::snapshot_service::content_hash;
+use super::vocabulary::tokenize;
+
+pub const SOURCE_LORE: &str = "lore";
+pub const SOURCE_CHARACTER: &str = "character";
+
+/// 单个条目送去计算向量的 token 上限
+const EMBEDDING_INPUT_TOKENS: usize = 2000;
+/// 关键词匹配时，条目名称直接出现在章节信息里的加分
+const NAME_MATCH_BONUS: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingRefreshReport {
+    pub model: String,
+    /// 本次新计算的条目数
+    pub embedded: usize,
+    /// 文本未变、沿用缓存的条目数
+    pub reused: usize,
+    /// 条目已删除而清理的缓存数
+    pub removed: usize,
+}
+
+struct Candidate {
+    source_type: &'static str,
+    source_id: String,
+    name: String,
+    text: String,
+}
+
+fn lore_candidates(lore: &[Lore]) -> Vec<Candidate> {
+    lore.iter()
+        .map(|entry| Candidate {
+            source_type: SOURCE_LORE,
+            source_id: entry.id.clone(),
+            name: entry.title.clone(),
+            text: format_lore(std::slice::from_ref(entry)),
+        })
+        .collect()
+}
+
+fn character_candidates(characters: &[Character]) -> Vec<Candidate> {
+    characters
+        .iter()
+        .map(|character| Candidate {
+            source_type: SOURCE_CHARACTER,
+            source_id: character.id.clone(),
+            name: character.name.clone(),
+            text: format_characters(std::slice::from_ref(character)),
+        })
+        .collect()
+}
+
+/// 检索用的章节信息：标题、本章目标和冲突
+pub fn chapter_query(chapter: &Chapter) -> String {
+    [Some(chapter.title.as_str()), chapter.outline_goal.as_deref(), chapter.conflict.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn embedding_client(config: &TextModelConfigInput, model: &str) -> DeepSeekClient {
+    let base = config.normalized_api_base_url();
+    let base = base.strip_suffix("/chat/completions").unwrap_or(&base).to_string();
+    DeepSeekClient::new(config.api_key.clone(), Some(base), Some(model.to_string()))
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f64, 0.0f64, 0.0f64);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+fn keyword_scores(query: &str, candidates: &[Candidate]) -> Vec<f64> {
+    let query_lower = query.to_lowercase();
+    let query_tokens: HashSet<String> = tokenize(query).into_iter().collect();
+    candidates
+        .iter()
+        .map(|candidate| {
+            let tokens: HashSet<String> = tokenize(&candidate.text).into_iter().collect();
+            let overlap = tokens.iter().filter(|token| query_tokens.contains(*token)).count();
+            // 按条目长度开方归一，避免长条目仅凭篇幅胜出
+            let mut score = if tokens.is_empty() {
+                0.0
+            } else {
+                overlap as f64 / (tokens.len() as f64).sqrt()
+            };
+            let name = candidate.name.trim().to_lowercase();
+            if !name.is_empty() && query_lower.contains(&name) {
+                score += NAME_MATCH_BONUS;
+            }
+            score
+        })
+        .collect()
+}
+
+/// 返回与 candidates 对齐的向量：缓存中文本未变的直接复用，其余按批计算并写回缓存。
+/// 第二个返回值为新计算的条目数
+async fn ensure_embeddings(
+    pool: &SqlitePool,
+    client: &DeepSeekClient,
+    model: &str,
+    batch_size: usize,
+    project_id: &str,
+    candidates: &[Candidate],
+) -> Result<(Vec<Vec<f32>>, usize)> {
+    let mut cached: HashMap<(String, String), StoredEmbedding> = EmbeddingService::get_by_project(pool, project_id, model)
+        .await?
+        .into_iter()
+        .map(|stored| ((stored.source_type.clone(), stored.source_id.clone()), stored))
+        .collect();
+
+    let mut vectors: Vec<Option<Vec<f32>>> = Vec::with_capacity(candidates.len());
+    let mut pending: Vec<(usize, String)> = Vec::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let hash = content_hash(&candidate.text);
+        let key = (candidate.source_type.to_string(), candidate.source_id.clone());
+        match cached.remove(&key) {
+            Some(stored) if stored.content_hash == hash && !stored.vector.is_empty() => vectors.push(Some(stored.vector)),
+            _ => {
+                vectors.push(None);
+                pending.push((index, hash));
+            }
+        }
+    }
+
+    for batch in pending.chunks(batch_size.max(1)) {
+        let inputs: Vec<String> = batch
+            .iter()
+            .map(|(index, _)| select_head_context(&candidates[*index].text, EMBEDDING_INPUT_TOKENS).to_string())
+            .collect();
+        let embedded = client.embeddings(&inputs).await?;
+        for ((index, hash), vector) in batch.iter().zip(embedded) {
+            let candidate = &candidates[*index];
+            let stored = StoredEmbedding {
+                source_type: candidate.source_type.to_string(),
+                source_id: candidate.source_id.clone(),
+                content_hash: hash.clone(),
+                vector,
+            };
+            EmbeddingService::upsert(pool, project_id, model, &stored).await?;
+            vectors[*index] = Some(stored.vector);
+        }
+    }
+
+    Ok((vectors.into_iter().map(Option::unwrap_or_default).collect(), pending.len()))
+}
+
+async fn embedding_scores(
+    pool: &SqlitePool,
+    config: &TextModelConfigInput,
+    settings: &RetrievalSettings,
+    project_id: &str,
+    query: &str,
+    candidates: &[Candidate],
+) -> Result<Vec<f64>> {
+    let model = settings.embedding_model.trim();
+    let client = embedding_client(config, model);
+    let (vectors, _) = ensure_embeddings(pool, &client, model, settings.batch_size, project_id, candidates).await?;
+    let query_vector = client
+        .embeddings(&[select_head_context(query, EMBEDDING_INPUT_TOKENS).to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow::anyhow!("Embedding API returned no vector for the query"))?;
+    Ok(vectors.iter().map(|vector| cosine(&query_vector, vector)).collect())
+}
+
+/// 按得分取前 top_k 个，保持原有顺序
+fn keep_top<T>(items: Vec<T>, scores: &[f64], top_k: usize) -> Vec<T> {
+    if items.len() <= top_k {
+        return items;
+    }
+    let mut ranked: Vec<usize> = (0..items.len()).collect();
+    ranked.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]));
+    let selected: HashSet<usize> = ranked.into_iter().take(top_k).collect();
+    items
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| selected.contains(index))
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// 挑选与 query 最相关的角色和设定；config 为空或未配置向量模型时按关键词匹配
+pub async fn select_relevant(
+    pool: &SqlitePool,
+    config: Option<&TextModelConfigInput>,
+    settings: &RetrievalSettings,
+    project_id: &str,
+    query: &str,
+    characters: Vec<Character>,
+    lore: Vec<Lore>,
+) -> (Vec<Character>, Vec<Lore>) {
+    let top_k = settings.top_k;
+    if top_k == 0 || query.trim().is_empty() || (characters.len() <= top_k && lore.len() <= top_k) {
+        return (characters, lore);
+    }
+
+    let mut candidates = character_candidates(&characters);
+    candidates.extend(lore_candidates(&lore));
+
+    let embedding_config = config.filter(|_| !settings.embedding_model.trim().is_empty());
+    let scores = match embedding_config {
+        Some(config) => match embedding_scores(pool, config, settings, project_id, query, &candidates).await {
+            Ok(scores) => scores,
+            Err(e) => {
+                log::warn!("Embedding retrieval failed, falling back to keyword matching: {}", e);
+                keyword_scores(query, &candidates)
+            }
+        },
+        None => keyword_scores(query, &candidates),
+    };
+
+    let (character_scores, lore_scores) = scores.split_at(characters.len());
+    (
+        keep_top(characters, character_scores, top_k),
+        keep_top(lore, lore_scores, top_k),
+    )
+}
+
+/// 预先计算（或更新）项目全部设定和角色的向量，并清理已删除条目的缓存
+pub async fn refresh_project_embeddings(
+    pool: &SqlitePool,
+    config: &TextModelConfigInput,
+    settings: &RetrievalSettings,
+    project_id: &str,
+) -> Result<EmbeddingRefreshReport> {
+    let model = settings.embedding_model.trim();
+    if model.is_empty() {
+        return Err(anyhow::anyhow!("Embedding model is not configured"));
+    }
+    let characters = CharacterService::get_by_project(pool, project_id).await?;
+    let lore = LoreService::get_by_project(pool, project_id).await?;
+    let mut candidates = character_candidates(&characters);
+    candidates.extend(lore_candidates(&lore));
+
+    let current: HashSet<(&str, &str)> = candidates
+        .iter()
+        .map(|candidate| (candidate.source_type, candidate.source_id.as_str()))
+        .collect();
+    let mut removed = 0;
+    for stored in EmbeddingService::get_by_project(pool, project_id, model).await? {
+        if !current.contains(&(stored.source_type.as_str(), stored.source_id.as_str())) {
+            EmbeddingService::delete(pool, &stored.source_type, &stored.source_id).await?;
+            removed += 1;
+        }
+    }
+
+    let client = embedding_client(config, model);
+    let (_, embedded) = ensure_embeddings(pool, &client, model, settings.batch_size, project_id, &candidates).await?;
+    Ok(EmbeddingRefreshReport {
+        model: model.to_string(),
+        embedded,
+        reused: candidates.len() - embedded,
+        removed,
+    })
+}
@@ -0,0 +1,196 @@
+//! 跨项目全文搜索
+//!
+//! 先用 LIKE 在数据库里筛出可能命中的章节，再在内存中计数和截取片段。
+//! 英文按 ASCII 忽略大小写匹配；归档项目的章节已移出数据库，不参与搜索。
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use crate::models::Chapter;
+use super::ProjectService;
+use super::chapter_context::chapter_text;
+
+/// 片段中关键词前后保留的字数
+const SNIPPET_CONTEXT_CHARS: usize = 30;
+const MAX_SNIPPETS_PER_CHAPTER: usize = 3;
+const DEFAULT_RESULT_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalSearchOptions {
+    /// 只搜索这些状态的项目，为空时不限
+    pub statuses: Vec<String>,
+    /// full_text：标题、大纲和正文；titles_outlines：只搜标题、大纲和摘要
+    pub scope: String,
+    /// 最多返回的项目数
+    pub limit: usize,
+}
+
+impl Default for GlobalSearchOptions {
+    fn default() -> Self {
+        Self {
+            statuses: Vec::new(),
+            scope: "full_text".to_string(),
+            limit: DEFAULT_RESULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSnippet {
+    /// title / outline_goal / conflict / summary / text
+    pub field: &'static str,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterSearchHit {
+    pub chapter_id: String,
+    pub title: String,
+    pub order_index: i32,
+    pub match_count: usize,
+    pub snippets: Vec<SearchSnippet>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectSearchGroup {
+    pub project_id: String,
+    pub project_title: String,
+    pub project_status: String,
+    pub match_count: usize,
+    /// 按命中次数从多到少排列
+    pub chapters: Vec<ChapterSearchHit>,
+}
+
+fn like_pattern(query: &str) -> String {
+    let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// 返回每处命中在原文中的字节区间
+fn find_matches(text: &str, needle: &str) -> Vec<(usize, usize)> {
+    let haystack = text.to_ascii_lowercase();
+    haystack
+        .match_indices(needle)
+        .map(|(start, m)| (start, start + m.len()))
+        .collect()
+}
+
+fn snippet(text: &str, (start, end): (usize, usize)) -> String {
+    let before: String = text[..start]
+        .chars()
+        .rev()
+        .take(SNIPPET_CONTEXT_CHARS)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    let after: String = text[end..].chars().take(SNIPPET_CONTEXT_CHARS).collect();
+    let mut snippet = String::new();
+    if before.len() < start {
+        snippet.push('…');
+    }
+    snippet.push_str(&before);
+    snippet.push_str(&text[start..end]);
+    snippet.push_str(&after);
+    if end + after.len() < text.len() {
+        snippet.push('…');
+    }
+    snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn search_chapter(chapter: &Chapter, needle: &str, full_text: bool) -> Option<ChapterSearchHit> {
+    let mut fields: Vec<(&'static str, &str)> = vec![("title", chapter.title.as_str())];
+    fields.extend(chapter.outline_goal.as_deref().map(|t| ("outline_goal", t)));
+    fields.extend(chapter.conflict.as_deref().map(|t| ("conflict", t)));
+    fields.extend(chapter.summary.as_deref().map(|t| ("summary", t)));
+    if full_text {
+        fields.extend(chapter_text(chapter).map(|t| ("text", t)));
+    }
+
+    let mut match_count = 0;
+    let mut snippets = Vec::new();
+    for (field, text) in fields {
+        let matches = find_matches(text, needle);
+        match_count += matches.len();
+        for range in matches {
+            if snippets.len() >= MAX_SNIPPETS_PER_CHAPTER {
+                break;
+            }
+            snippets.push(SearchSnippet { field, text: snippet(text, range) });
+        }
+    }
+
+    (match_count > 0).then(|| ChapterSearchHit {
+        chapter_id: chapter.id.clone(),
+        title: chapter.title.clone(),
+        order_index: chapter.order_index,
+        match_count,
+        snippets,
+    })
+}
+
+pub async fn search_all_projects(
+    pool: &SqlitePool,
+    query: &str,
+    options: &GlobalSearchOptions,
+) -> Result<Vec<ProjectSearchGroup>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(anyhow::anyhow!("Search query is empty"));
+    }
+    let full_text = match options.scope.as_str() {
+        "full_text" => true,
+        "titles_outlines" => false,
+        other => return Err(anyhow::anyhow!("Unknown search scope: {}", other)),
+    };
+
+    let projects: HashMap<String, _> = ProjectService::get_all(pool)
+        .await?
+        .into_iter()
+        .filter(|p| options.statuses.is_empty() || options.statuses.contains(&p.status))
+        .map(|p| (p.id.clone(), p))
+        .collect();
+
+    let mut sql = String::from(
+        "SELECT * FROM chapters WHERE (title LIKE ?1 ESCAPE '\\' OR outline_goal LIKE ?1 ESCAPE '\\' \
+         OR conflict LIKE ?1 ESCAPE '\\' OR summary LIKE ?1 ESCAPE '\\'",
+    );
+    if full_text {
+        sql.push_str(" OR final_text LIKE ?1 ESCAPE '\\' OR draft_text LIKE ?1 ESCAPE '\\'");
+    }
+    sql.push_str(") ORDER BY project_id, order_index ASC");
+    let chapters = sqlx::query_as::<_, Chapter>(&sql)
+        .bind(like_pattern(query))
+        .fetch_all(pool)
+        .await?;
+
+    let needle = query.to_ascii_lowercase();
+    let mut groups: HashMap<String, ProjectSearchGroup> = HashMap::new();
+    for chapter in &chapters {
+        let Some(project) = projects.get(&chapter.project_id) else {
+            continue;
+        };
+        let Some(hit) = search_chapter(chapter, &needle, full_text) else {
+            continue;
+        };
+        let group = groups.entry(project.id.clone()).or_insert_with(|| ProjectSearchGroup {
+            project_id: project.id.clone(),
+            project_title: project.title.clone(),
+            project_status: project.status.clone(),
+            match_count: 0,
+            chapters: Vec::new(),
+        });
+        group.match_count += hit.match_count;
+        group.chapters.push(hit);
+    }
+
+    let mut groups: Vec<ProjectSearchGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group.chapters.sort_by(|a, b| b.match_count.cmp(&a.match_count).then(a.order_index.cmp(&b.order_index)));
+    }
+    groups.sort_by(|a, b| b.match_count.cmp(&a.match_count).then_with(|| a.project_title.cmp(&b.project_title)));
+    groups.truncate(options.limit.max(1));
+    Ok(groups)
+}
@@ -0,0 +1,460 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use anyhow::Result;
+use serde_json::Value;
+use crate::models::{
+    AppSettings, ConfigFieldIssue, ImageSettings, ProjectExportSettings, ProjectImageConfig,
+    ProjectNarrativeSettings, TextConfigValidation, TextModelConfigInput,
+};
+use super::font_coverage::is_safe_file_name;
+use super::narrative_check::{POVS, TENSES};
+
+const APP_SETTINGS_KEY: &str = "app";
+const EXPORT_FORMATS: [&str; 4] = ["pdf", "epub", "txt", "mobi"];
+const PROJECT_EXPORT_FORMATS: [&str; 5] = ["pdf", "epub", "txt", "mobi", "docx"];
+const PAGE_SIZES: [&str; 3] = ["A4", "A5", "Letter"];
+const MAX_EXPORT_MARGIN_MM: f64 = 100.0;
+const LANGUAGE_CHECK_MODES: [&str; 3] = ["off", "flag", "retry"];
+const MIN_MILESTONE_INTERVAL: i64 = 1000;
+const MAX_VOCABULARY_TOP_N: usize = 500;
+const MAX_RETRIEVAL_TOP_K: usize = 200;
+const MAX_EMBEDDING_BATCH: usize = 256;
+const MAX_IMAGE_SIDE: u32 = 2048;
+const MAX_STREAM_FLUSH_CHARS: usize = 2000;
+const MAX_STREAM_FLUSH_INTERVAL_MS: u64 = 2000;
+const MAX_STREAM_UNACKED_EVENTS: u64 = 1000;
+const MAX_STREAM_BUFFERED_EVENTS: usize = 10_000;
+const MIN_INJECTION_TOKENS: usize = 500;
+const MAX_INJECTION_TOKENS: usize = 200_000;
+const MAX_MODEL_NAME_CHARS: usize = 200;
+const MAX_RETRY_ATTEMPTS: u32 = 10;
+const MAX_RETRY_DELAY_MS: u64 = 300_000;
+const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+
+pub struct SettingsService;
+
+// 递归合并 JSON：对象逐字段合并，null 表示恢复默认值，其余类型直接覆盖
+fn merge_json(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Object(base_map), Value::Object(patch_map)) => {
+            for (key, value) in patch_map {
+                if value.is_null() {
+                    base_map.remove(&key);
+                } else {
+                    merge_json(base_map.entry(key).or_insert(Value::Null), value);
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+fn validate_settings(settings: &AppSettings) -> Result<()> {
+    let text = &settings.text_config;
+    let temperature = text.temperature;
+    if !temperature.is_finite() || !(0.0..=2.0).contains(&temperature) {
+        return Err(anyhow::anyhow!("Temperature 必须在 0 到 2 之间"));
+    }
+    let api_url = text.api_url.trim();
+    if !api_url.is_empty() && !api_url.starts_with("http://") && !api_url.starts_with("https://") {
+        return Err(anyhow::anyhow!("API URL 必须以 http:// 或 https:// 开头"));
+    }
+    if let Some(ref font) = settings.pdf_font_file_name {
+        if !is_safe_file_name(font) {
+            return Err(anyhow::anyhow!("字体文件名不合法"));
+        }
+    }
+    if !EXPORT_FORMATS.contains(&settings.export.format.as_str()) {
+        return Err(anyhow::anyhow!("不支持的导出格式: {}", settings.export.format));
+    }
+    if !PAGE_SIZES.contains(&settings.export.page_size.as_str()) {
+        return Err(anyhow::anyhow!("不支持的页面尺寸: {}", settings.export.page_size));
+    }
+    let image = &settings.image;
+    if image.model.trim().is_empty() {
+        return Err(anyhow::anyhow!("图片模型不能为空"));
+    }
+    let sides = [image.width, image.height, image.promo_width, image.promo_height];
+    if sides.iter().any(|side| *side == 0 || *side > MAX_IMAGE_SIDE) {
+        return Err(anyhow::anyhow!("图片尺寸必须在 1 到 {} 之间", MAX_IMAGE_SIDE));
+    }
+    if settings.stream.flush_chars > MAX_STREAM_FLUSH_CHARS {
+        return Err(anyhow::anyhow!("流式推送字符数不能超过 {}", MAX_STREAM_FLUSH_CHARS));
+    }
+    if settings.stream.flush_interval_ms > MAX_STREAM_FLUSH_INTERVAL_MS {
+        return Err(anyhow::anyhow!("流式推送间隔不能超过 {} 毫秒", MAX_STREAM_FLUSH_INTERVAL_MS));
+    }
+    if settings.stream.max_unacked_events > MAX_STREAM_UNACKED_EVENTS {
+        return Err(anyhow::anyhow!("未确认的流式事件上限不能超过 {}", MAX_STREAM_UNACKED_EVENTS));
+    }
+    if settings.stream.max_buffered_events == 0 || settings.stream.max_buffered_events > MAX_STREAM_BUFFERED_EVENTS {
+        return Err(anyhow::anyhow!("流式缓冲批次必须在 1 到 {} 之间", MAX_STREAM_BUFFERED_EVENTS));
+    }
+    let language = &settings.language_check;
+    if !LANGUAGE_CHECK_MODES.contains(&language.mode.as_str()) {
+        return Err(anyhow::anyhow!("不支持的语言检查模式: {}", language.mode));
+    }
+    let threshold = language.mismatch_threshold;
+    if !threshold.is_finite() || threshold <= 0.0 || threshold > 1.0 {
+        return Err(anyhow::anyhow!("语言不符阈值必须在 0 到 1 之间"));
+    }
+    let injection = settings.context_budget.injection_tokens;
+    if !(MIN_INJECTION_TOKENS..=MAX_INJECTION_TOKENS).contains(&injection) {
+        return Err(anyhow::anyhow!(
+            "上下文注入预算必须在 {} 到 {} tokens 之间",
+            MIN_INJECTION_TOKENS,
+            MAX_INJECTION_TOKENS
+        ));
+    }
+    if settings.milestone_interval < MIN_MILESTONE_INTERVAL {
+        return Err(anyhow::anyhow!("里程碑间隔不能小于 {} 字", MIN_MILESTONE_INTERVAL));
+    }
+    let vocabulary = &settings.vocabulary;
+    if vocabulary.top_n == 0 || vocabulary.top_n > MAX_VOCABULARY_TOP_N {
+        return Err(anyhow::anyhow!("高频词数量必须在 1 到 {} 之间", MAX_VOCABULARY_TOP_N));
+    }
+    if !vocabulary.overuse_per_thousand.is_finite() || vocabulary.overuse_per_thousand <= 0.0 {
+        return Err(anyhow::anyhow!("高频词阈值必须大于 0"));
+    }
+    let retrieval = &settings.retrieval;
+    if retrieval.embedding_model.trim().chars().count() > MAX_MODEL_NAME_CHARS {
+        return Err(anyhow::anyhow!("向量模型名称不能超过 {} 个字符", MAX_MODEL_NAME_CHARS));
+    }
+    if retrieval.top_k > MAX_RETRIEVAL_TOP_K {
+        return Err(anyhow::anyhow!("注入条目数不能超过 {}", MAX_RETRIEVAL_TOP_K));
+    }
+    if retrieval.batch_size == 0 || retrieval.batch_size > MAX_EMBEDDING_BATCH {
+        return Err(anyhow::anyhow!("向量批量大小必须在 1 到 {} 之间", MAX_EMBEDDING_BATCH));
+    }
+    let pricing = &settings.pricing;
+    if [pricing.input_per_1k, pricing.output_per_1k]
+        .iter()
+        .any(|price| !price.is_finite() || *price < 0.0)
+    {
+        return Err(anyhow::anyhow!("计费单价不能为负数"));
+    }
+    let retry = &settings.retry;
+    if !(1..=MAX_RETRY_ATTEMPTS).contains(&retry.max_attempts) {
+        return Err(anyhow::anyhow!("请求次数必须在 1 到 {} 之间", MAX_RETRY_ATTEMPTS));
+    }
+    if retry.max_delay_ms > MAX_RETRY_DELAY_MS || retry.base_delay_ms > retry.max_delay_ms {
+        return Err(anyhow::anyhow!(
+            "重试间隔不能超过最大等待时间，最大等待时间不能超过 {} 毫秒",
+            MAX_RETRY_DELAY_MS
+        ));
+    }
+    if !retry.jitter.is_finite() || !(0.0..=1.0).contains(&retry.jitter) {
+        return Err(anyhow::anyhow!("重试随机浮动比例必须在 0 到 1 之间"));
+    }
+    Ok(())
+}
+
+fn validate_project_export(settings: &ProjectExportSettings) -> Result<()> {
+    if !PROJECT_EXPORT_FORMATS.contains(&settings.format.as_str()) {
+        return Err(anyhow::anyhow!("不支持的导出格式: {}", settings.format));
+    }
+    if !PAGE_SIZES.contains(&settings.page_size.as_str()) {
+        return Err(anyhow::anyhow!("不支持的页面尺寸: {}", settings.page_size));
+    }
+    if let Some(ref font) = settings.font_file_name {
+        if !is_safe_file_name(font) {
+            return Err(anyhow::anyhow!("字体文件名不合法"));
+        }
+    }
+    if let Some(margin) = settings.margin_mm {
+        if !margin.is_finite() || !(0.0..=MAX_EXPORT_MARGIN_MM).contains(&margin) {
+            return Err(anyhow::anyhow!("页边距必须在 0 到 {} 毫米之间", MAX_EXPORT_MARGIN_MM));
+        }
+    }
+    Ok(())
+}
+
+fn validate_project_image(config: &ProjectImageConfig) -> Result<()> {
+    if config.model.as_deref().is_some_and(|m| m.trim().is_empty() || m.chars().count() > MAX_MODEL_NAME_CHARS) {
+        return Err(anyhow::anyhow!("图片模型名称无效"));
+    }
+    let sides = [config.width, config.height, config.promo_width, config.promo_height];
+    if sides.iter().flatten().any(|side| *side == 0 || *side > MAX_IMAGE_SIDE) {
+        return Err(anyhow::anyhow!("图片尺寸必须在 1 到 {} 之间", MAX_IMAGE_SIDE));
+    }
+    Ok(())
+}
+
+fn validate_project_narrative(settings: &ProjectNarrativeSettings) -> Result<()> {
+    if settings.pov.as_deref().is_some_and(|pov| !POVS.contains(&pov)) {
+        return Err(anyhow::anyhow!("不支持的叙述人称，可选值: {}", POVS.join(", ")));
+    }
+    if settings.tense.as_deref().is_some_and(|tense| !TENSES.contains(&tense)) {
+        return Err(anyhow::anyhow!("不支持的叙述时态，可选值: {}", TENSES.join(", ")));
+    }
+    Ok(())
+}
+
+fn project_export_key(project_id: &str) -> String {
+    format!("project_export:{}", project_id)
+}
+
+fn project_image_key(project_id: &str) -> String {
+    format!("project_image:{}", project_id)
+}
+
+fn project_narrative_key(project_id: &str) -> String {
+    format!("project_narrative:{}", project_id)
+}
+
+async fn load_value(pool: &SqlitePool, key: &str) -> Result<Option<String>> {
+    let value = sqlx::query_scalar::<_, String>(
+        "SELECT value FROM settings WHERE key = ?"
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(value)
+}
+
+async fn store_value(pool: &SqlitePool, key: &str, value: &str) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+    sqlx::query(
+        r#"
+        INSERT INTO settings (key, value, updated_at)
+        VALUES (?, ?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
+        "#
+    )
+    .bind(key)
+    .bind(value)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+impl SettingsService {
+    pub async fn get(pool: &SqlitePool) -> Result<AppSettings> {
+        match load_value(pool, APP_SETTINGS_KEY).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(AppSettings::default()),
+        }
+    }
+
+    /// 合并部分更新并校验后保存，返回完整设置
+    pub async fn update(pool: &SqlitePool, patch: Value) -> Result<AppSettings> {
+        let current = Self::get(pool).await?;
+        let mut merged = serde_json::to_value(&current)?;
+        merge_json(&mut merged, patch);
+
+        let settings: AppSettings = serde_json::from_value(merged)
+            .map_err(|e| anyhow::anyhow!("设置格式无效: {}", e))?;
+        validate_settings(&settings)?;
+
+        store_value(pool, APP_SETTINGS_KEY, &serde_json::to_string(&settings)?).await?;
+        Ok(settings)
+    }
+
+    /// 项目最近一次的导出参数；从未导出过时使用全局导出设置
+    pub async fn get_project_export(pool: &SqlitePool, project_id: &str) -> Result<ProjectExportSettings> {
+        if let Some(json) = load_value(pool, &project_export_key(project_id)).await? {
+            return Ok(serde_json::from_str(&json)?);
+        }
+
+        let app = Self::get(pool).await?;
+        Ok(ProjectExportSettings {
+            format: app.export.format,
+            font_file_name: app.pdf_font_file_name,
+            include_cover: app.export.include_cover,
+            page_size: app.export.page_size,
+            margin_mm: None,
+        })
+    }
+
+    /// 合并本次导出实际使用的参数并保存（规则同 update：null 表示恢复默认）
+    pub async fn save_project_export(
+        pool: &SqlitePool,
+        project_id: &str,
+        patch: Value,
+    ) -> Result<ProjectExportSettings> {
+        let current = Self::get_project_export(pool, project_id).await?;
+        let mut merged = serde_json::to_value(&current)?;
+        merge_json(&mut merged, patch);
+
+        let settings: ProjectExportSettings = serde_json::from_value(merged)
+            .map_err(|e| anyhow::anyhow!("导出设置格式无效: {}", e))?;
+        validate_project_export(&settings)?;
+
+        store_value(pool, &project_export_key(project_id), &serde_json::to_string(&settings)?).await?;
+        Ok(settings)
+    }
+
+    pub async fn get_project_image(pool: &SqlitePool, project_id: &str) -> Result<ProjectImageConfig> {
+        match load_value(pool, &project_image_key(project_id)).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(ProjectImageConfig::default()),
+        }
+    }
+
+    /// 保存项目图片配置（整体覆盖）；模型是否受支持由调用方校验
+    pub async fn save_project_image(
+        pool: &SqlitePool,
+        project_id: &str,
+        config: ProjectImageConfig,
+    ) -> Result<ProjectImageConfig> {
+        validate_project_image(&config)?;
+        let config = ProjectImageConfig {
+            model: config.model.map(|m| m.trim().to_string()),
+            ..config
+        };
+        store_value(pool, &project_image_key(project_id), &serde_json::to_string(&config)?).await?;
+        Ok(config)
+    }
+
+    pub async fn get_project_narrative(pool: &SqlitePool, project_id: &str) -> Result<ProjectNarrativeSettings> {
+        match load_value(pool, &project_narrative_key(project_id)).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(ProjectNarrativeSettings::default()),
+        }
+    }
+
+    /// 保存项目的叙述人称与时态（整体覆盖），空字符串视为不设置
+    pub async fn save_project_narrative(
+        pool: &SqlitePool,
+        project_id: &str,
+        settings: ProjectNarrativeSettings,
+    ) -> Result<ProjectNarrativeSettings> {
+        let normalize = |value: Option<String>| {
+            value.map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty())
+        };
+        let settings = ProjectNarrativeSettings {
+            pov: normalize(settings.pov),
+            tense: normalize(settings.tense),
+            ..settings
+        };
+        validate_project_narrative(&settings)?;
+        store_value(pool, &project_narrative_key(project_id), &serde_json::to_string(&settings)?).await?;
+        Ok(settings)
+    }
+
+    /// 全局图片设置叠加项目配置；未指定项目时只用全局设置
+    pub async fn image_settings_for(pool: &SqlitePool, project_id: Option<&str>) -> Result<ImageSettings> {
+        let settings = Self::get(pool).await?.image;
+        match project_id {
+            Some(project_id) => Ok(settings.for_project(&Self::get_project_image(pool, project_id).await?)),
+            None => Ok(settings),
+        }
+    }
+
+    /// 删除项目级设置（导出参数、图片配置、叙述人称与时态）
+    pub async fn delete_project_settings(pool: &SqlitePool, project_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM settings WHERE key IN (?, ?, ?)")
+            .bind(project_export_key(project_id))
+            .bind(project_image_key(project_id))
+            .bind(project_narrative_key(project_id))
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 逐字段检查文本模型配置的格式，供设置表单在连接测试前即时提示
+    pub fn check_text_config(config: &TextModelConfigInput) -> TextConfigValidation {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        let issue = |field: &str, message: &str| ConfigFieldIssue {
+            field: field.to_string(),
+            message: message.to_string(),
+        };
+
+        if config.provider.trim().is_empty() {
+            errors.push(issue("provider", "服务商不能为空"));
+        }
+
+        let api_key = config.api_key.trim();
+        if api_key.is_empty() {
+            errors.push(issue("api_key", "API Key 不能为空"));
+        } else if api_key.to_ascii_lowercase().starts_with("bearer ") {
+            errors.push(issue("api_key", "API Key 不需要包含 Bearer 前缀"));
+        } else if api_key.chars().any(char::is_whitespace) {
+            errors.push(issue("api_key", "API Key 不能包含空白字符"));
+        } else if api_key != config.api_key {
+            warnings.push(issue("api_key", "API Key 首尾有空白，可能是复制时带入的"));
+        }
+
+        let api_url = config.api_url.trim();
+        let mut url_ok = false;
+        if api_url.is_empty() {
+            errors.push(issue("api_url", "API URL 不能为空"));
+        } else {
+            match reqwest::Url::parse(api_url) {
+                Err(_) => errors.push(issue("api_url", "API URL 格式无效")),
+                Ok(url) if url.scheme() != "http" && url.scheme() != "https" => {
+                    errors.push(issue("api_url", "API URL 必须以 http:// 或 https:// 开头"));
+                }
+                Ok(url) if url.host_str().is_none_or(str::is_empty) => {
+                    errors.push(issue("api_url", "API URL 缺少主机名"));
+                }
+                Ok(url) => {
+                    url_ok = true;
+                    let path = url.path().trim_end_matches('/');
+                    if path.matches(CHAT_COMPLETIONS_PATH).count() > 1 {
+                        errors.push(issue("api_url", "API URL 中重复出现了 /chat/completions"));
+                        url_ok = false;
+                    } else if path.ends_with(CHAT_COMPLETIONS_PATH) {
+                        warnings.push(issue("api_url", "API URL 已包含 /chat/completions，将直接使用该地址"));
+                    } else if path.contains(CHAT_COMPLETIONS_PATH) {
+                        errors.push(issue("api_url", "/chat/completions 之后不应再有其他路径"));
+                        url_ok = false;
+                    }
+                    if url.query().is_some() || url.fragment().is_some() {
+                        warnings.push(issue("api_url", "API URL 带有查询参数或锚点，可能导致请求地址错误"));
+                    }
+                    let local = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+                    if url.scheme() == "http" && !local {
+                        warnings.push(issue("api_url", "使用 http:// 时 API Key 将以明文传输"));
+                    }
+                }
+            }
+        }
+
+        let model = config.model.trim();
+        if model.is_empty() {
+            errors.push(issue("model", "模型名称不能为空"));
+        } else if model.chars().any(char::is_whitespace) {
+            errors.push(issue("model", "模型名称不能包含空白字符"));
+        } else if model.chars().count() > MAX_MODEL_NAME_CHARS {
+            errors.push(issue("model", "模型名称过长"));
+        } else if !model.chars().all(|c| c.is_ascii_alphanumeric() || "-_.:/@+".contains(c)) {
+            warnings.push(issue("model", "模型名称包含不常见的字符，请确认与服务商提供的名称一致"));
+        }
+
+        let temperature = config.temperature;
+        if !temperature.is_finite() || !(0.0..=2.0).contains(&temperature) {
+            errors.push(issue("temperature", "Temperature 必须在 0 到 2 之间"));
+        }
+
+        // 以请求时使用的基础校验兜底，避免两处规则不一致
+        if errors.is_empty() {
+            if let Err(e) = config.validate() {
+                errors.push(issue("config", &e));
+            }
+        }
+
+        TextConfigValidation {
+            valid: errors.is_empty(),
+            errors,
+            warnings,
+            chat_completions_url: url_ok.then(|| config.chat_completions_url()),
+        }
+    }
+
+    /// 调用方未传文本模型配置时，使用已保存的设置
+    pub async fn resolve_text_config(
+        pool: &SqlitePool,
+        input: Option<TextModelConfigInput>,
+    ) -> Result<TextModelConfigInput> {
+        match input {
+            Some(config) => Ok(config),
+            None => Ok(Self::get(pool).await?.text_config),
+        }
+    }
+}
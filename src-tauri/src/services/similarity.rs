@@ -0,0 +1,93 @@
+//! 文本相似度（用于查找重复章节和重名角色）
+//!
+//! 中文没有天然的词边界，因此按字符切分 n-gram（shingle），
+//! 再用 Jaccard 系数比较两段文本的 shingle 集合。
+//! 角色名很短，改用编辑距离比较。
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+/// 每个 shingle 的字符数；中英文混排下 4 个字符兼顾区分度与对改写的容忍度
+const SHINGLE_CHARS: usize = 4;
+
+/// 去除空白和标点、统一小写后切分为 shingle 哈希集合
+pub fn shingles(text: &str) -> HashSet<u64> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    if chars.is_empty() {
+        return HashSet::new();
+    }
+    if chars.len() < SHINGLE_CHARS {
+        return HashSet::from([hash_chars(&chars)]);
+    }
+
+    chars.windows(SHINGLE_CHARS).map(hash_chars).collect()
+}
+
+/// 两个 shingle 集合的 Jaccard 系数（0.0 ~ 1.0）
+pub fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let intersection = small.iter().filter(|item| large.contains(item)).count();
+    let union = a.len() + b.len() - intersection;
+    intersection as f64 / union as f64
+}
+
+/// Jaccard 系数的上界（集合大小差距过大时可跳过计算）
+pub fn jaccard_upper_bound(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let (min, max) = (a.len().min(b.len()), a.len().max(b.len()));
+    if max == 0 {
+        return 0.0;
+    }
+    min as f64 / max as f64
+}
+
+fn hash_chars(chars: &[char]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    chars.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 角色名归一化：去掉括注（“李明（少年）”）、空白和标点，统一小写
+pub fn normalize_name(name: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0usize;
+    for c in name.chars() {
+        match c {
+            '(' | '（' | '[' | '【' => depth += 1,
+            ')' | '）' | ']' | '】' => depth = depth.saturating_sub(1),
+            _ if depth == 0 && c.is_alphanumeric() => out.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// 两个名字归一化后的相似度：1 - 编辑距离 / 较长者字符数（0.0 ~ 1.0）
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalize_name(a).chars().collect();
+    let b: Vec<char> = normalize_name(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
@@ -0,0 +1,146 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use crate::models::{Chapter, Milestone, Snapshot, CreateSnapshotInput};
+use super::ChapterService;
+use super::chapter_context::chapter_text;
+
+pub struct SnapshotService;
+
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+impl SnapshotService {
+    pub async fn create(pool: &SqlitePool, input: CreateSnapshotInput) -> Result<Snapshot> {
+        // 没有任何生成参数的版本视为手动编辑
+        let source = if input.model.is_some() || input.prompt_template.is_some() {
+            "generated"
+        } else {
+            "manual"
+        };
+
+        let snapshot = Snapshot {
+            id: Uuid::new_v4().to_string(),
+            target_type: input.target_type,
+            target_id: input.target_id,
+            content_hash: content_hash(&input.content),
+            content: input.content,
+            note: input.note,
+            created_at: Utc::now().to_rfc3339(),
+            source: source.to_string(),
+            model: input.model,
+            temperature: input.temperature,
+            prompt_template: input.prompt_template,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO snapshots (id, target_type, target_id, content, content_hash, note, created_at, source, model, temperature, prompt_template)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&snapshot.id)
+        .bind(&snapshot.target_type)
+        .bind(&snapshot.target_id)
+        .bind(&snapshot.content)
+        .bind(&snapshot.content_hash)
+        .bind(&snapshot.note)
+        .bind(&snapshot.created_at)
+        .bind(&snapshot.source)
+        .bind(&snapshot.model)
+        .bind(snapshot.temperature)
+        .bind(&snapshot.prompt_template)
+        .execute(pool)
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    /// 与该对象最近一次快照内容相同时不再新建，直接返回最近一次快照
+    pub async fn create_if_changed(pool: &SqlitePool, input: CreateSnapshotInput) -> Result<Snapshot> {
+        if let Some(latest) = Self::get_latest(pool, &input.target_type, &input.target_id).await? {
+            if latest.content_hash == content_hash(&input.content) {
+                return Ok(latest);
+            }
+        }
+        Self::create(pool, input).await
+    }
+
+    /// 保存章节当前正文（final_text，为空时取 draft_text）的快照
+    pub async fn create_for_chapter(pool: &SqlitePool, chapter_id: &str, note: Option<String>) -> Result<Snapshot> {
+        let chapter = ChapterService::get_by_id(pool, chapter_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+        let content = chapter_text(&chapter)
+            .ok_or_else(|| anyhow::anyhow!("Chapter has no text to snapshot"))?
+            .to_string();
+
+        Self::create_if_changed(
+            pool,
+            CreateSnapshotInput {
+                target_type: "chapter".to_string(),
+                target_id: chapter.id,
+                content,
+                note,
+                model: None,
+                temperature: None,
+                prompt_template: None,
+            },
+        )
+        .await
+    }
+
+    /// 用章节快照覆盖 final_text，草稿保持不变；返回恢复后的章节和新达成的字数里程碑
+    pub async fn restore_chapter(pool: &SqlitePool, snapshot: &Snapshot) -> Result<(Chapter, Vec<Milestone>)> {
+        if snapshot.target_type != "chapter" {
+            return Err(anyhow::anyhow!("Snapshot {} is not a chapter snapshot", snapshot.id));
+        }
+        let chapter = ChapterService::get_by_id(pool, &snapshot.target_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+
+        let milestones = ChapterService::update_text(pool, &chapter.id, chapter.draft_text, Some(snapshot.content.clone()), None).await?;
+
+        let restored = ChapterService::get_by_id(pool, &chapter.id).await?
+            .ok_or_else(|| anyhow::anyhow!("Chapter not found after restore"))?;
+        Ok((restored, milestones))
+    }
+
+    pub async fn get_latest(pool: &SqlitePool, target_type: &str, target_id: &str) -> Result<Option<Snapshot>> {
+        let snapshot = sqlx::query_as::<_, Snapshot>(
+            "SELECT * FROM snapshots WHERE target_type = ? AND target_id = ? ORDER BY created_at DESC LIMIT 1"
+        )
+        .bind(target_type)
+        .bind(target_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    pub async fn get_by_target(pool: &SqlitePool, target_type: &str, target_id: &str) -> Result<Vec<Snapshot>> {
+        let snapshots = sqlx::query_as::<_, Snapshot>(
+            "SELECT * FROM snapshots WHERE target_type = ? AND target_id = ? ORDER BY created_at DESC"
+        )
+        .bind(target_type)
+        .bind(target_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<Snapshot>> {
+        let snapshot = sqlx::query_as::<_, Snapshot>(
+            "SELECT * FROM snapshots WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,268 @@
+//! 设定集 PDF
+//!
+//! 把角色（有立绘资源时附图）、按分类整理的世界观设定、时间线和人物关系排成一份 PDF，
+//! 供作者离线翻阅。人物关系取自分类名含“关系”的设定条目，外加按同章出场次数统计的角色组合。
+//! 排版见 pdf_layout，字体由调用方读取后传入。
+
+use anyhow::Result;
+use printpdf::image_crate::{self, DynamicImage};
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use crate::models::{Character, Lore, Project, TimelineEvent};
+use super::export_service::write_export_file;
+use super::pdf_layout::PdfLayout;
+use super::{AssetService, CharacterService, LoreService, ProjectService, TimelineService};
+
+/// 同章出场统计最多列出的角色组合数
+const MAX_CO_APPEARANCE_PAIRS: i64 = 30;
+const TITLE_SIZE: f32 = 24.0;
+const SECTION_SIZE: f32 = 18.0;
+const ENTRY_SIZE: f32 = 13.0;
+const BODY_SIZE: f32 = 10.5;
+/// 立绘最大尺寸（毫米）
+const PORTRAIT_WIDTH: f32 = 50.0;
+const PORTRAIT_HEIGHT: f32 = 70.0;
+
+struct Labels {
+    characters: &'static str,
+    lore: &'static str,
+    timeline: &'static str,
+    relationships: &'static str,
+    co_appearances: &'static str,
+    role: &'static str,
+    description: &'static str,
+    personality: &'static str,
+    background: &'static str,
+    motivation: &'static str,
+    voice_style: &'static str,
+    empty: &'static str,
+}
+
+const ZH_LABELS: Labels = Labels {
+    characters: "角色",
+    lore: "世界观设定",
+    timeline: "时间线",
+    relationships: "人物关系",
+    co_appearances: "同章出场",
+    role: "定位",
+    description: "简介",
+    personality: "性格",
+    background: "背景",
+    motivation: "动机",
+    voice_style: "说话风格",
+    empty: "（暂无）",
+};
+
+const EN_LABELS: Labels = Labels {
+    characters: "Characters",
+    lore: "World Building",
+    timeline: "Timeline",
+    relationships: "Relationships",
+    co_appearances: "Shared chapters",
+    role: "Role",
+    description: "Description",
+    personality: "Personality",
+    background: "Background",
+    motivation: "Motivation",
+    voice_style: "Voice",
+    empty: "(none)",
+};
+
+fn is_relationship_category(category: &str) -> bool {
+    category.contains("关系") || category.to_lowercase().contains("relation")
+}
+
+/// 排版所需的全部数据，查询完成后交给阻塞线程排版
+struct StoryBible {
+    project: Project,
+    characters: Vec<Character>,
+    portraits: HashMap<String, DynamicImage>,
+    lore: Vec<Lore>,
+    timeline: Vec<TimelineEvent>,
+    /// (角色 A, 角色 B, 同时出场的章节数)
+    co_appearances: Vec<(String, String, i64)>,
+}
+
+/// 每个角色取最新一张能解码的关联图片
+async fn load_portraits(pool: &SqlitePool, project_id: &str) -> Result<HashMap<String, DynamicImage>> {
+    let assets = AssetService::get_linked(pool, project_id, "character").await?;
+    let mut seen = HashSet::new();
+    let candidates: Vec<(String, String)> = assets
+        .into_iter()
+        .filter_map(|asset| Some((asset.linked_to_id?, asset.file_path)))
+        .filter(|(character_id, _)| seen.insert(character_id.clone()))
+        .collect();
+
+    let portraits = tokio::task::spawn_blocking(move || {
+        candidates
+            .into_iter()
+            .filter_map(|(character_id, path)| match image_crate::open(&path) {
+                Ok(image) => Some((character_id, image)),
+                Err(e) => {
+                    log::warn!("Skipping portrait {} for story bible: {}", path, e);
+                    None
+                }
+            })
+            .collect()
+    })
+    .await?;
+    Ok(portraits)
+}
+
+async fn co_appearances(
+    pool: &SqlitePool,
+    project_id: &str,
+    characters: &[Character],
+) -> Result<Vec<(String, String, i64)>> {
+    let rows: Vec<(String, String, i64)> = sqlx::query_as(
+        r#"
+        SELECT a.character_id, b.character_id, COUNT(*) AS shared
+        FROM character_appearances a
+        JOIN character_appearances b ON a.chapter_id = b.chapter_id AND a.character_id < b.character_id
+        WHERE a.project_id = ?
+        GROUP BY a.character_id, b.character_id
+        ORDER BY shared DESC
+        LIMIT ?
+        "#
+    )
+    .bind(project_id)
+    .bind(MAX_CO_APPEARANCE_PAIRS)
+    .fetch_all(pool)
+    .await?;
+
+    let names: HashMap<&str, &str> = characters.iter().map(|c| (c.id.as_str(), c.name.as_str())).collect();
+    Ok(rows
+        .into_iter()
+        .filter_map(|(left, right, shared)| {
+            Some((names.get(left.as_str())?.to_string(), names.get(right.as_str())?.to_string(), shared))
+        })
+        .collect())
+}
+
+fn layout(bible: &StoryBible, font_bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let labels = if bible.project.language == "en" { &EN_LABELS } else { &ZH_LABELS };
+    let mut pdf = PdfLayout::new(&bible.project.title, font_bytes)?;
+
+    pdf.heading(&bible.project.title, TITLE_SIZE, false);
+    let byline = [bible.project.author.as_deref(), bible.project.genre.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|value| !value.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ");
+    if !byline.is_empty() {
+        pdf.paragraph(&byline, BODY_SIZE, 0.0);
+    }
+    if let Some(description) = bible.project.description.as_deref() {
+        pdf.paragraph(description, BODY_SIZE, 0.0);
+    }
+
+    pdf.page_break();
+    pdf.heading(labels.characters, SECTION_SIZE, true);
+    if bible.characters.is_empty() {
+        pdf.paragraph(labels.empty, BODY_SIZE, 0.0);
+    }
+    for character in &bible.characters {
+        pdf.heading(&character.name, ENTRY_SIZE, false);
+        if let Some(portrait) = bible.portraits.get(&character.id) {
+            pdf.image(portrait, PORTRAIT_WIDTH, PORTRAIT_HEIGHT);
+        }
+        let fields = [
+            (labels.role, &character.role),
+            (labels.description, &character.description),
+            (labels.personality, &character.personality),
+            (labels.background, &character.background),
+            (labels.motivation, &character.motivation),
+            (labels.voice_style, &character.voice_style),
+        ];
+        for (label, value) in fields {
+            if let Some(value) = value.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+                pdf.paragraph(&format!("{}：{}", label, value), BODY_SIZE, 0.0);
+            }
+        }
+        pdf.spacing(3.0);
+    }
+
+    let (relationship_lore, world_lore): (Vec<&Lore>, Vec<&Lore>) =
+        bible.lore.iter().partition(|entry| is_relationship_category(&entry.category));
+
+    pdf.page_break();
+    pdf.heading(labels.lore, SECTION_SIZE, true);
+    if world_lore.is_empty() {
+        pdf.paragraph(labels.empty, BODY_SIZE, 0.0);
+    }
+    let mut current_category: Option<&str> = None;
+    for entry in world_lore {
+        if current_category != Some(entry.category.as_str()) {
+            current_category = Some(entry.category.as_str());
+            pdf.heading(&entry.category, ENTRY_SIZE + 1.0, false);
+        }
+        pdf.paragraph(&entry.title, BODY_SIZE + 1.0, 0.0);
+        if let Some(content) = entry.content.as_deref() {
+            pdf.paragraph(content, BODY_SIZE, 5.0);
+        }
+    }
+
+    pdf.page_break();
+    pdf.heading(labels.timeline, SECTION_SIZE, true);
+    if bible.timeline.is_empty() {
+        pdf.paragraph(labels.empty, BODY_SIZE, 0.0);
+    }
+    for event in &bible.timeline {
+        let title = match event.event_time.as_deref().map(str::trim).filter(|time| !time.is_empty()) {
+            Some(time) => format!("{}　{}", time, event.title),
+            None => event.title.clone(),
+        };
+        pdf.paragraph(&title, BODY_SIZE + 1.0, 0.0);
+        if let Some(description) = event.description.as_deref() {
+            pdf.paragraph(description, BODY_SIZE, 5.0);
+        }
+    }
+
+    pdf.page_break();
+    pdf.heading(labels.relationships, SECTION_SIZE, true);
+    if relationship_lore.is_empty() && bible.co_appearances.is_empty() {
+        pdf.paragraph(labels.empty, BODY_SIZE, 0.0);
+    }
+    for entry in relationship_lore {
+        pdf.paragraph(&entry.title, BODY_SIZE + 1.0, 0.0);
+        if let Some(content) = entry.content.as_deref() {
+            pdf.paragraph(content, BODY_SIZE, 5.0);
+        }
+    }
+    if !bible.co_appearances.is_empty() {
+        pdf.heading(labels.co_appearances, ENTRY_SIZE, false);
+        for (left, right, shared) in &bible.co_appearances {
+            pdf.paragraph(&format!("{} — {}：{}", left, right, shared), BODY_SIZE, 0.0);
+        }
+    }
+
+    pdf.finish()
+}
+
+/// 生成设定集 PDF 并写到 output_path，返回写入的路径
+pub async fn export_story_bible_pdf(
+    pool: &SqlitePool,
+    project_id: &str,
+    output_path: &str,
+    font_bytes: Vec<u8>,
+) -> Result<String> {
+    let project = ProjectService::get_by_id(pool, project_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+    let characters = CharacterService::get_by_project(pool, project_id).await?;
+    let bible = StoryBible {
+        portraits: load_portraits(pool, project_id).await?,
+        co_appearances: co_appearances(pool, project_id, &characters).await?,
+        lore: LoreService::get_by_project(pool, project_id).await?,
+        timeline: TimelineService::get_by_project(pool, project_id).await?,
+        characters,
+        project,
+    };
+
+    // printpdf 的文档对象不是 Send，整个排版放在阻塞线程里完成
+    let bytes = tokio::task::spawn_blocking(move || layout(&bible, font_bytes)).await??;
+
+    write_export_file(output_path, bytes).await?;
+    Ok(output_path.to_string())
+}
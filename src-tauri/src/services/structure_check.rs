@@ -0,0 +1,164 @@
+//! 项目结构检查
+//!
+//! 发布前的结构性自检：空章节、章节序号断档/重复、缺少大纲要素的章节、
+//! 从未在正文出现的角色和从未被用到的设定。只报告问题，不修改数据。
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashSet};
+use crate::models::Chapter;
+use super::{ChapterService, CharacterService, LoreService, ProjectService};
+use super::chapter_context::{chapter_text, is_blank};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterRef {
+    pub id: String,
+    pub title: String,
+    pub order_index: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateOrder {
+    pub order_index: i32,
+    pub chapters: Vec<ChapterRef>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IncompleteOutline {
+    pub chapter: ChapterRef,
+    /// 缺少的字段：outline_goal / conflict
+    pub missing_fields: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnreferencedCharacter {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnusedLore {
+    pub id: String,
+    pub category: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectStructureReport {
+    pub project_id: String,
+    pub chapter_count: usize,
+    /// 所有检查项均无问题
+    pub passed: bool,
+    pub empty_chapters: Vec<ChapterRef>,
+    /// 首末章节序号之间缺失的 order_index
+    pub order_gaps: Vec<i32>,
+    pub duplicate_orders: Vec<DuplicateOrder>,
+    pub incomplete_outlines: Vec<IncompleteOutline>,
+    pub unreferenced_characters: Vec<UnreferencedCharacter>,
+    pub unused_lore: Vec<UnusedLore>,
+}
+
+pub async fn validate_project_structure(pool: &SqlitePool, project_id: &str) -> Result<ProjectStructureReport> {
+    ProjectService::get_by_id(pool, project_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+
+    let chapters = ChapterService::get_by_project(pool, project_id).await?;
+    let characters = CharacterService::get_by_project(pool, project_id).await?;
+    let lore = LoreService::get_by_project(pool, project_id).await?;
+
+    let chapter_ref = |chapter: &Chapter| ChapterRef {
+        id: chapter.id.clone(),
+        title: chapter.title.clone(),
+        order_index: chapter.order_index,
+    };
+
+    let mut empty_chapters = Vec::new();
+    let mut incomplete_outlines = Vec::new();
+    let mut by_order: BTreeMap<i32, Vec<ChapterRef>> = BTreeMap::new();
+    for chapter in &chapters {
+        if chapter_text(chapter).is_none() {
+            empty_chapters.push(chapter_ref(chapter));
+        }
+        let mut missing_fields = Vec::new();
+        if is_blank(&chapter.outline_goal) {
+            missing_fields.push("outline_goal");
+        }
+        if is_blank(&chapter.conflict) {
+            missing_fields.push("conflict");
+        }
+        if !missing_fields.is_empty() {
+            incomplete_outlines.push(IncompleteOutline { chapter: chapter_ref(chapter), missing_fields });
+        }
+        by_order.entry(chapter.order_index).or_default().push(chapter_ref(chapter));
+    }
+
+    let order_gaps: Vec<i32> = match (by_order.keys().next(), by_order.keys().next_back()) {
+        (Some(&first), Some(&last)) => (first..=last).filter(|i| !by_order.contains_key(i)).collect(),
+        _ => Vec::new(),
+    };
+    let duplicate_orders: Vec<DuplicateOrder> = by_order
+        .into_iter()
+        .filter(|(_, chapters)| chapters.len() > 1)
+        .map(|(order_index, chapters)| DuplicateOrder { order_index, chapters })
+        .collect();
+
+    // 正文、摘要和大纲要素都算作“用到”
+    let corpus: Vec<&str> = chapters
+        .iter()
+        .flat_map(|c| {
+            [chapter_text(c), c.summary.as_deref(), c.outline_goal.as_deref(), c.conflict.as_deref()]
+        })
+        .flatten()
+        .collect();
+    let mentioned = |name: &str| {
+        let name = name.trim();
+        !name.is_empty() && corpus.iter().any(|text| text.contains(name))
+    };
+
+    // 手动登记过出场的角色即使正文未写名字也视为已出场
+    let appeared: HashSet<String> = sqlx::query_scalar(
+        "SELECT DISTINCT character_id FROM character_appearances WHERE project_id = ?"
+    )
+    .bind(project_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let unreferenced_characters: Vec<UnreferencedCharacter> = characters
+        .iter()
+        .filter(|c| !appeared.contains(&c.id) && !mentioned(&c.name))
+        .map(|c| UnreferencedCharacter { id: c.id.clone(), name: c.name.clone() })
+        .collect();
+
+    let unused_lore: Vec<UnusedLore> = lore
+        .iter()
+        .filter(|entry| !mentioned(&entry.title))
+        .map(|entry| UnusedLore {
+            id: entry.id.clone(),
+            category: entry.category.clone(),
+            title: entry.title.clone(),
+        })
+        .collect();
+
+    let passed = empty_chapters.is_empty()
+        && order_gaps.is_empty()
+        && duplicate_orders.is_empty()
+        && incomplete_outlines.is_empty()
+        && unreferenced_characters.is_empty()
+        && unused_lore.is_empty();
+
+    Ok(ProjectStructureReport {
+        project_id: project_id.to_string(),
+        chapter_count: chapters.len(),
+        passed,
+        empty_chapters,
+        order_gaps,
+        duplicate_orders,
+        incomplete_outlines,
+        unreferenced_characters,
+        unused_lore,
+    })
+}
@@ -0,0 +1,54 @@
+//! 按行比较两段文本
+//!
+//! 基于 similar 的逐行 diff，把相邻的同类行合并成一段，便于前端按段着色显示。
+//! 每段文本保留原有换行符，按顺序拼接 unchanged + removed 得到旧文本，unchanged + added 得到新文本。
+
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSpan {
+    Unchanged { text: String, lines: usize },
+    Added { text: String, lines: usize },
+    Removed { text: String, lines: usize },
+}
+
+impl DiffSpan {
+    fn new(tag: ChangeTag) -> Self {
+        let (text, lines) = (String::new(), 0);
+        match tag {
+            ChangeTag::Equal => Self::Unchanged { text, lines },
+            ChangeTag::Insert => Self::Added { text, lines },
+            ChangeTag::Delete => Self::Removed { text, lines },
+        }
+    }
+
+    fn tag(&self) -> ChangeTag {
+        match self {
+            Self::Unchanged { .. } => ChangeTag::Equal,
+            Self::Added { .. } => ChangeTag::Insert,
+            Self::Removed { .. } => ChangeTag::Delete,
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        let (Self::Unchanged { text, lines } | Self::Added { text, lines } | Self::Removed { text, lines }) = self;
+        text.push_str(line);
+        *lines += 1;
+    }
+}
+
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffSpan> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    for change in diff.iter_all_changes() {
+        if spans.last().map(DiffSpan::tag) != Some(change.tag()) {
+            spans.push(DiffSpan::new(change.tag()));
+        }
+        if let Some(span) = spans.last_mut() {
+            span.push_line(change.value());
+        }
+    }
+    spans
+}
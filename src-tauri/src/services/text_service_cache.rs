@@ -0,0 +1,73 @@
+//! 文本生成服务缓存
+//!
+//! 同一份连接配置（提供商、地址、模型、API Key）复用同一个 GenerationService，
+//! 其中的 HTTP 客户端自带连接池，连续生成多章时不必每次重新建连。
+//! Temperature 和 seed 按次覆盖，不参与缓存键。
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::models::TextModelConfigInput;
+use super::GenerationService;
+
+// 同时缓存的配置数量上限，超出时淘汰最久未用的
+const MAX_CACHED_SERVICES: usize = 4;
+
+struct CachedService {
+    service: GenerationService,
+    last_used: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref TEXT_SERVICES: Mutex<HashMap<u64, CachedService>> = Mutex::new(HashMap::new());
+}
+
+// 只保存哈希，缓存表里不另存明文 Key
+fn config_key(config: &TextModelConfigInput) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    config.provider.hash(&mut hasher);
+    config.normalized_api_base_url().hash(&mut hasher);
+    config.model.hash(&mut hasher);
+    config.api_key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 取出（或创建）该配置对应的服务，调用方拿到的是独立副本，可以继续链式设置
+pub fn text_service(config: &TextModelConfigInput) -> GenerationService {
+    let key = config_key(config);
+    let mut services = TEXT_SERVICES.lock().unwrap_or_else(|e| e.into_inner());
+
+    if !services.contains_key(&key) && services.len() >= MAX_CACHED_SERVICES {
+        let oldest = services
+            .iter()
+            .min_by_key(|(_, cached)| cached.last_used)
+            .map(|(key, _)| *key);
+        if let Some(oldest) = oldest {
+            services.remove(&oldest);
+        }
+    }
+
+    let cached = services.entry(key).or_insert_with(|| CachedService {
+        service: GenerationService::new_with_text_config(
+            Some(config.api_key.clone()),
+            Some(config.normalized_api_base_url()),
+            Some(config.model.clone()),
+            None,
+            None,
+            None,
+        ),
+        last_used: Instant::now(),
+    });
+    cached.last_used = Instant::now();
+    cached
+        .service
+        .clone()
+        .with_sampling(config.normalized_temperature(0.7), config.effective_seed())
+}
+
+/// 设置变更后清空缓存，避免继续使用旧的 Key 或地址
+pub fn clear() {
+    TEXT_SERVICES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
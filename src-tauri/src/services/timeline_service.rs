@@ -0,0 +1,341 @@
+use sqlx::SqlitePool;
+use chrono::Utc;
+use uuid::Uuid;
+use anyhow::Result;
+use regex::Regex;
+use crate::models::{CreateTimelineEventInput, TimelineEvent, UpdateTimelineEventInput};
+use super::chapter_number::first_chapter_reference;
+
+const TITLE_MAX_CHARS: usize = 40;
+
+lazy_static::lazy_static! {
+    static ref LIST_ITEM: Regex = Regex::new(r"^\s*\d+\s*[.、)）]\s*(.+)$").unwrap();
+    static ref BRACKETED: Regex = Regex::new(r"^[【\[]([^】\]]+)[】\]]\s*[:：-]?\s*(.*)$").unwrap();
+}
+
+/// 从大纲中解析出的时间线条目
+struct OutlineTimelineEntry {
+    event_type: &'static str,
+    title: String,
+    description: String,
+    event_time: Option<String>,
+    chapter_number: Option<i32>,
+}
+
+pub struct TimelineService;
+
+// 判断 ### 标题属于哪一类时间线
+fn section_event_type(heading: &str) -> Option<&'static str> {
+    let lower = heading.to_lowercase();
+    if heading.contains("历史事件") || lower.contains("historical events") {
+        Some("history")
+    } else if heading.contains("剧情时间线") || lower.contains("story timeline") {
+        Some("story")
+    } else {
+        None
+    }
+}
+
+fn is_timeline_section(heading: &str) -> bool {
+    heading.contains("时间线事件") || heading.to_lowercase().contains("timeline events")
+}
+
+fn short_title(description: &str) -> String {
+    let head = description
+        .split(['，', ',', '。', '；', ';', '：', ':'])
+        .next()
+        .unwrap_or(description)
+        .trim();
+    let head = if head.is_empty() { description.trim() } else { head };
+    head.chars().take(TITLE_MAX_CHARS).collect()
+}
+
+fn parse_entry(event_type: &'static str, text: &str) -> Option<OutlineTimelineEntry> {
+    let text = text.trim().trim_matches('*').trim();
+    if text.is_empty() || text.chars().all(|c| c == '.' || c == '…') {
+        return None;
+    }
+
+    let (event_time, description) = match BRACKETED.captures(text) {
+        Some(caps) => (
+            Some(caps[1].trim().to_string()),
+            caps[2].trim().to_string(),
+        ),
+        None => (None, text.to_string()),
+    };
+    let description = if description.is_empty() {
+        event_time.clone().unwrap_or_default()
+    } else {
+        description
+    };
+
+    // 只有剧情时间线的条目才关联章节
+    let chapter_number = if event_type == "story" {
+        event_time
+            .as_deref()
+            .and_then(first_chapter_reference)
+            .map(|number| number as i32)
+    } else {
+        None
+    };
+
+    Some(OutlineTimelineEntry {
+        event_type,
+        title: short_title(&description),
+        description,
+        event_time,
+        chapter_number,
+    })
+}
+
+/// 解析“时间线事件”章节下的历史事件与剧情时间线列表
+fn parse_outline_timeline(outline: &str) -> Vec<OutlineTimelineEntry> {
+    let mut entries = Vec::new();
+    let mut in_timeline = false;
+    let mut current_type: Option<&'static str> = None;
+
+    for line in outline.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix("### ") {
+            if in_timeline {
+                current_type = section_event_type(heading);
+            }
+            continue;
+        }
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            in_timeline = is_timeline_section(heading);
+            current_type = None;
+            continue;
+        }
+
+        let event_type = match (in_timeline, current_type) {
+            (true, Some(event_type)) => event_type,
+            _ => continue,
+        };
+        if let Some(caps) = LIST_ITEM.captures(line) {
+            if let Some(entry) = parse_entry(event_type, &caps[1]) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    entries
+}
+
+impl TimelineService {
+    /// 大纲中是否有可同步的时间线事件
+    pub fn outline_has_entries(outline: &str) -> bool {
+        !parse_outline_timeline(outline).is_empty()
+    }
+
+    pub async fn create(pool: &SqlitePool, input: CreateTimelineEventInput) -> Result<TimelineEvent> {
+        let order_index = match input.order_index {
+            Some(order_index) => order_index,
+            None => sqlx::query_scalar(
+                "SELECT COALESCE(MAX(order_index), 0) + 1 FROM timeline_events WHERE project_id = ?"
+            )
+            .bind(&input.project_id)
+            .fetch_one(pool)
+            .await?,
+        };
+
+        let event = TimelineEvent {
+            id: Uuid::new_v4().to_string(),
+            project_id: input.project_id,
+            title: input.title,
+            description: input.description,
+            event_time: input.event_time,
+            order_index: Some(order_index),
+            event_type: input.event_type,
+            chapter_id: input.chapter_id,
+            sync_key: None,
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO timeline_events (id, project_id, title, description, event_time, order_index, event_type, chapter_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&event.id)
+        .bind(&event.project_id)
+        .bind(&event.title)
+        .bind(&event.description)
+        .bind(&event.event_time)
+        .bind(event.order_index)
+        .bind(&event.event_type)
+        .bind(&event.chapter_id)
+        .bind(&event.created_at)
+        .execute(pool)
+        .await?;
+
+        Ok(event)
+    }
+
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> Result<Option<TimelineEvent>> {
+        let event = sqlx::query_as::<_, TimelineEvent>("SELECT * FROM timeline_events WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(event)
+    }
+
+    pub async fn update(pool: &SqlitePool, id: &str, input: UpdateTimelineEventInput) -> Result<TimelineEvent> {
+        let result = sqlx::query(
+            r#"
+            UPDATE timeline_events
+            SET title = COALESCE(?, title),
+                description = COALESCE(?, description),
+                event_time = COALESCE(?, event_time),
+                order_index = COALESCE(?, order_index),
+                event_type = COALESCE(?, event_type),
+                chapter_id = COALESCE(?, chapter_id)
+            WHERE id = ?
+            "#
+        )
+        .bind(input.title)
+        .bind(input.description)
+        .bind(input.event_time)
+        .bind(input.order_index)
+        .bind(input.event_type)
+        .bind(input.chapter_id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Timeline event not found"));
+        }
+
+        Self::get_by_id(pool, id).await?
+            .ok_or_else(|| anyhow::anyhow!("Timeline event not found after update"))
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM timeline_events WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 批量设置事件顺序（id, order_index），在同一事务中完成；任一 id 不存在时整体回滚
+    pub async fn reorder(pool: &SqlitePool, orders: &[(String, i32)]) -> Result<()> {
+        let mut tx = pool.begin().await?;
+        for (id, order_index) in orders {
+            let result = sqlx::query("UPDATE timeline_events SET order_index = ? WHERE id = ?")
+                .bind(order_index)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() == 0 {
+                return Err(anyhow::anyhow!("Timeline event {} not found", id));
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str) -> Result<Vec<TimelineEvent>> {
+        let events = sqlx::query_as::<_, TimelineEvent>(
+            "SELECT * FROM timeline_events WHERE project_id = ? ORDER BY order_index ASC, event_time ASC, created_at ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// 将大纲中的时间线同步到 timeline_events。
+    /// 按“类型+序号”生成 sync_key 做 upsert，重复同步只会更新；大纲中已删除的条目一并移除，手动添加的事件不受影响。
+    pub async fn sync_from_outline(
+        pool: &SqlitePool,
+        project_id: &str,
+        outline: &str,
+    ) -> Result<Vec<TimelineEvent>> {
+        let entries = parse_outline_timeline(outline);
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!("No timeline entries found in outline"));
+        }
+
+        let chapter_ids: Vec<(String, i32)> = sqlx::query_as(
+            "SELECT id, order_index FROM chapters WHERE project_id = ?"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        let now = Utc::now().to_rfc3339();
+        let mut tx = pool.begin().await?;
+        let mut sync_keys = Vec::with_capacity(entries.len());
+        let mut type_counts = (0, 0);
+
+        for (index, entry) in entries.iter().enumerate() {
+            let seq = if entry.event_type == "history" {
+                type_counts.0 += 1;
+                type_counts.0
+            } else {
+                type_counts.1 += 1;
+                type_counts.1
+            };
+            let sync_key = format!("outline:{}:{}", entry.event_type, seq);
+            let chapter_id = entry.chapter_number.and_then(|number| {
+                chapter_ids
+                    .iter()
+                    .find(|(_, order_index)| *order_index == number)
+                    .map(|(id, _)| id.clone())
+            });
+
+            sqlx::query(
+                r#"
+                INSERT INTO timeline_events (id, project_id, title, description, event_time, order_index, event_type, chapter_id, sync_key, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(project_id, sync_key) DO UPDATE SET
+                    title = excluded.title,
+                    description = excluded.description,
+                    event_time = excluded.event_time,
+                    order_index = excluded.order_index,
+                    event_type = excluded.event_type,
+                    chapter_id = excluded.chapter_id
+                "#
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(project_id)
+            .bind(&entry.title)
+            .bind(&entry.description)
+            .bind(&entry.event_time)
+            .bind(index as i32 + 1)
+            .bind(entry.event_type)
+            .bind(&chapter_id)
+            .bind(&sync_key)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+            sync_keys.push(sync_key);
+        }
+
+        // 清理大纲中已不存在的同步条目
+        let stale: Vec<String> = sqlx::query_scalar(
+            "SELECT sync_key FROM timeline_events WHERE project_id = ? AND sync_key LIKE 'outline:%'"
+        )
+        .bind(project_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        for key in stale.iter().filter(|key| !sync_keys.contains(key)) {
+            sqlx::query("DELETE FROM timeline_events WHERE project_id = ? AND sync_key = ?")
+                .bind(project_id)
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Self::get_by_project(pool, project_id).await
+    }
+}
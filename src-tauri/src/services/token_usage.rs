@@ -0,0 +1,43 @@
+//! 按生成任务累计 token 用量
+//!
+//! track_generation 在 scope 中执行一次生成，期间 DeepSeek 客户端每收到一个带 usage 的响应就累加，
+//! 结束后把合计和估算费用写入 generation_tasks。不在 scope 内的请求不统计；
+//! 与进行中的相同请求合并时只由真正发出请求的一方计数。
+
+use std::cell::Cell;
+use std::future::Future;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+}
+
+impl TokenUsage {
+    pub fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+tokio::task_local! {
+    static USAGE: Cell<Option<TokenUsage>>;
+}
+
+/// 执行 future 并返回其间累计的用量；没有任何响应带 usage 时为 None
+pub async fn scope<F: Future>(future: F) -> (F::Output, Option<TokenUsage>) {
+    USAGE
+        .scope(Cell::new(None), async {
+            let output = future.await;
+            (output, USAGE.with(Cell::get))
+        })
+        .await
+}
+
+pub fn record(usage: TokenUsage) {
+    let _ = USAGE.try_with(|total| total.set(Some(total.get().unwrap_or_default().add(usage))));
+}
@@ -0,0 +1,212 @@
+//! 词频与词汇丰富度统计
+//!
+//! 纯本地统计，不调用模型。中文没有分词词典，连续汉字按相邻两字切分（单字片段保留单字），
+//! 含虚词（的、了、着…）的片段视为跨词边界直接丢弃；拉丁文字按单词切分并转小写。
+//! 停用词表为内置表加设置中的自定义词。
+
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use crate::models::{Chapter, VocabularySettings};
+use super::{ChapterService, ProjectService};
+use super::chapter_context::chapter_text;
+use super::language_check::is_cjk;
+
+/// 出现次数少于该值的词不判定为高频滥用（避免短文本误报）
+const MIN_OVERUSE_COUNT: usize = 5;
+
+/// 常见虚词：含这些字的两字片段多半跨越了词边界
+const CJK_FUNCTION_CHARS: &str = "的了着过是在和与也就都而又很把被让给对向从到这那个之其所吗呢吧啊呀么";
+
+const DEFAULT_STOPWORDS: [&str; 60] = [
+    "我们", "你们", "他们", "她们", "它们", "自己", "什么", "怎么", "这样", "那样",
+    "一个", "没有", "不是", "已经", "还是", "因为", "所以", "但是", "如果", "可以",
+    "一下", "一些", "时候", "知道", "现在", "然后", "只是", "起来", "出来", "下来",
+    "the", "a", "an", "and", "or", "but", "of", "to", "in", "on",
+    "at", "for", "with", "as", "is", "was", "were", "be", "been", "it",
+    "he", "she", "they", "his", "her", "their", "that", "this", "had", "not",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WordFrequency {
+    pub word: String,
+    pub count: usize,
+    /// 每千词出现次数
+    pub per_thousand: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VocabularyReport {
+    /// project / chapter
+    pub scope: &'static str,
+    pub target_id: String,
+    /// 参与统计的章节数（无正文的章节不计）
+    pub chapter_count: usize,
+    /// 去除停用词后的词数
+    pub total_words: usize,
+    pub unique_words: usize,
+    /// 类符/形符比（unique_words / total_words），文本越长通常越低
+    pub type_token_ratio: f64,
+    pub top_words: Vec<WordFrequency>,
+    /// 每千词出现次数达到阈值的词
+    pub overused: Vec<WordFrequency>,
+    pub stopwords_excluded: usize,
+}
+
+/// 切分文本为词（停用词过滤之前）
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    let mut latin_word = String::new();
+
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut Vec<String>| {
+        if run.len() == 1 {
+            if !CJK_FUNCTION_CHARS.contains(run[0]) {
+                tokens.push(run[0].to_string());
+            }
+        } else {
+            for pair in run.windows(2) {
+                if !pair.iter().any(|c| CJK_FUNCTION_CHARS.contains(*c)) {
+                    tokens.push(pair.iter().collect());
+                }
+            }
+        }
+        run.clear();
+    };
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !latin_word.is_empty() {
+                tokens.push(std::mem::take(&mut latin_word));
+            }
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() || (c == '\'' && !latin_word.is_empty()) {
+            if !cjk_run.is_empty() {
+                flush_cjk(&mut cjk_run, &mut tokens);
+            }
+            latin_word.extend(c.to_lowercase());
+        } else {
+            if !cjk_run.is_empty() {
+                flush_cjk(&mut cjk_run, &mut tokens);
+            }
+            if !latin_word.is_empty() {
+                tokens.push(std::mem::take(&mut latin_word));
+            }
+        }
+    }
+    if !cjk_run.is_empty() {
+        flush_cjk(&mut cjk_run, &mut tokens);
+    }
+    if !latin_word.is_empty() {
+        tokens.push(latin_word);
+    }
+    // 纯数字和英文所有格残留的撇号不计入词汇
+    tokens
+        .into_iter()
+        .map(|t| t.trim_end_matches('\'').to_string())
+        .filter(|t| !t.is_empty() && !t.chars().all(|c| c.is_numeric()))
+        .collect()
+}
+
+fn stopword_set(settings: &VocabularySettings) -> HashSet<String> {
+    let mut stopwords: HashSet<String> = settings
+        .stopwords
+        .iter()
+        .map(|w| w.trim().to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if settings.use_default_stopwords {
+        stopwords.extend(DEFAULT_STOPWORDS.iter().map(|w| w.to_string()));
+    }
+    stopwords
+}
+
+/// 统计多段文本的词频，返回 (词频, 被过滤的停用词数)
+fn analyze_texts<'a>(
+    texts: impl IntoIterator<Item = &'a str>,
+    settings: &VocabularySettings,
+) -> (HashMap<String, usize>, usize) {
+    let stopwords = stopword_set(settings);
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut excluded = 0;
+    for text in texts {
+        for token in tokenize(text) {
+            if stopwords.contains(&token) {
+                excluded += 1;
+            } else {
+                *counts.entry(token).or_insert(0) += 1;
+            }
+        }
+    }
+    (counts, excluded)
+}
+
+fn build_report(
+    scope: &'static str,
+    target_id: &str,
+    chapters: &[Chapter],
+    settings: &VocabularySettings,
+) -> VocabularyReport {
+    let texts: Vec<&str> = chapters.iter().filter_map(chapter_text).collect();
+    let (counts, stopwords_excluded) = analyze_texts(texts.iter().copied(), settings);
+    let total_words: usize = counts.values().sum();
+    let per_thousand = |count: usize| {
+        if total_words == 0 {
+            0.0
+        } else {
+            count as f64 * 1000.0 / total_words as f64
+        }
+    };
+
+    let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let unique_words = ranked.len();
+
+    let frequency = |(word, count): &(String, usize)| WordFrequency {
+        word: word.clone(),
+        count: *count,
+        per_thousand: per_thousand(*count),
+    };
+    let top_words = ranked.iter().take(settings.top_n).map(frequency).collect();
+    let overused = ranked
+        .iter()
+        .filter(|(_, count)| *count >= MIN_OVERUSE_COUNT && per_thousand(*count) >= settings.overuse_per_thousand)
+        .map(frequency)
+        .collect();
+
+    VocabularyReport {
+        scope,
+        target_id: target_id.to_string(),
+        chapter_count: texts.len(),
+        total_words,
+        unique_words,
+        type_token_ratio: if total_words == 0 { 0.0 } else { unique_words as f64 / total_words as f64 },
+        top_words,
+        overused,
+        stopwords_excluded,
+    }
+}
+
+pub async fn analyze_project(
+    pool: &SqlitePool,
+    project_id: &str,
+    settings: &VocabularySettings,
+) -> Result<VocabularyReport> {
+    ProjectService::get_by_id(pool, project_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+    let chapters = ChapterService::get_by_project(pool, project_id).await?;
+    Ok(build_report("project", project_id, &chapters, settings))
+}
+
+pub async fn analyze_chapter(
+    pool: &SqlitePool,
+    chapter_id: &str,
+    settings: &VocabularySettings,
+) -> Result<VocabularyReport> {
+    let chapter = ChapterService::get_by_id(pool, chapter_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Chapter not found"))?;
+    Ok(build_report("chapter", chapter_id, &[chapter], settings))
+}
@@ -0,0 +1,120 @@
+use sqlx::SqlitePool;
+use chrono::{Duration, Local, NaiveDate, Utc};
+use anyhow::Result;
+use crate::models::{CompletionEstimate, DailyWordCount};
+use super::ProjectService;
+
+/// 推算完成日期时参考的最近天数
+const VELOCITY_WINDOW_DAYS: i64 = 14;
+/// 推算结果超过该天数时不给出日期
+const MAX_ESTIMATE_DAYS: i64 = 3650;
+
+pub struct WordCountHistoryService;
+
+impl WordCountHistoryService {
+    /// 记录项目当天的总字数（同一天只保留最后一次），失败只记录日志
+    pub async fn record(pool: &SqlitePool, project_id: &str, word_count: i64) {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO word_count_history (project_id, day, word_count, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(project_id, day) DO UPDATE SET
+                word_count = excluded.word_count,
+                updated_at = excluded.updated_at
+            "#
+        )
+        .bind(project_id)
+        .bind(Local::now().date_naive().to_string())
+        .bind(word_count)
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await;
+
+        if let Err(e) = result {
+            log::warn!("Failed to record word count history for {}: {}", project_id, e);
+        }
+    }
+
+    pub async fn get_by_project(pool: &SqlitePool, project_id: &str) -> Result<Vec<DailyWordCount>> {
+        let history = sqlx::query_as::<_, DailyWordCount>(
+            "SELECT * FROM word_count_history WHERE project_id = ? ORDER BY day ASC"
+        )
+        .bind(project_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(history)
+    }
+
+    /// 用最近 VELOCITY_WINDOW_DAYS 天的日均净增字数推算达到目标字数的日期
+    pub async fn estimate_completion(pool: &SqlitePool, project_id: &str) -> Result<CompletionEstimate> {
+        let project = ProjectService::get_by_id(pool, project_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Project not found"))?;
+        let history = Self::get_by_project(pool, project_id).await?;
+
+        let current = project.current_word_count;
+        let target = project.target_word_count.filter(|target| *target > 0);
+        let mut estimate = CompletionEstimate {
+            project_id: project_id.to_string(),
+            target_word_count: target,
+            current_word_count: current,
+            remaining_words: target.map_or(0, |target| (target - current).max(0)),
+            daily_average: None,
+            window_days: 0,
+            estimated_date: None,
+            days_remaining: None,
+            reason: None,
+        };
+
+        let Some(target) = target else {
+            estimate.reason = Some("未设置目标字数".to_string());
+            return Ok(estimate);
+        };
+        let today = Local::now().date_naive();
+        if current >= target {
+            estimate.estimated_date = Some(today.to_string());
+            estimate.days_remaining = Some(0);
+            estimate.reason = Some("已达到目标字数".to_string());
+            return Ok(estimate);
+        }
+
+        let days: Vec<(NaiveDate, i64)> = history
+            .iter()
+            .filter_map(|entry| entry.day.parse().ok().map(|day| (day, entry.word_count)))
+            .collect();
+        // 以窗口开始前最后一天的字数为基准；记录不足一个窗口时用最早一天
+        let window_start = today - Duration::days(VELOCITY_WINDOW_DAYS);
+        let baseline = days
+            .iter()
+            .rev()
+            .find(|(day, _)| *day <= window_start)
+            .or_else(|| days.first());
+        let Some(&(baseline_day, baseline_count)) = baseline else {
+            estimate.reason = Some("暂无字数记录".to_string());
+            return Ok(estimate);
+        };
+
+        let span = (today - baseline_day).num_days();
+        if span < 1 {
+            estimate.reason = Some("字数记录不足，至少需要两天的写作记录".to_string());
+            return Ok(estimate);
+        }
+        let average = (current - baseline_count) as f64 / span as f64;
+        estimate.window_days = span;
+        estimate.daily_average = Some((average * 10.0).round() / 10.0);
+        if average <= 0.0 {
+            estimate.reason = Some("近期字数没有增长，无法推算".to_string());
+            return Ok(estimate);
+        }
+
+        let days_remaining = (estimate.remaining_words as f64 / average).ceil() as i64;
+        if days_remaining > MAX_ESTIMATE_DAYS {
+            estimate.reason = Some("按近期速度需要超过十年，暂不推算".to_string());
+            return Ok(estimate);
+        }
+        estimate.days_remaining = Some(days_remaining);
+        estimate.estimated_date = Some((today + Duration::days(days_remaining)).to_string());
+        Ok(estimate)
+    }
+}
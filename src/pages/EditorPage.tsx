@@ -6,7 +6,7 @@ import { Button } from '@components/Button';
 import { ArrowLeft, Save, Sparkles, StopCircle, Check, FileText, ChevronRight, RefreshCw, Image, ChevronDown, ChevronUp, Loader2 } from 'lucide-react';
 import { listen } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/tauri';
-import type { Chapter } from '@typings/index';
+import type { Chapter, StreamChunkEvent } from '@typings/index';
 import { confirmDialog } from '@utils/index';
 import { tx } from '@utils/i18n';
 
@@ -466,8 +466,8 @@ export function EditorPage() {
     }
 
     try {
-      const unlisten = await listen<string>('chapter-stream', (event) => {
-        setContent(prev => prev + event.payload);
+      const unlisten = await listen<StreamChunkEvent>('chapter-stream', (event) => {
+        setContent(prev => prev + event.payload.content);
       });
 
       // 获取上下文信息
@@ -543,8 +543,8 @@ export function EditorPage() {
     let unlisten: (() => void) | null = null;
 
     try {
-      unlisten = await listen<string>('chapter-stream', (event) => {
-        setContent(prev => prev + event.payload);
+      unlisten = await listen<StreamChunkEvent>('chapter-stream', (event) => {
+        setContent(prev => prev + event.payload.content);
       });
 
       const project = await projectApi.getById(projectId);
@@ -10,6 +10,7 @@ import ReactMarkdown from 'react-markdown';
 import remarkGfm from 'remark-gfm';
 import { tx } from '@utils/i18n';
 import { alertDialog } from '@utils/index';
+import type { StreamChunkEvent } from '@typings/index';
 
 interface OutlineLine {
   id: string;
@@ -337,8 +338,8 @@ export function OutlinePage() {
 
     try {
       // 设置事件监听器接收流式内容
-      const unlisten = await listen<string>('outline-stream', (event) => {
-        setOutline(prev => prev + event.payload);
+      const unlisten = await listen<StreamChunkEvent>('outline-stream', (event) => {
+        setOutline(prev => prev + event.payload.content);
       });
 
       // 合并额外要求和角色信息
@@ -196,3 +196,9 @@ export interface GenerateImageInput {
   save_path: string;
   pollinations_key?: string;
 }
+
+// 流式生成正文事件（chapter-stream、outline-stream 等）
+export interface StreamChunkEvent {
+  generation_id: string | null;
+  content: string;
+}